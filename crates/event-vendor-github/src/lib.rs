@@ -1,4 +1,4 @@
-use super::{event_vendor::EventVendorFunctions, loki::ValueItem};
+use dora_event_vendor::{EventVendorFunctions, ValueItem};
 
 pub struct GitHub {}
 
@@ -19,34 +19,40 @@ impl EventVendorFunctions for GitHub {
     ///
     /// # Returns
     ///
-    /// A `String` representing the transformed commit URL.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the `deployment` field inside the `json_data` of the `ValueItem`
-    /// is `None` or if any of the fields inside the deployment (such as `url`, `id`, or `sha`) are missing.
+    /// A `String` representing the transformed commit URL, or an empty string if the entry has no deployment.
     ///
     /// # Example
     ///
     /// ```
-    /// let entry = ValueItem::new(Some(Deployment {
-    ///     url: "https://api.github.com/repos/owner/repo/deployments/123456".to_string(),
-    ///     id: 123456,
-    ///     sha: "abcdef".to_string(),
-    /// }));
-    ///
-    /// let result = extract_change_url(&entry);
+    /// # use dora_event_vendor::{Deployment, EventVendorFunctions, JsonData, ValueItem};
+    /// # use dora_event_vendor_github::GitHub;
+    /// let entry = ValueItem {
+    ///     json_data: JsonData {
+    ///         deployment: Some(Deployment {
+    ///             url: "https://api.github.com/repos/owner/repo/deployments/123456".to_string(),
+    ///             id: 123456,
+    ///             sha: "abcdef".to_string(),
+    ///             ..Default::default()
+    ///         }),
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let result = GitHub::extract_change_url(&entry);
     /// assert_eq!(result, "https://github.com/owner/repo/commit/abcdef");
     /// ```
     fn extract_change_url(entry: &ValueItem) -> String {
-        let deployment = entry.json_data.deployment.as_ref().unwrap();
+        let Some(deployment) = entry.json_data.deployment.as_ref() else {
+            return String::new();
+        };
 
-        return deployment
+        deployment
             .url
             .replace("api.", "")
             .replace("repos/", "")
             .replace("deployments/", "commit/")
-            .replace(deployment.id.to_string().as_str(), &deployment.sha);
+            .replace(deployment.id.to_string().as_str(), &deployment.sha)
     }
 
     /// Extracts a workflow run URL from a deployment entry, if a workflow is present.
@@ -67,70 +73,67 @@ impl EventVendorFunctions for GitHub {
     ///
     /// # Returns
     ///
-    /// A `String` representing the transformed workflow run URL, or an empty string if no workflow run with a `workflow_id` is found.
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if the `deployment` field inside the `json_data` of the `ValueItem`
-    /// is `None`, or if the required fields inside the deployment (such as `url` or `id`) are missing.
+    /// A `String` representing the transformed workflow run URL, or an empty string if the entry has no deployment
+    /// or no workflow run with a `workflow_id` is found.
     ///
     /// # Example
     ///
     /// ```
-    /// let entry = ValueItem::new(
-    ///     Some(Deployment {
-    ///         url: "https://api.github.com/repos/owner/repo/deployments/123456".to_string(),
-    ///         id: 123456,
-    ///         sha: "abcdef".to_string(),
-    ///     }),
-    ///     Some(WorkflowRun {
-    ///         workflow_id: Some(654321),
-    ///     })
-    /// );
-    ///
-    /// let result = extract_deployment_url(&entry);
+    /// # use dora_event_vendor::{Deployment, EventVendorFunctions, JsonData, ValueItem, WorkflowRun};
+    /// # use dora_event_vendor_github::GitHub;
+    /// let mut entry = ValueItem {
+    ///     json_data: JsonData {
+    ///         deployment: Some(Deployment {
+    ///             url: "https://api.github.com/repos/owner/repo/deployments/123456".to_string(),
+    ///             id: 123456,
+    ///             sha: "abcdef".to_string(),
+    ///             ..Default::default()
+    ///         }),
+    ///         workflow_run: Some(WorkflowRun {
+    ///             workflow_id: Some(654321),
+    ///             ..Default::default()
+    ///         }),
+    ///         ..Default::default()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let result = GitHub::extract_deployment_url(&entry);
     /// assert_eq!(result, "https://github.com/owner/repo/actions/runs/654321");
-    /// ```
     ///
-    /// If no workflow run or `workflow_id` is present, an empty string is returned:
+    /// // If no workflow run or `workflow_id` is present, an empty string is returned
+    /// entry.json_data.workflow_run = None;
     ///
-    /// ```
-    /// let entry = ValueItem::new(Some(Deployment { ... }), None);
-    ///
-    /// let result = extract_deployment_url(&entry);
-    /// assert_eq!(result, "");
+    /// assert_eq!(GitHub::extract_deployment_url(&entry), "");
     /// ```
     fn extract_deployment_url(entry: &ValueItem) -> String {
-        let deployment = entry.json_data.deployment.as_ref().unwrap();
-
-        if let Some(wf) = entry
-            .json_data
-            .workflow_run
-            .as_ref()
-            .filter(|wf| wf.workflow_id.is_some())
-        {
-            return deployment
-                .url
-                .replace("api.", "")
-                .replace("repos/", "")
-                .replace("deployments/", "actions/runs/")
-                .replace(
-                    deployment.id.to_string().as_str(),
-                    wf.workflow_id.unwrap().to_string().as_str(),
-                );
-        }
-
-        String::new()
+        let (Some(deployment), Some(workflow_id)) = (
+            entry.json_data.deployment.as_ref(),
+            entry
+                .json_data
+                .workflow_run
+                .as_ref()
+                .and_then(|wf| wf.workflow_id),
+        ) else {
+            return String::new();
+        };
+
+        deployment
+            .url
+            .replace("api.", "")
+            .replace("repos/", "")
+            .replace("deployments/", "actions/runs/")
+            .replace(
+                deployment.id.to_string().as_str(),
+                workflow_id.to_string().as_str(),
+            )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::helpers::{
-        event_vendor::EventVendorFunctions,
-        github::GitHub,
-        loki::{Deployment, JsonData, ValueItem, WorkflowRun},
-    };
+    use super::GitHub;
+    use dora_event_vendor::{Deployment, EventVendorFunctions, JsonData, ValueItem, WorkflowRun};
 
     #[test]
     fn test_extract_change_url() {
@@ -144,6 +147,7 @@ mod tests {
                 }),
                 ..Default::default()
             },
+            ..Default::default()
         };
 
         let result = GitHub::extract_change_url(&entry);
@@ -167,6 +171,7 @@ mod tests {
                 }),
                 ..Default::default()
             },
+            ..Default::default()
         };
 
         let result = GitHub::extract_deployment_url(&entry);
@@ -186,10 +191,28 @@ mod tests {
                 }),
                 ..Default::default()
             },
+            ..Default::default()
         };
 
         let result = GitHub::extract_deployment_url(&entry);
 
         assert_eq!(result, "");
     }
+
+    #[test]
+    fn test_extract_urls_without_deployment() {
+        let entry = ValueItem {
+            json_data: JsonData {
+                workflow_run: Some(WorkflowRun {
+                    workflow_id: Some(7890),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(GitHub::extract_change_url(&entry), "");
+        assert_eq!(GitHub::extract_deployment_url(&entry), "");
+    }
 }
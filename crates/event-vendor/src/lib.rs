@@ -0,0 +1,160 @@
+//! The vendor-facing API for event vendors.
+//!
+//! Events are read from Loki as stream values whose payload is the webhook event the collector received.
+//! Each vendor crate implements `EventVendorFunctions` to turn those payloads into the links the API
+//! returns, so the core crate doesn't depend on any vendor's URL scheme.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+pub trait EventVendorFunctions {
+    fn extract_change_url(entry: &ValueItem) -> String;
+    fn extract_deployment_url(entry: &ValueItem) -> String;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValueItem {
+    pub timestamp: DateTime<Utc>,
+    pub json_data: JsonData,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct JsonData {
+    pub pull_request: Option<PullRequest>,
+    pub deployment: Option<Deployment>,
+    pub deployment_status: Option<DeploymentStatus>,
+    pub issue: Option<Issue>,
+    pub repository: Option<Repository>,
+    pub workflow_run: Option<WorkflowRun>,
+    pub review: Option<Review>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Issue {
+    pub created_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub number: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Repository {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PullRequest {
+    pub title: String,
+    pub user: User,
+    pub number: Option<u32>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub html_url: Option<String>,
+    /// Only set once the pull request has been merged, or GitHub has checked that it can be.
+    pub merge_commit_sha: Option<String>,
+    pub base: Option<Branch>,
+    pub head: Option<Branch>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// The GitHub API URL listing the pull request's commits.
+    pub commits_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Branch {
+    #[serde(rename = "ref")]
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Review {
+    /// `approved`, `changes_requested`, `commented` or `dismissed`.
+    pub state: String,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub user: Option<User>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct DeploymentStatus {
+    pub state: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Deployment {
+    pub id: u32,
+    pub created_at: DateTime<Utc>,
+    pub sha: String,
+    pub url: String,
+    /// The task the deployment runs, `deploy` unless the workflow names another, e.g. `deploy:service-a`.
+    pub task: Option<String>,
+    /// Extra data the workflow attached to the deployment, e.g. `{"service": "service-a"}`.
+    pub payload: Option<serde_json::Value>,
+}
+
+impl Deployment {
+    /// Returns the service a monorepo deployment is for, from the `service` of its payload or else a task
+    /// named `deploy:<service>`.
+    pub fn service(&self) -> Option<String> {
+        let from_payload = match &self.payload {
+            Some(serde_json::Value::Object(payload)) => payload.get("service").cloned(),
+            Some(serde_json::Value::String(payload)) => {
+                serde_json::from_str::<serde_json::Value>(payload)
+                    .ok()
+                    .and_then(|payload| payload.get("service").cloned())
+            }
+            _ => None,
+        };
+
+        let service = match from_payload {
+            Some(serde_json::Value::String(service)) => Some(service),
+            _ => self
+                .task
+                .as_deref()
+                .and_then(|task| task.strip_prefix("deploy:"))
+                .map(str::to_string),
+        };
+
+        service
+            .map(|service| service.trim().to_lowercase())
+            .filter(|service| !service.is_empty())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WorkflowRun {
+    pub workflow_id: Option<u32>,
+    pub status: Option<String>,
+    pub run_started_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct User {
+    pub login: String,
+}
+
+impl<'de> Deserialize<'de> for ValueItem {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let vec: Vec<String> = Vec::deserialize(deserializer)?;
+        if vec.len() != 2 {
+            return Err(serde::de::Error::custom("Expected a tuple of two elements"));
+        }
+        let timestamp = vec[0]
+            .parse::<i64>()
+            .map(DateTime::from_timestamp_nanos)
+            .map_err(serde::de::Error::custom)?;
+        let json_data: JsonData =
+            serde_json::from_str(&vec[1]).map_err(serde::de::Error::custom)?;
+        Ok(ValueItem {
+            timestamp,
+            json_data,
+        })
+    }
+}
@@ -0,0 +1,51 @@
+//! The command line options, which take precedence over the environment and the configuration file, so the
+//! API can be run and its configuration checked locally without writing a `.env` file.
+
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone, Default, PartialEq)]
+#[command(version, about = "Serves DORA metrics gathered from Loki and GitHub")]
+pub struct Cli {
+    /// The port the REST API listens on, in place of `PORT`.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// A YAML or TOML configuration file, in place of `CONFIG_FILE`.
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+
+    /// The log level or filter, e.g. `debug` or `warn,liatrio_dora_api=info`, in place of `RUST_LOG`.
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Loads and validates the configuration, reports any problems and exits without serving.
+    #[arg(long)]
+    pub check_config: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "liatrio-dora-api",
+            "--port",
+            "3000",
+            "--config",
+            "dora.yaml",
+            "--check-config",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.port, Some(3000));
+        assert_eq!(cli.config, Some(PathBuf::from("dora.yaml")));
+        assert!(cli.check_config);
+        assert!(Cli::try_parse_from(["liatrio-dora-api", "--port", "http"]).is_err());
+    }
+}
@@ -0,0 +1,1017 @@
+//! The application's configuration, loaded once at startup from an optional YAML or TOML file named by
+//! `CONFIG_FILE`, with the environment variables taking precedence over the file. Settings that aren't in the
+//! file or the environment keep their defaults.
+//!
+//! The loaded configuration is returned by `crate::init` and shared with the handlers through `AppState`. The
+//! helpers build their own settings from its sections, e.g. `CacheConfig::from_config(&config.cache)`.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{
+    cli::Cli,
+    helpers::{http, recordings::RecordingMode, secrets},
+};
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub loki: LokiConfig,
+    pub github: GithubConfig,
+    pub environments: EnvironmentsConfig,
+    pub cache: CacheSettings,
+    pub alerts: AlertsSettings,
+    pub digest: DigestSettings,
+    pub prewarm: PrewarmSettings,
+    pub cors: CorsSettings,
+    pub limits: LimitsSettings,
+    pub breaker: BreakerSettings,
+    pub readiness: ReadinessSettings,
+    pub http: HttpSettings,
+    pub batching: BatchingSettings,
+    pub auth: AuthSettings,
+    pub oidc: OidcSettings,
+    pub hotfixes: HotfixesSettings,
+    pub branches: BranchesSettings,
+    pub dedup: DedupSettings,
+    pub cohorts: CohortsSettings,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `PORT`, the port the REST API listens on.
+    pub port: Option<u16>,
+    /// `GRPC_PORT`, the port the gRPC service listens on, or `None` to not serve it.
+    pub grpc_port: Option<u16>,
+    /// `ADMIN_TOKEN`, the bearer token required by the `/admin` routes.
+    pub admin_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LokiConfig {
+    /// `LOKI_URL`
+    pub url: Option<String>,
+    /// `LOKI_USER`
+    pub user: Option<String>,
+    /// `LOKI_TOKEN`, the token or a reference to it in a secret manager, see `helpers::secrets`.
+    pub token: Option<String>,
+    /// `LOKI_TOKEN_FILE`, a file holding the token in place of `token`.
+    pub token_file: Option<PathBuf>,
+    /// `LOKI_TENANT_ID`, the tenant queried when a request doesn't name one.
+    pub tenant_id: Option<String>,
+    /// `LOKI_ALLOWED_TENANTS`, the tenants requests may name.
+    pub allowed_tenants: Vec<String>,
+    /// `SERVICE_NAME`, the service namespaces queried when a request doesn't name any.
+    pub service_names: Vec<String>,
+    /// `LOKI_MAX_PAGES`
+    pub max_pages: usize,
+    /// `LOKI_RETENTION_DAYS`, or `None` when retention is unlimited.
+    pub retention_days: Option<i64>,
+    /// `MERGE_LOOKBACK_DAYS`
+    pub merge_lookback_days: i64,
+    /// `FAILURE_LOOKAHEAD_MAX_DAYS`
+    pub failure_lookahead_max_days: i64,
+    /// `LOKI_RECORDINGS`, whether query responses are replayed from or recorded to `recordings_dir`, see
+    /// `helpers::recordings`.
+    pub recordings: Option<RecordingMode>,
+    /// `LOKI_RECORDINGS_DIR`
+    pub recordings_dir: PathBuf,
+}
+
+impl Default for LokiConfig {
+    fn default() -> Self {
+        LokiConfig {
+            url: None,
+            user: None,
+            token: None,
+            token_file: None,
+            tenant_id: None,
+            allowed_tenants: Vec::new(),
+            service_names: vec!["github".to_string()],
+            max_pages: 10,
+            retention_days: None,
+            merge_lookback_days: 0,
+            failure_lookahead_max_days: 7,
+            recordings: None,
+            recordings_dir: PathBuf::from("recordings"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GithubConfig {
+    /// `GITHUB_ORG`
+    pub org: Option<String>,
+    /// `GITHUB_TOKEN`, the token or a reference to it in a secret manager, see `helpers::secrets`.
+    pub token: Option<String>,
+    /// `GITHUB_TOKEN_FILE`, a file holding the token in place of `token`.
+    pub token_file: Option<PathBuf>,
+    /// `TEAMS_CACHE_TTL_SECONDS`, how long the teams cache is considered fresh.
+    pub teams_cache_ttl_seconds: u64,
+}
+
+impl Default for GithubConfig {
+    fn default() -> Self {
+        GithubConfig {
+            org: None,
+            token: None,
+            token_file: None,
+            teams_cache_ttl_seconds: 3600,
+        }
+    }
+}
+
+impl GithubConfig {
+    pub fn teams_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.teams_cache_ttl_seconds)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvironmentsConfig {
+    /// `PRODUCTION_ENVIRONMENT_NAMES`, the patterns of the environments counted as production.
+    pub production: Vec<String>,
+    /// `PRODUCTION_ENVIRONMENT_EXCLUDE`, the patterns of environments never counted as production.
+    pub exclude: Vec<String>,
+}
+
+impl Default for EnvironmentsConfig {
+    fn default() -> Self {
+        EnvironmentsConfig {
+            production: vec![
+                "production".to_string(),
+                "prod".to_string(),
+                "prod-*".to_string(),
+            ],
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSettings {
+    /// `DATA_CACHE_TTL_SECONDS`
+    pub ttl_seconds: u64,
+    /// `DATA_CACHE_MAX_ENTRIES`
+    pub max_entries: usize,
+    /// `DATA_CACHE_STALE_AFTER_SECONDS`, or `None` to not refresh responses in the background.
+    pub stale_after_seconds: Option<u64>,
+    /// `DATA_CACHE_REQUERY_SECONDS`
+    pub requery_seconds: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            ttl_seconds: 3600,
+            max_entries: 500,
+            stale_after_seconds: None,
+            requery_seconds: 86_400,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsSettings {
+    /// `ALERT_WEBHOOK_URLS`
+    pub webhook_urls: Vec<String>,
+    /// `ALERT_SLACK_WEBHOOK_URLS`
+    pub slack_webhook_urls: Vec<String>,
+    /// `ALERT_TEAMS_WEBHOOK_URLS`
+    pub teams_webhook_urls: Vec<String>,
+    /// `ALERT_EMAIL_TO`, emailed through the `digest` SMTP relay.
+    pub email_to: Vec<String>,
+    /// `ALERT_CHANGE_FAILURE_RATE`
+    pub change_failure_rate: Option<f64>,
+    /// `ALERT_CHANGE_FAILURE_RATE_CHANNELS`
+    pub change_failure_rate_channels: Vec<String>,
+    /// `ALERT_MTTR_HOURS`
+    pub mttr_hours: Option<f64>,
+    /// `ALERT_MTTR_HOURS_CHANNELS`
+    pub mttr_hours_channels: Vec<String>,
+    /// `ALERT_WINDOW_DAYS`
+    pub window_days: i64,
+    /// `ALERT_INTERVAL_SECONDS`
+    pub interval_seconds: u64,
+}
+
+impl Default for AlertsSettings {
+    fn default() -> Self {
+        AlertsSettings {
+            webhook_urls: Vec::new(),
+            slack_webhook_urls: Vec::new(),
+            teams_webhook_urls: Vec::new(),
+            email_to: Vec::new(),
+            change_failure_rate: None,
+            change_failure_rate_channels: Vec::new(),
+            mttr_hours: None,
+            mttr_hours_channels: Vec::new(),
+            window_days: 7,
+            interval_seconds: 900,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestSettings {
+    /// `DIGEST_SCHEDULE`
+    pub schedule: String,
+    /// `DIGEST_WINDOW_DAYS`
+    pub window_days: i64,
+    /// `DIGEST_WEBHOOK_URLS`
+    pub webhook_urls: Vec<String>,
+    /// `DIGEST_TEAM_WEBHOOK_URLS`, `team=url` pairs.
+    pub team_webhook_urls: Vec<String>,
+    /// `DIGEST_SMTP_HOST`
+    pub smtp_host: Option<String>,
+    /// `DIGEST_SMTP_PORT`
+    pub smtp_port: Option<u16>,
+    /// `DIGEST_SMTP_USERNAME`
+    pub smtp_username: Option<String>,
+    /// `DIGEST_SMTP_PASSWORD`
+    pub smtp_password: Option<String>,
+    /// `DIGEST_EMAIL_FROM`
+    pub email_from: Option<String>,
+    /// `DIGEST_EMAIL_TO`
+    pub email_to: Vec<String>,
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        DigestSettings {
+            schedule: "0 0 9 * * Mon".to_string(),
+            window_days: 7,
+            webhook_urls: Vec::new(),
+            team_webhook_urls: Vec::new(),
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            email_from: None,
+            email_to: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrewarmSettings {
+    /// `PREWARM_TEAMS`, where `*` is the unfiltered query.
+    pub teams: Vec<String>,
+    /// `PREWARM_DAYS`
+    pub days: Vec<i64>,
+    /// `PREWARM_READINESS_GATE`
+    pub readiness_gate: bool,
+    /// `PREWARM_READINESS_TIMEOUT_SECONDS`
+    pub readiness_timeout_seconds: u64,
+    /// `PREWARM_SCHEDULE`
+    pub schedule: Option<String>,
+}
+
+impl Default for PrewarmSettings {
+    fn default() -> Self {
+        PrewarmSettings {
+            teams: Vec::new(),
+            days: vec![30],
+            readiness_gate: false,
+            readiness_timeout_seconds: 120,
+            schedule: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    /// `CORS_ALLOWED_ORIGINS`, or `*` for any origin. CORS is disabled when empty.
+    pub allowed_origins: Vec<String>,
+    /// `CORS_ALLOWED_METHODS`
+    pub allowed_methods: Vec<String>,
+    /// `CORS_ALLOWED_HEADERS`
+    pub allowed_headers: Vec<String>,
+    /// `CORS_MAX_AGE_SECONDS`
+    pub max_age_seconds: u64,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        CorsSettings {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string(), "POST".to_string(), "OPTIONS".to_string()],
+            allowed_headers: vec!["content-type".to_string(), "authorization".to_string()],
+            max_age_seconds: 3600,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSettings {
+    /// `REQUEST_TIMEOUT_SECONDS`, `0` for no timeout.
+    pub request_timeout_seconds: u64,
+    /// `MAX_CONCURRENT_REQUESTS`, `0` for no limit.
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for LimitsSettings {
+    fn default() -> Self {
+        LimitsSettings {
+            request_timeout_seconds: 300,
+            max_concurrent_requests: 64,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakerSettings {
+    /// `CIRCUIT_BREAKER_FAILURES`, `0` to never open the breakers.
+    pub failures: u32,
+    /// `CIRCUIT_BREAKER_COOLDOWN_SECONDS`
+    pub cooldown_seconds: u64,
+}
+
+impl Default for BreakerSettings {
+    fn default() -> Self {
+        BreakerSettings {
+            failures: 5,
+            cooldown_seconds: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadinessSettings {
+    /// `READY_TIMEOUT_SECONDS`
+    pub timeout_seconds: u64,
+    /// `READY_CACHE_SECONDS`, `0` to probe on every call.
+    pub cache_seconds: u64,
+    /// `READY_REQUIRE_GITHUB`
+    pub require_github: bool,
+}
+
+impl Default for ReadinessSettings {
+    fn default() -> Self {
+        ReadinessSettings {
+            timeout_seconds: 2,
+            cache_seconds: 10,
+            require_github: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+    /// `HTTP_CONNECT_TIMEOUT_SECONDS`
+    pub connect_timeout_seconds: u64,
+    /// `HTTP_TIMEOUT_SECONDS`
+    pub timeout_seconds: u64,
+    /// `HTTP_POOL_MAX_IDLE_PER_HOST`
+    pub pool_max_idle_per_host: usize,
+    /// `HTTP_POOL_IDLE_TIMEOUT_SECONDS`
+    pub pool_idle_timeout_seconds: u64,
+    /// `HTTP_TCP_KEEPALIVE_SECONDS`, `0` to not send TCP keepalives.
+    pub tcp_keepalive_seconds: u64,
+    /// `HTTP_CLIENT_CERT_PATH`
+    pub client_cert_path: Option<PathBuf>,
+    /// `HTTP_CLIENT_KEY_PATH`
+    pub client_key_path: Option<PathBuf>,
+    /// `HTTP_CA_BUNDLE_PATH`
+    pub ca_bundle_path: Option<PathBuf>,
+    /// `HTTP_PROXY_URL`
+    pub proxy_url: Option<String>,
+    /// `HTTP_NO_PROXY`
+    pub no_proxy: Option<String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            connect_timeout_seconds: 10,
+            timeout_seconds: 120,
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout_seconds: 90,
+            tcp_keepalive_seconds: 60,
+            client_cert_path: None,
+            client_key_path: None,
+            ca_bundle_path: None,
+            proxy_url: None,
+            no_proxy: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchingSettings {
+    /// `LOKI_DAYS_BATCH_SIZE`
+    pub days_batch_size: i64,
+    /// `LOKI_MIN_BATCH_HOURS`
+    pub min_batch_hours: i64,
+    /// `LOKI_MAX_BATCH_DAYS`
+    pub max_batch_days: i64,
+    /// `LOKI_ADAPTIVE_BATCHING`
+    pub adaptive: bool,
+}
+
+impl Default for BatchingSettings {
+    fn default() -> Self {
+        BatchingSettings {
+            days_batch_size: 5,
+            min_batch_hours: 1,
+            max_batch_days: 30,
+            adaptive: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// `API_KEYS`, `name:key` pairs. Clients aren't asked for a key when empty.
+    pub api_keys: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcSettings {
+    /// `OIDC_ISSUER_URL`, or `None` to not accept OIDC tokens.
+    pub issuer_url: Option<String>,
+    /// `OIDC_AUDIENCE`
+    pub audience: Option<String>,
+    /// `OIDC_JWKS_URL`
+    pub jwks_url: Option<String>,
+    /// `OIDC_JWKS_CACHE_SECONDS`
+    pub jwks_cache_seconds: u64,
+    /// `OIDC_ADMIN_CLAIM`, a `claim=value` pair.
+    pub admin_claim: Option<String>,
+}
+
+impl Default for OidcSettings {
+    fn default() -> Self {
+        OidcSettings {
+            issuer_url: None,
+            audience: None,
+            jwks_url: None,
+            jwks_cache_seconds: 3600,
+            admin_claim: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotfixesSettings {
+    /// `HOTFIX_BRANCHES`
+    pub branches: Vec<String>,
+    /// `HOTFIX_LABELS`
+    pub labels: Vec<String>,
+    /// `HOTFIX_TITLE_PATTERN`
+    pub title_pattern: Option<String>,
+}
+
+impl Default for HotfixesSettings {
+    fn default() -> Self {
+        HotfixesSettings {
+            branches: vec!["hotfix/*".to_string(), "hotfix-*".to_string()],
+            labels: vec!["hotfix".to_string()],
+            title_pattern: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BranchesSettings {
+    /// `MAIN_BRANCH_NAMES`, the patterns of the branches whose merges count towards lead time.
+    pub main: Vec<String>,
+}
+
+impl Default for BranchesSettings {
+    fn default() -> Self {
+        BranchesSettings {
+            main: vec!["main".to_string(), "master".to_string()],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DedupSettings {
+    /// `DEPLOYMENT_DEDUPLICATION`, how repeated deployments of a commit to an environment are counted.
+    pub deployments: String,
+}
+
+impl Default for DedupSettings {
+    fn default() -> Self {
+        DedupSettings {
+            deployments: "keep-first".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CohortsSettings {
+    /// `COHORT_STACKS`, `stack=Language|Language` pairs.
+    pub stacks: Vec<String>,
+}
+
+impl AppConfig {
+    /// Loads the configuration file named by `--config` or `CONFIG_FILE`, if any, and applies the environment and
+    /// then the command line options on top of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns every problem found, e.g. a file that can't be parsed, an environment variable that isn't a
+    /// valid number, a secret that can't be read or a required setting that is missing, so they can all be
+    /// fixed at once. Loki and GitHub aren't required when `fixtures` are served in their place.
+    pub async fn load(cli: &Cli, fixtures: bool) -> Result<Self, Vec<String>> {
+        let mut config = match &cli.config {
+            Some(path) if !path.as_os_str().is_empty() => {
+                AppConfig::from_file(path).map_err(|e| vec![e.to_string()])?
+            }
+            _ => AppConfig::default(),
+        };
+
+        let mut problems = config.apply_env();
+
+        if let Some(port) = cli.port {
+            config.server.port = Some(port);
+        }
+
+        // Secrets are fetched from secret managers through the shared client, so it is built first.
+        if let Err(e) = http::init(&config.http) {
+            problems.push(e.to_string());
+        }
+
+        problems.extend(config.resolve_secrets().await);
+        problems.extend(config.validate(fixtures));
+
+        match problems.is_empty() {
+            true => Ok(config),
+            false => Err(problems),
+        }
+    }
+
+    /// Reads a YAML or TOML file, chosen by its extension.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed, or its extension is neither.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!(format!("{}: {}", e, path.display())))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => AppConfig::from_yaml(&contents),
+            Some("toml") => AppConfig::from_toml(&contents),
+            _ => Err(anyhow!(format!(
+                "CONFIG_FILE has to be a .yaml, .yml or .toml file: {}",
+                path.display()
+            ))),
+        }
+        .map_err(|e| anyhow!(format!("{}: {}", e, path.display())))
+    }
+
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Overrides the settings with the environment variables that are set, returning the variables whose
+    /// value isn't valid.
+    pub fn apply_env(&mut self) -> Vec<String> {
+        let mut env = Overrides::default();
+
+        env.set_option(&mut self.server.port, "PORT");
+        env.set_option(&mut self.server.grpc_port, "GRPC_PORT");
+        env.set_option(&mut self.server.admin_token, "ADMIN_TOKEN");
+
+        env.set_option(&mut self.loki.url, "LOKI_URL");
+        env.set_option(&mut self.loki.user, "LOKI_USER");
+        env.set_option(&mut self.loki.token, "LOKI_TOKEN");
+        env.set_option(&mut self.loki.token_file, "LOKI_TOKEN_FILE");
+        env.set_option(&mut self.loki.tenant_id, "LOKI_TENANT_ID");
+        env.set_list(&mut self.loki.allowed_tenants, "LOKI_ALLOWED_TENANTS");
+        env.set_list(&mut self.loki.service_names, "SERVICE_NAME");
+        env.set(&mut self.loki.max_pages, "LOKI_MAX_PAGES");
+        env.set_option(&mut self.loki.retention_days, "LOKI_RETENTION_DAYS");
+        env.set(&mut self.loki.merge_lookback_days, "MERGE_LOOKBACK_DAYS");
+        env.set(
+            &mut self.loki.failure_lookahead_max_days,
+            "FAILURE_LOOKAHEAD_MAX_DAYS",
+        );
+        env.set_option(&mut self.loki.recordings, "LOKI_RECORDINGS");
+        env.set(&mut self.loki.recordings_dir, "LOKI_RECORDINGS_DIR");
+
+        env.set_option(&mut self.github.org, "GITHUB_ORG");
+        env.set_option(&mut self.github.token, "GITHUB_TOKEN");
+        env.set_option(&mut self.github.token_file, "GITHUB_TOKEN_FILE");
+        env.set(
+            &mut self.github.teams_cache_ttl_seconds,
+            "TEAMS_CACHE_TTL_SECONDS",
+        );
+
+        env.set_list(
+            &mut self.environments.production,
+            "PRODUCTION_ENVIRONMENT_NAMES",
+        );
+        env.set_list(
+            &mut self.environments.exclude,
+            "PRODUCTION_ENVIRONMENT_EXCLUDE",
+        );
+
+        env.set(&mut self.cache.ttl_seconds, "DATA_CACHE_TTL_SECONDS");
+        env.set(&mut self.cache.max_entries, "DATA_CACHE_MAX_ENTRIES");
+        env.set_option(
+            &mut self.cache.stale_after_seconds,
+            "DATA_CACHE_STALE_AFTER_SECONDS",
+        );
+        env.set(
+            &mut self.cache.requery_seconds,
+            "DATA_CACHE_REQUERY_SECONDS",
+        );
+
+        env.set_list(&mut self.alerts.webhook_urls, "ALERT_WEBHOOK_URLS");
+        env.set_list(
+            &mut self.alerts.slack_webhook_urls,
+            "ALERT_SLACK_WEBHOOK_URLS",
+        );
+        env.set_list(
+            &mut self.alerts.teams_webhook_urls,
+            "ALERT_TEAMS_WEBHOOK_URLS",
+        );
+        env.set_list(&mut self.alerts.email_to, "ALERT_EMAIL_TO");
+        env.set_option(
+            &mut self.alerts.change_failure_rate,
+            "ALERT_CHANGE_FAILURE_RATE",
+        );
+        env.set_list(
+            &mut self.alerts.change_failure_rate_channels,
+            "ALERT_CHANGE_FAILURE_RATE_CHANNELS",
+        );
+        env.set_option(&mut self.alerts.mttr_hours, "ALERT_MTTR_HOURS");
+        env.set_list(
+            &mut self.alerts.mttr_hours_channels,
+            "ALERT_MTTR_HOURS_CHANNELS",
+        );
+        env.set(&mut self.alerts.window_days, "ALERT_WINDOW_DAYS");
+        env.set(&mut self.alerts.interval_seconds, "ALERT_INTERVAL_SECONDS");
+
+        env.set(&mut self.digest.schedule, "DIGEST_SCHEDULE");
+        env.set(&mut self.digest.window_days, "DIGEST_WINDOW_DAYS");
+        env.set_list(&mut self.digest.webhook_urls, "DIGEST_WEBHOOK_URLS");
+        env.set_list(
+            &mut self.digest.team_webhook_urls,
+            "DIGEST_TEAM_WEBHOOK_URLS",
+        );
+        env.set_option(&mut self.digest.smtp_host, "DIGEST_SMTP_HOST");
+        env.set_option(&mut self.digest.smtp_port, "DIGEST_SMTP_PORT");
+        env.set_option(&mut self.digest.smtp_username, "DIGEST_SMTP_USERNAME");
+        env.set_option(&mut self.digest.smtp_password, "DIGEST_SMTP_PASSWORD");
+        env.set_option(&mut self.digest.email_from, "DIGEST_EMAIL_FROM");
+        env.set_list(&mut self.digest.email_to, "DIGEST_EMAIL_TO");
+
+        env.set_list(&mut self.prewarm.teams, "PREWARM_TEAMS");
+        env.set_list(&mut self.prewarm.days, "PREWARM_DAYS");
+        env.set(&mut self.prewarm.readiness_gate, "PREWARM_READINESS_GATE");
+        env.set(
+            &mut self.prewarm.readiness_timeout_seconds,
+            "PREWARM_READINESS_TIMEOUT_SECONDS",
+        );
+        env.set_option(&mut self.prewarm.schedule, "PREWARM_SCHEDULE");
+
+        env.set_list(&mut self.cors.allowed_origins, "CORS_ALLOWED_ORIGINS");
+        env.set_list(&mut self.cors.allowed_methods, "CORS_ALLOWED_METHODS");
+        env.set_list(&mut self.cors.allowed_headers, "CORS_ALLOWED_HEADERS");
+        env.set(&mut self.cors.max_age_seconds, "CORS_MAX_AGE_SECONDS");
+
+        env.set(
+            &mut self.limits.request_timeout_seconds,
+            "REQUEST_TIMEOUT_SECONDS",
+        );
+        env.set(
+            &mut self.limits.max_concurrent_requests,
+            "MAX_CONCURRENT_REQUESTS",
+        );
+
+        env.set(&mut self.breaker.failures, "CIRCUIT_BREAKER_FAILURES");
+        env.set(
+            &mut self.breaker.cooldown_seconds,
+            "CIRCUIT_BREAKER_COOLDOWN_SECONDS",
+        );
+
+        env.set(&mut self.readiness.timeout_seconds, "READY_TIMEOUT_SECONDS");
+        env.set(&mut self.readiness.cache_seconds, "READY_CACHE_SECONDS");
+        env.set(&mut self.readiness.require_github, "READY_REQUIRE_GITHUB");
+
+        env.set(
+            &mut self.http.connect_timeout_seconds,
+            "HTTP_CONNECT_TIMEOUT_SECONDS",
+        );
+        env.set(&mut self.http.timeout_seconds, "HTTP_TIMEOUT_SECONDS");
+        env.set(
+            &mut self.http.pool_max_idle_per_host,
+            "HTTP_POOL_MAX_IDLE_PER_HOST",
+        );
+        env.set(
+            &mut self.http.pool_idle_timeout_seconds,
+            "HTTP_POOL_IDLE_TIMEOUT_SECONDS",
+        );
+        env.set(
+            &mut self.http.tcp_keepalive_seconds,
+            "HTTP_TCP_KEEPALIVE_SECONDS",
+        );
+        env.set_option(&mut self.http.client_cert_path, "HTTP_CLIENT_CERT_PATH");
+        env.set_option(&mut self.http.client_key_path, "HTTP_CLIENT_KEY_PATH");
+        env.set_option(&mut self.http.ca_bundle_path, "HTTP_CA_BUNDLE_PATH");
+        env.set_option(&mut self.http.proxy_url, "HTTP_PROXY_URL");
+        env.set_option(&mut self.http.no_proxy, "HTTP_NO_PROXY");
+
+        env.set(&mut self.batching.days_batch_size, "LOKI_DAYS_BATCH_SIZE");
+        env.set(&mut self.batching.min_batch_hours, "LOKI_MIN_BATCH_HOURS");
+        env.set(&mut self.batching.max_batch_days, "LOKI_MAX_BATCH_DAYS");
+        env.set(&mut self.batching.adaptive, "LOKI_ADAPTIVE_BATCHING");
+
+        env.set_list(&mut self.auth.api_keys, "API_KEYS");
+
+        env.set_option(&mut self.oidc.issuer_url, "OIDC_ISSUER_URL");
+        env.set_option(&mut self.oidc.audience, "OIDC_AUDIENCE");
+        env.set_option(&mut self.oidc.jwks_url, "OIDC_JWKS_URL");
+        env.set(&mut self.oidc.jwks_cache_seconds, "OIDC_JWKS_CACHE_SECONDS");
+        env.set_option(&mut self.oidc.admin_claim, "OIDC_ADMIN_CLAIM");
+
+        env.set_list(&mut self.hotfixes.branches, "HOTFIX_BRANCHES");
+        env.set_list(&mut self.hotfixes.labels, "HOTFIX_LABELS");
+        env.set_option(&mut self.hotfixes.title_pattern, "HOTFIX_TITLE_PATTERN");
+
+        env.set_list(&mut self.branches.main, "MAIN_BRANCH_NAMES");
+
+        env.set(&mut self.dedup.deployments, "DEPLOYMENT_DEDUPLICATION");
+
+        env.set_list(&mut self.cohorts.stacks, "COHORT_STACKS");
+
+        env.problems
+    }
+
+    /// Replaces the tokens with the contents of their files, when set, and fetches the tokens that reference
+    /// a secret manager, returning the secrets that couldn't be read.
+    pub async fn resolve_secrets(&mut self) -> Vec<String> {
+        let mut problems = vec![];
+
+        for (name, token, token_file) in [
+            ("LOKI_TOKEN", &mut self.loki.token, &self.loki.token_file),
+            (
+                "GITHUB_TOKEN",
+                &mut self.github.token,
+                &self.github.token_file,
+            ),
+        ] {
+            if let Some(path) = token_file {
+                match secrets::read_file(path) {
+                    Ok(value) => *token = Some(value),
+                    Err(e) => problems.push(format!("{}: {}_FILE", e, name)),
+                }
+            }
+
+            if let Some(value) = token.as_deref() {
+                match secrets::resolve(name, value).await {
+                    Ok(value) => *token = Some(value),
+                    Err(e) => problems.push(e.to_string()),
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Returns the required settings that are missing or invalid.
+    pub fn validate(&self, fixtures: bool) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.server.port.is_none() {
+            problems.push("PORT is required".to_string());
+        }
+
+        if fixtures {
+            return problems;
+        }
+
+        match (&self.loki.url, self.loki.recordings) {
+            (_, Some(RecordingMode::Replay)) => {
+                if !self.loki.recordings_dir.is_dir() {
+                    problems.push(format!(
+                        "LOKI_RECORDINGS_DIR is not a directory: {}",
+                        self.loki.recordings_dir.display()
+                    ));
+                }
+            }
+            (None, _) => problems.push("LOKI_URL is required".to_string()),
+            (Some(url), _) if !is_http_url(url) => {
+                problems.push("LOKI_URL has to be an http or https URL".to_string())
+            }
+            (Some(_), _) => {}
+        }
+
+        if self.github.org.as_deref().is_none_or(str::is_empty) {
+            problems.push("GITHUB_ORG is required".to_string());
+        }
+
+        if self.github.token.as_deref().is_none_or(str::is_empty) {
+            problems.push("GITHUB_TOKEN is required".to_string());
+        }
+
+        problems
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// Applies environment variables to settings, collecting the ones that aren't valid.
+#[derive(Default)]
+struct Overrides {
+    problems: Vec<String>,
+}
+
+impl Overrides {
+    fn parse<T: FromStr>(&mut self, name: &str) -> Option<T> {
+        let value = env::var(name).ok()?;
+
+        match value.trim().parse::<T>() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                self.problems
+                    .push(format!("{} is not valid: {}", name, value));
+                None
+            }
+        }
+    }
+
+    fn set<T: FromStr>(&mut self, setting: &mut T, name: &str) {
+        if let Some(value) = self.parse(name) {
+            *setting = value;
+        }
+    }
+
+    fn set_option<T: FromStr>(&mut self, setting: &mut Option<T>, name: &str) {
+        if let Some(value) = self.parse(name) {
+            *setting = Some(value);
+        }
+    }
+
+    /// Overrides a list with a comma-separated environment variable, unless one of its items isn't valid.
+    fn set_list<T: FromStr>(&mut self, setting: &mut Vec<T>, name: &str) {
+        let Ok(value) = env::var(name) else {
+            return;
+        };
+
+        let items = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse::<T>().map_err(|_| item))
+            .collect::<Result<Vec<T>, &str>>();
+
+        match items {
+            Ok(items) => *setting = items,
+            Err(item) => self
+                .problems
+                .push(format!("{} is not valid: {}", name, item)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_formats() {
+        let yaml = AppConfig::from_yaml(
+            "
+loki:
+  url: https://loki.example.com
+  service_names: [github, gitlab]
+github:
+  org: liatrio
+",
+        )
+        .unwrap();
+
+        let toml = AppConfig::from_toml(
+            r#"
+[loki]
+url = "https://loki.example.com"
+service_names = ["github", "gitlab"]
+
+[github]
+org = "liatrio"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(yaml, toml);
+        assert_eq!(yaml.loki.url.as_deref(), Some("https://loki.example.com"));
+        assert_eq!(yaml.loki.max_pages, 10);
+        assert_eq!(yaml.github.teams_cache_ttl_seconds, 3600);
+        assert_eq!(yaml.environments, EnvironmentsConfig::default());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let mut config = AppConfig::default();
+
+        assert_eq!(
+            config.validate(false),
+            [
+                "PORT is required",
+                "LOKI_URL is required",
+                "GITHUB_ORG is required",
+                "GITHUB_TOKEN is required"
+            ]
+        );
+        assert_eq!(config.validate(true), ["PORT is required"]);
+
+        config.server.port = Some(3000);
+        config.loki.url = Some("loki.example.com".to_string());
+        config.github.org = Some("liatrio".to_string());
+        config.github.token = Some("s3cr3t".to_string());
+
+        assert_eq!(
+            config.validate(false),
+            ["LOKI_URL has to be an http or https URL"]
+        );
+
+        config.loki.url = Some("https://loki.example.com".to_string());
+
+        assert!(config.validate(false).is_empty());
+
+        config.loki.url = None;
+        config.loki.recordings = Some(RecordingMode::Replay);
+        config.loki.recordings_dir = PathBuf::from("does-not-exist");
+
+        assert_eq!(
+            config.validate(false),
+            ["LOKI_RECORDINGS_DIR is not a directory: does-not-exist"]
+        );
+
+        config.loki.recordings_dir = PathBuf::from("src");
+
+        assert!(config.validate(false).is_empty());
+    }
+
+    #[test]
+    fn test_config_sections() {
+        let config = AppConfig::from_yaml(
+            "
+cache:
+  ttl_seconds: 60
+limits:
+  max_concurrent_requests: 0
+hotfixes:
+  labels: [urgent]
+dedup:
+  deployments: keep-last
+",
+        )
+        .unwrap();
+
+        assert_eq!(config.cache.ttl_seconds, 60);
+        assert_eq!(config.cache.max_entries, 500);
+        assert_eq!(config.limits.max_concurrent_requests, 0);
+        assert_eq!(config.limits.request_timeout_seconds, 300);
+        assert_eq!(config.hotfixes.labels, ["urgent"]);
+        assert_eq!(config.hotfixes.branches, ["hotfix/*", "hotfix-*"]);
+        assert_eq!(config.dedup.deployments, "keep-last");
+        assert_eq!(config.breaker, BreakerSettings::default());
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_settings() {
+        assert!(AppConfig::from_yaml("loki:\n  uri: https://loki.example.com\n").is_err());
+        assert!(AppConfig::from_toml("[logs]\nurl = \"https://loki.example.com\"\n").is_err());
+    }
+}
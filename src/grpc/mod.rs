@@ -0,0 +1,286 @@
+//! A gRPC service for platform services that prefer streaming, strongly typed clients, served alongside the
+//! REST API. The service is defined in `proto/dora/v1/dora.proto`.
+
+mod proto;
+
+use axum::http::StatusCode;
+use chrono::DateTime;
+use futures::stream;
+use tonic::{
+    codec::ProstCodec,
+    codegen::{empty_body, http, Body, BoxFuture, BoxStream, Context, Poll, Service, StdError},
+    server::{Grpc, NamedService, ServerStreamingService},
+    Code, Status,
+};
+
+use crate::{
+    helpers::{
+        auth::{self, Authenticated},
+        context, request, response,
+    },
+    routes::{
+        auth::log_authenticated,
+        data::{get_records, DataCache},
+        teams::{expand_child_teams, TeamsCache},
+    },
+};
+
+const GET_RECORDS: &str = "/dora.v1.DoraMetrics/GetRecords";
+
+/// Serves the `dora.v1.DoraMetrics` service from the same caches as the REST routes.
+#[derive(Clone)]
+pub struct DoraMetricsServer {
+    ctx: context::Context,
+    data_cache: DataCache,
+    teams_cache: TeamsCache,
+}
+
+impl DoraMetricsServer {
+    pub fn new(ctx: context::Context, data_cache: DataCache, teams_cache: TeamsCache) -> Self {
+        DoraMetricsServer {
+            ctx,
+            data_cache,
+            teams_cache,
+        }
+    }
+
+    async fn get_records(
+        &self,
+        request: proto::DataRequest,
+        authenticated_as: Option<String>,
+    ) -> Result<Vec<proto::ResponseRecord>, Status> {
+        let mut request = to_data_request(request).map_err(to_status)?;
+        request.authenticated_as = authenticated_as;
+
+        expand_child_teams(&self.ctx, &self.teams_cache, &mut request)
+            .await
+            .map_err(to_status)?;
+
+        let records = get_records(&self.ctx, &self.data_cache, request)
+            .await
+            .map_err(to_status)?;
+
+        Ok(records.into_iter().map(to_proto_record).collect())
+    }
+}
+
+impl NamedService for DoraMetricsServer {
+    const NAME: &'static str = "dora.v1.DoraMetrics";
+}
+
+struct GetRecordsService(DoraMetricsServer);
+
+impl ServerStreamingService<proto::DataRequest> for GetRecordsService {
+    type Response = proto::ResponseRecord;
+    type ResponseStream = BoxStream<proto::ResponseRecord>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<proto::DataRequest>) -> Self::Future {
+        let server = self.0.clone();
+
+        Box::pin(async move {
+            let authenticated = authorize(&request).await.map_err(to_status)?;
+
+            let records = server
+                .get_records(request.into_inner(), authenticated.principal())
+                .await?;
+            let stream: Self::ResponseStream = Box::pin(stream::iter(records.into_iter().map(Ok)));
+
+            Ok(tonic::Response::new(stream))
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for DoraMetricsServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        match request.uri().path() {
+            GET_RECORDS => {
+                let service = GetRecordsService(self.clone());
+
+                Box::pin(async move {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+
+                    Ok(grpc.server_streaming(service, request).await)
+                })
+            }
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", (Code::Unimplemented as i32).to_string())
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap())
+            }),
+        }
+    }
+}
+
+/// Rejects calls that don't carry one of the `API_KEYS` in the `x-api-key` metadata, or a valid OIDC token in
+/// the `authorization` metadata, like the REST routes, returning who the call was made by.
+async fn authorize<T>(request: &tonic::Request<T>) -> Result<Authenticated, StatusCode> {
+    let metadata = |name: &str| {
+        request
+            .metadata()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+
+    let bearer = metadata("authorization").and_then(|value| value.strip_prefix("Bearer "));
+
+    match auth::authenticate(metadata("x-api-key"), bearer).await {
+        Ok(authenticated) => {
+            log_authenticated(GET_RECORDS, &authenticated);
+            Ok(authenticated)
+        }
+        Err(e) => {
+            tracing::warn!("{} Rejected: {}", GET_RECORDS, e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+fn to_data_request(request: proto::DataRequest) -> Result<request::DataRequest, StatusCode> {
+    let (Some(start), Some(end)) = (
+        DateTime::from_timestamp(request.start, 0),
+        DateTime::from_timestamp(request.end, 0),
+    ) else {
+        tracing::error!("Request Window Out Of Range");
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    Ok(request::DataRequest {
+        repositories: match request.repositories.is_empty() {
+            true => None,
+            false => Some(request.repositories),
+        },
+        team: request.team,
+        teams: match request.teams.is_empty() {
+            true => None,
+            false => Some(request.teams),
+        },
+        start,
+        end,
+        include_child_teams: Some(request.include_child_teams),
+        environments: match request.environments.is_empty() {
+            true => None,
+            false => Some(request.environments),
+        },
+        exclude_repositories: match request.exclude_repositories.is_empty() {
+            true => None,
+            false => Some(request.exclude_repositories),
+        },
+        exclude_authors: match request.exclude_authors.is_empty() {
+            true => None,
+            false => Some(request.exclude_authors),
+        },
+        exclude_bots: Some(request.exclude_bots),
+        include_first_commit: Some(request.include_first_commit),
+        deduplication: request.deduplication,
+        failure_lookahead_days: request.failure_lookahead_days,
+        services: match request.services.is_empty() {
+            true => None,
+            false => Some(request.services),
+        },
+        namespaces: match request.namespaces.is_empty() {
+            true => None,
+            false => Some(request.namespaces),
+        },
+        tenant: request.tenant,
+        ..Default::default()
+    })
+}
+
+fn to_proto_record(record: response::ResponseRecord) -> proto::ResponseRecord {
+    proto::ResponseRecord {
+        repository: record.repository,
+        team: record.team,
+        title: record.title,
+        user: record.user,
+        sha: record.sha,
+        status: record.status,
+        failed_at: record.failed_at.map(|t| t.timestamp()),
+        merged_at: record.merged_at.map(|t| t.timestamp()),
+        created_at: record.created_at.timestamp(),
+        fixed_at: record.fixed_at.map(|t| t.timestamp()),
+        fixed_url: record.fixed_url,
+        deploy_url: record.deploy_url,
+        issue_url: record.issue_url,
+        change_url: record.change_url,
+        lead_time_seconds: record.lead_time.map(|d| d.seconds),
+        time_to_restore_seconds: record.time_to_restore.map(|d| d.seconds),
+        environment: record.environment,
+        first_commit_at: record.first_commit_at.map(|t| t.timestamp()),
+        hotfix: record.hotfix,
+        deploy_duration_seconds: record.deploy_duration_seconds,
+        open_failure: record.open_failure,
+        service: record.service,
+    }
+}
+
+fn to_status(status: StatusCode) -> Status {
+    match status {
+        StatusCode::BAD_REQUEST => Status::invalid_argument("Invalid Request"),
+        StatusCode::NOT_FOUND => Status::not_found("Not Found"),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated("Invalid API Key"),
+        StatusCode::SERVICE_UNAVAILABLE => Status::unavailable("Upstream Unavailable"),
+        _ => Status::internal("Processing Data Failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::duration::DurationValue;
+    use prost::Message;
+
+    #[test]
+    fn test_data_request_conversion() {
+        let request = proto::DataRequest {
+            team: Some("team-a".to_string()),
+            start: 1725840000,
+            end: 1725926400,
+            ..Default::default()
+        };
+
+        let decoded = proto::DataRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        let converted = to_data_request(decoded).unwrap();
+
+        assert_eq!(converted.team, Some("team-a".to_string()));
+        assert_eq!(converted.repositories, None);
+        assert_eq!(converted.start.to_rfc3339(), "2024-09-09T00:00:00+00:00");
+        assert!(to_data_request(proto::DataRequest {
+            start: i64::MAX,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_record_conversion() {
+        let record = response::ResponseRecord {
+            sha: "abc".to_string(),
+            created_at: DateTime::from_timestamp(1725926400, 0).unwrap(),
+            lead_time: Some(DurationValue::from_seconds(3600)),
+            ..Default::default()
+        };
+
+        let converted = to_proto_record(record);
+
+        assert_eq!(converted.sha, "abc");
+        assert_eq!(converted.created_at, 1725926400);
+        assert_eq!(converted.lead_time_seconds, Some(3600));
+        assert_eq!(converted.merged_at, None);
+    }
+}
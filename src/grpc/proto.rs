@@ -0,0 +1,88 @@
+//! The messages of `proto/dora/v1/dora.proto`.
+//!
+//! These are written out with `prost` derives instead of generated at build time, so building the API
+//! doesn't require `protoc`. Keep the field tags in step with the `.proto` file.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DataRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub repositories: Vec<String>,
+    #[prost(string, optional, tag = "2")]
+    pub team: Option<String>,
+    #[prost(int64, tag = "3")]
+    pub start: i64,
+    #[prost(int64, tag = "4")]
+    pub end: i64,
+    #[prost(bool, tag = "5")]
+    pub include_child_teams: bool,
+    #[prost(string, repeated, tag = "6")]
+    pub environments: Vec<String>,
+    #[prost(string, repeated, tag = "7")]
+    pub exclude_repositories: Vec<String>,
+    #[prost(string, repeated, tag = "8")]
+    pub teams: Vec<String>,
+    #[prost(string, repeated, tag = "9")]
+    pub exclude_authors: Vec<String>,
+    #[prost(bool, tag = "10")]
+    pub exclude_bots: bool,
+    #[prost(bool, tag = "11")]
+    pub include_first_commit: bool,
+    #[prost(string, optional, tag = "12")]
+    pub deduplication: Option<String>,
+    #[prost(int64, optional, tag = "13")]
+    pub failure_lookahead_days: Option<i64>,
+    #[prost(string, repeated, tag = "14")]
+    pub services: Vec<String>,
+    #[prost(string, repeated, tag = "15")]
+    pub namespaces: Vec<String>,
+    #[prost(string, optional, tag = "16")]
+    pub tenant: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ResponseRecord {
+    #[prost(string, tag = "1")]
+    pub repository: String,
+    #[prost(string, tag = "2")]
+    pub team: String,
+    #[prost(string, optional, tag = "3")]
+    pub title: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub user: Option<String>,
+    #[prost(string, tag = "5")]
+    pub sha: String,
+    #[prost(bool, tag = "6")]
+    pub status: bool,
+    #[prost(int64, optional, tag = "7")]
+    pub failed_at: Option<i64>,
+    #[prost(int64, optional, tag = "8")]
+    pub merged_at: Option<i64>,
+    #[prost(int64, tag = "9")]
+    pub created_at: i64,
+    #[prost(int64, optional, tag = "10")]
+    pub fixed_at: Option<i64>,
+    #[prost(string, optional, tag = "11")]
+    pub fixed_url: Option<String>,
+    #[prost(string, tag = "12")]
+    pub deploy_url: String,
+    #[prost(string, optional, tag = "13")]
+    pub issue_url: Option<String>,
+    #[prost(string, tag = "14")]
+    pub change_url: String,
+    #[prost(int64, optional, tag = "15")]
+    pub lead_time_seconds: Option<i64>,
+    #[prost(int64, optional, tag = "16")]
+    pub time_to_restore_seconds: Option<i64>,
+    #[prost(string, tag = "17")]
+    pub environment: String,
+    #[prost(int64, optional, tag = "18")]
+    pub first_commit_at: Option<i64>,
+    #[prost(bool, tag = "19")]
+    pub hotfix: bool,
+    #[prost(int64, optional, tag = "20")]
+    pub deploy_duration_seconds: Option<i64>,
+    #[prost(bool, tag = "21")]
+    pub open_failure: bool,
+    #[prost(string, optional, tag = "22")]
+    pub service: Option<String>,
+}
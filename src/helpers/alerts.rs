@@ -0,0 +1,400 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+
+use super::context::Context;
+use super::{
+    metrics::{group_by_team, summarize, MetricsSummary},
+    notifiers::{
+        notify_all, Channel, EmailNotifier, Notification, Notifier, SlackNotifier, SmtpConfig,
+        TeamsNotifier, WebhookNotifier,
+    },
+    request::DataRequest,
+};
+use crate::{
+    config::{AlertsSettings, DigestSettings},
+    routes::data::{refresh_cache, DataCache},
+};
+
+/// A limit on one of a team's metrics, and the channels it alerts through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub metric: &'static str,
+    pub threshold: f64,
+    /// The channels the alert is sent through, or every configured channel when empty.
+    pub channels: Vec<Channel>,
+}
+
+impl AlertRule {
+    fn value(&self, summary: &MetricsSummary) -> Option<f64> {
+        match self.metric {
+            "change_failure_rate" => summary.change_failure_rate,
+            "mttr_hours" => summary.mttr_hours,
+            _ => None,
+        }
+    }
+}
+
+/// Configures the metric limits teams are alerted on, and where the alerts are sent.
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    pub webhooks: Vec<String>,
+    pub slack_webhooks: Vec<String>,
+    pub teams_webhooks: Vec<String>,
+    pub smtp: Option<SmtpConfig>,
+    pub rules: Vec<AlertRule>,
+    pub window_days: i64,
+    pub interval: std::time::Duration,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig {
+            webhooks: Vec::new(),
+            slack_webhooks: Vec::new(),
+            teams_webhooks: Vec::new(),
+            smtp: None,
+            rules: Vec::new(),
+            window_days: 7,
+            interval: std::time::Duration::from_secs(900),
+        }
+    }
+}
+
+/// Parses the channels a rule alerts through, naming the setting they were read from when one is unknown.
+fn parse_channels(channels: &[String], setting: &str) -> Result<Vec<Channel>> {
+    channels
+        .iter()
+        .map(|channel| channel.parse())
+        .collect::<Result<_>>()
+        .map_err(|e| anyhow::anyhow!(format!("{}: {}", e, setting)))
+}
+
+impl AlertConfig {
+    /// Builds the alerting configuration from the `alerts` settings, see `ALERT_WEBHOOK_URLS`,
+    /// `ALERT_SLACK_WEBHOOK_URLS`, `ALERT_TEAMS_WEBHOOK_URLS`, `ALERT_EMAIL_TO`, `ALERT_CHANGE_FAILURE_RATE`,
+    /// `ALERT_MTTR_HOURS` and their `_CHANNELS`, `ALERT_WINDOW_DAYS` and `ALERT_INTERVAL_SECONDS`. Alerts are
+    /// emailed through the SMTP relay of the `digest` settings. Negative thresholds are ignored, and a window or
+    /// interval of `0` keeps the default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a rule names an unknown channel, or an alert email address is invalid.
+    pub fn from_config(config: &AlertsSettings, digest: &DigestSettings) -> Result<Self> {
+        let defaults = AlertConfig::default();
+        let mut rules = Vec::new();
+
+        for (metric, threshold, channels, setting) in [
+            (
+                "change_failure_rate",
+                config.change_failure_rate,
+                &config.change_failure_rate_channels,
+                "ALERT_CHANGE_FAILURE_RATE_CHANNELS",
+            ),
+            (
+                "mttr_hours",
+                config.mttr_hours,
+                &config.mttr_hours_channels,
+                "ALERT_MTTR_HOURS_CHANNELS",
+            ),
+        ] {
+            let channels = parse_channels(channels, setting)?;
+
+            if let Some(threshold) = threshold.filter(|value| *value >= 0.0) {
+                rules.push(AlertRule {
+                    metric,
+                    threshold,
+                    channels,
+                });
+            }
+        }
+
+        Ok(AlertConfig {
+            webhooks: config.webhook_urls.clone(),
+            slack_webhooks: config.slack_webhook_urls.clone(),
+            teams_webhooks: config.teams_webhook_urls.clone(),
+            smtp: SmtpConfig::from_config(digest, &config.email_to, "ALERT_EMAIL_TO")?
+                .filter(|smtp| !smtp.to.is_empty()),
+            rules,
+            window_days: Some(config.window_days)
+                .filter(|value| *value > 0)
+                .unwrap_or(defaults.window_days),
+            interval: Some(config.interval_seconds)
+                .filter(|value| *value > 0)
+                .map_or(defaults.interval, std::time::Duration::from_secs),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.notifiers().is_empty() && !self.rules.is_empty()
+    }
+
+    /// Returns a notifier for every configured destination.
+    pub fn notifiers(&self) -> Vec<Box<dyn Notifier>> {
+        let webhooks = self
+            .webhooks
+            .iter()
+            .map(|url| Box::new(WebhookNotifier { url: url.clone() }) as Box<dyn Notifier>);
+        let slack = self
+            .slack_webhooks
+            .iter()
+            .map(|url| Box::new(SlackNotifier { url: url.clone() }) as Box<dyn Notifier>);
+        let teams = self
+            .teams_webhooks
+            .iter()
+            .map(|url| Box::new(TeamsNotifier { url: url.clone() }) as Box<dyn Notifier>);
+        let email = self
+            .smtp
+            .iter()
+            .map(|smtp| Box::new(EmailNotifier { smtp: smtp.clone() }) as Box<dyn Notifier>);
+
+        webhooks.chain(slack).chain(teams).chain(email).collect()
+    }
+
+    /// Returns an alert for each of a team's metrics that is over its limit.
+    pub fn evaluate(&self, team: &str, summary: &MetricsSummary) -> Vec<Alert> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match rule.value(summary) {
+                Some(value) if value > rule.threshold => Some(Alert {
+                    text: format!(
+                        "DORA alert: {} for {} is {:.1}, over the limit of {:.1}",
+                        rule.metric, team, value, rule.threshold
+                    ),
+                    team: team.to_string(),
+                    metric: rule.metric,
+                    value,
+                    threshold: rule.threshold,
+                    channels: rule.channels.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A metric over its limit. `text` makes the payload a valid Slack incoming webhook message, the other
+/// fields are for webhooks that process alerts themselves.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub text: String,
+    pub team: String,
+    pub metric: &'static str,
+    pub value: f64,
+    pub threshold: f64,
+    /// The channels of the rule the alert was raised by.
+    #[serde(skip)]
+    pub channels: Vec<Channel>,
+}
+
+impl Alert {
+    /// The alert as a notification, with its details in the payload sent to generic webhooks.
+    pub fn notification(&self) -> Notification {
+        Notification {
+            subject: format!("DORA alert for {}", self.team),
+            text: self.text.clone(),
+            payload: json!(self),
+        }
+    }
+
+    /// Returns whether the alert is sent through a notifier, by the channels of its rule.
+    pub fn is_sent_to(&self, notifier: &dyn Notifier) -> bool {
+        self.channels.is_empty() || self.channels.contains(&notifier.channel())
+    }
+}
+
+/// Keeps the alerts that weren't already firing on the last evaluation, and remembers which are firing,
+/// so a team is alerted when a metric crosses its limit rather than on every evaluation.
+fn newly_firing(firing: &mut HashSet<(String, &'static str)>, alerts: Vec<Alert>) -> Vec<Alert> {
+    let previous = std::mem::take(firing);
+
+    firing.extend(
+        alerts
+            .iter()
+            .map(|alert| (alert.team.clone(), alert.metric)),
+    );
+
+    alerts
+        .into_iter()
+        .filter(|alert| !previous.contains(&(alert.team.clone(), alert.metric)))
+        .collect()
+}
+
+/// Evaluates every team's metrics over the trailing window on an interval, sending an alert through the
+/// channels of its rule when a metric crosses its limit.
+///
+/// The metrics are recalculated from fresh data each time, queried again instead of reusing the gathered
+/// events, so a failure or fix is seen on the next evaluation. This also refreshes the cached response.
+///
+/// # Arguments
+///
+/// * `config` - The alerting configuration, see `AlertConfig::from_env`.
+/// * `ctx` - The configuration and HTTP client the data is gathered and the webhooks are called with.
+/// * `cache` - The data cache the evaluated data is stored in.
+pub async fn evaluate_periodically(config: AlertConfig, ctx: Context, cache: DataCache) {
+    let client = &ctx.client;
+    let mut firing = HashSet::new();
+    let mut interval = tokio::time::interval(config.interval);
+
+    loop {
+        interval.tick().await;
+
+        let request = DataRequest::trailing_days(None, config.window_days, Utc::now());
+        let days = (request.end - request.start).num_seconds() as f64 / 86_400.0;
+
+        let records = match refresh_cache(&ctx, &cache, request, true).await {
+            Ok(response) => response.into_records(),
+            Err(e) => {
+                tracing::error!("Alert Evaluation Failed: {:?}", e);
+                continue;
+            }
+        };
+
+        let alerts = group_by_team(&records)
+            .into_iter()
+            .flat_map(|(team, team_records)| {
+                config.evaluate(&team, &summarize(&team_records, days))
+            })
+            .collect();
+
+        for alert in newly_firing(&mut firing, alerts) {
+            tracing::warn!("{}", alert.text);
+
+            let notifiers: Vec<Box<dyn Notifier>> = config
+                .notifiers()
+                .into_iter()
+                .filter(|notifier| alert.is_sent_to(notifier.as_ref()))
+                .collect();
+
+            notify_all(client, &notifiers, &alert.notification(), "Alert").await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AlertConfig {
+        AlertConfig::from_config(
+            &AlertsSettings {
+                webhook_urls: vec!["http://localhost/hook".to_string()],
+                teams_webhook_urls: vec!["http://localhost/teams".to_string()],
+                change_failure_rate: Some(15.0),
+                change_failure_rate_channels: vec!["teams".to_string()],
+                mttr_hours: Some(24.0),
+                ..Default::default()
+            },
+            &DigestSettings::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_alert_rules() {
+        let config = config();
+
+        assert!(config.is_enabled());
+        assert_eq!(
+            config.rules,
+            vec![
+                AlertRule {
+                    metric: "change_failure_rate",
+                    threshold: 15.0,
+                    channels: vec![Channel::Teams],
+                },
+                AlertRule {
+                    metric: "mttr_hours",
+                    threshold: 24.0,
+                    channels: vec![],
+                },
+            ]
+        );
+        assert!(AlertConfig::from_config(
+            &AlertsSettings {
+                mttr_hours_channels: vec!["pager".to_string()],
+                ..Default::default()
+            },
+            &DigestSettings::default(),
+        )
+        .unwrap_err()
+        .to_string()
+        .ends_with("ALERT_MTTR_HOURS_CHANNELS"));
+    }
+
+    #[test]
+    fn test_alerts_are_sent_to_their_channels() {
+        let summary = MetricsSummary {
+            change_failure_rate: Some(20.0),
+            mttr_hours: Some(30.0),
+            ..Default::default()
+        };
+        let config = config();
+        let alerts = config.evaluate("team-a", &summary);
+
+        let channels = |alert: &Alert| {
+            config
+                .notifiers()
+                .iter()
+                .filter(|notifier| alert.is_sent_to(notifier.as_ref()))
+                .map(|notifier| notifier.channel())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(channels(&alerts[0]), vec![Channel::Teams]);
+        assert_eq!(channels(&alerts[1]), vec![Channel::Webhook, Channel::Teams]);
+        assert_eq!(alerts[0].notification().subject, "DORA alert for team-a");
+        assert_eq!(
+            alerts[0].notification().payload,
+            json!({
+                "text": "DORA alert: change_failure_rate for team-a is 20.0, over the limit of 15.0",
+                "team": "team-a",
+                "metric": "change_failure_rate",
+                "value": 20.0,
+                "threshold": 15.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_evaluate_alerts() {
+        let summary = MetricsSummary {
+            change_failure_rate: Some(20.0),
+            mttr_hours: Some(4.0),
+            ..Default::default()
+        };
+
+        let alerts = config().evaluate("team-a", &summary);
+
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric, "change_failure_rate");
+        assert_eq!(
+            alerts[0].text,
+            "DORA alert: change_failure_rate for team-a is 20.0, over the limit of 15.0"
+        );
+        assert!(config()
+            .evaluate("team-a", &MetricsSummary::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_alerts_fire_once_per_crossing() {
+        let summary = MetricsSummary {
+            change_failure_rate: Some(20.0),
+            ..Default::default()
+        };
+        let mut firing = HashSet::new();
+
+        let first = newly_firing(&mut firing, config().evaluate("team-a", &summary));
+        let second = newly_firing(&mut firing, config().evaluate("team-a", &summary));
+        let recovered = newly_firing(&mut firing, Vec::new());
+        let third = newly_firing(&mut firing, config().evaluate("team-a", &summary));
+
+        assert_eq!(first.len(), 1);
+        assert!(second.is_empty());
+        assert!(recovered.is_empty());
+        assert_eq!(third.len(), 1);
+    }
+}
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::env;
+
+/// Configures how unusual a bucket has to be to be flagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyConfig {
+    pub window: usize,
+    pub threshold: f64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        AnomalyConfig {
+            window: 4,
+            threshold: 2.0,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    /// Reads the anomaly detection configuration from the environment.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `ANOMALY_WINDOW_BUCKETS` - How many preceding buckets the rolling mean is taken over. Defaults to `4`.
+    /// * `ANOMALY_THRESHOLD` - How many standard deviations from the rolling mean a bucket has to be to be
+    ///   flagged. Defaults to `2.0`.
+    pub fn from_env() -> Self {
+        let defaults = AnomalyConfig::default();
+
+        let window = env::var("ANOMALY_WINDOW_BUCKETS")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value >= 2)
+            .unwrap_or(defaults.window);
+
+        let threshold = env::var("ANOMALY_THRESHOLD")
+            .ok()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| *value > 0.0)
+            .unwrap_or(defaults.threshold);
+
+        AnomalyConfig { window, threshold }
+    }
+}
+
+/// A bucket whose value deviates significantly from the buckets before it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub start: DateTime<Utc>,
+    pub metric: &'static str,
+    pub value: f64,
+    /// The rolling mean of the preceding buckets.
+    pub expected: f64,
+    /// How many standard deviations the value is from `expected`, negative when below it.
+    pub deviation: f64,
+}
+
+/// Flags the buckets that are more than `threshold` standard deviations from the rolling mean of the
+/// `window` buckets before them.
+///
+/// Buckets without a value are skipped, and a bucket is only compared once at least two of the preceding
+/// buckets have a value. Preceding buckets that don't vary at all are never flagged against, since any
+/// change would be an infinite deviation.
+///
+/// # Arguments
+///
+/// * `metric` - The name of the metric the annotations are for, e.g. `deployments`.
+/// * `buckets` - The start and value of each bucket, in order.
+/// * `config` - The rolling window and threshold.
+pub fn detect(
+    metric: &'static str,
+    buckets: &[(DateTime<Utc>, Option<f64>)],
+    config: &AnomalyConfig,
+) -> Vec<Annotation> {
+    buckets
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(index, (start, value))| {
+            let value = (*value)?;
+
+            let preceding: Vec<f64> = buckets[index.saturating_sub(config.window)..index]
+                .iter()
+                .filter_map(|(_, value)| *value)
+                .collect();
+
+            if preceding.len() < 2 {
+                return None;
+            }
+
+            let mean = preceding.iter().sum::<f64>() / preceding.len() as f64;
+            let variance = preceding
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / preceding.len() as f64;
+
+            let deviation = match variance.sqrt() {
+                0.0 => return None,
+                std_dev => (value - mean) / std_dev,
+            };
+
+            (deviation.abs() >= config.threshold).then_some(Annotation {
+                start: *start,
+                metric,
+                value,
+                expected: mean,
+                deviation,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_detect() {
+        let start = Utc::now();
+        let values = [
+            Some(10.0),
+            Some(12.0),
+            Some(10.0),
+            Some(12.0),
+            Some(30.0),
+            None,
+            Some(11.0),
+        ];
+
+        let buckets: Vec<(DateTime<Utc>, Option<f64>)> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (start + Duration::weeks(index as i64), *value))
+            .collect();
+
+        let annotations = detect("deployments", &buckets, &AnomalyConfig::default());
+
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].start, start + Duration::weeks(4));
+        assert_eq!(annotations[0].expected, 11.0);
+        assert_eq!(annotations[0].deviation, 19.0);
+    }
+
+    #[test]
+    fn test_detect_flat_history() {
+        let start = Utc::now();
+        let buckets = vec![
+            (start, Some(0.0)),
+            (start + Duration::weeks(1), Some(0.0)),
+            (start + Duration::weeks(2), Some(5.0)),
+        ];
+
+        assert!(detect("deployments", &buckets, &AnomalyConfig::default()).is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! The API keys and OIDC tokens clients authenticate with, so the API can be exposed beyond the cluster.
+
+use anyhow::{anyhow, Result};
+use std::sync::OnceLock;
+
+use super::oidc::{self, Claims};
+use crate::config::AuthSettings;
+
+/// Compares two tokens in constant time, so a token can't be guessed from response timings.
+pub fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (l, r)| diff | (l ^ r))
+            == 0
+}
+
+/// The accepted API keys, each with a name identifying the client in the logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeys {
+    keys: Vec<(String, String)>,
+}
+
+impl ApiKeys {
+    /// Parses comma separated `name:key` pairs, e.g. `dashboard:k3y,ci:s3cr3t`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first entry without a name or key, or whose name was already used.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut keys: Vec<(String, String)> = vec![];
+
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, key) = match entry.split_once(':') {
+                Some((name, key)) if !name.trim().is_empty() && !key.trim().is_empty() => {
+                    (name.trim(), key.trim())
+                }
+                // The entry may be a bare key, so it's left out of the error.
+                _ => return Err(anyhow!("API_KEYS entries have to be name:key pairs")),
+            };
+
+            if keys.iter().any(|(existing, _)| existing == name) {
+                return Err(anyhow!(format!("Duplicate API Key Name: {}", name)));
+            }
+
+            keys.push((name.to_string(), key.to_string()));
+        }
+
+        Ok(ApiKeys { keys })
+    }
+
+    /// Returns the name of the provided key, or `None` when it isn't one of the keys. Every key is compared,
+    /// so the timing doesn't reveal which keys were close.
+    pub fn name_of(&self, provided: &str) -> Option<&str> {
+        self.keys.iter().fold(None, |found, (name, key)| {
+            match tokens_match(provided, key) {
+                true => Some(name.as_str()),
+                false => found,
+            }
+        })
+    }
+
+    /// Returns the names of the keys, which unlike the keys can be shown.
+    pub fn names(&self) -> Vec<&str> {
+        self.keys.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+static API_KEYS: OnceLock<Option<ApiKeys>> = OnceLock::new();
+
+/// Loads the API keys at startup, so a malformed entry fails fast instead of rejecting every request.
+pub fn init(config: &AuthSettings) -> Result<()> {
+    let keys = Some(ApiKeys::parse(&config.api_keys.join(","))?).filter(|keys| !keys.is_empty());
+
+    API_KEYS
+        .set(keys)
+        .map_err(|_| anyhow!("API keys are already initialized"))
+}
+
+/// Returns the configured API keys, or `None` when requests aren't authenticated with them.
+pub fn api_keys() -> Option<&'static ApiKeys> {
+    API_KEYS.get_or_init(|| None).as_ref()
+}
+
+/// Who a request was made by.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authenticated {
+    /// Neither `API_KEYS` nor OIDC is configured, so requests aren't authenticated.
+    Anonymous,
+    /// The name of the API key the request carried.
+    ApiKey(&'static str),
+    /// The claims of the OIDC token the request carried.
+    Token(Claims),
+}
+
+impl Authenticated {
+    /// The principal Loki usage is attributed to, the name of the API key or the subject of the token.
+    pub fn principal(&self) -> Option<String> {
+        match self {
+            Authenticated::Anonymous => None,
+            Authenticated::ApiKey(name) => Some(name.to_string()),
+            Authenticated::Token(claims) => Some(claims.sub.clone()),
+        }
+    }
+}
+
+/// Authenticates a request by its API key, or else its bearer token.
+///
+/// # Errors
+///
+/// Returns an error when authentication is configured and the request carries neither a valid API key nor a
+/// valid token.
+pub async fn authenticate(api_key: Option<&str>, bearer: Option<&str>) -> Result<Authenticated> {
+    let keys = api_keys();
+    let verifier = oidc::verifier();
+
+    if keys.is_none() && verifier.is_none() {
+        return Ok(Authenticated::Anonymous);
+    }
+
+    if let (Some(keys), Some(api_key)) = (keys, api_key) {
+        return keys
+            .name_of(api_key)
+            .map(Authenticated::ApiKey)
+            .ok_or_else(|| anyhow!("Unknown API Key"));
+    }
+
+    if let (Some(verifier), Some(bearer)) = (verifier, bearer) {
+        return verifier.verify(bearer).await.map(Authenticated::Token);
+    }
+
+    Err(anyhow!("Missing Credentials"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_keys() {
+        let keys = ApiKeys::parse(" dashboard:k3y, ci:s3:cr3t ,").unwrap();
+
+        assert_eq!(keys.name_of("k3y"), Some("dashboard"));
+        assert_eq!(keys.name_of("s3:cr3t"), Some("ci"));
+        assert_eq!(keys.name_of("k3"), None);
+        assert!(ApiKeys::parse("").unwrap().is_empty());
+        assert!(ApiKeys::parse("k3y").is_err());
+        assert!(ApiKeys::parse("ci:").is_err());
+        assert!(ApiKeys::parse("ci:a,ci:b").is_err());
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret-longer", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[test]
+    fn test_principal() {
+        let claims = Claims {
+            sub: "alice".to_string(),
+            other: Default::default(),
+        };
+
+        assert_eq!(Authenticated::Anonymous.principal(), None);
+        assert_eq!(
+            Authenticated::ApiKey("dashboard").principal().as_deref(),
+            Some("dashboard")
+        );
+        assert_eq!(
+            Authenticated::Token(claims).principal().as_deref(),
+            Some("alice")
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Splits a request's window into the batches Loki is queried in, sizing each batch by how the previous ones
+//! went: busy or slow windows are queried in smaller batches, quiet ones in larger batches.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::OnceLock;
+
+use super::request::DataRequest;
+use crate::config::BatchingSettings;
+
+/// A batch is shrunk when its fullest query returned at least this share of the entry limit.
+const SHRINK_FILL: f64 = 0.8;
+/// A batch is grown when its fullest query returned less than this share of the entry limit.
+const GROW_FILL: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// The window of the first batch, and of every batch when `adaptive` is `false`.
+    pub initial: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub adaptive: bool,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            initial: Duration::days(5),
+            min: Duration::hours(1),
+            max: Duration::days(30),
+            adaptive: true,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Builds the batch sizes from the `batching` settings, see `LOKI_DAYS_BATCH_SIZE`,
+    /// `LOKI_ADAPTIVE_BATCHING`, `LOKI_MIN_BATCH_HOURS` and `LOKI_MAX_BATCH_DAYS`, keeping the default of any
+    /// size that isn't positive.
+    pub fn from_config(config: &BatchingSettings) -> Self {
+        let default = BatchConfig::default();
+
+        let positive = |value: i64| Some(value).filter(|value| *value > 0);
+
+        let min = positive(config.min_batch_hours).map_or(default.min, Duration::hours);
+        let max = positive(config.max_batch_days)
+            .map_or(default.max, Duration::days)
+            .max(min);
+
+        BatchConfig {
+            initial: positive(config.days_batch_size)
+                .map_or(default.initial, Duration::days)
+                .clamp(min, max),
+            min,
+            max,
+            adaptive: config.adaptive,
+        }
+    }
+}
+
+static CONFIG: OnceLock<BatchConfig> = OnceLock::new();
+
+/// Sets the batch sizes once at startup, instead of reading them on every request.
+pub fn init(config: &BatchingSettings) -> Result<()> {
+    CONFIG
+        .set(BatchConfig::from_config(config))
+        .map_err(|_| anyhow!("Batch sizes are already initialized"))
+}
+
+/// Returns the configured batch sizes, or the defaults when they were never initialized.
+pub fn config() -> BatchConfig {
+    *CONFIG.get_or_init(BatchConfig::default)
+}
+
+/// The batches of a request, from the end of its window back.
+///
+/// Each batch is taken with `current`, then either `complete`d with the entries its fullest query returned, which
+/// moves on to the rest of the window, or `retry`d after it failed, which queries it again with a smaller
+/// window when it timed out.
+#[derive(Debug, Clone)]
+pub struct Batches {
+    request: DataRequest,
+    end: DateTime<Utc>,
+    size: Duration,
+    limit: usize,
+    config: BatchConfig,
+}
+
+impl Batches {
+    /// Creates the batches of a request whose queries return at most `limit` entries per page.
+    pub fn new(request: &DataRequest, limit: u16, config: BatchConfig) -> Self {
+        Batches {
+            request: request.clone(),
+            end: request.end,
+            size: config.initial,
+            limit: usize::from(limit),
+            config,
+        }
+    }
+
+    /// Returns the request for the current batch, or `None` once the whole window has been queried. The last
+    /// batch covers whatever is left of the window, including windows shorter than a batch.
+    pub fn current(&self) -> Option<DataRequest> {
+        (self.end > self.request.start).then(|| DataRequest {
+            start: self.start(),
+            end: self.end,
+            ..self.request.clone()
+        })
+    }
+
+    /// Moves on from the current batch, whose fullest query returned `entries`, sizing the next batch by it.
+    pub fn complete(&mut self, entries: usize) {
+        self.end = self.start();
+
+        if !self.config.adaptive {
+            return;
+        }
+
+        let fill = entries as f64 / self.limit.max(1) as f64;
+
+        if fill >= SHRINK_FILL {
+            self.resize(self.size / 2);
+        } else if fill < GROW_FILL {
+            self.resize(self.size * 2);
+        }
+    }
+
+    /// Shrinks the current batch after its query failed, so it can be queried again.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error back when it isn't a timeout, or the batch can't be made any smaller.
+    pub fn retry(&mut self, e: anyhow::Error) -> Result<()> {
+        if !self.config.adaptive || self.size <= self.config.min || !is_timeout(&e) {
+            return Err(e);
+        }
+
+        tracing::warn!("Loki Batch Timed Out, Retrying: {:?}", e);
+        self.resize(self.size / 2);
+
+        Ok(())
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        (self.end - self.size).max(self.request.start)
+    }
+
+    fn resize(&mut self, size: Duration) {
+        let size = size.clamp(self.config.min, self.config.max);
+
+        if size != self.size {
+            tracing::debug!(
+                "Loki Batch Resized From {}s To {}s",
+                self.size.num_seconds(),
+                size.num_seconds()
+            );
+            self.size = size;
+        }
+    }
+}
+
+/// Returns whether a query failed because it took longer than `HTTP_TIMEOUT_SECONDS`.
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(hours: i64) -> DataRequest {
+        let end = DateTime::parse_from_rfc3339("2024-09-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        DataRequest {
+            start: end - Duration::hours(hours),
+            end,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fixed_batches() {
+        let request = request(12 * 24 + 6);
+        let config = BatchConfig {
+            adaptive: false,
+            ..Default::default()
+        };
+        let mut batches = Batches::new(&request, 5000, config);
+        let mut windows = vec![];
+
+        while let Some(batch) = batches.current() {
+            windows.push((batch.start, batch.end));
+            batches.complete(0);
+        }
+
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].0, request.end - Duration::days(5));
+        assert_eq!(
+            windows[2],
+            (request.start, request.end - Duration::days(10))
+        );
+        assert!(Batches::new(&self::request(0), 5000, config)
+            .current()
+            .is_none());
+    }
+
+    #[test]
+    fn test_adaptive_batches() {
+        let request = request(60 * 24);
+        let config = BatchConfig {
+            initial: Duration::days(4),
+            min: Duration::days(1),
+            max: Duration::days(8),
+            adaptive: true,
+        };
+        let mut batches = Batches::new(&request, 100, config);
+        let mut sizes = vec![];
+
+        for entries in [90, 50, 10, 10, 10, 0, 100, 100] {
+            let batch = batches.current().unwrap();
+
+            sizes.push((batch.end - batch.start).num_days());
+            batches.complete(entries);
+        }
+
+        assert_eq!(sizes, vec![4, 2, 2, 4, 8, 8, 8, 4]);
+        assert_eq!(batches.size, Duration::days(2));
+    }
+
+    #[test]
+    fn test_retry_only_shrinks_timeouts() {
+        let request = request(10 * 24);
+        let mut batches = Batches::new(&request, 5000, BatchConfig::default());
+
+        assert!(batches
+            .retry(anyhow::anyhow!("Loki Responded with status: 400"))
+            .is_err());
+        assert_eq!(
+            batches.current().unwrap().start,
+            request.end - Duration::days(5)
+        );
+    }
+}
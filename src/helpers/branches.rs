@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use std::sync::OnceLock;
+
+use super::patterns::{matches_any, parse_patterns, NamePattern};
+use crate::config::BranchesSettings;
+
+static MAIN_BRANCHES: OnceLock<Vec<NamePattern>> = OnceLock::new();
+
+/// Parses the branches whose merges count towards lead time from the `branches` settings, see
+/// `MAIN_BRANCH_NAMES`.
+///
+/// # Errors
+///
+/// Returns an error if a glob or regex is invalid.
+pub fn from_config(config: &BranchesSettings) -> Result<Vec<NamePattern>> {
+    parse_patterns(
+        &config
+            .main
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<&str>>(),
+    )
+}
+
+/// Loads the main branches at startup, so an invalid pattern fails fast instead of on the first query.
+pub fn init(config: &BranchesSettings) -> Result<()> {
+    let branches = from_config(config)?;
+
+    MAIN_BRANCHES
+        .set(branches)
+        .map_err(|_| anyhow!("Main branches are already initialized"))
+}
+
+/// Returns whether a merge into a branch counts towards lead time. Merges from events that don't record
+/// their base branch always count.
+pub fn is_main_branch(branch: Option<&str>) -> bool {
+    branch.is_none_or(|branch| matches_any(main_branches(), branch))
+}
+
+/// Returns the configured main branches, or the defaults when they were never initialized.
+pub fn main_branches() -> &'static [NamePattern] {
+    MAIN_BRANCHES.get_or_init(|| parse_patterns(&["main", "master"]).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_main_branches() {
+        assert!(is_main_branch(Some("main")));
+        assert!(is_main_branch(Some("Master")));
+        assert!(is_main_branch(None));
+        assert!(!is_main_branch(Some("feature/login")));
+        assert!(!is_main_branch(Some("main-backup")));
+    }
+}
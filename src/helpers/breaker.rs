@@ -0,0 +1,349 @@
+//! Circuit breakers for the Loki and GitHub clients, so requests fail fast while an upstream is down instead of
+//! each waiting for its own timeout.
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::{
+    fmt,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use super::upstreams::Upstream;
+use crate::config::BreakerSettings;
+
+/// When a breaker opens and how long it stays open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerConfig {
+    /// The consecutive failed calls that open a breaker, or `0` to never open it.
+    pub failures: u32,
+    /// How long an open breaker rejects calls before letting them through again to test the upstream.
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        BreakerConfig {
+            failures: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl BreakerConfig {
+    /// Builds the breaker settings from the `breaker` settings, see `CIRCUIT_BREAKER_FAILURES` and
+    /// `CIRCUIT_BREAKER_COOLDOWN_SECONDS`. A cooldown of `0` keeps the default.
+    pub fn from_config(config: &BreakerSettings) -> Self {
+        BreakerConfig {
+            failures: config.failures,
+            cooldown: Some(config.cooldown_seconds)
+                .filter(|value| *value > 0)
+                .map_or(BreakerConfig::default().cooldown, Duration::from_secs),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls go through.
+    Closed,
+    /// Calls are rejected until the cooldown ends.
+    Open,
+    /// The cooldown ended and a single call goes through to test the upstream, the others are rejected until
+    /// its outcome closes or reopens the breaker.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A probe is in flight. Another one is let through after `until`, in case its outcome is never recorded.
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: State,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Breaker {
+            state: State::Closed { failures: 0 },
+        }
+    }
+
+    /// Returns how long calls are still rejected for, or lets the first call after the cooldown through as a
+    /// probe, rejecting the others until its outcome is recorded.
+    fn check(&mut self, now: Instant, config: &BreakerConfig) -> Result<(), Duration> {
+        match self.state {
+            State::Open { until } | State::HalfOpen { until } if now < until => Err(until - now),
+            State::Open { .. } | State::HalfOpen { .. } => {
+                self.state = State::HalfOpen {
+                    until: now + config.cooldown,
+                };
+                Ok(())
+            }
+            State::Closed { .. } => Ok(()),
+        }
+    }
+
+    fn record(&mut self, ok: bool, config: &BreakerConfig, now: Instant) {
+        self.state = match (self.state, ok) {
+            (_, true) => State::Closed { failures: 0 },
+            (State::HalfOpen { .. }, false) => State::Open {
+                until: now + config.cooldown,
+            },
+            (State::Closed { failures }, false) if failures + 1 >= config.failures => State::Open {
+                until: now + config.cooldown,
+            },
+            (State::Closed { failures }, false) => State::Closed {
+                failures: failures + 1,
+            },
+            // A call started before the breaker opened doesn't extend its cooldown.
+            (open, false) => open,
+        };
+    }
+
+    fn status(&self, now: Instant) -> (BreakerState, Option<Duration>) {
+        match self.state {
+            State::Closed { .. } => (BreakerState::Closed, None),
+            State::Open { until } if now < until => (BreakerState::Open, Some(until - now)),
+            State::HalfOpen { until } if now < until => (BreakerState::HalfOpen, Some(until - now)),
+            State::Open { .. } | State::HalfOpen { .. } => (BreakerState::HalfOpen, None),
+        }
+    }
+}
+
+static CONFIG: OnceLock<BreakerConfig> = OnceLock::new();
+static LOKI: LazyLock<Mutex<Breaker>> = LazyLock::new(|| Mutex::new(Breaker::new()));
+static GITHUB: LazyLock<Mutex<Breaker>> = LazyLock::new(|| Mutex::new(Breaker::new()));
+
+/// Sets the breaker settings once at startup.
+pub fn init(config: &BreakerSettings) -> anyhow::Result<()> {
+    CONFIG
+        .set(BreakerConfig::from_config(config))
+        .map_err(|_| anyhow::anyhow!("Circuit breakers are already initialized"))
+}
+
+/// Returns the breaker settings, or the defaults when they were never initialized.
+fn config() -> &'static BreakerConfig {
+    CONFIG.get_or_init(BreakerConfig::default)
+}
+
+fn breaker(upstream: Upstream) -> &'static Mutex<Breaker> {
+    match upstream {
+        Upstream::Loki => &LOKI,
+        Upstream::GitHub => &GITHUB,
+    }
+}
+
+/// The error of a call rejected because its upstream's breaker is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerOpen {
+    pub upstream: Upstream,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for BreakerOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is unavailable, retry in {} seconds",
+            self.upstream.name(),
+            retry_after_seconds(self.retry_after)
+        )
+    }
+}
+
+impl std::error::Error for BreakerOpen {}
+
+/// Checks whether a call to an upstream may be made.
+///
+/// # Errors
+///
+/// Returns `BreakerOpen` while the upstream's breaker is open.
+pub fn check(upstream: Upstream) -> Result<(), BreakerOpen> {
+    if config().failures == 0 {
+        return Ok(());
+    }
+
+    match breaker(upstream).lock() {
+        Ok(mut breaker) => breaker
+            .check(Instant::now(), config())
+            .map_err(|retry_after| BreakerOpen {
+                upstream,
+                retry_after,
+            }),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Records the outcome of a call to an upstream, where `ok` is `false` when the upstream couldn't be reached
+/// or reported an outage, see `is_outage`.
+pub fn record(upstream: Upstream, ok: bool) {
+    if config().failures == 0 {
+        return;
+    }
+
+    if let Ok(mut breaker) = breaker(upstream).lock() {
+        breaker.record(ok, config(), Instant::now());
+    }
+}
+
+/// Returns whether a response status means the upstream is down or overloaded, as opposed to rejecting the
+/// particular request.
+pub fn is_outage(status: u16) -> bool {
+    status >= 500 || status == 429
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BreakerStatus {
+    pub upstream: &'static str,
+    pub state: BreakerState,
+    /// How long calls are still rejected for, while the breaker is open or its probe is in flight.
+    pub retry_after_seconds: Option<u64>,
+}
+
+/// Returns the state of each upstream's breaker.
+pub fn statuses() -> Vec<BreakerStatus> {
+    let now = Instant::now();
+
+    [Upstream::Loki, Upstream::GitHub]
+        .into_iter()
+        .map(|upstream| {
+            let (state, retry_after) = match breaker(upstream).lock() {
+                Ok(breaker) => breaker.status(now),
+                Err(_) => (BreakerState::Closed, None),
+            };
+
+            BreakerStatus {
+                upstream: upstream.name(),
+                state,
+                retry_after_seconds: retry_after.map(retry_after_seconds),
+            }
+        })
+        .collect()
+}
+
+/// Returns the longest time any open breaker still rejects calls for, as a hint for when to retry.
+pub fn retry_after() -> Option<u64> {
+    statuses()
+        .into_iter()
+        .filter_map(|status| status.retry_after_seconds)
+        .max()
+}
+
+fn retry_after_seconds(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
+/// Returns the status for a request that failed with the error, `503` when an upstream's breaker is open
+/// and `500` otherwise.
+pub fn error_status(e: &anyhow::Error) -> StatusCode {
+    match e.downcast_ref::<BreakerOpen>() {
+        Some(_) => StatusCode::SERVICE_UNAVAILABLE,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_after_consecutive_failures() {
+        let config = BreakerConfig {
+            failures: 2,
+            cooldown: Duration::from_secs(30),
+        };
+        let now = Instant::now();
+        let mut breaker = Breaker::new();
+
+        breaker.record(false, &config, now);
+        breaker.record(true, &config, now);
+        breaker.record(false, &config, now);
+        assert_eq!(breaker.check(now, &config), Ok(()));
+
+        breaker.record(false, &config, now);
+        assert_eq!(breaker.check(now, &config), Err(Duration::from_secs(30)));
+        assert_eq!(
+            breaker.status(now + Duration::from_secs(10)),
+            (BreakerState::Open, Some(Duration::from_secs(20)))
+        );
+    }
+
+    #[test]
+    fn test_breaker_half_opens_after_cooldown() {
+        let config = BreakerConfig {
+            failures: 1,
+            cooldown: Duration::from_secs(30),
+        };
+        let now = Instant::now();
+        let later = now + Duration::from_secs(31);
+        let mut breaker = Breaker::new();
+
+        breaker.record(false, &config, now);
+        assert_eq!(breaker.check(later, &config), Ok(()));
+        assert_eq!(breaker.status(later).0, BreakerState::HalfOpen);
+
+        breaker.record(false, &config, later);
+        assert!(breaker.check(later, &config).is_err());
+
+        let recovered = later + Duration::from_secs(31);
+
+        assert_eq!(breaker.check(recovered, &config), Ok(()));
+        breaker.record(true, &config, recovered);
+        assert_eq!(breaker.status(recovered), (BreakerState::Closed, None));
+    }
+
+    #[test]
+    fn test_half_open_breaker_admits_a_single_probe() {
+        let config = BreakerConfig {
+            failures: 1,
+            cooldown: Duration::from_secs(30),
+        };
+        let now = Instant::now();
+        let later = now + Duration::from_secs(31);
+        let mut breaker = Breaker::new();
+
+        breaker.record(false, &config, now);
+        assert_eq!(breaker.check(later, &config), Ok(()));
+        assert_eq!(
+            breaker.check(later + Duration::from_secs(1), &config),
+            Err(Duration::from_secs(29))
+        );
+
+        // A probe whose outcome is never recorded doesn't keep the breaker from probing again.
+        let abandoned = later + Duration::from_secs(30);
+
+        assert_eq!(breaker.check(abandoned, &config), Ok(()));
+        breaker.record(true, &config, abandoned);
+        assert_eq!(breaker.check(abandoned, &config), Ok(()));
+        assert_eq!(breaker.check(abandoned, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_error_status() {
+        let open = anyhow::Error::new(BreakerOpen {
+            upstream: Upstream::Loki,
+            retry_after: Duration::from_millis(1500),
+        });
+
+        assert_eq!(error_status(&open), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(open.to_string(), "Loki is unavailable, retry in 2 seconds");
+        assert_eq!(
+            error_status(&anyhow::anyhow!("Loki Responded with status: 400")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(is_outage(503) && is_outage(429) && !is_outage(404));
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use serde::Serialize;
+use std::str::FromStr;
+
+use super::response::ResponseRecord;
+
+/// The size of the time buckets aggregated metrics are grouped into. Buckets are aligned to UTC, with
+/// weeks starting on Monday.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketSize {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl FromStr for BucketSize {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "day" => Ok(BucketSize::Day),
+            "week" => Ok(BucketSize::Week),
+            "month" => Ok(BucketSize::Month),
+            other => Err(anyhow!(format!("Unknown bucket: {}", other))),
+        }
+    }
+}
+
+impl BucketSize {
+    /// Returns the start of the bucket a time falls into.
+    pub fn start_of(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let date = time.date_naive();
+
+        let start = match self {
+            BucketSize::Day => date,
+            BucketSize::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            BucketSize::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap(),
+        };
+
+        start.and_hms_opt(0, 0, 0).unwrap().and_utc()
+    }
+
+    fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            BucketSize::Day => start + Duration::days(1),
+            BucketSize::Week => start + Duration::weeks(1),
+            BucketSize::Month => start + Months::new(1),
+        }
+    }
+
+    /// Returns the start of every bucket overlapping a window, in order.
+    pub fn starts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut starts = vec![];
+        let mut current = self.start_of(start);
+
+        while current < end {
+            starts.push(current);
+            current = self.next(current);
+        }
+
+        starts
+    }
+
+    /// Returns whether a window covers the whole bucket starting at `start`, as opposed to only part of the
+    /// first or last bucket it overlaps.
+    pub fn is_full(
+        &self,
+        start: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> bool {
+        start >= window_start && self.next(start) <= window_end
+    }
+}
+
+/// Returns the index of the bucket a time falls into, given the bucket starts from `BucketSize::starts`.
+fn bucket_index(starts: &[DateTime<Utc>], time: DateTime<Utc>) -> Option<usize> {
+    starts
+        .partition_point(|start| *start <= time)
+        .checked_sub(1)
+}
+
+/// Splits deployment records into the buckets they were deployed in, one entry per bucket start.
+/// Records deployed before the first bucket are left out.
+pub fn bucket_records<'a>(
+    starts: &[DateTime<Utc>],
+    records: &[&'a ResponseRecord],
+) -> Vec<Vec<&'a ResponseRecord>> {
+    let mut buckets = vec![vec![]; starts.len()];
+
+    for record in records {
+        if let Some(index) = bucket_index(starts, record.created_at) {
+            buckets[index].push(*record);
+        }
+    }
+
+    buckets
+}
+
+/// Counts the times falling into each bucket, one count per bucket start. Times before the first bucket are
+/// left out.
+pub fn count_times(starts: &[DateTime<Utc>], times: &[DateTime<Utc>]) -> Vec<usize> {
+    let mut counts = vec![0; starts.len()];
+
+    for time in times {
+        if let Some(index) = bucket_index(starts, *time) {
+            counts[index] += 1;
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_bucket_starts() {
+        let start = time("2024-08-28T10:00:00Z");
+        let end = time("2024-09-10T00:00:00Z");
+
+        let weeks = BucketSize::Week.starts(start, end);
+
+        assert_eq!(weeks.len(), 3);
+        assert_eq!(weeks[0], time("2024-08-26T00:00:00Z"));
+        assert_eq!(weeks[2], time("2024-09-09T00:00:00Z"));
+
+        let months = BucketSize::Month.starts(start, end);
+
+        assert_eq!(
+            months,
+            vec![time("2024-08-01T00:00:00Z"), time("2024-09-01T00:00:00Z")]
+        );
+        assert_eq!(BucketSize::Day.starts(start, end).len(), 13);
+
+        let full: Vec<bool> = weeks
+            .iter()
+            .map(|week| BucketSize::Week.is_full(*week, start, end))
+            .collect();
+
+        assert_eq!(full, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_bucket_index() {
+        let starts =
+            BucketSize::Day.starts(time("2024-09-01T00:00:00Z"), time("2024-09-03T00:00:00Z"));
+
+        assert_eq!(bucket_index(&starts, time("2024-08-31T23:00:00Z")), None);
+        assert_eq!(bucket_index(&starts, time("2024-09-01T00:00:00Z")), Some(0));
+        assert_eq!(bucket_index(&starts, time("2024-09-02T23:00:00Z")), Some(1));
+    }
+
+    #[test]
+    fn test_bucket_records() {
+        let starts =
+            BucketSize::Day.starts(time("2024-09-01T00:00:00Z"), time("2024-09-03T00:00:00Z"));
+        let records = [
+            ResponseRecord {
+                created_at: time("2024-09-01T10:00:00Z"),
+                ..Default::default()
+            },
+            ResponseRecord {
+                created_at: time("2024-09-01T12:00:00Z"),
+                ..Default::default()
+            },
+        ];
+
+        let buckets = bucket_records(&starts, &records.iter().collect::<Vec<_>>());
+
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].len(), 2);
+        assert!(buckets[1].is_empty());
+    }
+
+    #[test]
+    fn test_bucket_size_from_str() {
+        assert_eq!(BucketSize::from_str("month").unwrap(), BucketSize::Month);
+        assert!(BucketSize::from_str("year").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use dashmap::{DashMap, DashSet};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::config::CacheSettings;
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry<V> {
+    pub value: V,
+    pub inserted_at: Instant,
+    pub last_accessed: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub ttl: Duration,
+    pub max_entries: usize,
+    pub stale_after: Option<Duration>,
+    /// How far back from the end of cached events they are queried again, since recent events may still be
+    /// arriving in Loki or change, e.g. an issue being closed.
+    pub requery: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl: Duration::from_secs(3600),
+            max_entries: 500,
+            stale_after: None,
+            requery: Duration::from_secs(86_400),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Builds the data cache configuration from the `cache` settings, see `DATA_CACHE_TTL_SECONDS`,
+    /// `DATA_CACHE_MAX_ENTRIES`, `DATA_CACHE_STALE_AFTER_SECONDS` and `DATA_CACHE_REQUERY_SECONDS`. A
+    /// `max_entries` of `0` keeps the default.
+    pub fn from_config(config: &CacheSettings) -> Self {
+        CacheConfig {
+            ttl: Duration::from_secs(config.ttl_seconds),
+            max_entries: match config.max_entries {
+                0 => CacheConfig::default().max_entries,
+                max_entries => max_entries,
+            },
+            stale_after: config.stale_after_seconds.map(Duration::from_secs),
+            requery: Duration::from_secs(config.requery_seconds),
+        }
+    }
+}
+
+/// A cached value, and whether it is old enough that it should be refreshed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cached<V> {
+    pub value: V,
+    pub stale: bool,
+}
+
+/// A rough estimate of how much memory a cached value holds on to.
+pub trait EstimateSize {
+    fn estimated_bytes(&self) -> usize;
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_ratio: Option<f64>,
+    pub estimated_bytes: usize,
+}
+
+/// A concurrent cache with per-entry expiry and a least-recently-used size cap.
+#[derive(Debug)]
+pub struct Cache<V> {
+    entries: DashMap<String, CacheEntry<V>>,
+    refreshing: DashSet<String>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    config: CacheConfig,
+}
+
+impl<V: Clone> Cache<V> {
+    pub fn new(config: CacheConfig) -> Self {
+        Cache {
+            entries: DashMap::new(),
+            refreshing: DashSet::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            config,
+        }
+    }
+
+    /// Returns an unexpired entry, marking it as recently used. Expired entries are removed and not returned.
+    ///
+    /// Entries older than `stale_after` are still returned, but flagged as stale.
+    pub fn get(&self, key: &str) -> Option<Cached<V>> {
+        let expired = match self.entries.get_mut(key) {
+            Some(mut entry) if entry.inserted_at.elapsed() < self.config.ttl => {
+                entry.last_accessed = Instant::now();
+                self.hits.fetch_add(1, Ordering::Relaxed);
+
+                let stale = self
+                    .config
+                    .stale_after
+                    .is_some_and(|stale_after| entry.inserted_at.elapsed() >= stale_after);
+
+                return Some(Cached {
+                    value: entry.value.clone(),
+                    stale,
+                });
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if expired {
+            self.entries.remove(key);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        None
+    }
+
+    /// Inserts or replaces an entry, evicting the least recently used entries when the cache is full.
+    pub fn insert(&self, key: String, value: V) {
+        let now = Instant::now();
+
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: now,
+                last_accessed: now,
+            },
+        );
+
+        while self.entries.len() > self.config.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|entry| entry.last_accessed)
+                .map(|entry| entry.key().clone());
+
+            match oldest {
+                Some(key) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// Claims the background refresh of an entry, returning `false` if a refresh is already running.
+    pub fn begin_refresh(&self, key: &str) -> bool {
+        self.refreshing.insert(key.to_string())
+    }
+
+    /// Releases a claim taken with `begin_refresh`.
+    pub fn end_refresh(&self, key: &str) {
+        self.refreshing.remove(key);
+    }
+
+    /// Removes the entries whose key matches a pattern, or every entry without a pattern, returning how many
+    /// were removed.
+    pub fn purge(&self, pattern: Option<&Regex>) -> usize {
+        let before = self.entries.len();
+
+        match pattern {
+            Some(pattern) => self.entries.retain(|key, _| !pattern.is_match(key)),
+            None => self.entries.clear(),
+        }
+
+        before - self.entries.len()
+    }
+}
+
+impl<V: EstimateSize> Cache<V> {
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+
+        CacheStats {
+            entries: self.entries.len(),
+            hits,
+            misses,
+            hit_ratio: match hits + misses {
+                0 => None,
+                total => Some(hits as f64 / total as f64),
+            },
+            estimated_bytes: self
+                .entries
+                .iter()
+                .map(|entry| entry.key().len() + entry.value.estimated_bytes())
+                .sum(),
+        }
+    }
+}
+
+/// A cache, or a group of caches, that expired entries can be swept from.
+pub trait Sweep {
+    /// Removes every expired entry, returning how many were removed.
+    fn sweep(&self) -> usize;
+}
+
+impl<V> Sweep for Cache<V> {
+    fn sweep(&self) -> usize {
+        let before = self.entries.len();
+
+        self.entries
+            .retain(|_, entry| entry.inserted_at.elapsed() < self.config.ttl);
+
+        before - self.entries.len()
+    }
+}
+
+/// Sweeps expired entries from a cache on an interval, so entries that are never requested again don't
+/// hold on to memory.
+pub async fn sweep_periodically<S: Sweep>(cache: std::sync::Arc<S>, every: Duration) {
+    let mut interval = tokio::time::interval(every.max(Duration::from_secs(1)));
+
+    loop {
+        interval.tick().await;
+
+        let removed = cache.sweep();
+
+        if removed > 0 {
+            tracing::info!("Swept {} expired cache entries", removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_get_and_insert() {
+        let cache: Cache<u32> = Cache::new(CacheConfig::default());
+
+        cache.insert("a".to_string(), 1);
+        cache.insert("a".to_string(), 2);
+
+        assert_eq!(cache.get("a").map(|cached| cached.value), Some(2));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_expired_entries() {
+        let cache: Cache<u32> = Cache::new(CacheConfig {
+            ttl: Duration::ZERO,
+            max_entries: 10,
+            stale_after: None,
+            requery: Duration::ZERO,
+        });
+
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.sweep(), 1);
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache: Cache<u32> = Cache::new(CacheConfig {
+            ttl: Duration::from_secs(60),
+            max_entries: 2,
+            stale_after: None,
+            requery: Duration::ZERO,
+        });
+
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        cache.get("a");
+        cache.insert("c".to_string(), 3);
+
+        assert_eq!(cache.get("a").map(|cached| cached.value), Some(1));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c").map(|cached| cached.value), Some(3));
+    }
+
+    #[test]
+    fn test_cache_stale_entries() {
+        let cache: Cache<u32> = Cache::new(CacheConfig {
+            ttl: Duration::from_secs(60),
+            max_entries: 10,
+            stale_after: Some(Duration::ZERO),
+            requery: Duration::ZERO,
+        });
+
+        cache.insert("a".to_string(), 1);
+
+        assert_eq!(
+            cache.get("a"),
+            Some(Cached {
+                value: 1,
+                stale: true
+            })
+        );
+        assert!(cache.begin_refresh("a"));
+        assert!(!cache.begin_refresh("a"));
+
+        cache.end_refresh("a");
+
+        assert!(cache.begin_refresh("a"));
+    }
+
+    impl EstimateSize for u32 {
+        fn estimated_bytes(&self) -> usize {
+            4
+        }
+    }
+
+    #[test]
+    fn test_cache_purge_and_stats() {
+        let cache: Cache<u32> = Cache::new(CacheConfig::default());
+
+        cache.insert("team-a:1".to_string(), 1);
+        cache.insert("team-a:2".to_string(), 2);
+        cache.insert("team-b:1".to_string(), 3);
+        cache.get("team-a:1");
+        cache.get("team-c:1");
+
+        let stats = cache.stats();
+
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.hit_ratio, Some(0.5));
+        assert_eq!(stats.estimated_bytes, 3 * (8 + 4));
+        assert_eq!(cache.purge(Some(&Regex::new("team-a").unwrap())), 2);
+        assert_eq!(cache.purge(None), 1);
+        assert_eq!(cache.entries.len(), 0);
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::response::RepositoryRecord;
+use crate::config::CohortsSettings;
+
+pub const UNKNOWN_COHORT: &str = "unknown";
+pub const OTHER_COHORT: &str = "other";
+
+/// How repositories are grouped into cohorts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CohortKind {
+    #[default]
+    Language,
+    Stack,
+}
+
+/// The stack tag of each language, by lowercase language name.
+pub type CohortStacks = Arc<HashMap<String, String>>;
+
+/// Parses the stack tags from the `cohorts` settings, see `COHORT_STACKS`, once at startup.
+///
+/// Each entry maps a stack tag to the primary languages that belong to it, e.g. `jvm=Java|Kotlin|Scala`.
+/// Languages are matched case-insensitively, and entries without a `=` are ignored.
+pub fn from_config(config: &CohortsSettings) -> CohortStacks {
+    Arc::new(parse_stacks(&config.stacks))
+}
+
+fn parse_stacks(entries: &[String]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .flat_map(|(stack, languages)| {
+            languages
+                .split('|')
+                .map(|language| language.trim().to_lowercase())
+                .filter(|language| !language.is_empty())
+                .map(move |language| (language, stack.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Maps each repository name to the cohort it belongs to.
+///
+/// # Arguments
+///
+/// * `repositories` - The repositories of the organization.
+/// * `kind` - Whether to group by primary language or by configured stack tag.
+/// * `stacks` - The language to stack tag mapping, see `from_config`.
+///
+/// # Returns
+///
+/// A `HashMap` from repository name to cohort. Repositories without a primary language are in the `unknown`
+/// cohort, and when grouping by stack, languages without a stack tag are in the `other` cohort.
+pub fn assign_cohorts(
+    repositories: &[RepositoryRecord],
+    kind: CohortKind,
+    stacks: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    repositories
+        .iter()
+        .map(|repository| {
+            let cohort = match (&repository.language, kind) {
+                (None, _) => UNKNOWN_COHORT.to_string(),
+                (Some(language), CohortKind::Language) => language.clone(),
+                (Some(language), CohortKind::Stack) => stacks
+                    .get(&language.to_lowercase())
+                    .cloned()
+                    .unwrap_or(OTHER_COHORT.to_string()),
+            };
+
+            (repository.name.clone(), cohort)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(name: &str, language: Option<&str>) -> RepositoryRecord {
+        RepositoryRecord {
+            name: name.to_string(),
+            language: language.map(|value| value.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_stacks() {
+        let stacks = from_config(&CohortsSettings {
+            stacks: vec![
+                "jvm=Java|Kotlin".to_string(),
+                " node=TypeScript".to_string(),
+                "invalid".to_string(),
+            ],
+        });
+
+        assert_eq!(stacks.get("java"), Some(&"jvm".to_string()));
+        assert_eq!(stacks.get("kotlin"), Some(&"jvm".to_string()));
+        assert_eq!(stacks.get("typescript"), Some(&"node".to_string()));
+        assert_eq!(stacks.len(), 3);
+    }
+
+    #[test]
+    fn test_assign_cohorts() {
+        let repositories = vec![
+            repository("api", Some("Kotlin")),
+            repository("web", Some("Go")),
+            repository("docs", None),
+        ];
+
+        let stacks = parse_stacks(&["jvm=Java|Kotlin".to_string()]);
+
+        let by_language = assign_cohorts(&repositories, CohortKind::Language, &stacks);
+        let by_stack = assign_cohorts(&repositories, CohortKind::Stack, &stacks);
+
+        assert_eq!(by_language["api"], "Kotlin");
+        assert_eq!(by_language["docs"], UNKNOWN_COHORT);
+        assert_eq!(by_stack["api"], "jvm");
+        assert_eq!(by_stack["web"], OTHER_COHORT);
+        assert_eq!(by_stack["docs"], UNKNOWN_COHORT);
+    }
+}
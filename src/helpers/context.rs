@@ -0,0 +1,39 @@
+//! The configuration, HTTP client and fixtures the Loki and GitHub helpers are called with, shared through `AppState`
+//! so the helpers don't read global state while serving and can be given a configuration of their own in
+//! tests.
+
+use std::sync::Arc;
+
+use super::{
+    fixtures::{self, Fixtures},
+    http,
+};
+use crate::config::AppConfig;
+
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub config: Arc<AppConfig>,
+    pub client: reqwest::Client,
+    /// The fixtures or demo data served in place of Loki and GitHub, see `DATA_BACKEND`.
+    pub fixtures: Option<&'static Fixtures>,
+}
+
+impl Context {
+    /// Builds a context that queries Loki and GitHub, without fixtures.
+    pub fn new(config: AppConfig, client: reqwest::Client) -> Self {
+        Context {
+            config: Arc::new(config),
+            client,
+            fixtures: None,
+        }
+    }
+
+    /// Builds the context from the configuration `crate::init` loaded, with the shared HTTP client and the
+    /// fixtures it set up.
+    pub fn loaded(config: AppConfig) -> Self {
+        Context {
+            fixtures: fixtures::get(),
+            ..Context::new(config, http::client().clone())
+        }
+    }
+}
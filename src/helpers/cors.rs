@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::{str::FromStr, time::Duration};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::CorsSettings;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowedOrigins {
+    Any,
+    List(Vec<HeaderValue>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    pub origins: AllowedOrigins,
+    pub methods: Vec<Method>,
+    pub headers: Vec<HeaderName>,
+    pub max_age: Duration,
+}
+
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+}
+
+impl CorsConfig {
+    /// Parses the CORS configuration from its comma-separated settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first origin, method or header that isn't valid.
+    pub fn parse(
+        origins: &str,
+        methods: &str,
+        headers: &str,
+        max_age_seconds: u64,
+    ) -> Result<Self> {
+        let origins = match origins.trim() {
+            "*" => AllowedOrigins::Any,
+            value => AllowedOrigins::List(
+                split(value)
+                    .map(|origin| {
+                        HeaderValue::from_str(origin)
+                            .map_err(|e| anyhow!(format!("{}: {}", e, origin)))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        };
+
+        let methods = split(methods)
+            .map(|method| {
+                Method::from_str(&method.to_uppercase())
+                    .map_err(|e| anyhow!(format!("{}: {}", e, method)))
+            })
+            .collect::<Result<_>>()?;
+
+        let headers = split(headers)
+            .map(|header| {
+                HeaderName::from_str(header).map_err(|e| anyhow!(format!("{}: {}", e, header)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(CorsConfig {
+            origins,
+            methods,
+            headers,
+            max_age: Duration::from_secs(max_age_seconds),
+        })
+    }
+
+    /// Builds the CORS configuration from the `cors` settings, see `CORS_ALLOWED_ORIGINS`,
+    /// `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS` and `CORS_MAX_AGE_SECONDS`, or `None` when no origins are
+    /// allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first origin, method or header that isn't valid.
+    pub fn from_config(config: &CorsSettings) -> Result<Option<Self>> {
+        if config.allowed_origins.is_empty() {
+            return Ok(None);
+        }
+
+        CorsConfig::parse(
+            &config.allowed_origins.join(","),
+            &config.allowed_methods.join(","),
+            &config.allowed_headers.join(","),
+            config.max_age_seconds,
+        )
+        .map(Some)
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let origins = match &self.origins {
+            AllowedOrigins::Any => AllowOrigin::any(),
+            AllowedOrigins::List(origins) => AllowOrigin::list(origins.clone()),
+        };
+
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(self.methods.clone())
+            .allow_headers(self.headers.clone())
+            .max_age(self.max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cors_config() {
+        let config = CorsConfig::parse(
+            "https://dora.example.com, http://localhost:3000",
+            "get,POST",
+            "content-type",
+            600,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.origins,
+            AllowedOrigins::List(vec![
+                HeaderValue::from_static("https://dora.example.com"),
+                HeaderValue::from_static("http://localhost:3000"),
+            ])
+        );
+        assert_eq!(config.methods, vec![Method::GET, Method::POST]);
+        assert_eq!(
+            config.headers,
+            vec![HeaderName::from_static("content-type")]
+        );
+        assert_eq!(config.max_age, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_parse_cors_config_any_origin() {
+        let config = CorsConfig::parse("*", "GET", "", 0).unwrap();
+
+        assert_eq!(config.origins, AllowedOrigins::Any);
+        assert!(config.headers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_cors_config_invalid_header() {
+        assert!(CorsConfig::parse("*", "GET", "not a header", 0).is_err());
+    }
+}
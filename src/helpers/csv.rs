@@ -0,0 +1,144 @@
+use super::response::ResponseRecord;
+
+const HEADER: [&str; 22] = [
+    "repository",
+    "team",
+    "title",
+    "user",
+    "sha",
+    "status",
+    "failed_at",
+    "merged_at",
+    "created_at",
+    "fixed_at",
+    "fixed_url",
+    "deploy_url",
+    "issue_url",
+    "change_url",
+    "lead_time_seconds",
+    "time_to_restore_seconds",
+    "environment",
+    "first_commit_at",
+    "hotfix",
+    "deploy_duration_seconds",
+    "open_failure",
+    "service",
+];
+
+/// Serializes records as CSV with a header row, one row per record.
+///
+/// Durations are written as whole seconds so they can be summed and averaged in a spreadsheet. Timestamps use RFC 3339 and missing values are left empty.
+pub fn to_csv(records: &[ResponseRecord]) -> String {
+    let mut csv = row(HEADER.iter().map(|column| column.to_string()));
+
+    for record in records {
+        csv.push_str(&row([
+            record.repository.clone(),
+            record.team.clone(),
+            record.title.clone().unwrap_or_default(),
+            record.user.clone().unwrap_or_default(),
+            record.sha.clone(),
+            record.status.to_string(),
+            record.failed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            record.merged_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            record.created_at.to_rfc3339(),
+            record.fixed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            record.fixed_url.clone().unwrap_or_default(),
+            record.deploy_url.clone(),
+            record.issue_url.clone().unwrap_or_default(),
+            record.change_url.clone(),
+            record
+                .lead_time
+                .as_ref()
+                .map(|d| d.seconds.to_string())
+                .unwrap_or_default(),
+            record
+                .time_to_restore
+                .as_ref()
+                .map(|d| d.seconds.to_string())
+                .unwrap_or_default(),
+            record.environment.clone(),
+            record
+                .first_commit_at
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            record.hotfix.to_string(),
+            record
+                .deploy_duration_seconds
+                .map(|seconds| seconds.to_string())
+                .unwrap_or_default(),
+            record.open_failure.to_string(),
+            record.service.clone().unwrap_or_default(),
+        ]));
+    }
+
+    csv
+}
+
+fn row(fields: impl IntoIterator<Item = String>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|field| escape(&field))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    line.push_str("\r\n");
+
+    line
+}
+
+/// Quotes a field when needed, and neutralizes values a spreadsheet would evaluate as a formula, since
+/// titles and users come from commit metadata.
+fn escape(field: &str) -> String {
+    let field = match field.starts_with(['=', '+', '-', '@']) {
+        true => format!("'{}", field),
+        false => field.to_string(),
+    };
+
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::duration::DurationValue;
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn test_to_csv() {
+        let record = ResponseRecord {
+            repository: "repo".to_string(),
+            team: "team-a".to_string(),
+            environment: "production".to_string(),
+            title: Some("Fix \"quotes\", and commas".to_string()),
+            user: Some("=HYPERLINK()".to_string()),
+            sha: "abc".to_string(),
+            status: true,
+            created_at: DateTime::parse_from_rfc3339("2024-09-09T17:34:12Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            lead_time: Some(DurationValue::from_seconds(5400)),
+            ..Default::default()
+        };
+
+        let csv = to_csv(&[record]);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("repository,team,title,user,sha"));
+        assert_eq!(
+            lines[1],
+            "repo,team-a,\"Fix \"\"quotes\"\", and commas\",'=HYPERLINK(),abc,true,,,2024-09-09T17:34:12+00:00,,,,,,5400,,production,,false,,false,"
+        );
+    }
+
+    #[test]
+    fn test_escape_quotes_line_breaks() {
+        assert_eq!(escape("first\rsecond"), "\"first\rsecond\"");
+        assert_eq!(escape("first\r\nsecond"), "\"first\r\nsecond\"");
+        assert_eq!(escape("plain"), "plain");
+    }
+}
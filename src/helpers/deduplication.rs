@@ -0,0 +1,388 @@
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use super::{domain::Sha, gatherer::DeployEntry};
+use crate::config::DedupSettings;
+
+/// How repeated deployments of the same commit to an environment are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Deduplication {
+    /// Keeps the first deployment of each commit, and the first successful one when earlier attempts failed.
+    #[default]
+    KeepFirst,
+    /// Keeps only the latest deployment of each commit.
+    KeepLast,
+    /// Keeps every deployment, counting each redeploy.
+    KeepAll,
+    /// Collapses back-to-back deployments of a commit like `KeepFirst`, but counts a commit again once another
+    /// commit was deployed in between, e.g. when rolling back.
+    CollapsePerEnvironment,
+}
+
+impl FromStr for Deduplication {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "keep-first" => Ok(Deduplication::KeepFirst),
+            "keep-last" => Ok(Deduplication::KeepLast),
+            "keep-all" => Ok(Deduplication::KeepAll),
+            "collapse-per-environment" => Ok(Deduplication::CollapsePerEnvironment),
+            other => Err(anyhow!(format!("Unknown deduplication: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for Deduplication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Deduplication::KeepFirst => "keep-first",
+            Deduplication::KeepLast => "keep-last",
+            Deduplication::KeepAll => "keep-all",
+            Deduplication::CollapsePerEnvironment => "collapse-per-environment",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+static DEDUPLICATION: OnceLock<Deduplication> = OnceLock::new();
+
+/// Parses how repeated deployments are counted from the `dedup` settings, see `DEPLOYMENT_DEDUPLICATION`.
+///
+/// # Errors
+///
+/// Returns an error if the strategy is unknown.
+pub fn from_config(config: &DedupSettings) -> Result<Deduplication> {
+    config.deployments.parse()
+}
+
+/// Loads the deduplication strategy at startup, so an unknown strategy fails fast instead of on the first query.
+pub fn init(config: &DedupSettings) -> Result<()> {
+    let deduplication = from_config(config)?;
+
+    DEDUPLICATION
+        .set(deduplication)
+        .map_err(|_| anyhow!("Deployment deduplication is already initialized"))
+}
+
+/// Returns the configured deduplication strategy, used when a request doesn't name one.
+pub fn configured() -> Deduplication {
+    DEDUPLICATION.get().copied().unwrap_or_default()
+}
+
+/// Filters duplicate deployments by their SHA, retaining only successful ones.
+///
+/// This function takes a mutable reference to a vector of `DeployEntry` structs and filters out duplicate
+/// deployments that share the same SHA. If multiple deployments have the same SHA, only the first successful
+/// deployment is retained. If a deployment with the same SHA is unsuccessful, it is removed unless a successful
+/// deployment with the same SHA is not yet encountered.
+///
+/// The filtering is done in-place, modifying the original vector by removing duplicates based on the SHA.
+///
+/// # Arguments
+///
+/// * `deploys` - A mutable reference to a vector of `DeployEntry` structs to be filtered.
+///
+/// # Behavior
+///
+/// - The function uses a `HashMap` to track SHAs that have been seen and their success status. SHAs are tracked per
+///   environment and service, so the same change deployed to two environments is kept once for each.
+/// - If a deployment's SHA has not been encountered, it is added to the map.
+/// - If a deployment's SHA has already been encountered, only the first successful deployment is retained, and
+///   any further deployments with the same SHA are removed.
+///
+/// # Example
+///
+/// ```rust
+/// let mut deploys = vec![
+///     DeployEntry {
+///         sha: "abcdef".into(),
+///         status: false,
+///         ..Default::default()
+///     },
+///     DeployEntry {
+///         sha: "abcdef".into(),
+///         status: true,
+///         ..Default::default()
+///     },
+///     DeployEntry {
+///         sha: "123456".into(),
+///         status: true,
+///         ..Default::default()
+///     },
+/// ];
+///
+/// filter_duplicate_deployments_by_sha(&mut deploys);
+///
+/// assert_eq!(deploys.len(), 2); // Only the successful "abcdef" and "123456" remain
+/// assert!(deploys.iter().any(|d| d.sha == "abcdef" && d.status));
+/// assert!(deploys.iter().any(|d| d.sha == "123456"));
+/// ```
+///
+/// This function is useful for cleaning up deployment lists where multiple entries may exist
+/// for the same deployment, but only the successful ones should be retained. It is the `keep-first`
+/// strategy of `Deduplication::apply`.
+fn filter_duplicate_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
+    let mut seen_shas: HashMap<(String, Sha), bool> = HashMap::new();
+
+    deploys.retain(|entry| {
+        let sha = (entry.scope(), entry.sha.clone());
+
+        if let Some(&seen) = seen_shas.get(&sha) {
+            if !seen && entry.status {
+                seen_shas.entry(sha).and_modify(|value| *value = true);
+                return true;
+            }
+            false
+        } else {
+            seen_shas.insert(sha, entry.status);
+            true
+        }
+    });
+}
+
+/// Keeps only the latest deployment of each SHA to an environment.
+fn keep_last_deployment_by_sha(deploys: &mut Vec<DeployEntry>) {
+    let mut last: HashMap<(String, &str), usize> = HashMap::new();
+
+    for (index, entry) in deploys.iter().enumerate() {
+        last.insert((entry.scope(), &entry.sha), index);
+    }
+
+    let keep: HashSet<usize> = last.into_values().collect();
+    let mut index = 0;
+
+    deploys.retain(|_| {
+        index += 1;
+        keep.contains(&(index - 1))
+    });
+}
+
+/// Collapses back-to-back deployments of a SHA to an environment, keeping the first deployment of each run
+/// and its first success when earlier attempts failed. A SHA deployed again after another SHA, e.g. a
+/// rollback, starts a new run and is kept.
+fn collapse_consecutive_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
+    let mut runs: HashMap<String, (Sha, bool)> = HashMap::new();
+
+    deploys.retain(|entry| match runs.get_mut(&entry.scope()) {
+        Some((sha, succeeded)) if *sha == entry.sha => {
+            if !*succeeded && entry.status {
+                *succeeded = true;
+                return true;
+            }
+            false
+        }
+        _ => {
+            runs.insert(entry.scope(), (entry.sha.clone(), entry.status));
+            true
+        }
+    });
+}
+
+impl Deduplication {
+    /// Drops repeated deployments of the same SHA from deployments sorted by creation time.
+    pub fn apply(self, deploys: &mut Vec<DeployEntry>) {
+        match self {
+            Deduplication::KeepFirst => filter_duplicate_deployments_by_sha(deploys),
+            Deduplication::KeepLast => keep_last_deployment_by_sha(deploys),
+            Deduplication::KeepAll => {}
+            Deduplication::CollapsePerEnvironment => {
+                collapse_consecutive_deployments_by_sha(deploys)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_with_successful_duplicates() {
+        let mut deploys = vec![
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: false,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "123456".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: false,
+                ..Default::default()
+            },
+        ];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 3);
+        assert!(deploys.iter().any(|d| d.sha == "abcdef" && !d.status));
+        assert!(deploys.iter().any(|d| d.sha == "abcdef" && d.status));
+        assert!(deploys.iter().any(|d| d.sha == "123456" && d.status));
+    }
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_without_successful_duplicates() {
+        let mut deploys = vec![
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: false,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "123456".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: false,
+                ..Default::default()
+            },
+        ];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 2);
+        assert!(deploys.iter().any(|d| d.sha == "abcdef" && !d.status));
+        assert!(deploys.iter().any(|d| d.sha == "123456" && d.status));
+    }
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_only_one_deployment() {
+        let mut deploys = vec![DeployEntry {
+            sha: "abcdef".into(),
+            status: true,
+            ..Default::default()
+        }];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 1);
+        assert_eq!(deploys[0].sha, "abcdef");
+        assert!(deploys[0].status);
+    }
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_no_deployments() {
+        let mut deploys: Vec<DeployEntry> = vec![];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 0);
+    }
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_all_successful() {
+        let mut deploys = vec![
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "abcdef".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "123456".into(),
+                status: true,
+                ..Default::default()
+            },
+        ];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 2);
+        assert!(deploys.iter().any(|d| d.sha == "abcdef" && d.status));
+        assert!(deploys.iter().any(|d| d.sha == "123456" && d.status));
+    }
+
+    #[test]
+    fn test_filter_duplicate_deployments_by_sha_per_environment() {
+        let mut deploys = vec![
+            DeployEntry {
+                sha: "abcdef".into(),
+                environment: "staging".into(),
+                status: true,
+                ..Default::default()
+            },
+            DeployEntry {
+                sha: "abcdef".into(),
+                environment: "production".into(),
+                status: true,
+                ..Default::default()
+            },
+        ];
+
+        filter_duplicate_deployments_by_sha(&mut deploys);
+
+        assert_eq!(deploys.len(), 2);
+    }
+
+    #[test]
+    fn test_dedup_deployments() {
+        let deployment = |sha: &str, status| DeployEntry {
+            sha: sha.into(),
+            status,
+            ..Default::default()
+        };
+        let deploys = vec![
+            deployment("a", false),
+            deployment("a", true),
+            deployment("b", true),
+            deployment("a", true),
+        ];
+        let dedup = |deduplication| {
+            let mut deploys = deploys.clone();
+            Deduplication::apply(deduplication, &mut deploys);
+
+            deploys
+                .iter()
+                .map(|d| format!("{}{}", d.sha, d.status))
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            dedup(Deduplication::KeepFirst),
+            ["afalse", "atrue", "btrue"]
+        );
+        assert_eq!(dedup(Deduplication::KeepLast), ["btrue", "atrue"]);
+        assert_eq!(dedup(Deduplication::KeepAll).len(), 4);
+        assert_eq!(
+            dedup(Deduplication::CollapsePerEnvironment),
+            ["afalse", "atrue", "btrue", "atrue"]
+        );
+    }
+
+    #[test]
+    fn test_parse_deduplication() {
+        assert_eq!(
+            "keep-last".parse::<Deduplication>().unwrap(),
+            Deduplication::KeepLast
+        );
+        assert_eq!(
+            " collapse-per-environment"
+                .parse::<Deduplication>()
+                .unwrap(),
+            Deduplication::CollapsePerEnvironment
+        );
+        assert!("keep-some".parse::<Deduplication>().is_err());
+    }
+}
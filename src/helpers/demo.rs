@@ -0,0 +1,399 @@
+//! Realistic generated events for a set of fake teams and repositories, so the dashboard can be demoed
+//! without a Loki backend or a GitHub organization.
+//!
+//! Every team is given its own delivery profile, from how often it deploys to how long it takes to restore
+//! service, so the teams land in different performance tiers. The events are generated in the same format
+//! the collector writes to Loki and served through the fixtures backend, so every endpoint works unchanged.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde_json::{json, Value};
+use std::{collections::HashMap, env};
+
+use super::{fixtures::Fixtures, loki::QueryResponse};
+
+/// The organization the generated repositories belong to.
+const DEMO_ORG: &str = "acme";
+
+/// The services each team's repositories are named after, in order, e.g. `payments-api`.
+const SERVICES: [(&str, &str); 6] = [
+    ("api", "Rust"),
+    ("web", "TypeScript"),
+    ("worker", "Go"),
+    ("infra", "HCL"),
+    ("mobile", "Kotlin"),
+    ("docs", "Markdown"),
+];
+
+const AUTHORS: [&str; 6] = ["alice", "bob", "carol", "dave", "erin", "frank"];
+
+const TITLES: [&str; 6] = [
+    "feat: add pagination to the listing",
+    "fix: handle an empty response",
+    "chore(deps): update dependencies",
+    "refactor: extract the retry policy",
+    "feat: support a new region",
+    "fix: correct the timeout",
+];
+
+/// Configures the generated teams, repositories and window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoConfig {
+    pub teams: Vec<String>,
+    pub repositories_per_team: usize,
+    pub days: i64,
+    pub seed: u64,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        DemoConfig {
+            teams: vec![
+                "Platform".to_string(),
+                "Payments".to_string(),
+                "Mobile".to_string(),
+                "Search".to_string(),
+            ],
+            repositories_per_team: 3,
+            days: 90,
+            seed: 42,
+        }
+    }
+}
+
+impl DemoConfig {
+    /// Reads the demo data configuration from the environment.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `DEMO_TEAMS` - A comma separated list of the fake teams. Defaults to `Platform,Payments,Mobile,Search`.
+    /// * `DEMO_REPOSITORIES_PER_TEAM` - How many repositories each team has, up to `6`. Defaults to `3`.
+    /// * `DEMO_DAYS` - How many days of events, ending today, are generated. Defaults to `90`.
+    /// * `DEMO_SEED` - The seed the events are generated from, so every instance serves the same data.
+    ///   Defaults to `42`.
+    pub fn from_env() -> Self {
+        let defaults = DemoConfig::default();
+
+        let teams: Vec<String> = env::var("DEMO_TEAMS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|team| team.trim().to_string())
+                    .filter(|team| !team.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let repositories_per_team = env::var("DEMO_REPOSITORIES_PER_TEAM")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .map(|value| value.min(SERVICES.len()))
+            .unwrap_or(defaults.repositories_per_team);
+
+        let days = env::var("DEMO_DAYS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(defaults.days);
+
+        let seed = env::var("DEMO_SEED")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(defaults.seed);
+
+        DemoConfig {
+            teams: match teams.is_empty() {
+                true => defaults.teams,
+                false => teams,
+            },
+            repositories_per_team,
+            days,
+            seed,
+        }
+    }
+}
+
+/// A small deterministic random number generator, SplitMix64, so the same seed always generates the same
+/// events.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number from 0 up to, but not including, 1.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn between(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.unit()
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.next() as usize % items.len()]
+    }
+
+    fn sha(&mut self) -> String {
+        format!(
+            "{:016x}{:016x}{:08x}",
+            self.next(),
+            self.next(),
+            self.next() as u32
+        )
+    }
+}
+
+/// How a team delivers, drawn once per team.
+struct Profile {
+    deploys_per_day: f64,
+    failure_rate: f64,
+    lead_time_hours: f64,
+    recovery_hours: f64,
+}
+
+impl Profile {
+    fn draw(rng: &mut Rng) -> Self {
+        Profile {
+            deploys_per_day: rng.between(0.2, 3.0),
+            failure_rate: rng.between(0.02, 0.3),
+            lead_time_hours: rng.between(2.0, 96.0),
+            recovery_hours: rng.between(0.5, 36.0),
+        }
+    }
+}
+
+fn slug(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+fn hours(value: f64) -> Duration {
+    Duration::seconds((value * 3600.0) as i64)
+}
+
+/// Wraps streams in a Loki `query_range` response.
+fn response(streams: Vec<Value>) -> QueryResponse {
+    serde_json::from_value(json!({"data": {"resultType": "streams", "result": streams}}))
+        .unwrap_or_default()
+}
+
+/// Generates the events and GitHub responses of the configured teams, for the days up to `now`.
+pub fn generate(config: &DemoConfig, now: DateTime<Utc>) -> Fixtures {
+    let mut rng = Rng(config.seed);
+    let end = now.duration_trunc(Duration::days(1)).unwrap_or(now);
+    let start = end - Duration::days(config.days - 1);
+
+    let mut deploys = vec![];
+    let mut issues = vec![];
+    let mut merges = vec![];
+    let mut teams = vec![];
+    let mut all_repositories = vec![];
+    let mut github = HashMap::new();
+    let mut next_id: u32 = 1000;
+
+    for (index, team) in config.teams.iter().enumerate() {
+        let team_slug = slug(team);
+        let profile = Profile::draw(&mut rng);
+        let mut repositories = vec![];
+
+        teams.push(json!({"id": index + 1, "name": team, "slug": team_slug, "parent": null}));
+
+        for (service, language) in SERVICES.iter().take(config.repositories_per_team) {
+            let repository = format!("{}-{}", team_slug, service);
+            let labels = json!({
+                "team_name": team,
+                "vcs_repository_name": repository,
+                "vcs_repository_owner": DEMO_ORG,
+                "service_namespace": "github",
+            });
+
+            repositories.push(json!({
+                "name": repository,
+                "archived": false,
+                "language": language,
+                "topics": [team_slug],
+            }));
+
+            for day in 0..config.days {
+                let day_start = start + Duration::days(day);
+                let count = (profile.deploys_per_day + rng.unit()).floor() as i64;
+
+                for _ in 0..count {
+                    let deployed_at = day_start + hours(rng.between(0.0, 24.0));
+
+                    if deployed_at >= now {
+                        continue;
+                    }
+
+                    next_id += 1;
+
+                    let sha = rng.sha();
+                    let failed = rng.unit() < profile.failure_rate;
+                    let merged_at =
+                        deployed_at - hours(profile.lead_time_hours * rng.between(0.3, 1.7));
+                    let opened_at = merged_at - hours(rng.between(1.0, 48.0));
+                    let run_started_at =
+                        deployed_at - Duration::minutes(rng.between(2.0, 15.0) as i64);
+                    let api = format!("https://api.github.com/repos/{}/{}", DEMO_ORG, repository);
+
+                    let mut deploy_labels = labels.clone();
+                    deploy_labels["deployment_environment_name"] = json!("production");
+
+                    deploys.push(json!({
+                        "stream": deploy_labels,
+                        "values": [[nanos(deployed_at), json!({
+                            "deployment": {
+                                "id": next_id,
+                                "created_at": deployed_at,
+                                "sha": sha,
+                                "url": format!("{}/deployments/{}", api, next_id),
+                                "task": "deploy",
+                            },
+                            "deployment_status": {
+                                "state": if failed { "failure" } else { "success" },
+                                "created_at": deployed_at,
+                            },
+                            "workflow_run": {
+                                "workflow_id": next_id + 500_000,
+                                "status": "completed",
+                                "run_started_at": run_started_at,
+                                "updated_at": deployed_at,
+                            },
+                            "repository": {"name": repository},
+                        }).to_string()]],
+                    }));
+
+                    let mut merge_labels = labels.clone();
+                    merge_labels["merged_at"] = json!(merged_at.to_rfc3339());
+
+                    merges.push(json!({
+                        "stream": merge_labels,
+                        "values": [[nanos(merged_at), json!({
+                            "pull_request": {
+                                "title": rng.pick(&TITLES),
+                                "user": {"login": rng.pick(&AUTHORS)},
+                                "number": next_id,
+                                "created_at": opened_at,
+                                "html_url": format!("https://github.com/{}/{}/pull/{}", DEMO_ORG, repository, next_id),
+                                "merge_commit_sha": sha,
+                                "base": {"ref": "main"},
+                                "head": {"ref": format!("change-{}", next_id)},
+                                "labels": [],
+                            },
+                            "repository": {"name": repository},
+                        }).to_string()]],
+                    }));
+
+                    if !failed {
+                        continue;
+                    }
+
+                    let created_at = deployed_at + Duration::minutes(rng.between(5.0, 60.0) as i64);
+                    let closed_at =
+                        created_at + hours(profile.recovery_hours * rng.between(0.3, 1.7));
+                    let closed_at = (closed_at < now).then_some(closed_at);
+
+                    issues.push(json!({
+                        "stream": labels,
+                        "values": [[nanos(closed_at.unwrap_or(created_at)), json!({
+                            "issue": {
+                                "created_at": created_at,
+                                "closed_at": closed_at,
+                                "number": next_id,
+                                "labels": [{"name": "incident"}],
+                            },
+                            "repository": {"name": repository},
+                        }).to_string()]],
+                    }));
+                }
+            }
+        }
+
+        github.insert(
+            format!("teams/{}/repos", team_slug),
+            Value::Array(repositories.clone()),
+        );
+        all_repositories.extend(repositories);
+    }
+
+    github.insert("teams".to_string(), Value::Array(teams));
+    github.insert("repos".to_string(), Value::Array(all_repositories));
+
+    Fixtures::generated(
+        response(deploys),
+        response(issues),
+        response(merges),
+        github,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::request::DataRequest;
+
+    #[test]
+    fn test_generate() {
+        let config = DemoConfig {
+            teams: vec!["Platform".to_string(), "Data Science".to_string()],
+            repositories_per_team: 2,
+            days: 30,
+            seed: 7,
+        };
+        let now = DateTime::parse_from_rfc3339("2024-09-30T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let request = DataRequest {
+            start: now - Duration::days(60),
+            end: now,
+            ..Default::default()
+        };
+
+        let fixtures = generate(&config, now);
+        let (deploys, issues, merges) = fixtures.query(&request);
+
+        assert!(fixtures.is_generated());
+        assert!(!deploys.data.result.is_empty());
+        assert!(!issues.data.result.is_empty());
+        assert_eq!(deploys.data.result.len(), merges.data.result.len());
+        assert!(deploys.data.result.iter().all(|item| {
+            ["Platform", "Data Science"].contains(&item.stream.team_name.as_str())
+                && item.values.iter().all(|value| value.timestamp < now)
+        }));
+        assert!(merges
+            .data
+            .result
+            .iter()
+            .all(|item| item.stream.merged_at.is_some()));
+
+        let (again, _, _) = generate(&config, now).query(&request);
+
+        assert_eq!(again.data.result.len(), deploys.data.result.len());
+
+        let teams: Vec<Value> = fixtures
+            .github("https://api.github.com/orgs/acme/teams")
+            .unwrap();
+        let repositories: Vec<Value> = fixtures
+            .github("https://api.github.com/orgs/acme/teams/data-science/repos")
+            .unwrap();
+
+        assert_eq!(teams.len(), 2);
+        assert_eq!(repositories[0]["name"], "data-science-api");
+    }
+}
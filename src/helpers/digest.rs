@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use std::{collections::HashMap, str::FromStr};
+
+use super::context::Context;
+use super::{
+    metrics::{group_by_team, summarize, MetricsSummary},
+    notifiers::{notify_all, EmailNotifier, Notification, Notifier, SmtpConfig, WebhookNotifier},
+    request::DataRequest,
+};
+use crate::{
+    config::DigestSettings,
+    routes::data::{get_records, DataCache},
+};
+
+#[derive(Debug, Clone)]
+pub struct DigestConfig {
+    pub schedule: Schedule,
+    pub window_days: i64,
+    pub webhooks: Vec<String>,
+    /// The webhooks each team's digest is also posted to, by team name.
+    pub team_webhooks: HashMap<String, Vec<String>>,
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl DigestConfig {
+    /// Builds the digest configuration from the `digest` settings, see `DIGEST_SCHEDULE`, `DIGEST_WINDOW_DAYS`,
+    /// `DIGEST_WEBHOOK_URLS`, `DIGEST_TEAM_WEBHOOK_URLS` and the `DIGEST_SMTP_*` and `DIGEST_EMAIL_*` settings.
+    /// Email is disabled when `smtp_host` isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `DIGEST_SCHEDULE` isn't a valid cron expression, a team webhook isn't a `team=url`
+    /// pair, or `DIGEST_EMAIL_FROM` is missing or an email address is invalid.
+    pub fn from_config(config: &DigestSettings) -> Result<Self> {
+        let schedule = Schedule::from_str(config.schedule.trim())
+            .map_err(|e| anyhow!(format!("{}: DIGEST_SCHEDULE", e)))?;
+
+        let window_days = Some(config.window_days)
+            .filter(|value| *value > 0)
+            .unwrap_or(7);
+
+        let mut team_webhooks: HashMap<String, Vec<String>> = HashMap::new();
+
+        for pair in &config.team_webhook_urls {
+            let (team, url) = pair
+                .split_once('=')
+                .map(|(team, url)| (team.trim(), url.trim()))
+                .filter(|(team, url)| !team.is_empty() && !url.is_empty())
+                .ok_or_else(|| {
+                    anyhow!(format!(
+                        "Invalid team webhook {}: DIGEST_TEAM_WEBHOOK_URLS",
+                        pair
+                    ))
+                })?;
+
+            team_webhooks
+                .entry(team.to_string())
+                .or_default()
+                .push(url.to_string());
+        }
+
+        let smtp = SmtpConfig::from_config(config, &config.email_to, "DIGEST_EMAIL_TO")?;
+
+        Ok(DigestConfig {
+            schedule,
+            window_days,
+            webhooks: config.webhook_urls.clone(),
+            team_webhooks,
+            smtp,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.webhooks.is_empty()
+            || !self.team_webhooks.is_empty()
+            || self.smtp.as_ref().is_some_and(|smtp| !smtp.to.is_empty())
+    }
+
+    /// Builds the request for the trailing window a digest sent at `now` covers, ending at `now` so the
+    /// deployment frequency isn't diluted by hours that haven't happened yet.
+    pub fn request(&self, now: DateTime<Utc>) -> DataRequest {
+        DataRequest {
+            start: now - Duration::days(self.window_days),
+            end: now,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the notifiers a digest is sent to, every configured webhook and email address, and its team's
+    /// webhooks.
+    fn notifiers(&self, digest: &Digest) -> Vec<Box<dyn Notifier>> {
+        let team_webhooks = digest
+            .team
+            .as_ref()
+            .and_then(|team| self.team_webhooks.get(team))
+            .into_iter()
+            .flatten();
+
+        let mut notifiers: Vec<Box<dyn Notifier>> = self
+            .webhooks
+            .iter()
+            .chain(team_webhooks)
+            .map(|url| Box::new(WebhookNotifier { url: url.clone() }) as Box<dyn Notifier>)
+            .collect();
+
+        if let Some(smtp) = self.smtp.as_ref().filter(|smtp| !smtp.to.is_empty()) {
+            notifiers.push(Box::new(EmailNotifier { smtp: smtp.clone() }));
+        }
+
+        notifiers
+    }
+}
+
+fn format_value(value: Option<f64>, unit: &str) -> String {
+    value
+        .map(|value| format!("{:.1}{}", value, unit))
+        .unwrap_or("n/a".to_string())
+}
+
+/// A digest message, for one team or, when no team deployed in the window, for every destination.
+#[derive(Debug, PartialEq)]
+pub struct Digest {
+    pub team: Option<String>,
+    pub text: String,
+}
+
+/// Renders one digest per team, or a single digest saying nothing was deployed when no team deployed.
+pub fn digests(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    teams: &[(String, MetricsSummary)],
+) -> Vec<Digest> {
+    if teams.is_empty() {
+        return vec![Digest {
+            team: None,
+            text: render(start, end, &[]),
+        }];
+    }
+
+    teams
+        .iter()
+        .map(|team| Digest {
+            team: Some(team.0.clone()),
+            text: render(start, end, std::slice::from_ref(team)),
+        })
+        .collect()
+}
+
+/// Renders the digest as plain text, with a line per team.
+pub fn render(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    teams: &[(String, MetricsSummary)],
+) -> String {
+    let mut text = format!(
+        "DORA digest for {} to {}\n",
+        start.format("%Y-%m-%d"),
+        end.format("%Y-%m-%d")
+    );
+
+    if teams.is_empty() {
+        text.push_str("\nNo deployments in this period.\n");
+    }
+
+    for (team, metrics) in teams {
+        text.push_str(&format!(
+            "\n{}: {} deployments ({}/day), lead time {}, change failure rate {}, MTTR {}",
+            team,
+            metrics.deployments,
+            format_value(Some(metrics.deployment_frequency), ""),
+            format_value(metrics.lead_time_hours, "h"),
+            format_value(metrics.change_failure_rate, "%"),
+            format_value(metrics.mttr_hours, "h"),
+        ));
+    }
+
+    text
+}
+
+impl Digest {
+    /// The digest as a `{"text": ...}` notification, which Slack and Teams webhooks both accept.
+    fn notification(&self) -> Notification {
+        let subject = match &self.team {
+            Some(team) => format!("DORA metrics digest for {}", team),
+            None => "DORA metrics digest".to_string(),
+        };
+
+        Notification::new(subject, self.text.clone())
+    }
+}
+
+/// Sends a digest of each team's metrics over the trailing window, one message per team, each time the
+/// schedule fires.
+///
+/// # Arguments
+///
+/// * `config` - The digest configuration, see `DigestConfig::from_env`.
+/// * `ctx` - The configuration and HTTP client the data is gathered and the webhooks are called with.
+/// * `cache` - The data cache the digest's data is read from.
+pub async fn send_on_schedule(config: DigestConfig, ctx: Context, cache: DataCache) {
+    let client = &ctx.client;
+
+    while let Some(next) = config.schedule.upcoming(Utc).next() {
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::time::sleep(wait).await;
+
+        let request = config.request(Utc::now());
+        let (start, end) = (request.start, request.end);
+        let days = (end - start).num_seconds() as f64 / 86_400.0;
+
+        let records = match get_records(&ctx, &cache, request).await {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::error!("Digest Query Failed: {:?}", e);
+                continue;
+            }
+        };
+
+        let teams: Vec<(String, MetricsSummary)> = group_by_team(&records)
+            .into_iter()
+            .map(|(team, team_records)| (team, summarize(&team_records, days)))
+            .collect();
+
+        tracing::info!("Sending digest for {} teams", teams.len());
+
+        for digest in digests(start, end, &teams) {
+            notify_all(
+                client,
+                &config.notifiers(&digest),
+                &digest.notification(),
+                "Digest",
+            )
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_digest() {
+        let start = DateTime::parse_from_rfc3339("2024-09-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let end = DateTime::parse_from_rfc3339("2024-09-09T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let teams = vec![(
+            "team-a".to_string(),
+            MetricsSummary {
+                deployments: 14,
+                deployment_frequency: 2.0,
+                lead_time_hours: Some(5.3),
+                change_failure_rate: Some(7.14),
+                ..Default::default()
+            },
+        )];
+
+        assert_eq!(
+            render(start, end, &teams),
+            "DORA digest for 2024-09-02 to 2024-09-09\n\nteam-a: 14 deployments (2.0/day), lead time 5.3h, change failure rate 7.1%, MTTR n/a"
+        );
+        assert!(render(start, end, &[]).ends_with("No deployments in this period.\n"));
+    }
+
+    #[test]
+    fn test_digests_per_team() {
+        let now = DateTime::parse_from_rfc3339("2024-09-09T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let config = DigestConfig {
+            schedule: Schedule::from_str("0 0 9 * * Mon").unwrap(),
+            window_days: 7,
+            webhooks: vec![],
+            team_webhooks: HashMap::new(),
+            smtp: None,
+        };
+        let request = config.request(now);
+
+        let summary = |deployments| MetricsSummary {
+            deployments,
+            deployment_frequency: deployments as f64 / 7.0,
+            ..Default::default()
+        };
+        let teams = vec![
+            ("team-a".to_string(), summary(14)),
+            ("team-b".to_string(), summary(7)),
+        ];
+
+        let sent = digests(request.start, request.end, &teams);
+
+        assert_eq!(request.end, now);
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].team.as_deref(), Some("team-a"));
+        assert_eq!(
+            sent[0].text,
+            "DORA digest for 2024-09-02 to 2024-09-09\n\nteam-a: 14 deployments (2.0/day), lead time n/a, change failure rate n/a, MTTR n/a"
+        );
+        assert!(!sent[1].text.contains("team-a"));
+        assert_eq!(digests(request.start, request.end, &[])[0].team, None);
+    }
+}
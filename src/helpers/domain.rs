@@ -0,0 +1,244 @@
+//! The types the gathered events are described with once they are read from Loki, kept apart from the raw
+//! payloads in `dora_event_vendor`, so a repository can't be passed where a commit is expected and deployment
+//! states are matched instead of compared as strings.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use dora_event_vendor::DeploymentStatus;
+
+/// Defines a newtype around a `String` naming something, which reads like a `str` but can't be mixed up with
+/// the other names.
+macro_rules! name {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Serialize, Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                $name(value)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                $name(value.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+name!(
+    /// The SHA of a deployed or merged commit.
+    Sha
+);
+
+name!(
+    /// The name of a repository, without its owner.
+    RepoName
+);
+
+name!(
+    /// The name of a team, as labelled on its events.
+    TeamName
+);
+
+/// The state GitHub reports for a deployment, see `DeploymentStatus`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    Queued,
+    Pending,
+    InProgress,
+    Success,
+    Failure,
+    Error,
+    Inactive,
+}
+
+impl DeploymentState {
+    pub fn is_success(self) -> bool {
+        self == DeploymentState::Success
+    }
+
+    /// Whether the deployment hasn't concluded yet.
+    pub fn is_active(self) -> bool {
+        matches!(
+            self,
+            DeploymentState::Queued | DeploymentState::Pending | DeploymentState::InProgress
+        )
+    }
+}
+
+impl FromStr for DeploymentState {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "queued" => Ok(DeploymentState::Queued),
+            "pending" => Ok(DeploymentState::Pending),
+            "in_progress" => Ok(DeploymentState::InProgress),
+            "success" => Ok(DeploymentState::Success),
+            "failure" => Ok(DeploymentState::Failure),
+            "error" => Ok(DeploymentState::Error),
+            "inactive" => Ok(DeploymentState::Inactive),
+            other => Err(anyhow!(format!("Unknown deployment state: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for DeploymentState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DeploymentState::Queued => "queued",
+            DeploymentState::Pending => "pending",
+            DeploymentState::InProgress => "in_progress",
+            DeploymentState::Success => "success",
+            DeploymentState::Failure => "failure",
+            DeploymentState::Error => "error",
+            DeploymentState::Inactive => "inactive",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl TryFrom<&DeploymentStatus> for DeploymentState {
+    type Error = anyhow::Error;
+
+    fn try_from(status: &DeploymentStatus) -> Result<Self> {
+        status.state.parse()
+    }
+}
+
+/// The environment group a deployment is linked and reported under, see `EnvironmentMatcher::group`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Environment {
+    /// Every environment matching the configured production environments.
+    #[default]
+    Production,
+    /// An environment named by the request, kept separate.
+    Named(String),
+}
+
+impl Environment {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Environment::Production => "production",
+            Environment::Named(name) => name,
+        }
+    }
+}
+
+impl From<String> for Environment {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "production" => Environment::Production,
+            _ => Environment::Named(value),
+        }
+    }
+}
+
+impl From<&str> for Environment {
+    fn from(value: &str) -> Self {
+        Environment::from(value.to_string())
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for Environment {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deployment_state() {
+        let state: DeploymentState = "IN_PROGRESS".parse().unwrap();
+
+        assert_eq!(state, DeploymentState::InProgress);
+        assert!(state.is_active());
+        assert!(!state.is_success());
+        assert_eq!(state.to_string(), "in_progress");
+        assert!("success".parse::<DeploymentState>().unwrap().is_success());
+        assert!("skipped".parse::<DeploymentState>().is_err());
+    }
+
+    #[test]
+    fn test_environment() {
+        assert_eq!(Environment::from("production"), Environment::Production);
+        assert_eq!(
+            Environment::from("staging"),
+            Environment::Named("staging".to_string())
+        );
+        assert_eq!(
+            Environment::Named("staging".to_string()).to_string(),
+            "staging"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        let sha = Sha::from("abcdef");
+
+        assert_eq!(sha, "abcdef");
+        assert_eq!(sha.len(), 6);
+        assert_eq!(serde_json::to_string(&sha).unwrap(), r#""abcdef""#);
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::Duration;
+use serde::Serialize;
+
+/// A duration serialized as whole seconds alongside its ISO 8601 representation.
+///
+/// This is used for duration fields in responses so clients don't have to guess the unit,
+/// and so the value survives serialization without floating point loss.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DurationValue {
+    pub seconds: i64,
+    pub iso8601: String,
+}
+
+impl DurationValue {
+    pub fn from_seconds(seconds: i64) -> Self {
+        DurationValue {
+            seconds,
+            iso8601: to_iso8601(seconds),
+        }
+    }
+
+    /// Builds a duration from fractional hours, as used by the aggregated metrics.
+    pub fn from_hours(hours: f64) -> Self {
+        Self::from_seconds((hours * 3600.0).round() as i64)
+    }
+}
+
+impl From<Duration> for DurationValue {
+    fn from(duration: Duration) -> Self {
+        Self::from_seconds(duration.num_seconds())
+    }
+}
+
+/// Formats seconds as an ISO 8601 duration, e.g. `P1DT2H3M4S`.
+///
+/// Days are the largest unit used since months and years vary in length.
+fn to_iso8601(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let mut remaining = seconds.unsigned_abs();
+
+    let days = remaining / 86_400;
+    remaining %= 86_400;
+    let hours = remaining / 3600;
+    remaining %= 3600;
+    let minutes = remaining / 60;
+    let secs = remaining % 60;
+
+    let mut formatted = format!("{}P", sign);
+
+    if days > 0 {
+        formatted.push_str(&format!("{}D", days));
+    }
+
+    if hours > 0 || minutes > 0 || secs > 0 || days == 0 {
+        formatted.push('T');
+
+        if hours > 0 {
+            formatted.push_str(&format!("{}H", hours));
+        }
+
+        if minutes > 0 {
+            formatted.push_str(&format!("{}M", minutes));
+        }
+
+        if secs > 0 || (hours == 0 && minutes == 0) {
+            formatted.push_str(&format!("{}S", secs));
+        }
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_iso8601() {
+        assert_eq!(to_iso8601(0), "PT0S");
+        assert_eq!(to_iso8601(59), "PT59S");
+        assert_eq!(to_iso8601(3600), "PT1H");
+        assert_eq!(to_iso8601(86_400), "P1D");
+        assert_eq!(to_iso8601(93_784), "P1DT2H3M4S");
+        assert_eq!(to_iso8601(-5400), "-PT1H30M");
+    }
+
+    #[test]
+    fn test_duration_value() {
+        let value = DurationValue::from(Duration::hours(4));
+
+        assert_eq!(value.seconds, 14_400);
+        assert_eq!(value.iso8601, "PT4H");
+        assert_eq!(
+            DurationValue::from_hours(1.5),
+            DurationValue::from_seconds(5400)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+use super::patterns::{parse_patterns, NamePattern};
+use crate::config::EnvironmentsConfig;
+
+/// Decides which deployment environments count towards the DORA metrics.
+#[derive(Serialize, Debug, Clone)]
+pub struct EnvironmentMatcher {
+    include: Vec<NamePattern>,
+    exclude: Vec<NamePattern>,
+    /// The name every matching environment is grouped under, or `None` to keep each environment separate.
+    group: Option<String>,
+}
+
+impl Default for EnvironmentMatcher {
+    fn default() -> Self {
+        EnvironmentMatcher::new("production,prod,prod-*", "").unwrap()
+    }
+}
+
+static PRODUCTION: OnceLock<EnvironmentMatcher> = OnceLock::new();
+
+impl EnvironmentMatcher {
+    /// Builds a matcher from comma-separated lists of environments to include and exclude, see
+    /// `NamePattern` for the supported patterns. Every matching environment is grouped as `production`.
+    pub fn new(include: &str, exclude: &str) -> Result<Self> {
+        Ok(EnvironmentMatcher {
+            include: parse_patterns(&include.split(',').collect::<Vec<&str>>())?,
+            exclude: parse_patterns(&exclude.split(',').collect::<Vec<&str>>())?,
+            group: Some("production".to_string()),
+        })
+    }
+
+    /// Builds the production environments from the configuration.
+    ///
+    /// * `PRODUCTION_ENVIRONMENT_NAMES` - A comma-separated list of environment patterns considered as
+    ///   production. Defaults to `production,prod,prod-*`.
+    /// * `PRODUCTION_ENVIRONMENT_EXCLUDE` - A comma-separated list of environment patterns that are never
+    ///   considered as production, even when they match an included pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob or regex is invalid.
+    pub fn from_config(config: &EnvironmentsConfig) -> Result<Self> {
+        EnvironmentMatcher::new(&config.production.join(","), &config.exclude.join(","))
+    }
+
+    /// Builds a matcher for exactly the named environments, keeping each environment separate.
+    pub fn exact(names: &[String]) -> Self {
+        EnvironmentMatcher {
+            include: names
+                .iter()
+                .map(|name| NamePattern::Exact(name.trim().to_lowercase()))
+                .collect(),
+            exclude: Vec::new(),
+            group: None,
+        }
+    }
+
+    /// Returns the group a matching environment's deployments are linked and reported under, or `None` when
+    /// the environment doesn't match.
+    pub fn group(&self, environment: &str) -> Option<String> {
+        match self.is_match(environment) {
+            true => Some(
+                self.group
+                    .clone()
+                    .unwrap_or_else(|| environment.trim().to_lowercase()),
+            ),
+            false => None,
+        }
+    }
+
+    /// Returns the group of an environment named `<environment>/<service>`, e.g. `prod/service-a`, together
+    /// with its service, so one repository's services can be reported separately. The environment is matched
+    /// without its service first, and as a whole when that doesn't match.
+    pub fn group_with_service(&self, environment: &str) -> Option<(String, Option<String>)> {
+        let Some((name, service)) = environment.split_once('/') else {
+            return self.group(environment).map(|group| (group, None));
+        };
+
+        let service = Some(service.trim().to_lowercase()).filter(|service| !service.is_empty());
+
+        self.group(name)
+            .or_else(|| self.group(environment))
+            .map(|group| (group, service))
+    }
+
+    pub fn is_match(&self, environment: &str) -> bool {
+        let environment = environment.to_lowercase();
+
+        self.include
+            .iter()
+            .any(|pattern| pattern.is_match(&environment))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.is_match(&environment))
+    }
+}
+
+/// Loads the production environments at startup, so an invalid pattern fails fast instead of on the first
+/// Loki query.
+pub fn init(config: &EnvironmentsConfig) -> Result<()> {
+    let matcher = EnvironmentMatcher::from_config(config)?;
+
+    PRODUCTION
+        .set(matcher)
+        .map_err(|_| anyhow!("Production environments are already initialized"))
+}
+
+/// Returns the configured production environments, or the defaults when they were never initialized.
+pub fn production() -> &'static EnvironmentMatcher {
+    PRODUCTION.get_or_init(EnvironmentMatcher::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_production_environments() {
+        let matcher = EnvironmentMatcher::default();
+
+        assert!(matcher.is_match("Production"));
+        assert!(matcher.is_match("prod"));
+        assert!(matcher.is_match("prod-eu"));
+        assert!(!matcher.is_match("pro"));
+        assert!(!matcher.is_match("preprod"));
+        assert!(!matcher.is_match("production-like"));
+    }
+
+    #[test]
+    fn test_environment_patterns_with_exclude() {
+        let matcher =
+            EnvironmentMatcher::new("live,prod-*,re:us-(east|west)-\\d", "prod-canary").unwrap();
+
+        assert!(matcher.is_match("live"));
+        assert!(matcher.is_match("prod-eu"));
+        assert!(matcher.is_match("US-EAST-1"));
+        assert!(!matcher.is_match("prod-canary"));
+        assert!(!matcher.is_match("us-east-12"));
+        assert!(!matcher.is_match("staging"));
+    }
+
+    #[test]
+    fn test_exact_environments() {
+        let matcher = EnvironmentMatcher::exact(&["Staging".to_string()]);
+
+        assert!(matcher.is_match("staging"));
+        assert!(!matcher.is_match("staging-2"));
+        assert!(!matcher.is_match("production"));
+        assert_eq!(matcher.group("STAGING"), Some("staging".to_string()));
+        assert_eq!(
+            EnvironmentMatcher::default().group("prod-eu"),
+            Some("production".to_string())
+        );
+    }
+
+    #[test]
+    fn test_environments_with_services() {
+        let matcher = EnvironmentMatcher::default();
+
+        assert_eq!(
+            matcher.group_with_service("prod/Service-A"),
+            Some(("production".to_string(), Some("service-a".to_string())))
+        );
+        assert_eq!(
+            matcher.group_with_service("prod"),
+            Some(("production".to_string(), None))
+        );
+        assert_eq!(matcher.group_with_service("staging/service-a"), None);
+        assert_eq!(
+            EnvironmentMatcher::new("prod/*", "")
+                .unwrap()
+                .group_with_service("prod/service-b"),
+            Some(("production".to_string(), Some("service-b".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_invalid_environment_regex() {
+        assert!(EnvironmentMatcher::new("re:(prod", "").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use super::{
+    demo::{self, DemoConfig},
+    loki::{QueryResponse, ResultItem},
+    request::DataRequest,
+};
+
+/// Event fixtures served in place of Loki and GitHub when `DATA_BACKEND` is `fixtures`.
+///
+/// The fixtures directory contains:
+///
+/// * `deploy_data.json`, `issue_data.json` and `merge_data.json` - Loki query responses for the deployment,
+///   issue and merge queries, in the same format as Loki's `query_range` API.
+/// * `review_data.json` and `opened_data.json` - Optionally, Loki query responses for the pull request review and
+///   opened queries.
+/// * `github/` - GitHub API responses, named after the request path below the organization, e.g.
+///   `github/teams.json` or `github/teams/team-a/repos.json`.
+///
+/// Fixtures generated by `DEMO_MODE` have no directory, and hold their GitHub responses in memory.
+#[derive(Debug, Default)]
+pub struct Fixtures {
+    dir: Option<PathBuf>,
+    deploy_data: QueryResponse,
+    issue_data: QueryResponse,
+    merge_data: QueryResponse,
+    github: HashMap<String, Value>,
+}
+
+static FIXTURES: OnceLock<Fixtures> = OnceLock::new();
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| anyhow!(format!("{}: {}", e, path.display())))?;
+
+    serde_json::from_str(&contents).map_err(|e| anyhow!(format!("{}: {}", e, path.display())))
+}
+
+/// Filters the streams and values of a Loki response down to the ones a request would have queried.
+fn filter(response: &QueryResponse, request: &DataRequest) -> QueryResponse {
+    let teams = request.team_names();
+    let matches_team =
+        |item: &ResultItem| teams.is_empty() || teams.contains(&item.stream.team_name.as_str());
+
+    let repositories = request.repository_filter().unwrap_or_default();
+    let matches_repository =
+        |item: &ResultItem| repositories.is_match(&item.stream.vcs_repository_name);
+
+    let mut filtered = QueryResponse::default();
+
+    filtered.data.result = response
+        .data
+        .result
+        .iter()
+        .filter(|item| matches_team(item) && matches_repository(item))
+        .map(|item| ResultItem {
+            stream: item.stream.clone(),
+            values: item
+                .values
+                .iter()
+                .filter(|value| value.timestamp >= request.start && value.timestamp <= request.end)
+                .cloned()
+                .collect(),
+        })
+        .filter(|item| !item.values.is_empty())
+        .collect();
+
+    filtered
+}
+
+impl Fixtures {
+    /// Loads the Loki fixtures from a directory. GitHub fixtures are read when they are requested.
+    pub fn load(dir: PathBuf) -> Result<Self> {
+        Ok(Fixtures {
+            deploy_data: read_json(&dir.join("deploy_data.json"))?,
+            issue_data: read_json(&dir.join("issue_data.json"))?,
+            merge_data: read_json(&dir.join("merge_data.json"))?,
+            dir: Some(dir),
+            github: HashMap::new(),
+        })
+    }
+
+    /// Creates fixtures from generated events and GitHub responses, keyed by the path below the organization,
+    /// e.g. `teams` or `teams/team-a/repos`.
+    pub fn generated(
+        deploy_data: QueryResponse,
+        issue_data: QueryResponse,
+        merge_data: QueryResponse,
+        github: HashMap<String, Value>,
+    ) -> Self {
+        Fixtures {
+            dir: None,
+            deploy_data,
+            issue_data,
+            merge_data,
+            github,
+        }
+    }
+
+    /// Whether the fixtures were generated by `DEMO_MODE` rather than loaded from a directory.
+    pub fn is_generated(&self) -> bool {
+        self.dir.is_none()
+    }
+
+    /// Returns the deployment, issue and merge data for a request, like `loki::query_data`.
+    pub fn query(&self, request: &DataRequest) -> (QueryResponse, QueryResponse, QueryResponse) {
+        (
+            filter(&self.deploy_data, request),
+            filter(&self.issue_data, request),
+            filter(&self.merge_data, request),
+        )
+    }
+
+    /// Returns the events of a Loki fixture that is only read when it is requested, e.g. `review_data.json`, or
+    /// no events when the fixture doesn't exist.
+    pub fn events(&self, file: &str, request: &DataRequest) -> Result<QueryResponse> {
+        let Some(path) = self.dir.as_ref().map(|dir| dir.join(file)) else {
+            return Ok(QueryResponse::default());
+        };
+
+        if !path.exists() {
+            tracing::warn!("No fixture named {}", file);
+            return Ok(QueryResponse::default());
+        }
+
+        Ok(filter(&read_json(&path)?, request))
+    }
+
+    /// Returns the GitHub fixture for an API URL, or an empty list when there is no fixture for it.
+    pub fn github<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let path = url.split('?').next().unwrap_or_default();
+        let path = match path.split_once("/orgs/") {
+            Some((_, rest)) => rest
+                .split_once('/')
+                .map(|(_, rest)| rest)
+                .unwrap_or_default(),
+            None => path.trim_start_matches("https://api.github.com/"),
+        };
+
+        if let Some(response) = self.github.get(path) {
+            return Ok(serde_json::from_value(response.clone())?);
+        }
+
+        let Some(file) = self
+            .dir
+            .as_ref()
+            .map(|dir| dir.join("github").join(format!("{}.json", path)))
+            .filter(|file| file.exists())
+        else {
+            tracing::warn!("No GitHub fixture for {}", url);
+            return Ok(vec![]);
+        };
+
+        read_json(&file)
+    }
+}
+
+/// Loads the fixtures backend when `DATA_BACKEND` is `fixtures`, or generates demo data when `DEMO_MODE` is
+/// `true`.
+///
+/// # Environment Variables
+///
+/// * `DATA_BACKEND` - `loki` to query Loki and GitHub, or `fixtures` to serve fixtures. Defaults to `loki`.
+/// * `FIXTURES_DIR` - The directory the fixtures are loaded from. Defaults to `fixtures`.
+/// * `DEMO_MODE` - `true` to serve generated demo data in place of Loki and GitHub, see `DemoConfig`.
+///
+/// # Errors
+///
+/// Returns an error if `DATA_BACKEND` is unknown or the fixtures can't be read, so a misconfigured demo
+/// environment fails at startup instead of serving empty data.
+pub fn init_from_env() -> Result<()> {
+    if env::var("DEMO_MODE").is_ok_and(|value| value == "true") {
+        let config = DemoConfig::from_env();
+
+        tracing::warn!(
+            "Serving generated demo data for the teams {}",
+            config.teams.join(", ")
+        );
+
+        return FIXTURES
+            .set(demo::generate(&config, Utc::now()))
+            .map_err(|_| anyhow!("Fixtures have already been loaded"));
+    }
+
+    match env::var("DATA_BACKEND")
+        .unwrap_or("loki".to_string())
+        .as_str()
+    {
+        "loki" => Ok(()),
+        "fixtures" => {
+            let dir = env::var("FIXTURES_DIR").unwrap_or("fixtures".to_string());
+            let fixtures = Fixtures::load(PathBuf::from(&dir))?;
+
+            tracing::warn!("Serving data from the fixtures in {}", dir);
+
+            FIXTURES
+                .set(fixtures)
+                .map_err(|_| anyhow!("Fixtures have already been loaded"))
+        }
+        other => Err(anyhow!(format!("Unknown DATA_BACKEND: {}", other))),
+    }
+}
+
+/// Returns the loaded fixtures, or `None` when Loki and GitHub are used.
+pub fn get() -> Option<&'static Fixtures> {
+    FIXTURES.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn test_filter_fixture_data() {
+        let deploy_data: QueryResponse = read_json(Path::new("test_deploy_data.json")).unwrap();
+        let item = &deploy_data.data.result[0];
+        let timestamp = item.values[0].timestamp;
+
+        let request = DataRequest {
+            team: Some(item.stream.team_name.clone()),
+            start: timestamp,
+            end: timestamp,
+            ..Default::default()
+        };
+
+        let filtered = filter(&deploy_data, &request);
+
+        assert!(!filtered.data.result.is_empty());
+        assert!(filtered.data.result.iter().all(|item| {
+            item.stream.team_name == request.team.clone().unwrap()
+                && item.values.iter().all(|value| value.timestamp == timestamp)
+        }));
+
+        let before = DataRequest {
+            end: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            ..Default::default()
+        };
+
+        assert!(filter(&deploy_data, &before).data.result.is_empty());
+    }
+
+    #[test]
+    fn test_github_fixture_path() {
+        let fixtures = Fixtures {
+            dir: Some(PathBuf::from("does-not-exist")),
+            ..Default::default()
+        };
+
+        let teams: Vec<serde_json::Value> = fixtures
+            .github("https://api.github.com/orgs/liatrio/teams?per_page=100")
+            .unwrap();
+
+        assert!(teams.is_empty());
+    }
+}
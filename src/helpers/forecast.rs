@@ -0,0 +1,120 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ForecastPoint {
+    pub start: DateTime<Utc>,
+    pub value: Option<f64>,
+}
+
+/// The weekly values of a metric, and the values projected by a trend line fitted over them.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricForecast {
+    pub history: Vec<ForecastPoint>,
+    /// The change in the metric per week, `None` when fewer than two weeks have a value.
+    pub slope: Option<f64>,
+    pub projections: Vec<ForecastPoint>,
+}
+
+/// Fits a least-squares line through a set of points, returning its slope and intercept.
+///
+/// Returns `None` when there are fewer than two distinct x values to fit through.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    if points.len() < 2 || variance == 0.0 {
+        return None;
+    }
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+
+    let slope = covariance / variance;
+
+    Some((slope, mean_y - slope * mean_x))
+}
+
+/// Projects weekly values forward with a fitted trend line.
+///
+/// # Arguments
+///
+/// * `history` - The value for each week, in order. Weeks without a value are left out of the fit.
+/// * `weeks` - How many weeks after the last to project.
+/// * `bounds` - The range projected values are clamped to, e.g. `0..=100` for a percentage.
+pub fn forecast(history: Vec<ForecastPoint>, weeks: usize, bounds: (f64, f64)) -> MetricForecast {
+    let points: Vec<(f64, f64)> = history
+        .iter()
+        .enumerate()
+        .filter_map(|(index, point)| point.value.map(|value| (index as f64, value)))
+        .collect();
+
+    let fit = linear_fit(&points);
+
+    let projections = match (fit, history.last()) {
+        (Some((slope, intercept)), Some(last)) => (1..=weeks)
+            .map(|ahead| {
+                let x = (history.len() - 1 + ahead) as f64;
+
+                ForecastPoint {
+                    start: last.start + Duration::weeks(ahead as i64),
+                    value: Some((slope * x + intercept).clamp(bounds.0, bounds.1)),
+                }
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    MetricForecast {
+        history,
+        slope: fit.map(|(slope, _)| slope),
+        projections,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_fit() {
+        let (slope, intercept) = linear_fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+
+        assert_eq!(slope, 2.0);
+        assert_eq!(intercept, 1.0);
+        assert_eq!(linear_fit(&[(0.0, 1.0)]), None);
+        assert_eq!(linear_fit(&[]), None);
+    }
+
+    #[test]
+    fn test_forecast() {
+        let start = Utc::now();
+        let history = vec![
+            ForecastPoint {
+                start,
+                value: Some(10.0),
+            },
+            ForecastPoint {
+                start: start + Duration::weeks(1),
+                value: None,
+            },
+            ForecastPoint {
+                start: start + Duration::weeks(2),
+                value: Some(6.0),
+            },
+        ];
+
+        let forecast = forecast(history, 3, (0.0, f64::MAX));
+
+        assert_eq!(forecast.slope, Some(-2.0));
+        assert_eq!(forecast.projections.len(), 3);
+        assert_eq!(forecast.projections[0].value, Some(4.0));
+        assert_eq!(forecast.projections[0].start, start + Duration::weeks(3));
+        assert_eq!(forecast.projections[2].value, Some(0.0));
+    }
+}
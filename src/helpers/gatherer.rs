@@ -1,32 +1,92 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use regex::Regex;
+use serde::Serialize;
 use std::collections::HashMap;
 
-use super::response::ResponseRecord;
+use super::{
+    deduplication::Deduplication,
+    domain::{Environment, RepoName, Sha, TeamName},
+    duration::DurationValue,
+    hotfixes,
+    response::ResponseRecord,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct IssueEntry {
     pub created_at: DateTime<Utc>,
+    /// When the issue was last closed.
     pub closed_at: Option<DateTime<Utc>>,
+    /// When the issue was last reopened, if it ever was.
+    pub reopened_at: Option<DateTime<Utc>>,
     pub number: u32,
 }
 
+impl IssueEntry {
+    /// Returns when the issue was resolved, its final close, or `None` when it was reopened since.
+    pub fn resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.closed_at.filter(|closed_at| {
+            self.reopened_at
+                .is_none_or(|reopened_at| reopened_at < *closed_at)
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct MergeEntry {
     pub merged_at: DateTime<Utc>,
     pub user: String,
     pub title: String,
+    pub commits_url: Option<String>,
+    /// The branch the pull request was merged from.
+    pub branch: Option<String>,
+    pub labels: Vec<String>,
+    /// When the pull request's first commit was authored, filled in when the request includes first commits.
+    pub first_commit_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct DeployEntry {
     pub status: bool,
-    pub repository: String,
-    pub team: String,
+    pub repository: RepoName,
+    pub team: TeamName,
+    /// The environment group the deployment belongs to, see `EnvironmentMatcher::group`.
+    pub environment: Environment,
+    /// The service of a monorepo the deployment is for, see `Deployment::service`.
+    pub service: Option<String>,
     pub created_at: DateTime<Utc>,
-    pub sha: String,
+    pub sha: Sha,
     pub deploy_url: String,
     pub change_url: String,
+    /// How long the deployment's workflow run took, when the event includes it.
+    pub duration_seconds: Option<i64>,
+}
+
+impl DeployEntry {
+    /// The environment, and service when there is one, whose deployments are deduplicated and linked to
+    /// failures together, see `scope`.
+    pub fn scope(&self) -> String {
+        scope(self.environment.as_str(), self.service.as_deref())
+    }
+}
+
+/// Names the history a deployment belongs to, its environment followed by its service, e.g.
+/// `production/service-a`, so the services of a monorepo are tracked separately.
+pub fn scope(environment: &str, service: Option<&str>) -> String {
+    match service {
+        Some(service) => format!("{}/{}", environment, service),
+        None => environment.to_string(),
+    }
+}
+
+/// An event left out of the gathered data because its payload was malformed, e.g. a deployment event without
+/// its deployment status, so a single bad event is reported instead of failing the whole request.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEvent {
+    /// `deployment`, `issue` or `merge`.
+    pub kind: &'static str,
+    pub repository: String,
+    pub timestamp: DateTime<Utc>,
+    pub reason: &'static str,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -34,6 +94,258 @@ pub struct GatheredData {
     pub deployments_by_repo: HashMap<String, Vec<DeployEntry>>,
     pub issues_by_repo: HashMap<String, Vec<IssueEntry>>,
     pub merges_by_sha: HashMap<String, MergeEntry>,
+    /// Deployments made after the window, only used to resolve the failures within it, see
+    /// `failure_lookahead_days`.
+    pub lookahead_by_repo: HashMap<String, Vec<DeployEntry>>,
+    /// How far after the window `lookahead_by_repo` reaches.
+    pub lookahead: Duration,
+    /// How the deployments were deduplicated, applied again when another data set is merged in.
+    pub deduplication: Deduplication,
+    pub warnings: Vec<String>,
+    /// The malformed events left out, kept with the events so they are reported for cached windows too.
+    pub skipped_events: Vec<SkippedEvent>,
+    /// Set when queries were skipped for a partial request, so the data must not be cached.
+    pub incomplete: bool,
+}
+
+impl GatheredData {
+    /// Merges another data set into this one, dropping the events both sets contain.
+    ///
+    /// The merged deployments are normalized again, so duplicates and failures spanning the boundary between
+    /// the two sets are handled as if the data was gathered at once. Skipped events are merged like the
+    /// events, warnings are not, since they describe the request each set was gathered for.
+    pub fn merge(&mut self, other: GatheredData) {
+        for (repository, deployments) in other.deployments_by_repo {
+            let merged = self.deployments_by_repo.entry(repository).or_default();
+            merged.extend(deployments);
+
+            normalize_deployments(merged, self.deduplication);
+        }
+
+        for (repository, deployments) in other.lookahead_by_repo {
+            let merged = self.lookahead_by_repo.entry(repository).or_default();
+            merged.extend(deployments);
+
+            normalize_deployments(merged, self.deduplication);
+        }
+
+        self.lookahead = self.lookahead.max(other.lookahead);
+
+        for (repository, issues) in other.issues_by_repo {
+            let merged = self.issues_by_repo.entry(repository).or_default();
+            merged.extend(issues);
+
+            normalize_issues(merged);
+        }
+
+        for (sha, merge) in other.merges_by_sha {
+            match self.merges_by_sha.get(&sha) {
+                Some(existing) if existing.merged_at <= merge.merged_at => {}
+                _ => {
+                    self.merges_by_sha.insert(sha, merge);
+                }
+            }
+        }
+
+        for skipped in other.skipped_events {
+            if !self.skipped_events.contains(&skipped) {
+                self.skipped_events.push(skipped);
+            }
+        }
+    }
+
+    /// Returns a copy limited to the deployments created within a window. The deployments up to `lookahead`
+    /// after it are kept apart to resolve failures, as if the window was gathered with the same look-ahead.
+    /// Only the issues opened over that span and the merges of the kept deployments are copied along, and the
+    /// skipped events that could have been among them.
+    pub fn within(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> GatheredData {
+        let mut lookahead_by_repo: HashMap<String, Vec<DeployEntry>> = HashMap::new();
+
+        if self.lookahead > Duration::zero() {
+            for (repository, deployments) in self
+                .deployments_by_repo
+                .iter()
+                .chain(self.lookahead_by_repo.iter())
+            {
+                lookahead_by_repo
+                    .entry(repository.clone())
+                    .or_default()
+                    .extend(
+                        deployments
+                            .iter()
+                            .filter(|d| d.created_at > end && d.created_at <= end + self.lookahead)
+                            .cloned(),
+                    );
+            }
+
+            lookahead_by_repo.retain(|_, deployments| !deployments.is_empty());
+
+            for deployments in lookahead_by_repo.values_mut() {
+                normalize_deployments(deployments, self.deduplication);
+            }
+        }
+
+        let deployments_by_repo = self
+            .deployments_by_repo
+            .iter()
+            .map(|(repository, deployments)| {
+                let deployments: Vec<DeployEntry> = deployments
+                    .iter()
+                    .filter(|d| d.created_at >= start && d.created_at <= end)
+                    .cloned()
+                    .collect();
+
+                (repository.clone(), deployments)
+            })
+            .filter(|(_, deployments)| !deployments.is_empty())
+            .collect::<HashMap<String, Vec<DeployEntry>>>();
+
+        let issues_by_repo = self
+            .issues_by_repo
+            .iter()
+            .map(|(repository, issues)| {
+                let issues: Vec<IssueEntry> = issues
+                    .iter()
+                    .filter(|i| i.created_at >= start && i.created_at <= end + self.lookahead)
+                    .cloned()
+                    .collect();
+
+                (repository.clone(), issues)
+            })
+            .filter(|(_, issues)| !issues.is_empty())
+            .collect();
+
+        let merges_by_sha = deployments_by_repo
+            .values()
+            .chain(lookahead_by_repo.values())
+            .flatten()
+            .filter_map(|d| {
+                self.merges_by_sha
+                    .get_key_value(d.sha.as_str())
+                    .map(|(sha, merge)| (sha.clone(), merge.clone()))
+            })
+            .collect();
+
+        // Merges are gathered from before the window too, so skipped merges are only clipped at its end.
+        let skipped_events = self
+            .skipped_events
+            .iter()
+            .filter(|e| match e.kind {
+                "merge" => e.timestamp <= end,
+                _ => e.timestamp >= start && e.timestamp <= end + self.lookahead,
+            })
+            .cloned()
+            .collect();
+
+        GatheredData {
+            deployments_by_repo,
+            issues_by_repo,
+            merges_by_sha,
+            lookahead_by_repo,
+            lookahead: self.lookahead,
+            deduplication: self.deduplication,
+            warnings: self.warnings.clone(),
+            skipped_events,
+            incomplete: self.incomplete,
+        }
+    }
+}
+
+/// Sorts a repository's deployments into a deterministic order, drops the same event gathered twice, e.g. by
+/// two batches sharing a boundary, and then deduplicates repeated deployments.
+///
+/// Deployments created at the same time are ordered by environment, service and SHA, with failures first, so
+/// the result doesn't depend on the order batches were gathered in.
+pub fn normalize_deployments(deployments: &mut Vec<DeployEntry>, deduplication: Deduplication) {
+    deployments.sort_by(|l, r| {
+        (l.created_at, &l.environment, &l.service, &l.sha, l.status).cmp(&(
+            r.created_at,
+            &r.environment,
+            &r.service,
+            &r.sha,
+            r.status,
+        ))
+    });
+    deployments.dedup_by(|r, l| {
+        (&l.environment, &l.service, &l.sha, l.created_at, l.status)
+            == (&r.environment, &r.service, &r.sha, r.created_at, r.status)
+    });
+
+    deduplication.apply(deployments);
+}
+
+/// Sorts a repository's issues by creation, combining the events of each issue number into one entry with its
+/// latest close and latest reopen, so a chain of closes and reopens resolves to the issue's final state.
+pub fn normalize_issues(issues: &mut Vec<IssueEntry>) {
+    issues.sort_by_key(|issue| issue.number);
+    issues.dedup_by(|next, kept| {
+        if next.number != kept.number {
+            return false;
+        }
+
+        kept.created_at = kept.created_at.min(next.created_at);
+        kept.closed_at = kept.closed_at.max(next.closed_at);
+        kept.reopened_at = kept.reopened_at.max(next.reopened_at);
+
+        true
+    });
+    issues.sort_by_key(|issue| (issue.created_at, issue.number));
+}
+
+/// Gathered data along with the window of events it covers.
+#[derive(Debug, Clone, Default)]
+pub struct CoveredData {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub data: GatheredData,
+}
+
+/// Finds the windows that must still be queried to extend covered data to a requested window.
+///
+/// Each window is a whole number of days long, since that is what Loki is queried in, so a window may
+/// overlap the covered data. Merging drops the duplicated events.
+///
+/// # Arguments
+///
+/// * `covered` - The data that has already been gathered.
+/// * `start` - The start of the requested window.
+/// * `end` - The end of the requested window.
+///
+/// # Returns
+///
+/// - `None` if the requested window doesn't touch the covered window, so it has to be queried in full.
+/// - `Some` with the missing head and/or tail windows, which is empty when the request is fully covered.
+///
+/// # Example
+///
+/// ```rust
+/// // Data covering the 1st to the 31st, requested from the 1st to the 2nd of the next month
+/// let windows = missing_windows(&covered, start, end);
+///
+/// assert_eq!(windows, Some(vec![(end - Duration::days(2), end)]));
+/// ```
+pub fn missing_windows(
+    covered: &CoveredData,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+    if start > covered.end || end < covered.start {
+        return None;
+    }
+
+    let whole_days = |gap: Duration| Duration::days((gap.num_seconds() + 86_399) / 86_400);
+
+    let mut windows = vec![];
+
+    if start < covered.start {
+        windows.push((start, start + whole_days(covered.start - start)));
+    }
+
+    if end > covered.end {
+        windows.push((end - whole_days(end - covered.end), end));
+    }
+
+    Some(windows)
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -77,9 +389,9 @@ struct Failure {
 /// let deployment = DeployEntry {
 ///     status: false,
 ///     created_at: Utc::now() - Duration::hours(2),
-///     sha: "abcdef".to_string(),
+///     sha: "abcdef".into(),
 ///     deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
-///     repository: "repo-a".to_string(),
+///     repository: "repo-a".into(),
 ///     ..Default::default()
 /// };
 ///
@@ -107,7 +419,7 @@ fn extract_failure_by_sha(
     let mut sha: String = String::default();
 
     if deployment.status {
-        if let Some(issues) = data.issues_by_repo.get(&deployment.repository) {
+        if let Some(issues) = data.issues_by_repo.get(deployment.repository.as_str()) {
             deploy_issues = issues
                 .iter()
                 .filter(|issue| {
@@ -117,7 +429,7 @@ fn extract_failure_by_sha(
                 .collect()
         }
     } else {
-        sha.clone_from(&deployment.sha);
+        sha = deployment.sha.to_string();
         failure.failed_at = Some(deployment.created_at);
     }
 
@@ -127,15 +439,22 @@ fn extract_failure_by_sha(
             .min_by_key(|record| record.created_at)
             .unwrap();
 
-        let closing = deploy_issues
+        // The failure is only fixed once every issue is resolved, so an incident reopened after being closed
+        // stays open until its final close.
+        let resolved_at = match deploy_issues
             .iter()
-            .filter_map(|record| record.closed_at.map(|time| (record, time)))
-            .max_by_key(|&(_, time)| time)
-            .map(|(record, _)| record);
+            .all(|issue| issue.resolved_at().is_some())
+        {
+            true => deploy_issues
+                .iter()
+                .filter_map(|issue| issue.resolved_at())
+                .max(),
+            false => None,
+        };
 
         failure.failed_at = Some(opened.created_at);
 
-        sha.clone_from(&deployment.sha);
+        sha = deployment.sha.to_string();
 
         let re = Regex::new(r"actions/runs/\d+").unwrap();
 
@@ -146,10 +465,8 @@ fn extract_failure_by_sha(
 
         failure.issue_url = Some(url.to_string());
 
-        if let Some(issue) = closing {
-            if issue.closed_at > failure.failed_at {
-                failure.fixed_at = issue.closed_at;
-            }
+        if resolved_at > failure.failed_at {
+            failure.fixed_at = resolved_at;
         }
     }
 
@@ -164,7 +481,9 @@ fn extract_failure_by_sha(
 ///
 /// If a failure is found but no fix is yet available (i.e., a succeeding deployment hasn’t fixed the failure),
 /// the function holds onto the failure until a fix is found or until the last deployment is processed.
-/// The failures are returned as a `HashMap` where the key is the deployment's SHA and the value is a `Failure` struct.
+/// The failures are returned as a `HashMap` where the key is the deployment's scope and SHA and the value is
+/// a `Failure` struct. Each environment's deployments, and each service's in a monorepo, are tracked separately,
+/// so a failure is only fixed by a later deployment of the same service to the same environment, see `scope`.
 ///
 /// # Arguments
 ///
@@ -172,8 +491,8 @@ fn extract_failure_by_sha(
 ///
 /// # Returns
 ///
-/// A `HashMap<String, Failure>` where:
-/// - The key is the SHA of the deployment.
+/// A `HashMap<(String, String), Failure>` where:
+/// - The key is the scope and SHA of the deployment.
 /// - The value is a `Failure` struct containing the failure details (failure time, fix time, issue URL, fixed URL).
 ///
 /// # Behavior
@@ -194,7 +513,7 @@ fn extract_failure_by_sha(
 ///
 /// let failures = find_failures_per_deployment(&gathered_data);
 ///
-/// for (sha, failure) in failures {
+/// for ((scope, sha), failure) in failures {
 ///     println!("SHA: {}, Failed at: {:?}, Fixed at: {:?}", sha, failure.failed_at, failure.fixed_at);
 ///     if let Some(issue_url) = failure.issue_url {
 ///         println!("Related issue: {}", issue_url);
@@ -208,12 +527,40 @@ fn extract_failure_by_sha(
 ///
 /// - The function handles the case where a failure is identified but has not yet been fixed by holding it in a temporary
 ///   variable (`previous_failure`) until a fix is found.
-/// - If no fix is found by the end of the deployments, the failure is recorded without a fix time.
-fn find_failures_per_deployment(data: &GatheredData) -> HashMap<String, Failure> {
-    let mut previous_failure: Option<(String, Failure)> = None;
-    let mut failures: HashMap<String, Failure> = HashMap::new();
+/// - A successful deployment fixes the failure before it, even when an incident issue is opened against it.
+/// - If no fix is found by the end of an environment's deployments, the first successful deployment after the window
+///   in `lookahead_by_repo` fixes it. Otherwise the failure is recorded without a fix time, and so is a failure of the
+///   last deployment, so unresolved failures are never dropped.
+fn find_failures_per_deployment(data: &GatheredData) -> HashMap<(String, String), Failure> {
+    let mut failures: HashMap<(String, String), Failure> = HashMap::new();
+
+    for (repository, deployments) in
+        data.deployments_by_repo
+            .iter()
+            .flat_map(|(repository, deployments)| {
+                by_environment(deployments)
+                    .into_iter()
+                    .map(move |deployments| (repository, deployments))
+            })
+    {
+        let later: Vec<&DeployEntry> = data
+            .lookahead_by_repo
+            .get(repository)
+            .into_iter()
+            .flatten()
+            .filter(|d| d.scope() == deployments[0].scope())
+            .collect();
+        let fix = later.iter().find(|d| d.status);
+        let resolve = |mut failure: Failure| {
+            if let Some(fix) = fix.filter(|_| failure.fixed_at.is_none()) {
+                failure.fixed_at = Some(fix.created_at);
+                failure.fixed_url = Some(fix.deploy_url.clone());
+            }
+
+            failure
+        };
 
-    for (_, deployments) in data.deployments_by_repo.iter() {
+        let mut previous_failure: Option<((String, String), Failure)> = None;
         let len: usize = deployments.len();
 
         for (index, deployment) in deployments.iter().enumerate() {
@@ -222,47 +569,57 @@ fn find_failures_per_deployment(data: &GatheredData) -> HashMap<String, Failure>
             let next_deployment_at = if !is_last {
                 deployments[index + 1].created_at
             } else {
-                DateTime::<Utc>::MAX_UTC
+                later
+                    .first()
+                    .map_or(DateTime::<Utc>::MAX_UTC, |d| d.created_at)
             };
 
             let (sha, failure) = extract_failure_by_sha(deployment, next_deployment_at, data);
+            let sha = (deployment.scope(), sha);
 
-            match failure.failed_at {
-                Some(_) => {
-                    if is_last {
-                        failures.insert(sha, failure);
-
-                        if previous_failure.is_some() {
-                            let (sha, failure_data) = previous_failure.unwrap();
-
-                            failures.insert(sha, failure_data);
-                            previous_failure = None;
-                        }
-                    } else if previous_failure.is_none() {
-                        previous_failure = Some((sha, failure));
+            if deployment.status {
+                if let Some((sha, mut failure_data)) = previous_failure.take() {
+                    if failure_data.fixed_at.is_none() {
+                        failure_data.fixed_at = Some(deployment.created_at);
+                        failure_data.fixed_url = Some(deployment.deploy_url.clone());
                     }
-                }
-                None => {
-                    if previous_failure.is_some() {
-                        let (sha, mut failure_data) = previous_failure.unwrap();
 
-                        if failure_data.fixed_at.is_none() {
-                            failure_data.fixed_at = Some(deployment.created_at);
-                            failure_data.fixed_url = Some(deployment.deploy_url.clone());
-                        }
-
-                        failures.insert(sha, failure_data);
+                    failures.insert(sha, failure_data);
+                }
+            }
 
-                        previous_failure = None;
-                    }
+            if failure.failed_at.is_some() {
+                if is_last {
+                    failures.insert(sha, resolve(failure));
+                } else if previous_failure.is_none() {
+                    previous_failure = Some((sha, failure));
                 }
             }
         }
+
+        if let Some((sha, failure_data)) = previous_failure {
+            failures.insert(sha, resolve(failure_data));
+        }
     }
 
     failures
 }
 
+/// Splits a repository's deployments by scope, keeping each environment's deployments in order, so
+/// failures are only fixed by a later deployment to the same environment, and of the same service.
+fn by_environment(deployments: &[DeployEntry]) -> Vec<Vec<&DeployEntry>> {
+    let mut environments: HashMap<String, Vec<&DeployEntry>> = HashMap::new();
+
+    for deployment in deployments {
+        environments
+            .entry(deployment.scope())
+            .or_default()
+            .push(deployment);
+    }
+
+    environments.into_values().collect()
+}
+
 /// Links deployment, failure, and merge data into a list of response records.
 ///
 /// This function processes the gathered deployment, issue, and merge data, and creates a list of
@@ -317,49 +674,89 @@ fn find_failures_per_deployment(data: &GatheredData) -> HashMap<String, Failure>
 /// 4. If a merge is found, it adds merge details to the `ResponseRecord`.
 /// 5. The resulting list of response records is returned.
 pub fn link_data(data: GatheredData) -> Vec<ResponseRecord> {
-    let mut records: Vec<ResponseRecord> = [].to_vec();
+    link_records(data).collect()
+}
+
+/// Returns the time from a record's first commit, or its merge when the first commit isn't known, to its
+/// deployment.
+///
+/// Failed deployments didn't deliver the change, so they have no cycle time, and neither do deployments
+/// that couldn't be linked to a merge.
+fn cycle_time(record: &ResponseRecord) -> Option<DurationValue> {
+    if !record.status {
+        return None;
+    }
+
+    let started_at = record.first_commit_at.or(record.merged_at)?;
 
+    Some(DurationValue::from(record.created_at - started_at))
+}
+
+/// Links deployment, failure, and merge data into response records lazily, one deployment at a time.
+///
+/// This is the iterator behind `link_data`, for callers that can hand records on as they are linked
+/// instead of holding the whole list, e.g. when streaming a response.
+pub fn link_records(data: GatheredData) -> impl Iterator<Item = ResponseRecord> {
     let failures = find_failures_per_deployment(&data);
+    let GatheredData {
+        deployments_by_repo,
+        merges_by_sha,
+        ..
+    } = data;
 
-    data.deployments_by_repo.iter().for_each(|(_, value)| {
-        value.iter().for_each(|deployment| {
+    deployments_by_repo
+        .into_values()
+        .flatten()
+        .map(move |deployment| {
             let mut record: ResponseRecord = ResponseRecord {
-                repository: deployment.repository.clone(),
-                team: deployment.team.clone(),
-                sha: deployment.sha.clone(),
+                repository: deployment.repository.into(),
+                team: deployment.team.into(),
+                environment: deployment.environment.to_string(),
+                service: deployment.service,
+                sha: deployment.sha.into(),
                 status: deployment.status,
                 created_at: deployment.created_at,
-                deploy_url: deployment.deploy_url.clone(),
-                change_url: deployment.change_url.clone(),
+                deploy_url: deployment.deploy_url,
+                change_url: deployment.change_url,
+                deploy_duration_seconds: deployment.duration_seconds,
                 ..Default::default()
             };
 
-            let failure = failures.get(&deployment.sha);
-
-            if failure.is_some() {
-                let failure_data = failure.unwrap();
-
+            if let Some(failure_data) = failures.get(&(
+                scope(&record.environment, record.service.as_deref()),
+                record.sha.clone(),
+            )) {
                 record.failed_at = failure_data.failed_at;
                 record.fixed_at = failure_data.fixed_at;
                 record.issue_url.clone_from(&failure_data.issue_url);
                 record.fixed_url.clone_from(&failure_data.fixed_url);
-            }
 
-            let merge = data.merges_by_sha.get(&deployment.sha);
+                if let (Some(failed_at), Some(fixed_at)) = (record.failed_at, record.fixed_at) {
+                    record.time_to_restore = Some(DurationValue::from(fixed_at - failed_at));
+                }
 
-            if merge.is_some() {
-                let merge_data = merge.unwrap();
+                record.open_failure = record.failed_at.is_some() && record.fixed_at.is_none();
+            }
 
+            if let Some(merge_data) = merges_by_sha.get(&record.sha) {
                 record.merged_at = Some(merge_data.merged_at);
                 record.title = Some(merge_data.title.clone());
                 record.user = Some(merge_data.user.clone());
+                record.first_commit_at = merge_data.first_commit_at;
+                record.hotfix = hotfixes::get().is_hotfix(merge_data);
+                record.lead_time = Some(DurationValue::from(
+                    record.created_at - merge_data.merged_at,
+                ));
             }
 
-            records.push(record);
-        })
-    });
+            record.cycle_time = cycle_time(&record);
+            record.total_cycle_time = record
+                .cycle_time
+                .as_ref()
+                .map(|cycle_time| cycle_time.seconds as f32 / 3600.0);
 
-    records
+            record
+        })
 }
 
 #[cfg(test)]
@@ -372,9 +769,9 @@ mod tests {
         let deployment = DeployEntry {
             status: false,
             created_at: Utc::now() - Duration::hours(3),
-            sha: "abcdef".to_string(),
+            sha: "abcdef".into(),
             deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
-            repository: "repo-a".to_string(),
+            repository: "repo-a".into(),
             ..Default::default()
         };
 
@@ -404,15 +801,16 @@ mod tests {
         let deployment = DeployEntry {
             status: true,
             created_at: Utc::now() - Duration::hours(3),
-            sha: "abcdef".to_string(),
+            sha: "abcdef".into(),
             deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
-            repository: "repo-a".to_string(),
+            repository: "repo-a".into(),
             ..Default::default()
         };
 
         let issue1 = IssueEntry {
             created_at: Utc::now() - Duration::hours(2),
             closed_at: Some(Utc::now() - Duration::hours(1)),
+            reopened_at: None,
             number: 42,
         };
 
@@ -444,15 +842,16 @@ mod tests {
         let deployment = DeployEntry {
             status: true,
             created_at: Utc::now() - Duration::hours(3),
-            sha: "abcdef".to_string(),
+            sha: "abcdef".into(),
             deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
-            repository: "repo-a".to_string(),
+            repository: "repo-a".into(),
             ..Default::default()
         };
 
         let issue1 = IssueEntry {
             created_at: Utc::now() - Duration::hours(2),
             closed_at: None,
+            reopened_at: None,
             number: 42,
         };
 
@@ -484,9 +883,9 @@ mod tests {
         let deployment = DeployEntry {
             status: true,
             created_at: Utc::now() - Duration::hours(3),
-            sha: "abcdef".to_string(),
+            sha: "abcdef".into(),
             deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
-            repository: "repo-a".to_string(),
+            repository: "repo-a".into(),
             ..Default::default()
         };
 
@@ -502,4 +901,599 @@ mod tests {
         assert_eq!(sha, "");
         assert_eq!(failure, Failure::default());
     }
+
+    #[test]
+    fn test_missing_windows() {
+        let start = Utc::now() - Duration::days(30);
+        let end = Utc::now();
+        let covered = CoveredData {
+            start,
+            end,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            missing_windows(&covered, start + Duration::days(1), end),
+            Some(vec![])
+        );
+        assert_eq!(
+            missing_windows(
+                &covered,
+                start - Duration::hours(36),
+                end + Duration::hours(2)
+            ),
+            Some(vec![
+                (start - Duration::hours(36), start + Duration::hours(12)),
+                (end - Duration::hours(22), end + Duration::hours(2)),
+            ])
+        );
+        assert_eq!(
+            missing_windows(&covered, end + Duration::days(1), end + Duration::days(2)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_failures_are_fixed_within_their_environment() {
+        let now = Utc::now();
+        let deployment = |sha: &str, environment: &str, status, created_at| DeployEntry {
+            sha: sha.into(),
+            environment: environment.into(),
+            status,
+            created_at,
+            ..Default::default()
+        };
+
+        let data = GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    deployment("a", "production", false, now - Duration::hours(3)),
+                    deployment("b", "staging", true, now - Duration::hours(2)),
+                    deployment("c", "production", true, now - Duration::hours(1)),
+                ],
+            )]),
+            ..Default::default()
+        };
+
+        let records = link_data(data);
+        let failed = records.iter().find(|r| r.sha == "a").unwrap();
+
+        assert_eq!(failed.environment, "production");
+        assert_eq!(failed.fixed_at, Some(now - Duration::hours(1)));
+        assert!(records
+            .iter()
+            .filter(|r| r.sha != "a")
+            .all(|r| r.failed_at.is_none()));
+    }
+
+    #[test]
+    fn test_failures_are_fixed_within_their_service() {
+        let now = Utc::now();
+        let deployment = |sha: &str, service: &str, status, created_at| DeployEntry {
+            sha: sha.into(),
+            environment: "production".into(),
+            service: Some(service.to_string()),
+            status,
+            created_at,
+            ..Default::default()
+        };
+
+        let data = GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "monorepo".to_string(),
+                vec![
+                    deployment("a", "service-a", false, now - Duration::hours(4)),
+                    deployment("a", "service-b", true, now - Duration::hours(3)),
+                    deployment("b", "service-b", true, now - Duration::hours(2)),
+                    deployment("c", "service-a", true, now - Duration::hours(1)),
+                ],
+            )]),
+            ..Default::default()
+        };
+
+        let records = link_data(data);
+        let failed: Vec<&ResponseRecord> =
+            records.iter().filter(|r| r.failed_at.is_some()).collect();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].service, Some("service-a".to_string()));
+        assert_eq!(failed[0].fixed_at, Some(now - Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_unresolved_failures_are_kept_open() {
+        let now = Utc::now();
+        let deployment = |repository: &str, sha: &str, status, created_at| DeployEntry {
+            repository: repository.into(),
+            sha: sha.into(),
+            environment: "production".into(),
+            status,
+            created_at,
+            deploy_url: "https://github.com/owner/repo/actions/runs/1".to_string(),
+            ..Default::default()
+        };
+
+        let data = GatheredData {
+            deployments_by_repo: HashMap::from([
+                (
+                    "repo-a".to_string(),
+                    vec![
+                        deployment("repo-a", "a", false, now - Duration::hours(4)),
+                        deployment("repo-a", "b", false, now - Duration::hours(3)),
+                        deployment("repo-a", "c", false, now - Duration::hours(2)),
+                    ],
+                ),
+                (
+                    "repo-b".to_string(),
+                    vec![
+                        deployment("repo-b", "d", false, now - Duration::hours(4)),
+                        deployment("repo-b", "e", true, now - Duration::hours(3)),
+                    ],
+                ),
+            ]),
+            issues_by_repo: HashMap::from([(
+                "repo-b".to_string(),
+                vec![IssueEntry {
+                    created_at: now - Duration::hours(2),
+                    closed_at: None,
+                    reopened_at: None,
+                    number: 7,
+                }],
+            )]),
+            ..Default::default()
+        };
+
+        let records = link_data(data);
+        let record = |sha: &str| records.iter().find(|r| r.sha == sha).unwrap();
+
+        assert!(record("a").open_failure);
+        assert!(record("c").open_failure);
+        assert_eq!(record("c").fixed_at, None);
+        assert!(!record("b").open_failure);
+
+        assert_eq!(record("d").fixed_at, Some(now - Duration::hours(3)));
+        assert!(!record("d").open_failure);
+        assert!(record("e").open_failure);
+        assert_eq!(
+            record("e").issue_url.as_deref(),
+            Some("https://github.com/owner/repo/issues/7")
+        );
+    }
+
+    fn cycle_time_data(now: DateTime<Utc>) -> GatheredData {
+        let deployment = |sha: &str, status| DeployEntry {
+            sha: sha.into(),
+            status,
+            created_at: now,
+            ..Default::default()
+        };
+        let merge = |first_commit_at| MergeEntry {
+            merged_at: now - Duration::hours(2),
+            first_commit_at,
+            ..Default::default()
+        };
+
+        GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    deployment("merged", true),
+                    deployment("committed", true),
+                    deployment("unmerged", true),
+                    deployment("failed", false),
+                ],
+            )]),
+            merges_by_sha: HashMap::from([
+                ("merged".to_string(), merge(None)),
+                (
+                    "committed".to_string(),
+                    merge(Some(now - Duration::minutes(330))),
+                ),
+                ("failed".to_string(), merge(None)),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_total_cycle_time() {
+        let records = link_data(cycle_time_data(Utc::now()));
+        let cycle_time = |sha: &str| {
+            records
+                .iter()
+                .find(|r| r.sha == sha)
+                .unwrap()
+                .total_cycle_time
+        };
+
+        assert_eq!(cycle_time("merged"), Some(2.0));
+        assert_eq!(cycle_time("committed"), Some(5.5));
+        assert_eq!(cycle_time("unmerged"), None);
+        assert_eq!(cycle_time("failed"), None);
+
+        let committed = records.iter().find(|r| r.sha == "committed").unwrap();
+
+        assert_eq!(
+            committed.cycle_time,
+            Some(DurationValue::from_seconds(5 * 3600 + 1800))
+        );
+    }
+
+    #[test]
+    fn test_merge_and_within() {
+        let now = Utc::now();
+        let deployment = |sha: &str, created_at| DeployEntry {
+            sha: sha.into(),
+            status: true,
+            created_at,
+            ..Default::default()
+        };
+
+        let mut data = GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    deployment("a", now - Duration::days(3)),
+                    deployment("b", now - Duration::days(2)),
+                ],
+            )]),
+            issues_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![IssueEntry {
+                    number: 1,
+                    ..Default::default()
+                }],
+            )]),
+            ..Default::default()
+        };
+
+        data.merge(GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    deployment("b", now - Duration::days(2)),
+                    deployment("c", now - Duration::days(1)),
+                ],
+            )]),
+            issues_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    IssueEntry {
+                        number: 1,
+                        ..Default::default()
+                    },
+                    IssueEntry {
+                        number: 2,
+                        ..Default::default()
+                    },
+                ],
+            )]),
+            ..Default::default()
+        });
+
+        let shas: Vec<&str> = data.deployments_by_repo["repo-a"]
+            .iter()
+            .map(|d| d.sha.as_str())
+            .collect();
+
+        assert_eq!(shas, vec!["a", "b", "c"]);
+        assert_eq!(data.issues_by_repo["repo-a"].len(), 2);
+
+        let within = data.within(now - Duration::hours(60), now);
+
+        assert_eq!(within.deployments_by_repo["repo-a"].len(), 2);
+    }
+
+    #[test]
+    fn test_within_clips_issues_and_merges() {
+        let now = Utc::now();
+        let issue = |number, created_at| IssueEntry {
+            number,
+            created_at,
+            ..Default::default()
+        };
+        let merge = |merged_at| MergeEntry {
+            merged_at,
+            ..Default::default()
+        };
+
+        let data = GatheredData {
+            deployments_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    deploy("a", true, now - Duration::days(5)),
+                    deploy("b", true, now - Duration::days(1)),
+                ],
+            )]),
+            issues_by_repo: HashMap::from([(
+                "repo-a".to_string(),
+                vec![
+                    issue(1, now - Duration::days(5)),
+                    issue(2, now - Duration::hours(12)),
+                ],
+            )]),
+            merges_by_sha: HashMap::from([
+                ("a".to_string(), merge(now - Duration::days(6))),
+                ("b".to_string(), merge(now - Duration::days(2))),
+            ]),
+            warnings: vec!["warning".to_string()],
+            skipped_events: vec![
+                skipped("deployment", now - Duration::days(5)),
+                skipped("deployment", now - Duration::days(1)),
+                skipped("merge", now - Duration::days(6)),
+            ],
+            ..Default::default()
+        };
+
+        let within = data.within(now - Duration::days(2), now);
+
+        assert_eq!(within.deployments_by_repo["repo-a"].len(), 1);
+        assert_eq!(within.issues_by_repo["repo-a"].len(), 1);
+        assert_eq!(within.issues_by_repo["repo-a"][0].number, 2);
+        assert_eq!(
+            within.merges_by_sha.keys().collect::<Vec<_>>(),
+            vec![&"b".to_string()]
+        );
+        assert_eq!(within.warnings, vec!["warning".to_string()]);
+        assert_eq!(within.skipped_events, data.skipped_events[1..].to_vec());
+    }
+
+    #[test]
+    fn test_merge_keeps_skipped_events_once() {
+        let now = Utc::now();
+        let mut data = GatheredData {
+            skipped_events: vec![skipped("deployment", now)],
+            ..Default::default()
+        };
+
+        data.merge(GatheredData {
+            skipped_events: vec![skipped("deployment", now), skipped("issue", now)],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            data.skipped_events,
+            vec![skipped("deployment", now), skipped("issue", now)]
+        );
+    }
+
+    fn skipped(kind: &'static str, timestamp: DateTime<Utc>) -> SkippedEvent {
+        SkippedEvent {
+            kind,
+            repository: "repo-a".into(),
+            timestamp,
+            reason: "malformed",
+        }
+    }
+
+    fn window(deployments: Vec<DeployEntry>, deduplication: Deduplication) -> GatheredData {
+        GatheredData {
+            deployments_by_repo: HashMap::from([("repo-a".to_string(), deployments)]),
+            deduplication,
+            ..Default::default()
+        }
+    }
+
+    fn deploy(sha: &str, status: bool, created_at: DateTime<Utc>) -> DeployEntry {
+        DeployEntry {
+            repository: "repo-a".into(),
+            environment: "production".into(),
+            sha: sha.into(),
+            status,
+            created_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_failures_are_fixed_across_windows() {
+        let now = Utc::now();
+        let mut data = window(
+            vec![deploy("a", false, now - Duration::days(3))],
+            Deduplication::KeepFirst,
+        );
+
+        data.merge(window(
+            vec![deploy("b", true, now - Duration::days(1))],
+            Deduplication::KeepFirst,
+        ));
+
+        let records = link_data(data);
+        let failed = records.iter().find(|r| r.sha == "a").unwrap();
+
+        assert_eq!(failed.fixed_at, Some(now - Duration::days(1)));
+        assert!(!failed.open_failure);
+    }
+
+    #[test]
+    fn test_merge_deduplicates_across_windows() {
+        let now = Utc::now();
+        let windows = |deduplication| {
+            let mut data = window(
+                vec![
+                    deploy("a", true, now - Duration::days(3)),
+                    deploy("b", true, now - Duration::days(2)),
+                ],
+                deduplication,
+            );
+
+            data.merge(window(
+                vec![
+                    deploy("b", true, now - Duration::days(2)),
+                    deploy("a", true, now - Duration::days(1)),
+                ],
+                deduplication,
+            ));
+
+            data.deployments_by_repo["repo-a"]
+                .iter()
+                .map(|d| (d.sha.to_string(), (now - d.created_at).num_days()))
+                .collect::<Vec<(String, i64)>>()
+        };
+        let deployment = |sha: &str, days| (sha.to_string(), days);
+
+        assert_eq!(
+            windows(Deduplication::KeepFirst),
+            vec![deployment("a", 3), deployment("b", 2)]
+        );
+        assert_eq!(
+            windows(Deduplication::KeepLast),
+            vec![deployment("b", 2), deployment("a", 1)]
+        );
+        assert_eq!(
+            windows(Deduplication::KeepAll),
+            vec![deployment("a", 3), deployment("b", 2), deployment("a", 1)]
+        );
+    }
+
+    #[test]
+    fn test_failures_are_resolved_by_lookahead() {
+        let now = Utc::now();
+        let mut data = window(
+            vec![
+                deploy("a", true, now - Duration::days(3)),
+                deploy("b", false, now - Duration::days(2)),
+            ],
+            Deduplication::KeepFirst,
+        );
+        data.lookahead = Duration::days(2);
+        data.lookahead_by_repo = HashMap::from([(
+            "repo-a".to_string(),
+            vec![
+                deploy("c", false, now - Duration::hours(20)),
+                deploy("d", true, now - Duration::hours(10)),
+            ],
+        )]);
+
+        let records = link_data(data.clone());
+        let failed = records.iter().find(|r| r.sha == "b").unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(failed.fixed_at, Some(now - Duration::hours(10)));
+        assert!(!failed.open_failure);
+
+        // A narrower window keeps the deployments after it, up to the look-ahead, apart.
+        let within = data.within(now - Duration::days(4), now - Duration::hours(60));
+        let lookahead: Vec<&str> = within.lookahead_by_repo["repo-a"]
+            .iter()
+            .map(|d| d.sha.as_str())
+            .collect();
+
+        assert_eq!(within.deployments_by_repo["repo-a"].len(), 1);
+        assert_eq!(lookahead, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_normalize_deployments_is_deterministic() {
+        let now = Utc::now();
+        let mut forward = vec![
+            deploy("b", true, now),
+            deploy("a", true, now),
+            deploy("a", false, now),
+        ];
+        let mut backward: Vec<DeployEntry> = forward.iter().rev().cloned().collect();
+
+        normalize_deployments(&mut forward, Deduplication::KeepAll);
+        normalize_deployments(&mut backward, Deduplication::KeepAll);
+
+        let order = |deployments: &[DeployEntry]| {
+            deployments
+                .iter()
+                .map(|d| (d.sha.to_string(), d.status))
+                .collect::<Vec<(String, bool)>>()
+        };
+
+        assert_eq!(order(&forward), order(&backward));
+        assert_eq!(
+            order(&forward),
+            vec![
+                ("a".to_string(), false),
+                ("a".to_string(), true),
+                ("b".to_string(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reopened_issues_are_fixed_by_their_final_close() {
+        let now = Utc::now();
+        let deployment = DeployEntry {
+            status: true,
+            created_at: now - Duration::hours(10),
+            sha: "abcdef".into(),
+            deploy_url: "https://github.com/owner/repo/actions/runs/123456".to_string(),
+            repository: "repo-a".into(),
+            ..Default::default()
+        };
+        let event = |closed_at: Option<i64>, reopened_at: Option<i64>| IssueEntry {
+            created_at: now - Duration::hours(9),
+            closed_at: closed_at.map(|hours| now - Duration::hours(hours)),
+            reopened_at: reopened_at.map(|hours| now - Duration::hours(hours)),
+            number: 42,
+        };
+        let fixed_at = |events: Vec<IssueEntry>| {
+            let mut issues = events;
+            normalize_issues(&mut issues);
+
+            let data = GatheredData {
+                issues_by_repo: HashMap::from([("repo-a".to_string(), issues)]),
+                ..Default::default()
+            };
+
+            extract_failure_by_sha(&deployment, now, &data).1.fixed_at
+        };
+
+        // Closed, reopened and closed again is fixed by the final close.
+        assert_eq!(
+            fixed_at(vec![
+                event(Some(8), None),
+                event(None, Some(6)),
+                event(Some(4), None),
+            ]),
+            Some(now - Duration::hours(4))
+        );
+        // Reopened after its last close, the incident is still open.
+        assert_eq!(
+            fixed_at(vec![
+                event(Some(8), None),
+                event(None, Some(6)),
+                event(Some(4), None),
+                event(None, Some(2)),
+            ]),
+            None
+        );
+        // The events may arrive in any order, e.g. from different batches.
+        assert_eq!(
+            fixed_at(vec![
+                event(Some(4), None),
+                event(None, Some(6)),
+                event(Some(8), None),
+            ]),
+            Some(now - Duration::hours(4))
+        );
+    }
+
+    #[test]
+    fn test_normalize_issues_keeps_latest_closing() {
+        let now = Utc::now();
+        let issue = |number, closed_at| IssueEntry {
+            created_at: now - Duration::days(number as i64),
+            closed_at,
+            reopened_at: None,
+            number,
+        };
+        let mut issues = vec![
+            issue(1, Some(now - Duration::hours(5))),
+            issue(2, None),
+            issue(1, Some(now - Duration::hours(1))),
+        ];
+
+        normalize_issues(&mut issues);
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].number, 2);
+        assert_eq!(issues[1].closed_at, Some(now - Duration::hours(1)));
+    }
 }
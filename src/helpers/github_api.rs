@@ -0,0 +1,319 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{header::LINK, Error};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::time::Instant;
+
+use super::{
+    breaker, fixtures, http,
+    upstreams::{self, Upstream},
+};
+use crate::config::GithubConfig;
+
+/// Reads the GitHub organization and token used for the GitHub API.
+///
+/// # Returns
+///
+/// A `Result` containing:
+/// - `Ok((String, String))` with the values of `GITHUB_ORG` and `GITHUB_TOKEN`.
+/// - `Err(anyhow::Error)` naming the variable that is missing.
+///
+/// Neither variable is required when the fixtures backend is enabled.
+pub fn get_org_and_token(github: &GithubConfig) -> Result<(String, String)> {
+    if fixtures::get().is_some() {
+        return Ok((
+            github.org.clone().unwrap_or("fixtures".to_string()),
+            github.token.clone().unwrap_or_default(),
+        ));
+    }
+
+    let gh_org = match &github.org {
+        Some(value) => value.clone(),
+        None => return Err(anyhow!("GITHUB_ORG is not configured")),
+    };
+
+    let gh_token = match &github.token {
+        Some(value) => value.clone(),
+        None => return Err(anyhow!("GITHUB_TOKEN is not configured")),
+    };
+
+    Ok((gh_org, gh_token))
+}
+
+/// Extracts the `next` page URL from an RFC 5988 `Link` header.
+///
+/// GitHub paginates list endpoints and advertises the surrounding pages in the `Link` header, e.g.
+/// `<https://api.github.com/orgs/liatrio/teams?page=2>; rel="next", <...?page=5>; rel="last"`.
+///
+/// # Arguments
+///
+/// * `header` - The raw value of the `Link` header.
+///
+/// # Returns
+///
+/// The URL of the next page, or `None` when the current page is the last one.
+///
+/// # Example
+///
+/// ```rust
+/// let header = r#"<https://api.github.com/orgs/liatrio/teams?page=2>; rel="next""#;
+///
+/// assert_eq!(
+///     parse_next_link(header),
+///     Some("https://api.github.com/orgs/liatrio/teams?page=2".to_string())
+/// );
+/// ```
+fn parse_next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim();
+
+        let is_next = parts.any(|param| {
+            let param = param.trim().replace(' ', "");
+            param == r#"rel="next""# || param == "rel=next"
+        });
+
+        if is_next && url.starts_with('<') && url.ends_with('>') {
+            Some(url[1..url.len() - 1].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Fetches every page of a GitHub list endpoint by following the `Link` headers.
+///
+/// The first request is made to `url` with `per_page=100`, and each subsequent request is made to the
+/// `next` URL advertised by GitHub until no further page is available. The items from all pages are
+/// collected in the order GitHub returned them.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client the requests are sent with.
+/// * `url` - The URL of the GitHub list endpoint, e.g. `https://api.github.com/orgs/{org}/teams`.
+/// * `gh_token` - The GitHub token used to authenticate the requests.
+///
+/// # Returns
+///
+/// A `Result` containing:
+/// - `Ok(Vec<T>)` with the items from every page.
+/// - `Err(anyhow::Error)` if any page request fails, responds with an error status, or cannot be parsed.
+///
+/// # Example
+///
+/// ```rust
+/// let url = format!("https://api.github.com/orgs/{}/teams", gh_org);
+///
+/// let teams: Vec<GitHubTeam> = get_paginated(&ctx.client, url, &gh_token).await?;
+/// ```
+pub async fn get_paginated<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: String,
+    gh_token: &str,
+) -> Result<Vec<T>> {
+    if let Some(fixtures) = fixtures::get() {
+        return fixtures.github(&url);
+    }
+
+    let mut items: Vec<T> = Vec::new();
+    let mut next_request = Some(client.get(url).query(&[("per_page", 100)]));
+
+    while let Some(request) = next_request.take() {
+        breaker::check(Upstream::GitHub)?;
+
+        let started = Instant::now();
+        let response_result = request
+            .headers(http::trace_headers())
+            .header("User-Agent", "request")
+            .header("Authorization", format!("token {}", gh_token))
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .send()
+            .await;
+
+        let response = match response_result {
+            Ok(value) => value,
+            Err(e) => {
+                upstreams::record(Upstream::GitHub, started, false);
+                breaker::record(Upstream::GitHub, false);
+                tracing::error!("GitHub Request Failed: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let status = response.status();
+
+        upstreams::record(Upstream::GitHub, started, status.is_success());
+        breaker::record(Upstream::GitHub, !breaker::is_outage(status.as_u16()));
+
+        if !status.is_success() {
+            tracing::error!("GitHub Request Responded with status: {:?}", status);
+            return Err(anyhow!(format!(
+                "GitHub responded with status: {:?}",
+                status
+            )));
+        }
+
+        next_request = response
+            .headers()
+            .get(LINK)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_next_link)
+            .map(|next_url| client.get(next_url));
+
+        let parse_result: Result<Vec<T>, Error> = response.json().await;
+
+        match parse_result {
+            Ok(mut value) => items.append(&mut value),
+            Err(e) => {
+                tracing::error!("GitHub Response Parsing Failed: {:?}", e);
+                return Err(e.into());
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GitHubCommit {
+    commit: GitHubCommitDetails,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GitHubCommitDetails {
+    author: Option<GitHubCommitAuthor>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GitHubCommitAuthor {
+    date: DateTime<Utc>,
+}
+
+/// The GitHub API every request is sent to.
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Rebuilds a pull request's `commits_url` from its owner, repository and number, so a URL taken from a webhook
+/// payload can't send the GitHub token to another host or endpoint.
+fn pull_commits_url(commits_url: &str) -> Result<String> {
+    let invalid = || anyhow!("Invalid commits URL: {}", commits_url);
+    let is_name = |part: &str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte))
+            && part != "."
+            && part != ".."
+    };
+
+    let path = commits_url
+        .strip_prefix(GITHUB_API)
+        .and_then(|path| path.strip_prefix("/repos/"))
+        .ok_or_else(invalid)?;
+
+    match path.split('/').collect::<Vec<&str>>()[..] {
+        [owner, repo, "pulls", number, "commits"]
+            if is_name(owner) && is_name(repo) && number.parse::<u64>().is_ok() =>
+        {
+            Ok(format!(
+                "{}/repos/{}/{}/pulls/{}/commits",
+                GITHUB_API, owner, repo, number
+            ))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Finds when the earliest commit of a pull request was authored.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client the requests are sent with.
+/// * `commits_url` - The pull request's `commits_url`, e.g. `https://api.github.com/repos/{org}/{repo}/pulls/1/commits`.
+///   URLs of other hosts or endpoints are rejected.
+/// * `gh_token` - The GitHub token used to authenticate the requests.
+///
+/// # Returns
+///
+/// The earliest author date, or `None` when the pull request has no commits with an author date.
+pub async fn get_first_commit_at(
+    client: &reqwest::Client,
+    commits_url: &str,
+    gh_token: &str,
+) -> Result<Option<DateTime<Utc>>> {
+    let commits: Vec<GitHubCommit> =
+        get_paginated(client, pull_commits_url(commits_url)?, gh_token).await?;
+
+    Ok(earliest_author_date(commits))
+}
+
+fn earliest_author_date(commits: Vec<GitHubCommit>) -> Option<DateTime<Utc>> {
+    commits
+        .into_iter()
+        .filter_map(|commit| commit.commit.author.map(|author| author.date))
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull_commits_url() {
+        assert_eq!(
+            pull_commits_url("https://api.github.com/repos/liatrio/repo-a/pulls/12/commits")
+                .unwrap(),
+            "https://api.github.com/repos/liatrio/repo-a/pulls/12/commits"
+        );
+
+        for url in [
+            "https://attacker.example/repos/liatrio/repo-a/pulls/12/commits",
+            "https://api.github.com.attacker.example/repos/liatrio/repo-a/pulls/12/commits",
+            "https://api.github.com/repos/liatrio/repo-a/pulls/12/commits?redirect=1",
+            "https://api.github.com/repos/liatrio/../user/pulls/12/commits",
+            "https://api.github.com/user",
+        ] {
+            assert!(pull_commits_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_parse_next_link_with_next() {
+        let header = r#"<https://api.github.com/organizations/1/teams?per_page=100&page=2>; rel="next", <https://api.github.com/organizations/1/teams?per_page=100&page=3>; rel="last""#;
+
+        assert_eq!(
+            parse_next_link(header),
+            Some("https://api.github.com/organizations/1/teams?per_page=100&page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_next_link_on_last_page() {
+        let header = r#"<https://api.github.com/organizations/1/teams?per_page=100&page=1>; rel="prev", <https://api.github.com/organizations/1/teams?per_page=100&page=1>; rel="first""#;
+
+        assert_eq!(parse_next_link(header), None);
+    }
+
+    #[test]
+    fn test_parse_next_link_empty() {
+        assert_eq!(parse_next_link(""), None);
+    }
+
+    #[test]
+    fn test_earliest_author_date() {
+        let commits: Vec<GitHubCommit> = serde_json::from_str(
+            r#"[
+                {"sha": "b", "commit": {"author": {"date": "2024-09-05T10:00:00Z"}}},
+                {"sha": "a", "commit": {"author": {"date": "2024-09-03T08:30:00Z"}}},
+                {"sha": "c", "commit": {"author": null}}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            earliest_author_date(commits).map(|date| date.to_rfc3339()),
+            Some("2024-09-03T08:30:00+00:00".to_string())
+        );
+        assert_eq!(earliest_author_date(vec![]), None);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::sync::OnceLock;
+
+use super::{
+    gatherer::MergeEntry,
+    patterns::{matches_any, parse_patterns, NamePattern},
+};
+use crate::config::HotfixesSettings;
+
+/// Decides which merged changes are hotfixes, by the branch they were merged from, their labels or their
+/// title.
+#[derive(Serialize, Debug, Clone)]
+pub struct HotfixMatcher {
+    branches: Vec<NamePattern>,
+    labels: Vec<NamePattern>,
+    #[serde(serialize_with = "serialize_title")]
+    title: Option<Regex>,
+}
+
+/// Writes the title regex as it was configured, without the case-insensitive flag it was compiled with.
+fn serialize_title<S: Serializer>(title: &Option<Regex>, serializer: S) -> Result<S::Ok, S::Error> {
+    title
+        .as_ref()
+        .map(|title| title.as_str().trim_start_matches("(?i)"))
+        .serialize(serializer)
+}
+
+impl Default for HotfixMatcher {
+    fn default() -> Self {
+        HotfixMatcher::new("hotfix/*,hotfix-*", "hotfix", None).unwrap()
+    }
+}
+
+static HOTFIXES: OnceLock<HotfixMatcher> = OnceLock::new();
+
+impl HotfixMatcher {
+    /// Builds a matcher from comma-separated lists of branch and label patterns, see `NamePattern`, and an
+    /// optional title regex.
+    pub fn new(branches: &str, labels: &str, title: Option<&str>) -> Result<Self> {
+        Ok(HotfixMatcher {
+            branches: parse_patterns(&branches.split(',').collect::<Vec<&str>>())?,
+            labels: parse_patterns(&labels.split(',').collect::<Vec<&str>>())?,
+            title: title
+                .filter(|title| !title.trim().is_empty())
+                .map(|title| Regex::new(&format!("(?i){}", title)))
+                .transpose()
+                .map_err(|e| anyhow!(format!("{}: HOTFIX_TITLE_PATTERN", e)))?,
+        })
+    }
+
+    /// Builds the hotfix rules from the `hotfixes` settings, see `HOTFIX_BRANCHES`, `HOTFIX_LABELS` and
+    /// `HOTFIX_TITLE_PATTERN`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob or regex is invalid.
+    pub fn from_config(config: &HotfixesSettings) -> Result<Self> {
+        HotfixMatcher::new(
+            &config.branches.join(","),
+            &config.labels.join(","),
+            config.title_pattern.as_deref(),
+        )
+    }
+
+    pub fn is_hotfix(&self, merge: &MergeEntry) -> bool {
+        merge
+            .branch
+            .as_ref()
+            .is_some_and(|branch| matches_any(&self.branches, branch))
+            || merge
+                .labels
+                .iter()
+                .any(|label| matches_any(&self.labels, label))
+            || self
+                .title
+                .as_ref()
+                .is_some_and(|title| title.is_match(&merge.title))
+    }
+}
+
+/// Loads the hotfix rules at startup, so an invalid pattern fails fast instead of when linking data.
+pub fn init(config: &HotfixesSettings) -> Result<()> {
+    let matcher = HotfixMatcher::from_config(config)?;
+
+    HOTFIXES
+        .set(matcher)
+        .map_err(|_| anyhow!("Hotfix rules are already initialized"))
+}
+
+/// Returns the configured hotfix rules, or the defaults when they were never initialized.
+pub fn get() -> &'static HotfixMatcher {
+    HOTFIXES.get_or_init(HotfixMatcher::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(title: &str, branch: &str, labels: &[&str]) -> MergeEntry {
+        MergeEntry {
+            title: title.to_string(),
+            branch: Some(branch.to_string()),
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_hotfixes() {
+        let matcher = HotfixMatcher::default();
+
+        assert!(matcher.is_hotfix(&merge("Fix login", "hotfix/login", &[])));
+        assert!(matcher.is_hotfix(&merge("Fix login", "fix-login", &["HotFix"])));
+        assert!(!matcher.is_hotfix(&merge("Hotfix login", "fix-login", &["bug"])));
+        assert!(!matcher.is_hotfix(&MergeEntry::default()));
+    }
+
+    #[test]
+    fn test_hotfix_title_pattern() {
+        let matcher = HotfixMatcher::new("", "", Some(r"^(hotfix|fix!)")).unwrap();
+
+        assert!(matcher.is_hotfix(&merge("HOTFIX: login", "main", &[])));
+        assert!(matcher.is_hotfix(&merge("fix!: login", "main", &[])));
+        assert!(!matcher.is_hotfix(&merge("Revert hotfix", "hotfix/login", &[])));
+        assert!(HotfixMatcher::new("", "", Some("(hotfix")).is_err());
+    }
+}
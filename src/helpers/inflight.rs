@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use dora_event_vendor::EventVendorFunctions;
+
+#[cfg(feature = "github")]
+use dora_event_vendor_github::GitHub as Vendor;
+
+use super::{domain::DeploymentState, loki::QueryResponse, response::ActiveDeployment};
+
+/// Finds the deployments started within a window whose latest status hasn't concluded yet.
+///
+/// Each deployment reports several statuses, e.g. `queued`, `in_progress` and then `success`. They are grouped
+/// by repository and deployment, and only the latest one decides whether the deployment is still in flight.
+pub fn find_active_deployments(
+    data: QueryResponse,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<ActiveDeployment> {
+    let mut latest: HashMap<(String, u32), ActiveDeployment> = HashMap::new();
+
+    for result in data.data.result {
+        for value in result.values {
+            let (Some(deployment), Some(status)) = (
+                value.json_data.deployment.as_ref(),
+                value.json_data.deployment_status.as_ref(),
+            ) else {
+                continue;
+            };
+
+            let Ok(state) = DeploymentState::try_from(status) else {
+                continue;
+            };
+
+            let updated_at = status.created_at.unwrap_or(value.timestamp);
+            let key = (result.stream.vcs_repository_name.clone(), deployment.id);
+
+            if latest
+                .get(&key)
+                .is_some_and(|current| current.updated_at > updated_at)
+            {
+                continue;
+            }
+
+            latest.insert(
+                key,
+                ActiveDeployment {
+                    repository: result.stream.vcs_repository_name.as_str().into(),
+                    team: result.stream.team_name.as_str().into(),
+                    environment: result
+                        .stream
+                        .deployment_environment_name
+                        .clone()
+                        .unwrap_or_default(),
+                    sha: deployment.sha.as_str().into(),
+                    state,
+                    started_at: deployment.created_at,
+                    updated_at,
+                    deploy_url: Vendor::extract_deployment_url(&value),
+                    change_url: Vendor::extract_change_url(&value),
+                },
+            );
+        }
+    }
+
+    let mut active: Vec<ActiveDeployment> = latest
+        .into_values()
+        .filter(|deployment| deployment.state.is_active())
+        .filter(|deployment| deployment.started_at >= start && deployment.started_at <= end)
+        .collect();
+
+    active.sort_by(|l, r| {
+        (l.started_at, &l.repository, &l.sha).cmp(&(r.started_at, &r.repository, &r.sha))
+    });
+
+    active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(id: u32, state: &str, at: &str) -> serde_json::Value {
+        let payload = serde_json::json!({
+            "deployment": {
+                "id": id,
+                "created_at": "2024-09-10T10:00:00Z",
+                "sha": format!("sha-{}", id),
+                "url": format!("https://api.github.com/repos/liatrio/repo-a/deployments/{}", id),
+            },
+            "deployment_status": {"state": state, "created_at": at},
+        });
+
+        serde_json::json!(["1725962400000000000", payload.to_string()])
+    }
+
+    #[test]
+    fn test_find_active_deployments() {
+        let data: QueryResponse = serde_json::from_value(serde_json::json!({"data": {"result": [{
+            "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a", "deployment_environment_name": "production"},
+            "values": [
+                status(1, "queued", "2024-09-10T10:00:00Z"),
+                status(1, "in_progress", "2024-09-10T10:01:00Z"),
+                status(2, "in_progress", "2024-09-10T10:01:00Z"),
+                status(2, "success", "2024-09-10T10:05:00Z"),
+                status(3, "queued", "2024-09-10T10:02:00Z"),
+            ],
+        }]}}))
+        .unwrap();
+
+        let start = DateTime::parse_from_rfc3339("2024-09-10T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let end = DateTime::parse_from_rfc3339("2024-09-11T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let active = find_active_deployments(data, start, end);
+
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].sha, "sha-1");
+        assert_eq!(active[0].state, DeploymentState::InProgress);
+        assert_eq!(active[0].environment, "production");
+        assert_eq!(active[1].state, DeploymentState::Queued);
+        assert_eq!(
+            active[0].change_url,
+            "https://github.com/liatrio/repo-a/commit/sha-1"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use dashmap::DashMap;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::Duration,
+};
+
+use super::prometheus::{Exposition, Histogram};
+
+/// Identifies the requests a duration is recorded against. The route is the matched path, e.g.
+/// `/teams/:team/repositories`, so the number of series stays bounded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RequestKey {
+    method: String,
+    route: String,
+    status: String,
+}
+
+static REQUESTS: LazyLock<DashMap<RequestKey, Histogram>> = LazyLock::new(DashMap::new);
+static LOKI_QUERIES: LazyLock<DashMap<&'static str, Histogram>> = LazyLock::new(DashMap::new);
+static LOKI_BATCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Records how long the API took to respond to a request.
+pub fn record_request(method: &str, route: &str, status: u16, duration: Duration) {
+    REQUESTS
+        .entry(RequestKey {
+            method: method.to_string(),
+            route: route.to_string(),
+            status: status.to_string(),
+        })
+        .or_default()
+        .observe(duration);
+}
+
+/// Records how long a Loki query took, and whether it succeeded.
+pub fn record_loki_query(duration: Duration, ok: bool) {
+    let outcome = if ok { "ok" } else { "error" };
+
+    LOKI_QUERIES.entry(outcome).or_default().observe(duration);
+}
+
+/// Counts a batch of Loki queries, see `Batches`.
+pub fn record_loki_batch() {
+    LOKI_BATCHES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Writes the request, Loki query and batch metrics recorded since startup.
+pub fn write(exposition: &mut Exposition) {
+    let requests: Vec<_> = REQUESTS
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().snapshot()))
+        .collect();
+
+    exposition.counter(
+        "dora_api_requests_total",
+        "API requests answered, by status.",
+        requests.iter().map(|(key, snapshot)| {
+            (
+                vec![
+                    ("method", key.method.as_str()),
+                    ("route", key.route.as_str()),
+                    ("status", key.status.as_str()),
+                ],
+                Some(snapshot.count as f64),
+            )
+        }),
+    );
+
+    exposition.histogram(
+        "dora_api_request_duration_seconds",
+        "Time taken to respond to API requests.",
+        requests.iter().map(|(key, snapshot)| {
+            (
+                vec![
+                    ("method", key.method.as_str()),
+                    ("route", key.route.as_str()),
+                    ("status", key.status.as_str()),
+                ],
+                snapshot.clone(),
+            )
+        }),
+    );
+
+    let loki_queries: Vec<_> = LOKI_QUERIES
+        .iter()
+        .map(|entry| (*entry.key(), entry.value().snapshot()))
+        .collect();
+
+    exposition.histogram(
+        "dora_api_loki_query_duration_seconds",
+        "Time taken by Loki queries.",
+        loki_queries
+            .into_iter()
+            .map(|(outcome, snapshot)| (vec![("outcome", outcome)], snapshot)),
+    );
+
+    exposition.counter(
+        "dora_api_loki_batches_total",
+        "Batches of Loki queries run to gather data.",
+        [(vec![], Some(LOKI_BATCHES.load(Ordering::Relaxed) as f64))],
+    );
+}
@@ -0,0 +1,130 @@
+//! Limits on the API's own requests, so a burst of large `/data` requests can't exhaust memory or starve
+//! `/health` and `/metrics`, which are left outside of them.
+
+use axum::{error_handling::HandleErrorLayer, http::StatusCode, BoxError, Router};
+use std::time::Duration;
+use tower::{
+    limit::GlobalConcurrencyLimitLayer,
+    load_shed::{error::Overloaded, LoadShedLayer},
+    timeout::{error::Elapsed, TimeoutLayer},
+    ServiceBuilder,
+};
+
+use crate::config::LimitsSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitsConfig {
+    /// How long a request may take before it is abandoned, or `None` for no limit.
+    pub timeout: Option<Duration>,
+    /// The most requests handled at once, or `None` for no limit.
+    pub max_concurrent: Option<usize>,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            timeout: Some(Duration::from_secs(300)),
+            max_concurrent: Some(64),
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// Builds the request limits from the `limits` settings, see `REQUEST_TIMEOUT_SECONDS` and
+    /// `MAX_CONCURRENT_REQUESTS`, where `0` disables a limit.
+    pub fn from_config(config: &LimitsSettings) -> Self {
+        LimitsConfig {
+            timeout: Some(config.request_timeout_seconds)
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+            max_concurrent: Some(config.max_concurrent_requests).filter(|max| *max > 0),
+        }
+    }
+
+    /// Applies the limits to every route of a router. The concurrency limit is shared by all of them, and by
+    /// clones of the router, e.g. the same routes nested under `/v1`.
+    pub fn apply<S>(&self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let router = match self.timeout {
+            Some(timeout) => router.layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(handle_error))
+                    .layer(TimeoutLayer::new(timeout)),
+            ),
+            None => router,
+        };
+
+        match self.max_concurrent {
+            Some(max) => router.layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(handle_error))
+                    .layer(LoadShedLayer::new())
+                    .layer(GlobalConcurrencyLimitLayer::new(max)),
+            ),
+            None => router,
+        }
+    }
+}
+
+async fn handle_error(e: BoxError) -> StatusCode {
+    error_status(&e)
+}
+
+/// Returns the status for a request rejected by a limit, `504` when it timed out and `503` when too many
+/// requests were being handled.
+fn error_status(e: &BoxError) -> StatusCode {
+    if e.is::<Elapsed>() {
+        tracing::warn!("Request Timed Out");
+        StatusCode::GATEWAY_TIMEOUT
+    } else if e.is::<Overloaded>() {
+        tracing::warn!("Request Rejected, Too Many Concurrent Requests");
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        tracing::error!("Request Failed: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_status() {
+        assert_eq!(
+            error_status(&BoxError::from(Elapsed::new())),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            error_status(&BoxError::from(Overloaded::new())),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            error_status(&BoxError::from("other")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_limits_from_config() {
+        assert_eq!(
+            LimitsConfig::from_config(&LimitsSettings::default()),
+            LimitsConfig::default()
+        );
+
+        let disabled = LimitsSettings {
+            request_timeout_seconds: 0,
+            max_concurrent_requests: 0,
+        };
+
+        assert_eq!(
+            LimitsConfig::from_config(&disabled),
+            LimitsConfig {
+                timeout: None,
+                max_concurrent: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! A small builder for the LogQL queries sent to Loki.
+//!
+//! Every value is written as a double quoted string with its quotes and backslashes escaped, so team,
+//! repository and environment names can't end a string early and change the query.
+
+use std::fmt;
+
+/// How a label is compared with a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Re,
+    NotRe,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Re => "=~",
+            Op::NotRe => "!~",
+        })
+    }
+}
+
+/// A label compared with a value, e.g. `team_name="platform"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matcher {
+    label: &'static str,
+    op: Op,
+    value: String,
+}
+
+impl Matcher {
+    pub fn eq(label: &'static str, value: impl Into<String>) -> Self {
+        Matcher::new(label, Op::Eq, value)
+    }
+
+    pub fn ne(label: &'static str, value: impl Into<String>) -> Self {
+        Matcher::new(label, Op::Ne, value)
+    }
+
+    /// Matches a regex, which Loki anchors to the whole value.
+    pub fn re(label: &'static str, value: impl Into<String>) -> Self {
+        Matcher::new(label, Op::Re, value)
+    }
+
+    pub fn not_re(label: &'static str, value: impl Into<String>) -> Self {
+        Matcher::new(label, Op::NotRe, value)
+    }
+
+    fn new(label: &'static str, op: Op, value: impl Into<String>) -> Self {
+        Matcher {
+            label,
+            op,
+            value: value.into(),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.label, self.op, quote(&self.value))
+    }
+}
+
+/// A pipeline stage after the label filters of a `LogQuery`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stage {
+    /// Keeps the entries matching any of the matchers.
+    Any(Vec<Matcher>),
+    /// Keeps the entries whose line contains the text.
+    Contains(String),
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Any(matchers) => write!(f, "| {}", join(matchers, " or ")),
+            Stage::Contains(text) => write!(f, "|= {}", quote(text)),
+        }
+    }
+}
+
+/// A log query, a stream selector followed by label filters and further stages, e.g.
+/// `{service_namespace="github"} | team_name="platform", event_name="change_opened"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogQuery {
+    selector: Matcher,
+    filters: Vec<Matcher>,
+    stages: Vec<Stage>,
+}
+
+impl LogQuery {
+    pub fn new(selector: Matcher) -> Self {
+        LogQuery {
+            selector,
+            filters: Vec::new(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Adds label filters that every entry has to match.
+    pub fn filter(mut self, matchers: impl IntoIterator<Item = Matcher>) -> Self {
+        self.filters.extend(matchers);
+        self
+    }
+
+    /// Adds a stage after the label filters, skipping an `Any` stage without matchers.
+    pub fn stage(mut self, stage: Stage) -> Self {
+        if !matches!(&stage, Stage::Any(matchers) if matchers.is_empty()) {
+            self.stages.push(stage);
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for LogQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}}}", self.selector)?;
+
+        if !self.filters.is_empty() {
+            write!(f, " | {}", join(&self.filters, ", "))?;
+        }
+
+        for stage in &self.stages {
+            write!(f, " {}", stage)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn join(matchers: &[Matcher], separator: &str) -> String {
+    matchers
+        .iter()
+        .map(Matcher::to_string)
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+/// Writes a value as a LogQL double quoted string, escaping the characters that would end or alter it.
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("platform"), r#""platform""#);
+        assert_eq!(quote(r#"a"} | drop"#), r#""a\"} | drop""#);
+        assert_eq!(quote(r"svc\-\d+"), r#""svc\\-\\d+""#);
+        assert_eq!(quote("a\nb"), r#""a\nb""#);
+    }
+
+    #[test]
+    fn test_log_query() {
+        let query = LogQuery::new(Matcher::eq("service_namespace", "github"))
+            .filter([
+                Matcher::eq("team_name", "o'neil \"team\""),
+                Matcher::ne("merged_at", ""),
+            ])
+            .stage(Stage::Any(vec![]))
+            .stage(Stage::Any(vec![
+                Matcher::re("environment", "(?i)prod"),
+                Matcher::not_re("env", r"qa\.eu"),
+            ]))
+            .stage(Stage::Contains("incident".to_string()));
+
+        assert_eq!(
+            query.to_string(),
+            r#"{service_namespace="github"} | team_name="o'neil \"team\"", merged_at!="" | environment=~"(?i)prod" or env!~"qa\\.eu" |= "incident""#
+        );
+        assert_eq!(
+            LogQuery::new(Matcher::eq("service_namespace", "github")).to_string(),
+            r#"{service_namespace="github"}"#
+        );
+    }
+}
@@ -1,15 +1,38 @@
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Duration, Utc};
+use futures::{stream, StreamExt};
 use reqwest::{Error, Response};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, time::Instant};
+
+use dora_event_vendor::{Deployment, EventVendorFunctions, ValueItem};
+
+#[cfg(feature = "github")]
+use dora_event_vendor_github::GitHub as Vendor;
+
+#[cfg(not(feature = "github"))]
+compile_error!("At least one event vendor feature, e.g. `github`, must be enabled");
 
 use super::{
-    event_vendor::EventVendorFunctions,
-    gatherer::{DeployEntry, GatheredData, IssueEntry, MergeEntry},
-    github::GitHub,
+    batching::{self, Batches},
+    branches, breaker,
+    context::Context,
+    deduplication::{self, Deduplication},
+    domain::DeploymentState,
+    environments::{self, EnvironmentMatcher},
+    gatherer::{
+        normalize_deployments, normalize_issues, DeployEntry, GatheredData, IssueEntry, MergeEntry,
+        SkippedEvent,
+    },
+    github_api, http, instrumentation,
+    logql::{LogQuery, Matcher, Stage},
+    patterns::{matches_any, NamePattern},
+    recordings::{self, RecordingMode},
     request::DataRequest,
+    upstreams::{self, Upstream},
+    usage,
 };
+use crate::config::LokiConfig;
 
 #[derive(Serialize, Debug, Clone, Default)]
 pub struct QueryParams {
@@ -17,25 +40,56 @@ pub struct QueryParams {
     pub start: String,
     pub end: String,
     pub limit: u16,
+    #[serde(skip)]
+    pub principal: String,
+    /// The tenant sent as `X-Scope-OrgID` to a multi-tenant Loki.
+    #[serde(skip)]
+    pub tenant: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+/// The most entries Loki returns per query page.
+const QUERY_LIMIT: u16 = 5000;
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct QueryResponse {
     pub data: Data,
+    /// Describes the entries left out when a query needed more pages than `LOKI_MAX_PAGES`, or more entries
+    /// than the limit shared one timestamp.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+    /// Set when entries were left out, so the data gathered from the response must not be cached.
+    #[serde(skip)]
+    pub incomplete: bool,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Data {
+    #[serde(deserialize_with = "deserialize_results")]
     pub result: Vec<ResultItem>,
+    #[serde(default)]
+    pub stats: QueryStats,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct QueryStats {
+    #[serde(default)]
+    pub summary: QueryStatsSummary,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStatsSummary {
+    #[serde(default)]
+    pub total_bytes_processed: u64,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct ResultItem {
     pub stream: Stream,
     pub values: Vec<ValueItem>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Stream {
     pub deployment_environment_name: Option<String>,
     pub vcs_repository_name: String,
@@ -43,83 +97,99 @@ pub struct Stream {
     pub merged_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Default)]
-pub struct ValueItem {
-    pub json_data: JsonData,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct JsonData {
-    pub pull_request: Option<PullRequest>,
-    pub deployment: Option<Deployment>,
-    pub deployment_status: Option<DeploymentStatus>,
-    pub issue: Option<Issue>,
-    pub repository: Option<Repository>,
-    pub workflow_run: Option<WorkflowRun>,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct Issue {
-    pub created_at: DateTime<Utc>,
-    pub closed_at: Option<DateTime<Utc>>,
-    pub number: u32,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct Repository {
-    pub name: String,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct PullRequest {
-    pub title: String,
-    pub user: User,
-    pub merge_commit_sha: String,
+/// The stream labels the collector has used for each field, from the newest version to the oldest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StreamProfile {
+    version: &'static str,
+    repository: &'static str,
+    team: &'static str,
+    environment: &'static str,
+    merged_at: &'static str,
 }
 
-#[derive(Deserialize, Debug, Default)]
-pub struct DeploymentStatus {
-    pub state: String,
+const STREAM_PROFILES: [StreamProfile; 2] = [
+    StreamProfile {
+        version: "v2",
+        repository: "vcs_repository_name",
+        team: "team_name",
+        environment: "deployment_environment_name",
+        merged_at: "merged_at",
+    },
+    StreamProfile {
+        version: "v1",
+        repository: "repository_name",
+        team: "team_name",
+        environment: "deployment_environment",
+        merged_at: "merged_at",
+    },
+];
+
+/// Returns the first profile whose labels are present on a stream.
+fn find_stream_profile(labels: &HashMap<String, String>) -> Option<&'static StreamProfile> {
+    STREAM_PROFILES.iter().find(|profile| {
+        labels.contains_key(profile.repository) && labels.contains_key(profile.team)
+    })
 }
 
-#[derive(Deserialize, Debug, Default)]
-pub struct Deployment {
-    pub id: u32,
-    pub created_at: DateTime<Utc>,
-    pub sha: String,
-    pub url: String,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct WorkflowRun {
-    pub workflow_id: Option<u32>,
-}
-
-#[derive(Deserialize, Debug, Default)]
-pub struct User {
-    pub login: String,
-}
-
-impl<'de> Deserialize<'de> for ValueItem {
+impl<'de> Deserialize<'de> for Stream {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let vec: Vec<String> = Vec::deserialize(deserializer)?;
-        if vec.len() != 2 {
-            return Err(serde::de::Error::custom("Expected a tuple of two elements"));
-        }
-        let json_data: JsonData =
-            serde_json::from_str(&vec[1]).map_err(serde::de::Error::custom)?;
-        Ok(ValueItem { json_data })
+        let labels: HashMap<String, String> = HashMap::deserialize(deserializer)?;
+
+        let profile = find_stream_profile(&labels).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "No stream profile matches the labels: {:?}",
+                labels.keys().collect::<Vec<_>>()
+            ))
+        })?;
+
+        let merged_at = match labels.get(profile.merged_at) {
+            Some(value) if !value.is_empty() => Some(
+                DateTime::parse_from_rfc3339(value)
+                    .map_err(serde::de::Error::custom)?
+                    .with_timezone(&Utc),
+            ),
+            _ => None,
+        };
+
+        Ok(Stream {
+            deployment_environment_name: labels.get(profile.environment).cloned(),
+            vcs_repository_name: labels[profile.repository].clone(),
+            team_name: labels[profile.team].clone(),
+            merged_at,
+        })
     }
 }
 
+/// Deserializes the streams of a Loki response, skipping the ones that can't be parsed instead of failing
+/// the whole response.
+fn deserialize_results<'de, D>(deserializer: D) -> Result<Vec<ResultItem>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values: Vec<serde_json::Value> = Vec::deserialize(deserializer)?;
+
+    Ok(values
+        .into_iter()
+        .filter_map(|value| match serde_json::from_value::<ResultItem>(value) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                tracing::warn!("Skipping Unparseable Stream: {:?}", e);
+                None
+            }
+        })
+        .collect())
+}
+
 /// Makes an asynchronous REST API call using GET and optional basic authentication.
 ///
 /// This function constructs and sends a GET request to the provided `url` with the given query parameters.
 /// If a `user` is supplied, basic authentication is used with the provided `password`. If no `user` is supplied,
-/// the request is made without authentication.
+/// the request is made without authentication. Every request is tagged with the requesting principal through
+/// the `X-Query-Tags` header, so Loki's own query logs can be attributed as well, and sends its tenant, if any,
+/// through the `X-Scope-OrgID` header.
 ///
 /// # Arguments
 ///
@@ -149,6 +219,7 @@ impl<'de> Deserialize<'de> for ValueItem {
 ///     end: "1625101200000000000".to_string(),
 ///     query: "some Loki query".to_string(),
 ///     limit: 5000,
+///     ..Default::default()
 /// };
 ///
 /// let result = make_rest_call("https://loki-server.com/api".to_string(), "".to_string(), "".to_string(), query_params).await;
@@ -172,34 +243,52 @@ impl<'de> Deserialize<'de> for ValueItem {
 ///
 /// Basic authentication is used if a `user` is provided. The `password` is optional but recommended.
 async fn make_rest_call(
+    client: &reqwest::Client,
     url: String,
     user: String,
     password: String,
     data: QueryParams,
 ) -> Result<Response, Error> {
-    let client = reqwest::Client::new();
-
-    match user.as_str() {
-        "" => client.get(url).query(&data).send().await,
-        _ => {
-            client
-                .get(url)
-                .query(&data)
-                .basic_auth(user, Some(password))
-                .send()
-                .await
-        }
+    let query_tags = format!(
+        "source=dora-api,principal={}",
+        sanitize_tag(&data.principal)
+    );
+
+    let mut request = client
+        .get(url)
+        .query(&data)
+        .headers(http::trace_headers())
+        .header("X-Query-Tags", query_tags);
+
+    if let Some(tenant) = &data.tenant {
+        request = request.header("X-Scope-OrgID", tenant);
     }
+
+    if !user.is_empty() {
+        request = request.basic_auth(user, Some(password));
+    }
+
+    request.send().await
+}
+
+/// Replaces the characters Loki doesn't accept in query tag values with `_`.
+fn sanitize_tag(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '/' => c,
+            _ => '_',
+        })
+        .collect()
 }
 
 /// Sends an asynchronous query request to a Loki server and returns the parsed response.
 ///
 /// This function constructs a REST call to a Loki instance using query parameters, authenticating
-/// with a username and token (if available via environment variables). It handles response
-/// parsing and error handling, returning a `QueryResponse` on success or an error if the request
-/// fails.
+/// with a username and token (if configured). It handles response parsing and error handling,
+/// returning a `QueryResponse` on success or an error if the request fails.
 ///
-/// Environment variables used:
+/// Configuration used, see `config::LokiConfig`:
 /// - `LOKI_URL`: The base URL of the Loki server (required).
 /// - `LOKI_USER`: Optional username for basic authentication (default: empty string).
 /// - `LOKI_TOKEN`: Optional password or token for basic authentication (default: empty string).
@@ -216,11 +305,17 @@ async fn make_rest_call(
 ///
 /// # Errors
 ///
-/// - If the `LOKI_URL` environment variable is missing or cannot be retrieved, an error is returned.
+/// - If `LOKI_URL` is not configured, an error is returned.
 /// - If the REST call fails, an error is returned and logged.
 /// - If the Loki server responds with a non-success HTTP status code, an error is returned.
+/// - If Loki's circuit breaker is open, a `BreakerOpen` error is returned without calling Loki.
 /// - If the response cannot be parsed into a `QueryResponse`, an error is returned and logged.
 ///
+/// # Recordings
+///
+/// When `LOKI_RECORDINGS` is `replay`, the response is read from the recording of the query instead of Loki,
+/// and when it is `record`, the response is recorded after it is received, see `helpers::recordings`.
+///
 /// # Example
 ///
 /// ```rust
@@ -231,7 +326,7 @@ async fn make_rest_call(
 ///     limit: 5000,
 /// };
 ///
-/// let result = query(query_params).await;
+/// let result = query_page(&ctx, query_params).await;
 ///
 /// match result {
 ///     Ok(response) => println!("Query succeeded with data: {:?}", response),
@@ -242,31 +337,60 @@ async fn make_rest_call(
 /// # Logging
 ///
 /// Errors are logged using the `tracing` crate for both request failures and response parsing failures.
-async fn query(data: QueryParams) -> Result<QueryResponse> {
-    let url_var = env::var("LOKI_URL");
+async fn query_page(ctx: &Context, data: QueryParams) -> Result<QueryResponse> {
+    let loki = &ctx.config.loki;
 
-    let url = match url_var {
-        Ok(value) => value,
-        Err(e) => return Err(anyhow!(format!("{}: LOKI_URL", e.to_string()))),
+    if loki.recordings == Some(RecordingMode::Replay) {
+        let response = recordings::replay(&loki.recordings_dir, &data)?;
+
+        return Ok(serde_json::from_value(response)?);
+    }
+
+    let url = match &loki.url {
+        Some(value) => value.clone(),
+        None => return Err(anyhow!("LOKI_URL is not configured")),
     };
 
-    let user = env::var("LOKI_USER").unwrap_or_default();
-    let password = env::var("LOKI_TOKEN").unwrap_or_default();
+    let user = loki.user.clone().unwrap_or_default();
+    let password = loki.token.clone().unwrap_or_default();
+
+    breaker::check(Upstream::Loki)?;
 
-    let response_result = make_rest_call(url, user, password, data.clone()).await;
+    let started = Instant::now();
+    let response_result = make_rest_call(&ctx.client, url, user, password, data.clone()).await;
 
     match response_result {
         Ok(response) => {
             let status = response.status();
 
+            upstreams::record(Upstream::Loki, started, status.is_success());
+            breaker::record(Upstream::Loki, !breaker::is_outage(status.as_u16()));
+            instrumentation::record_loki_query(started.elapsed(), status.is_success());
+
             if !status.is_success() {
                 return Err(anyhow!(format!("Loki Responded with status: {:?}", status)));
             }
 
-            let parse_result: Result<QueryResponse, Error> = response.json().await;
+            let body = response.text().await?;
+
+            if loki.recordings == Some(RecordingMode::Record) {
+                if let Err(e) = recordings::record(&loki.recordings_dir, &data, &body) {
+                    tracing::warn!("Recording Loki Response Failed: {:?}", e);
+                }
+            }
+
+            let parse_result: Result<QueryResponse, serde_json::Error> =
+                serde_json::from_str(&body);
 
             match parse_result {
-                Ok(value) => Ok(value),
+                Ok(value) => {
+                    usage::record(
+                        &data.principal,
+                        value.data.stats.summary.total_bytes_processed,
+                    );
+
+                    Ok(value)
+                }
                 Err(e) => {
                     tracing::error!("Loki Response Parsing Failed: {:?}", e);
                     Err(e.into())
@@ -274,30 +398,161 @@ async fn query(data: QueryParams) -> Result<QueryResponse> {
             }
         }
         Err(e) => {
+            upstreams::record(Upstream::Loki, started, false);
+            breaker::record(Upstream::Loki, false);
+            instrumentation::record_loki_query(started.elapsed(), false);
             tracing::error!("Loki Request Failed: {:?}", e);
             Err(e.into())
         }
     }
 }
 
-/// Constructs a set of query parameters based on the provided request, query, and optional filter.
+/// Queries Loki for every entry of a window, page by page.
+///
+/// Loki returns at most `limit` entries per query, newest first, so when a page is full the rest of the
+/// window, up to and including the page's oldest timestamp, is queried again, see `split_page`. After
+/// `LOKI_MAX_PAGES` pages, or when more entries than the limit share one timestamp, the oldest part of the
+/// window is left out and the response carries a warning describing it and is marked incomplete.
+async fn query(ctx: &Context, data: QueryParams) -> Result<QueryResponse> {
+    let mut response = QueryResponse::default();
+    let mut params = data;
+
+    for _ in 0..get_max_pages(&ctx.config.loki) {
+        let mut page = query_page(ctx, params.clone()).await?;
+        let split = split_page(&mut page, params.limit);
+
+        response.data.result.extend(page.data.result);
+
+        match split {
+            PageSplit::Last => return Ok(response),
+            PageSplit::Next(end) => params.end = end.to_string(),
+            PageSplit::Saturated(timestamp) => {
+                let warning = format!(
+                    "Loki returned {} events at {}, events at that time beyond them are missing",
+                    params.limit,
+                    timestamp.to_rfc3339()
+                );
+
+                tracing::warn!("{}", warning);
+                response.warnings.push(warning);
+                response.incomplete = true;
+
+                return Ok(response);
+            }
+        }
+    }
+
+    let end = params.end.parse::<i64>().unwrap_or_default();
+    let start = params.start.parse::<i64>().unwrap_or_default();
+    let warning = format!(
+        "Loki returned more than {} events per query, events from {} to {} are missing",
+        params.limit,
+        DateTime::from_timestamp_nanos(start).to_rfc3339(),
+        DateTime::from_timestamp_nanos(end).to_rfc3339()
+    );
+
+    tracing::warn!("{}", warning);
+    response.warnings.push(warning);
+    response.incomplete = true;
+
+    Ok(response)
+}
+
+/// Retrieves the most pages fetched for one query, see `query`.
+///
+/// This function reads the configured `LOKI_MAX_PAGES`. If it is not a positive integer, it defaults to 10 pages.
+pub fn get_max_pages(loki: &LokiConfig) -> usize {
+    Some(loki.max_pages)
+        .filter(|value| *value > 0)
+        .unwrap_or(10)
+}
+
+/// How a page continues its query, see `split_page`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageSplit {
+    /// The page holds fewer entries than the limit, so it is the last one.
+    Last,
+    /// The rest of the window, ending at this timestamp in nanoseconds, must be queried too.
+    Next(i64),
+    /// The page is full of entries sharing this one timestamp, so it can't be paged further and any more
+    /// entries at that time are lost.
+    Saturated(DateTime<Utc>),
+}
+
+/// Prepares a page for querying the rest of its window, when it is full.
+///
+/// The entries at the page's oldest timestamp are removed, since more of them may be beyond the limit, and the
+/// returned end in nanoseconds includes that timestamp, so the next page returns all of them.
+fn split_page(page: &mut QueryResponse, limit: u16) -> PageSplit {
+    let values = page.data.result.iter().flat_map(|item| item.values.iter());
+
+    if values.clone().count() < usize::from(limit) {
+        return PageSplit::Last;
+    }
+
+    let Some(oldest) = values.map(|value| value.timestamp).min() else {
+        return PageSplit::Last;
+    };
+
+    if page
+        .data
+        .result
+        .iter()
+        .flat_map(|item| item.values.iter())
+        .all(|value| value.timestamp == oldest)
+    {
+        return PageSplit::Saturated(oldest);
+    }
+
+    for item in &mut page.data.result {
+        item.values.retain(|value| value.timestamp > oldest);
+    }
+
+    page.data.result.retain(|item| !item.values.is_empty());
+
+    match oldest.timestamp_nanos_opt() {
+        Some(end) => PageSplit::Next(end + 1),
+        None => PageSplit::Last,
+    }
+}
+
+/// Returns the entries of the fullest response, which sizes the next batch, see `Batches::complete`.
+fn entries(responses: &[&QueryResponse]) -> usize {
+    responses
+        .iter()
+        .map(|response| {
+            response
+                .data
+                .result
+                .iter()
+                .map(|item| item.values.len())
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Constructs a set of query parameters based on the provided request, label filters, and optional stage.
 ///
-/// This function takes a `DataRequest` object, a query string, and an optional filter string to
-/// build a `QueryParams` structure for querying data. It also reads an environment variable
-/// `SERVICE_NAME` to determine the service namespace, defaulting to "github" if the variable is
-/// not set.
+/// This function takes a `DataRequest` object, the label filters of the events, and an optional further
+/// pipeline stage to build a `QueryParams` structure for querying data. The query is composed with
+/// `LogQuery`, which escapes every value. The events are selected from the request's `namespaces`,
+/// or else the comma-separated service namespaces in the `SERVICE_NAME` environment variable, defaulting to
+/// "github" if the variable is not set. Several namespaces are matched with a regex, see `namespace_selector`.
 ///
 /// The constructed query includes:
 ///
-/// 1. A team name filter, if present in the `request`.
-/// 2. A repository filter, if present in the `request`.
-/// 3. The main query and an optional filter string.
+/// 1. A team name filter, if present in the `request`. When the request names several teams or includes child
+///    teams, the filter becomes a regex matching any of the teams.
+/// 2. A repository filter, if present in the `request`, matching its repository patterns and excluding its
+///    excluded repositories.
+/// 3. The event's label filters and an optional stage, e.g. a line filter.
 ///
 /// # Arguments
 ///
 /// * `request` - A reference to a `DataRequest` that contains the query request information such as team, repositories, and time range.
-/// * `query` - A string reference representing the main query string.
-/// * `filter` - An optional string reference representing an additional filter to be applied.
+/// * `filters` - The label filters selecting the events.
+/// * `stage` - An optional stage applied after the label filters.
 ///
 /// # Returns
 ///
@@ -310,10 +565,10 @@ async fn query(data: QueryParams) -> Result<QueryResponse> {
 /// - `query`: The constructed query string.
 /// - `limit`: A hardcoded limit of 5000 for the query results.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function will panic if the environment variable `SERVICE_NAME` cannot be retrieved and is
-/// not set to a default, or if the `timestamp_nanos_opt` values from the `request` are `None`.
+/// Returns an error if the request's `start` or `end` can't be represented in nanoseconds, i.e. falls outside
+/// 1677-09-21 to 2262-04-11.
 ///
 /// # Example
 ///
@@ -325,60 +580,148 @@ async fn query(data: QueryParams) -> Result<QueryResponse> {
 ///     end: Some(Utc::now()),
 /// };
 ///
-/// let query_params = fill_query_params(&request, "deployment_status", Some("success"));
+/// let query_params = fill_query_params(
+///     &config.loki,
+///     &request,
+///     vec![Matcher::ne("deployment_status", "")],
+///     None,
+/// )?;
 ///
 /// assert_eq!(query_params.limit, 5000);
 /// assert!(query_params.query.contains(r#"team_name="team-a""#));
-/// assert!(query_params.query.contains(r#"vcs_repository_name="repo-a|repo-b""#));
-/// ```
-///
-/// If no filter is provided:
-///
-/// ```
-/// let query_params = fill_query_params(&request, "deployment_status", None);
-///
-/// assert!(query_params.query.ends_with("deployment_status"));
+/// assert!(query_params.query.contains(r#"vcs_repository_name=~"(?i)repo\\-a|repo\\-b" or repository_name=~"(?i)repo\\-a|repo\\-b""#));
 /// ```
-fn fill_query_params<T: AsRef<str>>(
+fn fill_query_params(
+    loki: &LokiConfig,
     request: &DataRequest,
-    query: T,
-    filter: Option<T>,
-) -> QueryParams {
-    let service_name_var = env::var("SERVICE_NAME").unwrap_or("github".to_string());
-
-    let team_query = match &request.team {
-        Some(t) => format!(r#"team_name="{}", "#, t),
-        None => "".to_string(),
+    filters: Vec<Matcher>,
+    stage: Option<Stage>,
+) -> Result<QueryParams> {
+    let team_filter = match request.team_names().as_slice() {
+        [] => None,
+        [team] => Some(Matcher::eq("team_name", *team)),
+        teams => {
+            let teams: Vec<String> = teams.iter().map(|team| regex::escape(team)).collect();
+
+            Some(Matcher::re("team_name", teams.join("|")))
+        }
     };
 
-    let repo_query = match &request.repositories {
-        Some(r) => format!(r#"vcs_repository_name="{}", "#, r.join("|")),
-        None => "".to_string(),
+    // The patterns are validated by `gather_data` before any query is built. Repositories are matched under
+    // every stream profile's label, since older events name them differently.
+    let repository_labels: Vec<&'static str> = STREAM_PROFILES
+        .iter()
+        .map(|profile| profile.repository)
+        .collect();
+    let repo_filters = request
+        .repository_filter()
+        .map(|filter| filter.stages(&repository_labels))
+        .unwrap_or_default();
+
+    let mut query = LogQuery::new(namespace_selector(&service_namespaces(loki, request)))
+        .filter(team_filter)
+        .filter(filters);
+
+    for repo_filter in repo_filters {
+        query = query.stage(repo_filter);
+    }
+
+    if let Some(stage) = stage {
+        query = query.stage(stage);
+    }
+
+    let query = query.to_string();
+
+    Ok(QueryParams {
+        start: timestamp_nanos(request.start)?,
+        end: timestamp_nanos(request.end)?,
+        query,
+        limit: QUERY_LIMIT,
+        principal: request.principal(),
+        tenant: query_tenant(loki, request),
+    })
+}
+
+/// Formats a timestamp as the nanoseconds since the epoch Loki expects, failing for timestamps outside the
+/// range nanoseconds can represent.
+fn timestamp_nanos(timestamp: DateTime<Utc>) -> Result<String> {
+    timestamp
+        .timestamp_nanos_opt()
+        .map(|nanos| nanos.to_string())
+        .ok_or_else(|| anyhow!("Timestamp {} is out of range", timestamp))
+}
+
+/// Returns the Loki tenant a request's events are queried from, the request's `tenant` or else
+/// `LOKI_TENANT_ID`. No tenant is sent when neither is set, as for a single tenant Loki.
+pub fn query_tenant(loki: &LokiConfig, request: &DataRequest) -> Option<String> {
+    match request.requested_tenant() {
+        Some(tenant) => Some(tenant.to_string()),
+        None => loki
+            .tenant_id
+            .as_ref()
+            .map(|tenant| tenant.trim().to_string())
+            .filter(|tenant| !tenant.is_empty()),
+    }
+}
+
+/// Checks that the tenant a request asks for is one of the comma-separated `LOKI_ALLOWED_TENANTS`, so callers
+/// can only read the tenants this API is meant to serve.
+///
+/// # Errors
+///
+/// Returns an error when the request names a tenant that isn't allowed, or any tenant while
+/// `LOKI_ALLOWED_TENANTS` is unset.
+pub fn validate_tenant(loki: &LokiConfig, request: &DataRequest) -> Result<()> {
+    let Some(tenant) = request.requested_tenant() else {
+        return Ok(());
     };
 
-    let query = match filter {
-        Some(f) => format!(
-            r#"{{service_namespace=`{}`}} | {}{}{} {}"#,
-            service_name_var,
-            team_query,
-            repo_query,
-            query.as_ref(),
-            f.as_ref()
-        ),
-        None => format!(
-            r#"{{service_namespace=`{}`}} | {}{}{}"#,
-            service_name_var,
-            team_query,
-            repo_query,
-            query.as_ref()
-        ),
+    match is_allowed_tenant(tenant, &loki.allowed_tenants) {
+        true => Ok(()),
+        false => Err(anyhow!(format!(
+            "Tenant {} is not in LOKI_ALLOWED_TENANTS",
+            tenant
+        ))),
+    }
+}
+
+fn is_allowed_tenant(tenant: &str, allowed: &[String]) -> bool {
+    allowed.iter().any(|allowed| allowed.trim() == tenant)
+}
+
+/// Returns the service namespaces a request's events are queried from, the request's `namespaces` or else the
+/// configured `SERVICE_NAME`, which defaults to `github`.
+pub fn service_namespaces(loki: &LokiConfig, request: &DataRequest) -> Vec<String> {
+    let namespaces = match request.requested_namespaces() {
+        Some(namespaces) => namespaces.to_vec(),
+        None => loki.service_names.clone(),
     };
 
-    QueryParams {
-        start: request.start.timestamp_nanos_opt().unwrap().to_string(),
-        end: request.end.timestamp_nanos_opt().unwrap().to_string(),
-        query,
-        limit: 5000,
+    let namespaces: Vec<String> = namespaces
+        .iter()
+        .map(|namespace| namespace.trim().to_string())
+        .filter(|namespace| !namespace.is_empty())
+        .collect();
+
+    match namespaces.is_empty() {
+        true => vec!["github".to_string()],
+        false => namespaces,
+    }
+}
+
+/// Builds the stream selector for the service namespaces, an exact match for one namespace and a regex
+/// matching any of them for several.
+fn namespace_selector(namespaces: &[String]) -> Matcher {
+    match namespaces {
+        [namespace] => Matcher::eq("service_namespace", namespace.as_str()),
+        namespaces => {
+            let namespaces: Vec<String> = namespaces
+                .iter()
+                .map(|namespace| regex::escape(namespace))
+                .collect();
+
+            Matcher::re("service_namespace", namespaces.join("|"))
+        }
     }
 }
 
@@ -408,7 +751,7 @@ fn fill_query_params<T: AsRef<str>>(
 ///     end: Some(Utc::now()),
 /// };
 ///
-/// let result = query_merge_data(&request).await;
+/// let result = query_merge_data(&ctx, &request).await;
 ///
 /// match result {
 ///     Ok(response) => println!("Query successful: {:?}", response),
@@ -417,14 +760,11 @@ fn fill_query_params<T: AsRef<str>>(
 /// ```
 ///
 /// This query specifically filters for events where a change was closed and successfully merged.
-async fn query_merge_data(request: &DataRequest) -> Result<QueryResponse> {
-    let query_params = fill_query_params(
-        request,
-        r#"event_name=`change_closed`, merged_at!="""#,
-        None::<&str>,
-    );
+async fn query_merge_data(ctx: &Context, request: &DataRequest) -> Result<QueryResponse> {
+    let query_params =
+        fill_query_params(&ctx.config.loki, request, EventKind::Merged.filters(), None)?;
 
-    query(query_params).await
+    query(ctx, query_params).await
 }
 
 /// Queries deployment data for successful or failed deployments.
@@ -453,7 +793,7 @@ async fn query_merge_data(request: &DataRequest) -> Result<QueryResponse> {
 ///     end: Some(Utc::now()),
 /// };
 ///
-/// let result = query_deploy_data(&request).await;
+/// let result = query_deploy_data(&ctx, &request).await;
 ///
 /// match result {
 ///     Ok(response) => println!("Query successful: {:?}", response),
@@ -461,21 +801,40 @@ async fn query_merge_data(request: &DataRequest) -> Result<QueryResponse> {
 /// }
 /// ```
 ///
-/// This query specifically filters for deployment events that resulted in either a success or failure.
-async fn query_deploy_data(request: &DataRequest) -> Result<QueryResponse> {
+/// This query specifically filters for deployment events that resulted in either a success or failure. When the
+/// request names its environments, only deployments to those environments are queried.
+async fn query_deploy_data(ctx: &Context, request: &DataRequest) -> Result<QueryResponse> {
     let query_params = fill_query_params(
+        &ctx.config.loki,
         request,
-        r#"deployment_status=~`failure|success`"#,
-        None::<&str>,
-    );
+        vec![Matcher::re("deployment_status", "failure|success")],
+        request.requested_environments().map(environment_filter),
+    )?;
+
+    query(ctx, query_params).await
+}
 
-    query(query_params).await
+/// Builds a LogQL label filter matching any of the environments, case-insensitively, under either stream
+/// profile's environment label. An environment also matches its services, e.g. `prod` matches `prod/service-a`.
+fn environment_filter(environments: &[String]) -> Stage {
+    let pattern = environments
+        .iter()
+        .map(|environment| regex::escape(environment.trim()))
+        .collect::<Vec<String>>()
+        .join("|");
+    let pattern = format!("(?i)({})(/.+)?", pattern);
+
+    Stage::Any(vec![
+        Matcher::re("deployment_environment_name", pattern.clone()),
+        Matcher::re("deployment_environment", pattern),
+    ])
 }
 
-/// Queries issue data for closed issues, optionally filtering for incidents.
+/// Queries issue data for closed and reopened issues, optionally filtering for incidents.
 ///
 /// This function constructs query parameters using the `fill_query_params` function, targeting
-/// events where the `event_name` is `issue_closed`. Additionally, it applies a filter for
+/// events where the `event_name` is `issue_closed` or `issue_reopened`, so an incident reopened after being
+/// closed isn't taken as fixed by its first close. Additionally, it applies a filter for
 /// events containing the word "incident". The query is then sent to the server using the `query`
 /// function to retrieve the relevant issue data.
 ///
@@ -499,7 +858,7 @@ async fn query_deploy_data(request: &DataRequest) -> Result<QueryResponse> {
 ///     end: Some(Utc::now()),
 /// };
 ///
-/// let result = query_issue_data(&request).await;
+/// let result = query_issue_data(&ctx, &request).await;
 ///
 /// match result {
 ///     Ok(response) => println!("Query successful: {:?}", response),
@@ -509,14 +868,15 @@ async fn query_deploy_data(request: &DataRequest) -> Result<QueryResponse> {
 ///
 /// This query specifically filters for events where an issue was closed, and optionally
 /// filters for incidents using the provided filter.
-async fn query_issue_data(request: &DataRequest) -> Result<QueryResponse> {
+async fn query_issue_data(ctx: &Context, request: &DataRequest) -> Result<QueryResponse> {
     let query_params = fill_query_params(
+        &ctx.config.loki,
         request,
-        r#"event_name=`issue_closed`"#,
-        Some("|= `incident`"),
-    );
+        vec![Matcher::re("event_name", "issue_closed|issue_reopened")],
+        Some(Stage::Contains("incident".to_string())),
+    )?;
 
-    query(query_params).await
+    query(ctx, query_params).await
 }
 
 /// Extracts deployment data from a `ValueItem` and constructs a `DeployEntry`.
@@ -534,12 +894,15 @@ async fn query_issue_data(request: &DataRequest) -> Result<QueryResponse> {
 /// * `value` - A reference to a `ValueItem` containing the deployment and deployment status.
 /// * `team_name` - A `String` representing the name of the team associated with the deployment.
 /// * `repository_name` - A `String` representing the name of the repository associated with the deployment.
+/// * `environment` - The environment group the deployment belongs to.
+/// * `environment_service` - The service named by the environment, e.g. `service-a` for `prod/service-a`.
 ///
 /// # Returns
 ///
 /// A `DeployEntry` struct containing:
 /// - The status of the deployment (`true` for success, `false` otherwise).
 /// - The repository and team names.
+/// - The service, from the deployment's payload or task, see `Deployment::service`, and otherwise its environment.
 /// - The timestamp when the deployment was created.
 /// - The SHA of the deployment.
 /// - The deployment URL.
@@ -565,119 +928,131 @@ async fn query_issue_data(request: &DataRequest) -> Result<QueryResponse> {
 ///     }
 /// };
 ///
-/// let entry = extract_deployment_data(&value, "team-a".to_string(), "repo-a".to_string());
+/// let entry = extract_deployment_data(
+///     &value,
+///     "team-a".to_string(),
+///     "repo-a".to_string(),
+///     "production".to_string(),
+///     None,
+/// )
+/// .unwrap();
 /// assert_eq!(entry.status, true);
 /// assert_eq!(entry.team, "team-a");
 /// assert_eq!(entry.repository, "repo-a");
 /// ```
 ///
 /// This function provides a convenient way to extract deployment information and populate a `DeployEntry` struct.
+///
+/// # Errors
+///
+/// Returns why the event can't be used when its payload is missing the deployment or its status, or the status
+/// isn't a known `DeploymentState`.
 fn extract_deployment_data(
     value: &ValueItem,
     team_name: String,
     repository_name: String,
-) -> DeployEntry {
-    let d: &Deployment = value.json_data.deployment.as_ref().unwrap();
-    let status = value.json_data.deployment_status.as_ref().unwrap().state == "success";
-
-    let deploy_url = GitHub::extract_deployment_url(value);
-    let change_url = GitHub::extract_change_url(value);
-
-    DeployEntry {
-        status,
-        repository: repository_name,
-        team: team_name,
+    environment: String,
+    environment_service: Option<String>,
+) -> Result<DeployEntry, &'static str> {
+    let d: &Deployment = value
+        .json_data
+        .deployment
+        .as_ref()
+        .ok_or("The event has no deployment")?;
+    let state = value
+        .json_data
+        .deployment_status
+        .as_ref()
+        .ok_or("The event has no deployment status")
+        .and_then(|status| {
+            DeploymentState::try_from(status)
+                .map_err(|_| "The event has an unknown deployment state")
+        })?;
+
+    let deploy_url = Vendor::extract_deployment_url(value);
+    let change_url = Vendor::extract_change_url(value);
+
+    Ok(DeployEntry {
+        status: state.is_success(),
+        repository: repository_name.into(),
+        team: team_name.into(),
+        environment: environment.into(),
+        service: d.service().or(environment_service),
         created_at: d.created_at,
-        sha: d.sha.clone(),
+        sha: d.sha.as_str().into(),
         deploy_url,
         change_url,
-    }
+        duration_seconds: deploy_duration_seconds(value),
+    })
 }
 
-/// Filters duplicate deployments by their SHA, retaining only successful ones.
-///
-/// This function takes a mutable reference to a vector of `DeployEntry` structs and filters out duplicate
-/// deployments that share the same SHA. If multiple deployments have the same SHA, only the first successful
-/// deployment is retained. If a deployment with the same SHA is unsuccessful, it is removed unless a successful
-/// deployment with the same SHA is not yet encountered.
-///
-/// The filtering is done in-place, modifying the original vector by removing duplicates based on the SHA.
-///
-/// # Arguments
-///
-/// * `deploys` - A mutable reference to a vector of `DeployEntry` structs to be filtered.
-///
-/// # Behavior
-///
-/// - The function uses a `HashMap` to track SHAs that have been seen and their success status.
-/// - If a deployment's SHA has not been encountered, it is added to the map.
-/// - If a deployment's SHA has already been encountered, only the first successful deployment is retained, and
-///   any further deployments with the same SHA are removed.
-///
-/// # Example
-///
-/// ```rust
-/// let mut deploys = vec![
-///     DeployEntry {
-///         sha: "abcdef".to_string(),
-///         status: false,
-///         ..Default::default()
-///     },
-///     DeployEntry {
-///         sha: "abcdef".to_string(),
-///         status: true,
-///         ..Default::default()
-///     },
-///     DeployEntry {
-///         sha: "123456".to_string(),
-///         status: true,
-///         ..Default::default()
-///     },
-/// ];
-///
-/// filter_duplicate_deployments_by_sha(&mut deploys);
-///
-/// assert_eq!(deploys.len(), 2); // Only the successful "abcdef" and "123456" remain
-/// assert!(deploys.iter().any(|d| d.sha == "abcdef" && d.status == true));
-/// assert!(deploys.iter().any(|d| d.sha == "123456"));
-/// ```
-///
-/// This function is useful for cleaning up deployment lists where multiple entries may exist
-/// for the same deployment, but only the successful ones should be retained.
-fn filter_duplicate_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
-    let mut seen_shas: HashMap<String, bool> = HashMap::new();
-
-    deploys.retain(|entry| {
-        let sha = entry.sha.clone();
+/// Logs a malformed event and records it in `skipped`, so it is reported with the response instead of
+/// failing the request.
+fn skip_event(
+    skipped: &mut Vec<SkippedEvent>,
+    kind: &'static str,
+    repository: &str,
+    value: &ValueItem,
+    reason: &'static str,
+) {
+    tracing::warn!(
+        kind,
+        repository,
+        timestamp = %value.timestamp.to_rfc3339(),
+        "Skipping Malformed Event: {}",
+        reason
+    );
 
-        if let Some(&seen) = seen_shas.get(&sha) {
-            if !seen && entry.status {
-                seen_shas.entry(sha).and_modify(|value| *value = true);
-                return true;
-            }
-            false
-        } else {
-            seen_shas.insert(sha, entry.status);
-            true
-        }
+    skipped.push(SkippedEvent {
+        kind,
+        repository: repository.to_string(),
+        timestamp: value.timestamp,
+        reason,
     });
 }
 
+/// Measures how long the workflow run behind a deployment took, from when the run started to when it
+/// completed, or to when it reported the deployment's status while it is still running.
+///
+/// Returns `None` when the payload doesn't include the workflow run's start.
+fn deploy_duration_seconds(value: &ValueItem) -> Option<i64> {
+    let run = value.json_data.workflow_run.as_ref()?;
+    let started_at = run.run_started_at?;
+
+    let finished_at = match (run.status.as_deref(), run.updated_at) {
+        (Some("completed"), Some(updated_at)) => updated_at,
+        _ => value
+            .json_data
+            .deployment_status
+            .as_ref()
+            .and_then(|status| status.created_at)
+            .unwrap_or(value.timestamp),
+    };
+
+    let seconds = (finished_at - started_at).num_seconds();
+
+    (seconds >= 0).then_some(seconds)
+}
+
 /// Sorts and filters deployment data by environment, repository, and timestamp.
 ///
 /// This function processes a `QueryResponse` containing deployment data, filters the deployments
 /// based on the environment names (targeting production environments), and groups the filtered
 /// deployments by repository name. It also sorts the deployments by their creation timestamp
-/// and filters out duplicate deployments based on their SHA, keeping only the first successful
-/// deployment for each SHA.
+/// and filters out duplicate deployments based on their SHA, by default keeping only the first successful
+/// deployment for each SHA, see `Deduplication`.
 ///
-/// The production environments are determined by reading the `PRODUCTION_ENVIRONMENT_NAMES` environment
-/// variable, which defaults to "production,prod" if not set. Only deployments from these environments are
-/// considered during processing.
+/// The environments are determined by the given `EnvironmentMatcher`, either the configured production
+/// environments, see `environments::production`, or the environments named by the request. Only deployments
+/// from these environments are considered during processing.
 ///
 /// # Arguments
 ///
 /// * `data` - A `QueryResponse` struct containing deployment data to be processed.
+/// * `environments` - The environments whose deployments are kept.
+/// * `services` - The services whose deployments are kept, or `None` to keep every deployment.
+/// * `deduplication` - How repeated deployments of the same SHA are counted.
+/// * `skipped` - Collects the events skipped because their payload is malformed, see `SkippedEvent`.
 ///
 /// # Returns
 ///
@@ -688,15 +1063,17 @@ fn filter_duplicate_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
 ///
 /// # Environment Variables
 ///
-/// * `PRODUCTION_ENVIRONMENT_NAMES` - A comma-separated list of environment names considered as production.
-///   Defaults to "production,prod" if not set.
+/// * `PRODUCTION_ENVIRONMENT_NAMES` - A comma-separated list of environment names, globs or regexes considered
+///   as production. Defaults to "production,prod,prod-*" if not set.
+/// * `PRODUCTION_ENVIRONMENT_EXCLUDE` - A comma-separated list of environments never considered as production.
 ///
 /// # Behavior
 ///
-/// 1. Filters deployments based on environment names (must match a name in the `PRODUCTION_ENVIRONMENT_NAMES` variable).
+/// 1. Filters deployments based on environment names (must match an included pattern and no excluded pattern),
+///    and on their service when `services` is given.
 /// 2. Groups the deployments by the repository name.
 /// 3. Sorts each group of deployments by their `created_at` timestamp.
-/// 4. Filters out duplicate deployments based on the SHA, retaining only the first successful deployment for each SHA.
+/// 4. Filters out duplicate deployments based on the SHA, according to `deduplication`.
 ///
 /// # Example
 ///
@@ -705,7 +1082,13 @@ fn filter_duplicate_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
 ///     data: ... // Query result data here
 /// };
 ///
-/// let sorted_deployments = sort_deploy_data(query_response);
+/// let sorted_deployments = sort_deploy_data(
+///     query_response,
+///     environments::production(),
+///     None,
+///     Deduplication::KeepFirst,
+///     &mut vec![],
+/// );
 ///
 /// for (repo, deploys) in sorted_deployments {
 ///     println!("Repository: {}", repo);
@@ -716,24 +1099,50 @@ fn filter_duplicate_deployments_by_sha(deploys: &mut Vec<DeployEntry>) {
 /// ```
 ///
 /// In this example, the deployment data is sorted by repository and timestamp, and duplicates are filtered by SHA.
-fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
+fn sort_deploy_data(
+    data: QueryResponse,
+    environments: &EnvironmentMatcher,
+    services: Option<&[NamePattern]>,
+    deduplication: Deduplication,
+    skipped: &mut Vec<SkippedEvent>,
+) -> HashMap<String, Vec<DeployEntry>> {
     let mut grouped_deploys: HashMap<String, Vec<DeployEntry>> = HashMap::new();
-    let prod_env_names =
-        env::var("PRODUCTION_ENVIRONMENT_NAMES").unwrap_or("production,prod".to_string());
 
     for r in data.data.result {
-        let env = r.stream.deployment_environment_name.unwrap().to_lowercase();
+        let env = r.stream.deployment_environment_name.unwrap_or_default();
 
-        if !prod_env_names.contains(&env) && !env.starts_with("prod-") {
+        let Some((environment, environment_service)) = environments.group_with_service(&env) else {
             continue;
-        }
+        };
 
         let repository_name = r.stream.vcs_repository_name;
         let team_name = r.stream.team_name;
 
         for value in r.values {
-            let record =
-                extract_deployment_data(&value, team_name.clone(), repository_name.clone());
+            let record = match extract_deployment_data(
+                &value,
+                team_name.clone(),
+                repository_name.clone(),
+                environment.clone(),
+                environment_service.clone(),
+            ) {
+                Ok(record) => record,
+                Err(reason) => {
+                    skip_event(skipped, "deployment", &repository_name, &value, reason);
+                    continue;
+                }
+            };
+
+            if let Some(services) = services {
+                let matches = record
+                    .service
+                    .as_deref()
+                    .is_some_and(|service| matches_any(services, service));
+
+                if !matches {
+                    continue;
+                }
+            }
 
             grouped_deploys
                 .entry(repository_name.clone())
@@ -743,9 +1152,7 @@ fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
     }
 
     for v in grouped_deploys.values_mut() {
-        v.sort_by(|l, r| l.created_at.cmp(&r.created_at));
-
-        filter_duplicate_deployments_by_sha(v);
+        normalize_deployments(v, deduplication);
     }
 
     grouped_deploys
@@ -756,11 +1163,13 @@ fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
 /// This function processes a `QueryResponse` containing issue data, groups the issues by
 /// repository name, and sorts each group of issues by their creation timestamp (`created_at`).
 ///
-/// Each issue is represented as an `IssueEntry` containing the issue's number, creation time, and closing time.
+/// Each issue is represented as an `IssueEntry` containing the issue's number, creation time, and its latest
+/// closing and reopening times, combined from all of its close and reopen events.
 ///
 /// # Arguments
 ///
 /// * `data` - A `QueryResponse` struct containing issue data to be processed.
+/// * `skipped` - Collects the events skipped because their payload is missing the issue or its repository.
 ///
 /// # Returns
 ///
@@ -770,7 +1179,8 @@ fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
 ///
 /// # Behavior
 ///
-/// 1. Extracts the repository name and issue details from the provided `QueryResponse`.
+/// 1. Extracts the repository name and issue details from the provided `QueryResponse`, skipping malformed
+///    events.
 /// 2. Groups the issues by the repository name.
 /// 3. Sorts each group of issues by their creation timestamp.
 ///
@@ -781,7 +1191,7 @@ fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
 ///     data: ... // Query result data here
 /// };
 ///
-/// let sorted_issues = sort_issue_data(query_response);
+/// let sorted_issues = sort_issue_data(query_response, &mut vec![]);
 ///
 /// for (repo, issues) in sorted_issues {
 ///     println!("Repository: {}", repo);
@@ -792,26 +1202,50 @@ fn sort_deploy_data(data: QueryResponse) -> HashMap<String, Vec<DeployEntry>> {
 /// ```
 ///
 /// In this example, the issues are grouped by repository and sorted by their creation time.
-fn sort_issue_data(data: QueryResponse) -> HashMap<String, Vec<IssueEntry>> {
+fn sort_issue_data(
+    data: QueryResponse,
+    skipped: &mut Vec<SkippedEvent>,
+) -> HashMap<String, Vec<IssueEntry>> {
     let mut grouped_issues: HashMap<String, Vec<IssueEntry>> = HashMap::new();
 
     for result in data.data.result {
         for value in result.values {
-            let rn = value.json_data.repository.unwrap().name;
-            let issue = value.json_data.issue.unwrap();
+            let (Some(repository), Some(issue)) = (
+                value.json_data.repository.as_ref(),
+                value.json_data.issue.as_ref(),
+            ) else {
+                let reason = match value.json_data.issue {
+                    Some(_) => "The event has no repository",
+                    None => "The event has no issue",
+                };
+
+                skip_event(
+                    skipped,
+                    "issue",
+                    &result.stream.vcs_repository_name,
+                    &value,
+                    reason,
+                );
+                continue;
+            };
 
+            // A reopened issue's payload has no `closed_at`, so the event's own time is when it was reopened.
             let ie = IssueEntry {
                 created_at: issue.created_at,
                 closed_at: issue.closed_at,
+                reopened_at: issue.closed_at.is_none().then_some(value.timestamp),
                 number: issue.number,
             };
 
-            grouped_issues.entry(rn.clone()).or_default().push(ie)
+            grouped_issues
+                .entry(repository.name.clone())
+                .or_default()
+                .push(ie)
         }
     }
 
     for v in grouped_issues.values_mut() {
-        v.sort_by(|l, r| l.created_at.cmp(&r.created_at))
+        normalize_issues(v);
     }
 
     grouped_issues
@@ -824,11 +1258,16 @@ fn sort_issue_data(data: QueryResponse) -> HashMap<String, Vec<IssueEntry>> {
 /// and merge timestamp, and creates a `MergeEntry`. The data is then stored in a `HashMap` where
 /// the key is the merge commit SHA, and the value is the corresponding `MergeEntry`.
 ///
-/// If multiple entries are encountered for the same SHA, only the first one is retained.
+/// If multiple entries are encountered for the same SHA, e.g. from batches sharing a boundary, the earliest
+/// merge is retained. Merges by an excluded author, e.g. a bot, and merges into a branch other than the main
+/// branches configured by `MAIN_BRANCH_NAMES`, e.g. a long-lived feature branch, are skipped, so they don't
+/// count towards lead time.
 ///
 /// # Arguments
 ///
 /// * `merge_data` - A `QueryResponse` struct containing merge data to be processed.
+/// * `excluded_authors` - The authors whose merges are skipped.
+/// * `skipped` - Collects the events skipped because they are missing the pull request or when it was merged.
 ///
 /// # Returns
 ///
@@ -843,7 +1282,7 @@ fn sort_issue_data(data: QueryResponse) -> HashMap<String, Vec<IssueEntry>> {
 ///     data: ... // Query result data here
 /// };
 ///
-/// let sorted_merges = sort_merge_data(merge_data);
+/// let sorted_merges = sort_merge_data(merge_data, &[], &mut vec![]);
 ///
 /// for (sha, entry) in sorted_merges {
 ///     println!("Merge commit SHA: {}", sha);
@@ -852,20 +1291,67 @@ fn sort_issue_data(data: QueryResponse) -> HashMap<String, Vec<IssueEntry>> {
 /// ```
 ///
 /// In this example, the merge data is grouped by SHA and contains details about the pull request and user who performed the merge.
-fn sort_merge_data(merge_data: QueryResponse) -> HashMap<String, MergeEntry> {
+fn sort_merge_data(
+    merge_data: QueryResponse,
+    excluded_authors: &[NamePattern],
+    skipped: &mut Vec<SkippedEvent>,
+) -> HashMap<String, MergeEntry> {
     let mut records_by_sha: HashMap<String, MergeEntry> = HashMap::new();
 
     for result in merge_data.data.result {
+        let repository = &result.stream.vcs_repository_name;
+
+        let Some(merged_at) = result.stream.merged_at else {
+            for value in &result.values {
+                skip_event(
+                    skipped,
+                    "merge",
+                    repository,
+                    value,
+                    "The stream has no merged_at label",
+                );
+            }
+            continue;
+        };
+
         for value in result.values {
-            let pr = value.json_data.pull_request.unwrap();
+            let Some(pr) = value.json_data.pull_request.as_ref() else {
+                skip_event(
+                    skipped,
+                    "merge",
+                    repository,
+                    &value,
+                    "The event has no pull request",
+                );
+                continue;
+            };
+
+            let Some(merge_commit_sha) = pr.merge_commit_sha.clone() else {
+                continue;
+            };
+
+            if matches_any(excluded_authors, &pr.user.login)
+                || !branches::is_main_branch(pr.base.as_ref().map(|base| base.name.as_str()))
+            {
+                continue;
+            }
 
             let record = MergeEntry {
                 user: pr.user.login.clone(),
                 title: pr.title.clone(),
-                merged_at: result.stream.merged_at.unwrap(),
+                merged_at,
+                commits_url: pr.commits_url.clone(),
+                branch: pr.head.as_ref().map(|head| head.name.clone()),
+                labels: pr.labels.iter().map(|label| label.name.clone()).collect(),
+                first_commit_at: None,
             };
 
-            records_by_sha.entry(pr.merge_commit_sha).or_insert(record);
+            match records_by_sha.get(&merge_commit_sha) {
+                Some(existing) if existing.merged_at <= record.merged_at => {}
+                _ => {
+                    records_by_sha.insert(merge_commit_sha, record);
+                }
+            }
         }
     }
 
@@ -882,6 +1368,7 @@ fn sort_merge_data(merge_data: QueryResponse) -> HashMap<String, MergeEntry> {
 /// # Arguments
 ///
 /// * `request` - A `DataRequest` struct containing the information needed to perform the queries.
+/// * `skipped` - Collects a warning for each query skipped because it failed while `request.partial` is set.
 ///
 /// # Returns
 ///
@@ -892,9 +1379,12 @@ fn sort_merge_data(merge_data: QueryResponse) -> HashMap<String, MergeEntry> {
 ///
 /// # Behavior
 ///
-/// 1. The function spawns three asynchronous tasks to query deployment data, issue data, and merge data.
+/// 1. The function spawns three asynchronous tasks to query deployment data, issue data, and merge data. When
+///    the fixtures backend is enabled, the data is read from the fixtures instead.
 /// 2. It waits for all three tasks to complete using `tokio::join!`.
-/// 3. If any of the queries result in an error, the function logs the error and returns it.
+/// 3. If any of the queries result in an error, the function logs the error and returns it. For partial requests,
+///    a failed issue or merge query is skipped with a warning instead, since deployments are still useful without
+///    them. A failed deployment query is always an error.
 /// 4. If all queries are successful, the function returns a tuple containing the three query responses.
 ///
 /// # Example
@@ -907,7 +1397,7 @@ fn sort_merge_data(merge_data: QueryResponse) -> HashMap<String, MergeEntry> {
 ///     end: Some(Utc::now()),
 /// };
 ///
-/// let result = query_data(request).await;
+/// let result = query_data(&ctx, request, &mut vec![]).await;
 ///
 /// match result {
 ///     Ok((deploy_data, issue_data, merge_data)) => {
@@ -922,10 +1412,18 @@ fn sort_merge_data(merge_data: QueryResponse) -> HashMap<String, MergeEntry> {
 /// ```
 ///
 /// In this example, the function queries deployment, issue, and merge data concurrently and handles any potential errors.
-async fn query_data(request: DataRequest) -> Result<(QueryResponse, QueryResponse, QueryResponse)> {
-    let deploy_data_task = query_deploy_data(&request);
-    let issue_data_task = query_issue_data(&request);
-    let merge_data_task = query_merge_data(&request);
+async fn query_data(
+    ctx: &Context,
+    request: DataRequest,
+    skipped: &mut Vec<String>,
+) -> Result<(QueryResponse, QueryResponse, QueryResponse)> {
+    if let Some(fixtures) = ctx.fixtures {
+        return Ok(fixtures.query(&request));
+    }
+
+    let deploy_data_task = query_deploy_data(ctx, &request);
+    let issue_data_task = query_issue_data(ctx, &request);
+    let merge_data_task = query_merge_data(ctx, &request);
 
     let (deploy_data_result, issue_data_result, merge_data_result) =
         tokio::join!(deploy_data_task, issue_data_task, merge_data_task);
@@ -940,8 +1438,23 @@ async fn query_data(request: DataRequest) -> Result<(QueryResponse, QueryRespons
         }
     };
 
+    let mut skip = |data: &str, missing: &str, e: anyhow::Error| {
+        tracing::warn!("Skipping {} Data: {:?}", data, e);
+        skipped.push(format!(
+            "{} data could not be queried between {} and {}, so {} are missing",
+            data,
+            request.start.to_rfc3339(),
+            request.end.to_rfc3339(),
+            missing
+        ));
+    };
+
     let issue_data = match issue_data_result {
         Ok(value) => value,
+        Err(e) if request.partial => {
+            skip("Issue", "failures", e);
+            QueryResponse::default()
+        }
         Err(e) => {
             return {
                 println!("Error: {:?}", e);
@@ -952,6 +1465,10 @@ async fn query_data(request: DataRequest) -> Result<(QueryResponse, QueryRespons
 
     let merge_data = match merge_data_result {
         Ok(value) => value,
+        Err(e) if request.partial => {
+            skip("Merge", "lead times", e);
+            QueryResponse::default()
+        }
         Err(e) => {
             return {
                 println!("Error: {:?}", e);
@@ -963,46 +1480,169 @@ async fn query_data(request: DataRequest) -> Result<(QueryResponse, QueryRespons
     Ok((deploy_data, issue_data, merge_data))
 }
 
-/// Retrieves the batch size for querying data over a specific number of days.
+/// Retrieves the number of days before a request's window that merges are also queried for.
+///
+/// This function reads the configured `MERGE_LOOKBACK_DAYS`. If it is not set, no look-back is applied.
+pub fn get_merge_lookback_days(loki: &LokiConfig) -> i64 {
+    loki.merge_lookback_days
+}
+
+/// Builds the request for the merges made in the days before a request's window, so deployments near its
+/// start still link to pull requests merged before it. Returns `None` when no look-back is configured.
+fn merge_lookback_request(request: &DataRequest, lookback_days: i64) -> Option<DataRequest> {
+    (lookback_days > 0).then(|| DataRequest {
+        start: request.start - Duration::days(lookback_days),
+        end: request.start,
+        ..request.clone()
+    })
+}
+
+/// Retrieves the most days after a request's window that are queried to resolve its failures.
+///
+/// This function reads the configured `FAILURE_LOOKAHEAD_MAX_DAYS`. If it is not set, it defaults to 7 days.
+pub fn get_failure_lookahead_max_days(loki: &LokiConfig) -> i64 {
+    loki.failure_lookahead_max_days
+}
+
+/// Builds the request for the days after a request's window, limited to `max_days` and to `now`, whose
+/// deployments and issues resolve the failures within the window. Returns `None` when the request doesn't
+/// ask for a look-ahead or its window already ends now.
+fn failure_lookahead_request(
+    request: &DataRequest,
+    max_days: i64,
+    now: DateTime<Utc>,
+) -> Option<DataRequest> {
+    let days = request
+        .failure_lookahead_days
+        .unwrap_or_default()
+        .min(max_days);
+    let end = (request.end + Duration::days(days.max(0))).min(now);
+
+    (days > 0 && end > request.end).then(|| DataRequest {
+        start: request.end,
+        end,
+        ..request.clone()
+    })
+}
+
+/// Queries the deployment and issue data after a request's window, in batches, for resolving failures.
+async fn query_lookahead_data(
+    ctx: &Context,
+    request: &DataRequest,
+) -> Result<(QueryResponse, QueryResponse)> {
+    let mut deploy_data = QueryResponse::default();
+    let mut issue_data = QueryResponse::default();
+
+    let mut batches = Batches::new(request, QUERY_LIMIT, batching::config());
+
+    while let Some(sub_request) = batches.current() {
+        instrumentation::record_loki_batch();
+
+        let (deploys, issues) = match ctx.fixtures {
+            Some(fixtures) => {
+                let (deploys, issues, _) = fixtures.query(&sub_request);
+                (deploys, issues)
+            }
+            None => {
+                let (deploys, issues) = tokio::join!(
+                    query_deploy_data(ctx, &sub_request),
+                    query_issue_data(ctx, &sub_request)
+                );
+
+                match (deploys, issues) {
+                    (Ok(deploys), Ok(issues)) => (deploys, issues),
+                    (Err(e), _) | (_, Err(e)) => {
+                        batches.retry(e)?;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        batches.complete(entries(&[&deploys, &issues]));
+        deploy_data.data.result.extend(deploys.data.result);
+        deploy_data.warnings.extend(deploys.warnings);
+        deploy_data.incomplete |= deploys.incomplete;
+        issue_data.data.result.extend(issues.data.result);
+        issue_data.warnings.extend(issues.warnings);
+        issue_data.incomplete |= issues.incomplete;
+    }
+
+    Ok((deploy_data, issue_data))
+}
+
+/// Retrieves the number of days of data Loki retains.
+///
+/// This function reads the configured `LOKI_RETENTION_DAYS`. If it is not set or not a positive integer,
+/// retention is treated as unlimited and `None` is returned.
+///
+/// # Returns
+///
+/// An `Option<i64>` with the configured retention in days.
+pub fn get_retention_days(loki: &LokiConfig) -> Option<i64> {
+    loki.retention_days.filter(|value| *value > 0)
+}
+
+/// Clamps the start of a request to the oldest data Loki still retains.
 ///
-/// This function reads the `LOKI_DAYS_BATCH_SIZE` environment variable to determine the number of days
-/// to use as the batch size for querying. If the environment variable is not set or cannot be parsed as an
-/// integer, the function defaults to returning `5`.
+/// Querying before the retention cutoff silently returns nothing for that portion of the window, which makes
+/// the metrics look misleadingly low. This function moves `request.start` (and `request.end`, if the whole
+/// window has expired) forward to the cutoff and describes the portion that had no available data.
+///
+/// # Arguments
+///
+/// * `request` - The `DataRequest` to clamp in place.
+/// * `retention_days` - The number of days Loki retains, or `None` if retention is unlimited.
+/// * `now` - The current time, used to compute the retention cutoff.
 ///
 /// # Returns
 ///
-/// An `i64` representing the number of days to use as the batch size. The value is either retrieved
-/// from the `LOKI_DAYS_BATCH_SIZE` environment variable or defaults to `5` if the variable is not set or
-/// contains an invalid value.
+/// An `Option<String>` containing a warning when the request was clamped, or `None` if it was left untouched.
 ///
 /// # Example
 ///
 /// ```rust
-/// // If LOKI_DAYS_BATCH_SIZE is set to "7"
-/// let batch_size = get_batch_days_size();
-/// assert_eq!(batch_size, 7);
+/// let mut request = DataRequest {
+///     start: Utc::now() - Duration::days(60),
+///     end: Utc::now(),
+///     ..
+/// };
 ///
-/// // If LOKI_DAYS_BATCH_SIZE is not set or is invalid, the default value of 5 is returned.
-/// let batch_size = get_batch_days_size();
-/// assert_eq!(batch_size, 5);
-/// ```
+/// let warning = clamp_to_retention(&mut request, Some(30), Utc::now());
 ///
-/// This function is useful for determining how many days' worth of data to process in each batch,
-/// with the flexibility of configuring the value via an environment variable.
-fn get_batch_days_size() -> i64 {
-    let var = env::var("LOKI_DAYS_BATCH_SIZE");
-
-    match var {
-        Ok(value) => value.parse::<i64>().unwrap_or(5),
-        Err(_) => 5,
+/// assert!(warning.is_some());
+/// ```
+fn clamp_to_retention(
+    request: &mut DataRequest,
+    retention_days: Option<i64>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let cutoff = now - Duration::days(retention_days?);
+
+    if request.start >= cutoff {
+        return None;
     }
+
+    let unavailable_end = request.end.min(cutoff);
+
+    let warning = format!(
+        "Loki retains {} days of data, no data is available from {} to {}",
+        retention_days?,
+        request.start.to_rfc3339(),
+        unavailable_end.to_rfc3339()
+    );
+
+    request.start = cutoff;
+    request.end = request.end.max(cutoff);
+
+    Some(warning)
 }
 
 /// Gathers deployment, issue, and merge data over a range of time by batching the requests.
 ///
-/// This function takes a `DataRequest` and processes it in batches, determined by the number of days
-/// specified in the environment variable `LOKI_DAYS_BATCH_SIZE` (defaulting to 5 days if not set). It repeatedly
-/// queries the data within smaller time windows until the entire requested time range is covered. The gathered
+/// This function takes a `DataRequest` and processes it in batches, see `Batches`, starting with the number of
+/// days specified in the environment variable `LOKI_DAYS_BATCH_SIZE` (defaulting to 5 days if not set). It
+/// repeatedly queries the data within smaller time windows until the entire requested time range is covered. The gathered
 /// data is then sorted and returned as a `GatheredData` struct containing deployment, issue, and merge data grouped
 /// by repository and SHA.
 ///
@@ -1018,9 +1658,14 @@ fn get_batch_days_size() -> i64 {
 ///
 /// # Batching Behavior
 ///
-/// The function calculates the total number of days in the request time range and divides the work into batches.
-/// Each batch retrieves data for a time window determined by `LOKI_DAYS_BATCH_SIZE`. The function uses multiple
-/// asynchronous queries, accumulating the results as it proceeds through the time range.
+/// The function divides the request time range into batches, from its end back. Each batch is sized by the
+/// previous one: it is halved when a query returned close to Loki's entry limit and doubled when every query
+/// returned few entries, and a batch that timed out is queried again at half its size. The function uses
+/// multiple asynchronous queries, accumulating the results as it proceeds through the time range.
+///
+/// The raw events of every batch are concatenated before anything is sorted, deduplicated or linked, so a
+/// failure and its fix in different batches are linked the same as within one batch. Events returned by two
+/// batches sharing a boundary are dropped, and ties are ordered deterministically, see `normalize_deployments`.
 ///
 /// # Example
 ///
@@ -1049,238 +1694,1150 @@ fn get_batch_days_size() -> i64 {
 ///
 /// # Environment Variables
 ///
-/// * `LOKI_DAYS_BATCH_SIZE` - Defines the number of days to include in each batch of the query. Defaults to 5 days if not set.
-pub async fn gather_data(request: DataRequest) -> Result<GatheredData> {
-    let mut time_length = (request.end - request.start).num_days();
-    let mut end = request.end;
-    let mut all_ok = vec![];
-
-    let batch_days_size = get_batch_days_size();
-    let batch_duration = Duration::days(batch_days_size);
+/// * `LOKI_DAYS_BATCH_SIZE` - Defines the number of days to include in the first batch of the query. Defaults to 5 days if not set.
+/// * `LOKI_ADAPTIVE_BATCHING`, `LOKI_MIN_BATCH_HOURS` and `LOKI_MAX_BATCH_DAYS` - How batches are resized, see
+///   `BatchConfig::from_config`. The sizes are read once at startup, see `batching::init`.
+/// * `LOKI_RETENTION_DAYS` - The number of days Loki retains. Requests starting before the retention cutoff are
+///   clamped to it and a warning is added to the gathered data. Unlimited if not set.
+pub async fn gather_data(ctx: &Context, mut request: DataRequest) -> Result<GatheredData> {
+    let loki = &ctx.config.loki;
+    let mut warnings = vec![];
+    let mut skipped = vec![];
+    let mut truncated = false;
+    let repositories = request.repository_filter()?;
+    let excluded_authors = request.excluded_authors()?;
+    let services = request.service_filter()?;
+    let deduplication = request
+        .deduplication()?
+        .unwrap_or_else(deduplication::configured);
+
+    if let Some(warning) = clamp_to_retention(&mut request, get_retention_days(loki), Utc::now()) {
+        tracing::warn!("{}", warning);
+        warnings.push(warning);
+    }
 
-    while time_length > 0 {
-        let mut sub_request = request.clone();
+    let mut all_ok = vec![];
 
-        sub_request.end = end;
+    let mut batches = Batches::new(&request, QUERY_LIMIT, batching::config());
 
-        if time_length > batch_days_size {
-            sub_request.start = end - batch_duration;
-        } else {
-            sub_request.start = end - Duration::days(time_length);
-        }
+    while let Some(sub_request) = batches.current() {
+        instrumentation::record_loki_batch();
 
-        let gather_result = query_data(sub_request).await;
+        let gather_result = query_data(ctx, sub_request, &mut skipped).await;
 
         match gather_result {
-            Ok(result) => all_ok.push(result),
-            Err(e) => return Err(e),
+            Ok(result) => {
+                batches.complete(entries(&[&result.0, &result.1, &result.2]));
+                all_ok.push(result);
+            }
+            Err(e) => batches.retry(e)?,
         };
-
-        time_length -= batch_days_size;
-        end -= batch_duration;
     }
 
     let mut deploy_data: QueryResponse = Default::default();
     let mut issue_data: QueryResponse = Default::default();
     let mut merge_data: QueryResponse = Default::default();
 
+    // Loki's regex dialect differs slightly from ours, so the repository patterns are applied again to
+    // what it returned.
+    let matches_repository =
+        |item: &ResultItem| repositories.is_match(&item.stream.vcs_repository_name);
+
     for (first, second, third) in all_ok {
-        deploy_data.data.result.extend(first.data.result);
-        issue_data.data.result.extend(second.data.result);
-        merge_data.data.result.extend(third.data.result);
+        warnings.extend(
+            [&first, &second, &third]
+                .into_iter()
+                .flat_map(|response| response.warnings.clone()),
+        );
+        truncated |= first.incomplete || second.incomplete || third.incomplete;
+        deploy_data
+            .data
+            .result
+            .extend(first.data.result.into_iter().filter(matches_repository));
+        issue_data
+            .data
+            .result
+            .extend(second.data.result.into_iter().filter(matches_repository));
+        merge_data
+            .data
+            .result
+            .extend(third.data.result.into_iter().filter(matches_repository));
+    }
+
+    // Only merges are looked for before the window, since they only link deployments within it. A look-back
+    // before Loki's retention is left out without a warning, as the window itself is still covered.
+    if let Some(lookback) = merge_lookback_request(&request, get_merge_lookback_days(loki)) {
+        match gather_events(ctx, lookback, EventKind::Merged).await {
+            Ok((lookback_data, _)) => merge_data.data.result.extend(lookback_data.data.result),
+            Err(e) if request.partial => {
+                tracing::warn!("Skipping Merge Look-back: {:?}", e);
+                skipped.push(format!(
+                    "Merges before {} could not be queried, so lead times near the start of the window may be missing",
+                    request.start.to_rfc3339()
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut lookahead_deploy_data = QueryResponse::default();
+    let mut lookahead = Duration::zero();
+
+    if let Some(lookahead_request) =
+        failure_lookahead_request(&request, get_failure_lookahead_max_days(loki), Utc::now())
+    {
+        match query_lookahead_data(ctx, &lookahead_request).await {
+            Ok((deploys, issues)) => {
+                lookahead = lookahead_request.end - lookahead_request.start;
+                warnings.extend(deploys.warnings.iter().chain(&issues.warnings).cloned());
+                truncated |= deploys.incomplete || issues.incomplete;
+                lookahead_deploy_data
+                    .data
+                    .result
+                    .extend(deploys.data.result.into_iter().filter(matches_repository));
+                issue_data
+                    .data
+                    .result
+                    .extend(issues.data.result.into_iter().filter(matches_repository));
+            }
+            Err(e) if request.partial => {
+                tracing::warn!("Skipping Failure Look-ahead: {:?}", e);
+                skipped.push(format!(
+                    "Deployments after {} could not be queried, so failures near the end of the window may be unresolved",
+                    request.end.to_rfc3339()
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let requested_environments = request
+        .requested_environments()
+        .map(EnvironmentMatcher::exact);
+    let environments = requested_environments
+        .as_ref()
+        .unwrap_or_else(|| environments::production());
+    let mut skipped_events = vec![];
+    let sorted_deploy_data = sort_deploy_data(
+        deploy_data,
+        environments,
+        services.as_deref(),
+        deduplication,
+        &mut skipped_events,
+    );
+    let sorted_lookahead_data = sort_deploy_data(
+        lookahead_deploy_data,
+        environments,
+        services.as_deref(),
+        deduplication,
+        &mut skipped_events,
+    );
+    let sorted_issue_data = sort_issue_data(issue_data, &mut skipped_events);
+    let mut sorted_merge_data = sort_merge_data(merge_data, &excluded_authors, &mut skipped_events);
+
+    if !skipped_events.is_empty() {
+        warnings.push(format!(
+            "{} malformed events were skipped, see skipped_events",
+            skipped_events.len()
+        ));
     }
 
-    let sorted_deploy_data = sort_deploy_data(deploy_data);
-    let sorted_issue_data = sort_issue_data(issue_data);
-    let sorted_merge_data = sort_merge_data(merge_data);
+    if request.include_first_commit.unwrap_or_default() {
+        warnings.extend(fill_first_commits(ctx, &mut sorted_merge_data).await);
+    }
 
     let gathered_data = GatheredData {
         deployments_by_repo: sorted_deploy_data,
         issues_by_repo: sorted_issue_data,
         merges_by_sha: sorted_merge_data,
+        lookahead_by_repo: sorted_lookahead_data,
+        lookahead,
+        deduplication,
+        incomplete: truncated || !skipped.is_empty(),
+        warnings: warnings.into_iter().chain(skipped).collect(),
+        skipped_events,
     };
 
     Ok(gathered_data)
 }
 
-#[cfg(test)]
-mod tests {
+/// The events gathered for the endpoints that aren't built on the records `gather_data` links.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// Pull requests being opened.
+    Opened,
+    /// Pull requests being merged, the same events `gather_data` links to deployments.
+    Merged,
+    /// Reviews submitted on a pull request, from GitHub's `pull_request_review` webhook.
+    Reviewed,
+    /// Every status a deployment reports, including `queued` and `in_progress`, not only its conclusion.
+    DeploymentStatuses,
+    /// Every status of the deployments of one commit, found by its SHA.
+    Deployed(String),
+}
+
+impl EventKind {
+    /// The label filters selecting the events.
+    fn filters(&self) -> Vec<Matcher> {
+        match self {
+            EventKind::Opened => vec![Matcher::eq("event_name", "change_opened")],
+            EventKind::Merged => vec![
+                Matcher::eq("event_name", "change_closed"),
+                Matcher::ne("merged_at", ""),
+            ],
+            EventKind::Reviewed => vec![Matcher::eq("event_name", "change_reviewed")],
+            EventKind::DeploymentStatuses | EventKind::Deployed(_) => {
+                vec![Matcher::ne("deployment_status", "")]
+            }
+        }
+    }
+
+    /// The line filter narrowing the events down further, if any.
+    fn stage(&self) -> Option<Stage> {
+        match self {
+            EventKind::Deployed(sha) => Some(Stage::Contains(sha.clone())),
+            _ => None,
+        }
+    }
+
+    /// The file the fixtures backend serves the events from.
+    fn fixture(&self) -> &'static str {
+        match self {
+            EventKind::Opened => "opened_data.json",
+            EventKind::Merged => "merge_data.json",
+            EventKind::Reviewed => "review_data.json",
+            EventKind::DeploymentStatuses | EventKind::Deployed(_) => "deploy_data.json",
+        }
+    }
+}
+
+/// Queries one kind of event over a request's window, in batches like `gather_data`.
+///
+/// # Returns
+///
+/// The events, limited to the requested repositories, and any warnings about the part of the window that
+/// could not be served.
+///
+/// # Errors
+///
+/// Returns an error if the repository patterns are invalid or any batch query fails.
+pub async fn gather_events(
+    ctx: &Context,
+    mut request: DataRequest,
+    event: EventKind,
+) -> Result<(QueryResponse, Vec<String>)> {
+    let loki = &ctx.config.loki;
+    let mut warnings = vec![];
+    let repositories = request.repository_filter()?;
+
+    if let Some(warning) = clamp_to_retention(&mut request, get_retention_days(loki), Utc::now()) {
+        tracing::warn!("{}", warning);
+        warnings.push(warning);
+    }
+
+    let mut events = QueryResponse::default();
+
+    let mut batches = Batches::new(&request, QUERY_LIMIT, batching::config());
+
+    while let Some(sub_request) = batches.current() {
+        instrumentation::record_loki_batch();
+
+        let response = match ctx.fixtures {
+            Some(fixtures) => fixtures.events(event.fixture(), &sub_request)?,
+            None => match query(
+                ctx,
+                fill_query_params(loki, &sub_request, event.filters(), event.stage())?,
+            )
+            .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    batches.retry(e)?;
+                    continue;
+                }
+            },
+        };
+
+        batches.complete(entries(&[&response]));
+        warnings.extend(response.warnings);
+
+        events.data.result.extend(
+            response
+                .data
+                .result
+                .into_iter()
+                .filter(|item| repositories.is_match(&item.stream.vcs_repository_name)),
+        );
+    }
+
+    Ok((events, warnings))
+}
+
+/// The number of pull requests whose commits are looked up in GitHub at once.
+const FIRST_COMMIT_CONCURRENCY: usize = 8;
+
+/// Looks up when the first commit of each merged pull request was authored, so lead time can be measured
+/// from the first commit instead of the merge.
+///
+/// The webhook payloads don't carry commit timestamps, so they are read from the GitHub API through each
+/// pull request's `commits_url`. Pull requests that can't be looked up are left without a first commit.
+///
+/// # Returns
+///
+/// Warnings for the lookups that failed.
+async fn fill_first_commits(
+    ctx: &Context,
+    merges: &mut HashMap<String, MergeEntry>,
+) -> Vec<String> {
+    let gh_token = match github_api::get_org_and_token(&ctx.config.github) {
+        Ok((_, gh_token)) => gh_token,
+        Err(e) => {
+            tracing::warn!("First Commits Skipped: {:?}", e);
+            return vec![
+                "First commits were not looked up because GitHub is not configured".to_string(),
+            ];
+        }
+    };
+
+    let lookups: Vec<(String, String)> = merges
+        .iter()
+        .filter(|(_, merge)| merge.first_commit_at.is_none())
+        .filter_map(|(sha, merge)| Some((sha.clone(), merge.commits_url.clone()?)))
+        .collect();
+
+    let results: Vec<(String, Result<Option<DateTime<Utc>>>)> = stream::iter(lookups)
+        .map(|(sha, commits_url)| {
+            let gh_token = &gh_token;
+
+            async move {
+                let result =
+                    github_api::get_first_commit_at(&ctx.client, &commits_url, gh_token).await;
+                (sha, result)
+            }
+        })
+        .buffer_unordered(FIRST_COMMIT_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut failed = 0;
+
+    for (sha, result) in results {
+        match result {
+            Ok(first_commit_at) => {
+                if let Some(merge) = merges.get_mut(&sha) {
+                    merge.first_commit_at = first_commit_at;
+                }
+            }
+            Err(e) => {
+                tracing::error!("First Commit Lookup Failed: {:?}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        0 => vec![],
+        _ => vec![format!(
+            "The first commit of {} pull requests could not be looked up",
+            failed
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::helpers::patterns::parse_patterns;
     use chrono::{DateTime, Utc};
-    use std::env;
 
     #[test]
     fn test_fill_query_params_with_all_fields() {
-        env::set_var("SERVICE_NAME", "test_service");
-
         let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
             team: Some("test_team".to_string()),
             repositories: Some(vec!["repo1".to_string(), "repo2".to_string()]),
             start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
             end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
         };
 
-        let filter = Some("filter".to_string());
-        let query = "query".to_string();
-
-        let result = fill_query_params(&request, &query, filter.as_ref());
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            vec![Matcher::eq("event_name", "change_opened")],
+            Some(Stage::Contains("incident".to_string())),
+        )
+        .unwrap();
 
         assert_eq!(result.start, "0");
         assert_eq!(result.end, "1000000000");
         assert_eq!(
             result.query,
-            r#"{service_namespace=`test_service`} | team_name="test_team", vcs_repository_name="repo1|repo2", query filter"#
+            r#"{service_namespace="test_service"} | team_name="test_team", event_name="change_opened" | vcs_repository_name=~"(?i)repo1|repo2" or repository_name=~"(?i)repo1|repo2" |= "incident""#
         );
         assert_eq!(result.limit, 5000);
     }
 
     #[test]
-    fn test_fill_query_params_without_optional_fields() {
-        env::set_var("SERVICE_NAME", "test_service");
+    fn test_fill_query_params_matches_v1_repositories() {
+        let request = DataRequest {
+            namespaces: Some(vec!["github".to_string()]),
+            repositories: Some(vec!["repo-a".to_string()]),
+            exclude_repositories: Some(vec!["repo-b".to_string()]),
+            ..Default::default()
+        };
+
+        let result = fill_query_params(&LokiConfig::default(), &request, vec![], None).unwrap();
 
+        assert_eq!(
+            result.query,
+            r#"{service_namespace="github"} | vcs_repository_name=~"(?i)repo\\-a" or repository_name=~"(?i)repo\\-a" | vcs_repository_name!~"(?i)repo\\-b" | repository_name!~"(?i)repo\\-b""#
+        );
+
+        let v1: ResultItem = serde_json::from_value(serde_json::json!({
+            "stream": {"repository_name": "repo-a", "team_name": "team-a"},
+            "values": [],
+        }))
+        .unwrap();
+
+        assert!(request
+            .repository_filter()
+            .unwrap()
+            .is_match(&v1.stream.vcs_repository_name));
+    }
+
+    #[test]
+    fn test_fill_query_params_without_optional_fields() {
         let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
             team: None,
             repositories: None,
             start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
             end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
         };
 
-        let filter = None;
-        let query = "query".to_string();
-
-        let result = fill_query_params(&request, &query, filter.as_ref());
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            vec![Matcher::ne("deployment_status", "")],
+            None,
+        )
+        .unwrap();
 
         assert_eq!(result.start, "0");
         assert_eq!(result.end, "1000000000");
         assert_eq!(
             result.query,
-            r#"{service_namespace=`test_service`} | query"#
+            r#"{service_namespace="test_service"} | deployment_status!="""#
         );
         assert_eq!(result.limit, 5000);
     }
 
     #[test]
-    fn test_filter_duplicate_deployments_by_sha_with_successful_duplicates() {
-        let mut deploys = vec![
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: false,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: true,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "123456".to_string(),
-                status: true,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: false,
-                ..Default::default()
-            },
-        ];
+    fn test_fill_query_params_with_child_teams() {
+        let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
+            team: Some("platform".to_string()),
+            child_teams: vec!["delivery".to_string(), "o11y.team".to_string()],
+            start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
+        };
 
-        filter_duplicate_deployments_by_sha(&mut deploys);
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            EventKind::Opened.filters(),
+            None,
+        )
+        .unwrap();
 
-        assert_eq!(deploys.len(), 3);
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "abcdef" && d.status == false));
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "abcdef" && d.status == true));
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "123456" && d.status == true));
+        assert_eq!(
+            result.query,
+            r#"{service_namespace="test_service"} | team_name=~"platform|delivery|o11y\\.team", event_name="change_opened""#
+        );
     }
 
     #[test]
-    fn test_filter_duplicate_deployments_by_sha_without_successful_duplicates() {
-        let mut deploys = vec![
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: false,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "123456".to_string(),
-                status: true,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: false,
-                ..Default::default()
-            },
-        ];
+    fn test_fill_query_params_for_one_commit() {
+        let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
+            start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
+        };
+        let event = EventKind::Deployed("abc123".to_string());
 
-        filter_duplicate_deployments_by_sha(&mut deploys);
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            event.filters(),
+            event.stage(),
+        )
+        .unwrap();
 
-        assert_eq!(deploys.len(), 2);
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "abcdef" && d.status == false));
-        assert!(deploys
+        assert_eq!(
+            result.query,
+            r#"{service_namespace="test_service"} | deployment_status!="" |= "abc123""#
+        );
+    }
+
+    #[test]
+    fn test_fill_query_params_with_teams() {
+        let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
+            teams: Some(vec!["squad-a".to_string(), "squad.b".to_string()]),
+            start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
+        };
+
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            EventKind::Opened.filters(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.query,
+            r#"{service_namespace="test_service"} | team_name=~"squad\\-a|squad\\.b", event_name="change_opened""#
+        );
+    }
+
+    #[test]
+    fn test_fill_query_params_escapes_names() {
+        let request = DataRequest {
+            namespaces: Some(vec!["test_service".to_string()]),
+            team: Some(r#"squad" } | drop"#.to_string()),
+            start: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
+        };
+
+        let result = fill_query_params(
+            &LokiConfig::default(),
+            &request,
+            EventKind::Opened.filters(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.query,
+            r#"{service_namespace="test_service"} | team_name="squad\" } | drop", event_name="change_opened""#
+        );
+    }
+
+    #[test]
+    fn test_fill_query_params_rejects_out_of_range_dates() {
+        let request = DataRequest {
+            start: "1500-01-01T00:00:00Z".parse().unwrap(),
+            end: DateTime::<Utc>::from_timestamp(1, 0).unwrap(),
+            ..Default::default()
+        };
+
+        let result = fill_query_params(&LokiConfig::default(), &request, vec![], None);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_split_page() {
+        let page = |timestamps: &[&str]| -> QueryResponse {
+            let values: Vec<serde_json::Value> = timestamps
+                .iter()
+                .map(|timestamp| serde_json::json!([timestamp, "{}"]))
+                .collect();
+
+            serde_json::from_value(serde_json::json!({"data": {"result": [{
+                "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a"},
+                "values": values,
+            }]}}))
+            .unwrap()
+        };
+        let timestamps = |page: &QueryResponse| -> Vec<i64> {
+            page.data.result[0]
+                .values
+                .iter()
+                .map(|value| value.timestamp.timestamp_nanos_opt().unwrap())
+                .collect()
+        };
+
+        let mut partial = page(&["30", "20"]);
+        assert_eq!(split_page(&mut partial, 3), PageSplit::Last);
+        assert_eq!(timestamps(&partial), vec![30, 20]);
+
+        let mut full = page(&["30", "20", "10", "10"]);
+        assert_eq!(split_page(&mut full, 4), PageSplit::Next(11));
+        assert_eq!(timestamps(&full), vec![30, 20]);
+
+        let mut same_timestamp = page(&["10", "10", "10"]);
+        assert_eq!(
+            split_page(&mut same_timestamp, 3),
+            PageSplit::Saturated(DateTime::from_timestamp_nanos(10))
+        );
+        assert_eq!(timestamps(&same_timestamp).len(), 3);
+    }
+
+    #[test]
+    fn test_namespace_selector() {
+        let request = DataRequest {
+            namespaces: Some(vec![" github".to_string(), "gitlab.eu".to_string()]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            namespace_selector(&service_namespaces(&LokiConfig::default(), &request)).to_string(),
+            r#"service_namespace=~"github|gitlab\\.eu""#
+        );
+        assert_eq!(
+            namespace_selector(&["github".to_string()]).to_string(),
+            r#"service_namespace="github""#
+        );
+    }
+
+    #[test]
+    fn test_is_allowed_tenant() {
+        let allowed = ["team-a".to_string(), "team-b".to_string()];
+
+        assert!(is_allowed_tenant("team-a", &allowed));
+        assert!(is_allowed_tenant("team-b", &allowed));
+        assert!(!is_allowed_tenant("team-c", &allowed));
+        assert!(!is_allowed_tenant("team-a", &[]));
+    }
+
+    #[test]
+    fn test_environment_filter() {
+        let filter = environment_filter(&["staging".to_string(), "qa.eu".to_string()]);
+
+        assert_eq!(
+            filter.to_string(),
+            r#"| deployment_environment_name=~"(?i)(staging|qa\\.eu)(/.+)?" or deployment_environment=~"(?i)(staging|qa\\.eu)(/.+)?""#
+        );
+    }
+
+    #[test]
+    fn test_clamp_to_retention_within_retention() {
+        let now = Utc::now();
+
+        let mut request = DataRequest {
+            team: None,
+            repositories: None,
+            start: now - Duration::days(7),
+            end: now,
+            ..Default::default()
+        };
+
+        let warning = clamp_to_retention(&mut request, Some(30), now);
+
+        assert_eq!(warning, None);
+        assert_eq!(request.start, now - Duration::days(7));
+    }
+
+    #[test]
+    fn test_clamp_to_retention_before_retention() {
+        let now = Utc::now();
+
+        let mut request = DataRequest {
+            team: None,
+            repositories: None,
+            start: now - Duration::days(60),
+            end: now,
+            ..Default::default()
+        };
+
+        let warning = clamp_to_retention(&mut request, Some(30), now);
+
+        assert!(warning.is_some());
+        assert_eq!(request.start, now - Duration::days(30));
+        assert_eq!(request.end, now);
+    }
+
+    #[test]
+    fn test_clamp_to_retention_fully_expired() {
+        let now = Utc::now();
+
+        let mut request = DataRequest {
+            team: None,
+            repositories: None,
+            start: now - Duration::days(90),
+            end: now - Duration::days(60),
+            ..Default::default()
+        };
+
+        let warning = clamp_to_retention(&mut request, Some(30), now);
+
+        assert!(warning.is_some());
+        assert_eq!(request.start, request.end);
+    }
+
+    #[test]
+    fn test_clamp_to_retention_unlimited() {
+        let now = Utc::now();
+
+        let mut request = DataRequest {
+            team: None,
+            repositories: None,
+            start: now - Duration::days(365),
+            end: now,
+            ..Default::default()
+        };
+
+        assert_eq!(clamp_to_retention(&mut request, None, now), None);
+    }
+
+    #[test]
+    fn test_sort_issue_data_with_reopened_issue() {
+        let event = |timestamp: &str, closed_at: Option<&str>| {
+            let payload = serde_json::json!({
+                "issue": {"created_at": "2024-09-10T10:00:00Z", "closed_at": closed_at, "number": 42},
+                "repository": {"name": "repo-a"},
+            });
+
+            serde_json::json!([timestamp, payload.to_string()])
+        };
+        let data: QueryResponse = serde_json::from_value(serde_json::json!({"data": {"result": [{
+            "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a"},
+            "values": [
+                event("1725966000000000000", Some("2024-09-10T11:00:00Z")),
+                event("1725969600000000000", None),
+            ],
+        }]}}))
+        .unwrap();
+
+        let issues = sort_issue_data(data, &mut vec![]);
+        let issue = &issues["repo-a"][0];
+
+        assert_eq!(issues["repo-a"].len(), 1);
+        assert_eq!(
+            issue.reopened_at.unwrap().to_rfc3339(),
+            "2024-09-10T12:00:00+00:00"
+        );
+        assert_eq!(issue.resolved_at(), None);
+    }
+
+    #[test]
+    fn test_sort_data_skips_malformed_events() {
+        let data = |payloads: &[serde_json::Value]| -> QueryResponse {
+            let values: Vec<_> = payloads
+                .iter()
+                .map(|payload| serde_json::json!(["1725962400000000000", payload.to_string()]))
+                .collect();
+
+            serde_json::from_value(serde_json::json!({"data": {"result": [{
+                "stream": {
+                    "vcs_repository_name": "repo-a",
+                    "team_name": "team-a",
+                    "deployment_environment_name": "production",
+                },
+                "values": values,
+            }]}}))
+            .unwrap()
+        };
+        let deployment = serde_json::json!({
+            "id": 1,
+            "sha": "a",
+            "url": "https://api.github.com/repos/org/repo-a/deployments/1",
+            "created_at": "2024-09-10T10:00:00Z",
+        });
+        let mut skipped = vec![];
+
+        let deploys = sort_deploy_data(
+            data(&[
+                serde_json::json!({"deployment": deployment, "deployment_status": {"state": "success"}}),
+                serde_json::json!({"deployment": deployment}),
+                serde_json::json!({"deployment_status": {"state": "success"}}),
+            ]),
+            &EnvironmentMatcher::default(),
+            None,
+            Deduplication::KeepAll,
+            &mut skipped,
+        );
+        let issues = sort_issue_data(
+            data(&[serde_json::json!({"repository": {"name": "repo-a"}})]),
+            &mut skipped,
+        );
+        let merges = sort_merge_data(data(&[serde_json::json!({})]), &[], &mut skipped);
+
+        assert_eq!(deploys["repo-a"].len(), 1);
+        assert!(issues.is_empty());
+        assert!(merges.is_empty());
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|event| (event.kind, event.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                ("deployment", "The event has no deployment status"),
+                ("deployment", "The event has no deployment"),
+                ("issue", "The event has no issue"),
+                ("merge", "The stream has no merged_at label"),
+            ]
+        );
+        assert!(skipped.iter().all(|event| event.repository == "repo-a"));
+    }
+
+    #[test]
+    fn test_sort_deploy_data_with_services() {
+        let stream = |environment: &str, sha: &str, deployment: serde_json::Value| {
+            let mut deployment = deployment;
+            deployment["id"] = serde_json::json!(1);
+            deployment["sha"] = serde_json::json!(sha);
+            deployment["url"] =
+                serde_json::json!("https://api.github.com/repos/org/monorepo/deployments/1");
+            deployment["created_at"] = serde_json::json!("2024-09-10T10:00:00Z");
+
+            let payload = serde_json::json!({
+                "deployment": deployment,
+                "deployment_status": {"state": "success"},
+            });
+
+            serde_json::json!({
+                "stream": {
+                    "vcs_repository_name": "monorepo",
+                    "team_name": "team-a",
+                    "deployment_environment_name": environment,
+                },
+                "values": [["1725962400000000000", payload.to_string()]],
+            })
+        };
+        let data = || -> QueryResponse {
+            serde_json::from_value(serde_json::json!({"data": {"result": [
+                stream("prod/service-a", "a", serde_json::json!({})),
+                stream("production", "b", serde_json::json!({"payload": {"service": "Service-B"}})),
+                stream("prod", "c", serde_json::json!({"task": "deploy:service-c", "payload": "{}"})),
+                stream("production", "d", serde_json::json!({"task": "deploy"})),
+            ]}}))
+            .unwrap()
+        };
+        let service_of = |deploys: &[DeployEntry], sha: &str| {
+            deploys
+                .iter()
+                .find(|d| d.sha == sha)
+                .map(|d| (d.environment.to_string(), d.service.clone()))
+        };
+
+        let deploys = sort_deploy_data(
+            data(),
+            &EnvironmentMatcher::default(),
+            None,
+            Deduplication::KeepFirst,
+            &mut vec![],
+        );
+        let deploys = &deploys["monorepo"];
+
+        assert_eq!(deploys.len(), 4);
+        assert_eq!(
+            service_of(deploys, "a"),
+            Some(("production".to_string(), Some("service-a".to_string())))
+        );
+        assert_eq!(
+            service_of(deploys, "b"),
+            Some(("production".to_string(), Some("service-b".to_string())))
+        );
+        assert_eq!(
+            service_of(deploys, "c"),
+            Some(("production".to_string(), Some("service-c".to_string())))
+        );
+        assert_eq!(
+            service_of(deploys, "d"),
+            Some(("production".to_string(), None))
+        );
+
+        let services = parse_patterns(&["service-a", "re:service-[b]"]).unwrap();
+        let filtered = sort_deploy_data(
+            data(),
+            &EnvironmentMatcher::default(),
+            Some(&services),
+            Deduplication::KeepFirst,
+            &mut vec![],
+        );
+        let shas: Vec<&str> = filtered["monorepo"]
             .iter()
-            .any(|d| d.sha == "123456" && d.status == true));
+            .map(|d| d.sha.as_str())
+            .collect();
+
+        assert_eq!(shas, vec!["a", "b"]);
     }
 
     #[test]
-    fn test_filter_duplicate_deployments_by_sha_only_one_deployment() {
-        let mut deploys = vec![DeployEntry {
-            sha: "abcdef".to_string(),
-            status: true,
+    fn test_failure_lookahead_request() {
+        let now = Utc::now();
+        let request = |end, days| DataRequest {
+            start: end - Duration::days(7),
+            end,
+            failure_lookahead_days: days,
             ..Default::default()
-        }];
+        };
+
+        let lookahead =
+            failure_lookahead_request(&request(now - Duration::days(30), Some(10)), 7, now)
+                .unwrap();
 
-        filter_duplicate_deployments_by_sha(&mut deploys);
+        assert_eq!(lookahead.start, now - Duration::days(30));
+        assert_eq!(lookahead.end, now - Duration::days(23));
 
-        assert_eq!(deploys.len(), 1);
-        assert_eq!(deploys[0].sha, "abcdef");
-        assert_eq!(deploys[0].status, true);
+        let capped =
+            failure_lookahead_request(&request(now - Duration::days(1), Some(3)), 7, now).unwrap();
+
+        assert_eq!(capped.end, now);
+        assert!(failure_lookahead_request(&request(now, Some(3)), 7, now).is_none());
+        assert!(
+            failure_lookahead_request(&request(now - Duration::days(1), None), 7, now).is_none()
+        );
     }
 
     #[test]
-    fn test_filter_duplicate_deployments_by_sha_no_deployments() {
-        let mut deploys: Vec<DeployEntry> = vec![];
+    fn test_merge_lookback_request() {
+        let request = DataRequest {
+            team: Some("team-a".to_string()),
+            start: Utc::now() - Duration::days(7),
+            end: Utc::now(),
+            ..Default::default()
+        };
 
-        filter_duplicate_deployments_by_sha(&mut deploys);
+        let lookback = merge_lookback_request(&request, 30).unwrap();
 
-        assert_eq!(deploys.len(), 0);
+        assert_eq!(lookback.start, request.start - Duration::days(30));
+        assert_eq!(lookback.end, request.start);
+        assert_eq!(lookback.team, request.team);
+        assert!(merge_lookback_request(&request, 0).is_none());
     }
 
     #[test]
-    fn test_filter_duplicate_deployments_by_sha_all_successful() {
-        let mut deploys = vec![
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: true,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "abcdef".to_string(),
-                status: true,
-                ..Default::default()
-            },
-            DeployEntry {
-                sha: "123456".to_string(),
-                status: true,
+    fn test_deploy_duration_seconds() {
+        let value = |workflow_run: serde_json::Value| -> ValueItem {
+            let payload = serde_json::json!({
+                "deployment_status": {"state": "success", "created_at": "2024-09-09T10:07:00Z"},
+                "workflow_run": workflow_run,
+            });
+
+            serde_json::from_value(serde_json::json!([
+                "1725876000000000000",
+                payload.to_string()
+            ]))
+            .unwrap()
+        };
+
+        let completed = value(serde_json::json!({
+            "status": "completed",
+            "run_started_at": "2024-09-09T10:00:00Z",
+            "updated_at": "2024-09-09T10:12:30Z",
+        }));
+        let in_progress = value(serde_json::json!({
+            "status": "in_progress",
+            "run_started_at": "2024-09-09T10:00:00Z",
+        }));
+        let without_start = value(serde_json::json!({"status": "completed"}));
+
+        assert_eq!(deploy_duration_seconds(&completed), Some(750));
+        assert_eq!(deploy_duration_seconds(&in_progress), Some(420));
+        assert_eq!(deploy_duration_seconds(&without_start), None);
+    }
+
+    #[test]
+    fn test_sanitize_tag() {
+        assert_eq!(sanitize_tag("team-a"), "team-a");
+        assert_eq!(sanitize_tag("team a,principal=b"), "team_a_principal_b");
+    }
+
+    #[test]
+    fn test_query_response_stats() {
+        let with_stats: QueryResponse = serde_json::from_str(
+            r#"{"data": {"result": [], "stats": {"summary": {"totalBytesProcessed": 1024}}}}"#,
+        )
+        .unwrap();
+        let without_stats: QueryResponse =
+            serde_json::from_str(r#"{"data": {"result": []}}"#).unwrap();
+
+        assert_eq!(with_stats.data.stats.summary.total_bytes_processed, 1024);
+        assert_eq!(without_stats.data.stats.summary.total_bytes_processed, 0);
+    }
+
+    #[test]
+    fn test_find_stream_profile() {
+        let v2 = HashMap::from([
+            ("vcs_repository_name".to_string(), "repo-a".to_string()),
+            ("team_name".to_string(), "team-a".to_string()),
+        ]);
+        let v1 = HashMap::from([
+            ("repository_name".to_string(), "repo-a".to_string()),
+            ("team_name".to_string(), "team-a".to_string()),
+        ]);
+
+        assert_eq!(find_stream_profile(&v2).unwrap().version, "v2");
+        assert_eq!(find_stream_profile(&v1).unwrap().version, "v1");
+        assert_eq!(find_stream_profile(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_query_response_with_mixed_stream_versions() {
+        let response: QueryResponse = serde_json::from_str(
+            r#"{"data": {"result": [
+                {"stream": {"vcs_repository_name": "repo-a", "team_name": "team-a", "deployment_environment_name": "prod"}, "values": []},
+                {"stream": {"repository_name": "repo-b", "team_name": "team-b", "deployment_environment": "prod", "merged_at": "2024-09-09T17:34:12Z"}, "values": []},
+                {"stream": {"service_name": "unknown"}, "values": []}
+            ]}}"#,
+        )
+        .unwrap();
+
+        let result = &response.data.result;
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].stream.vcs_repository_name, "repo-a");
+        assert_eq!(result[1].stream.vcs_repository_name, "repo-b");
+        assert_eq!(
+            result[1].stream.deployment_environment_name,
+            Some("prod".to_string())
+        );
+        assert!(result[1].stream.merged_at.is_some());
+    }
+
+    #[test]
+    fn test_query_response_sample_data() {
+        let contents = std::fs::read_to_string("test_merge_data.json").unwrap();
+        let response: QueryResponse = serde_json::from_str(&contents).unwrap();
+
+        assert_eq!(response.data.result.len(), 514);
+        assert!(response
+            .data
+            .result
+            .iter()
+            .all(|item| item.stream.merged_at.is_some()));
+    }
+
+    #[test]
+    fn test_sort_merge_data_ignores_other_branches() {
+        let merge = |sha: &str, base: &str| {
+            let payload = serde_json::json!({"pull_request": {
+                "title": "Change",
+                "user": {"login": "octocat"},
+                "merge_commit_sha": sha,
+                "base": {"ref": base},
+            }});
+
+            serde_json::json!(["1725900000000000000", payload.to_string()])
+        };
+        let response: QueryResponse = serde_json::from_value(serde_json::json!({"data": {"result": [{
+            "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a", "merged_at": "2024-09-09T17:34:12Z"},
+            "values": [merge("aaa", "main"), merge("bbb", "feature/login")],
+        }]}}))
+        .unwrap();
+
+        let merges = sort_merge_data(response, &[], &mut vec![]);
+
+        assert!(merges.contains_key("aaa"));
+        assert!(!merges.contains_key("bbb"));
+    }
+
+    #[test]
+    fn test_sort_merge_data_excludes_bots() {
+        let contents = std::fs::read_to_string("test_merge_data.json").unwrap();
+        let response: QueryResponse = serde_json::from_str(&contents).unwrap();
+        let bots = DataRequest {
+            exclude_bots: Some(true),
+            ..Default::default()
+        }
+        .excluded_authors()
+        .unwrap();
+
+        let all = sort_merge_data(response.clone(), &[], &mut vec![]);
+        let humans = sort_merge_data(response, &bots, &mut vec![]);
+
+        assert!(all.values().any(|entry| entry.user == "renovate[bot]"));
+        assert!(humans.len() < all.len());
+        assert!(humans
+            .values()
+            .all(|entry| !matches_any(&bots, &entry.user)));
+    }
+
+    #[tokio::test]
+    async fn test_gather_data_skips_a_failed_window_when_partial() {
+        use axum::{extract::Query, http::StatusCode, routing::get, Json, Router};
+
+        let start = DateTime::parse_from_rfc3339("2024-09-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let failing_start = start.timestamp_nanos_opt().unwrap().to_string();
+
+        // Every window has one deployment a day after it starts, and the first window's issue query fails.
+        let loki = Router::new().route(
+            "/",
+            get(move |Query(params): Query<HashMap<String, String>>| {
+                let failing_start = failing_start.clone();
+
+                async move {
+                    if params["query"].contains("issue_closed") && params["start"] == failing_start
+                    {
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+
+                    if !params["query"].contains("deployment_status") {
+                        return Ok(Json(serde_json::json!({"data": {"result": []}})));
+                    }
+
+                    let window_start = params["start"].parse::<i64>().unwrap();
+                    let created_at =
+                        DateTime::from_timestamp_nanos(window_start) + Duration::days(1);
+                    let payload = serde_json::json!({
+                        "deployment": {
+                            "id": 1,
+                            "sha": created_at.to_rfc3339(),
+                            "url": "https://api.github.com/repos/org/repo-a/deployments/1",
+                            "created_at": created_at.to_rfc3339(),
+                        },
+                        "deployment_status": {"state": "success"},
+                    });
+
+                    Ok(Json(serde_json::json!({"data": {"result": [{
+                        "stream": {
+                            "vcs_repository_name": "repo-a",
+                            "team_name": "team-a",
+                            "deployment_environment_name": "production",
+                        },
+                        "values": [[
+                            created_at.timestamp_nanos_opt().unwrap().to_string(),
+                            payload.to_string(),
+                        ]],
+                    }]}})))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, loki).await.unwrap() });
+
+        let ctx = Context::new(
+            crate::config::AppConfig {
+                loki: LokiConfig {
+                    url: Some(url),
+                    failure_lookahead_max_days: 0,
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-        ];
+            reqwest::Client::new(),
+        );
+        let request = DataRequest {
+            start,
+            end: start + Duration::days(10),
+            partial: true,
+            ..Default::default()
+        };
 
-        filter_duplicate_deployments_by_sha(&mut deploys);
+        let data = gather_data(&ctx, request).await.unwrap();
 
-        assert_eq!(deploys.len(), 2);
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "abcdef" && d.status == true));
-        assert!(deploys
-            .iter()
-            .any(|d| d.sha == "123456" && d.status == true));
+        assert_eq!(data.deployments_by_repo["repo-a"].len(), 2);
+        assert_eq!(
+            data.warnings,
+            vec![
+                "Issue data could not be queried between 2024-09-01T00:00:00+00:00 and 2024-09-06T00:00:00+00:00, so failures are missing"
+            ]
+        );
+        assert!(data.incomplete);
     }
 }
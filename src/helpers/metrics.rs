@@ -0,0 +1,447 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::{duration::DurationValue, response::ResponseRecord};
+
+/// The four DORA metrics aggregated over a set of deployment records.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricsSummary {
+    pub deployments: usize,
+    pub deployment_frequency: f64,
+    pub lead_time_hours: Option<f64>,
+    pub lead_time: Option<DurationValue>,
+    pub lead_time_count: usize,
+    pub failures: usize,
+    pub change_failure_rate: Option<f64>,
+    pub mttr_hours: Option<f64>,
+    pub mttr: Option<DurationValue>,
+    pub restored: usize,
+    pub hotfixes: usize,
+    pub hotfix_rate: Option<f64>,
+    pub deploy_duration_p50: Option<DurationValue>,
+    pub deploy_duration_p95: Option<DurationValue>,
+}
+
+/// Calculates the median of a list of values, or `None` if the list is empty.
+pub fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|l, r| l.total_cmp(r));
+
+    let middle = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+/// Calculates a percentile from 0 to 100 of a list of values, interpolating between the closest ranks, or
+/// `None` if the list is empty.
+pub fn percentile(mut values: Vec<f64>, percentile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|l, r| l.total_cmp(r));
+
+    let rank = percentile.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+
+    Some(values[lower] + (values[upper] - values[lower]) * (rank - lower as f64))
+}
+
+/// Calculates the mean of a list of values, or `None` if the list is empty.
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+pub fn hours_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_seconds() as f64 / 3600.0
+}
+
+/// Summarizes deployment records into the four DORA metrics.
+///
+/// # Arguments
+///
+/// * `records` - The linked deployment records to summarize.
+/// * `days` - The length of the requested window in days, used for the deployment frequency.
+///
+/// # Returns
+///
+/// A `MetricsSummary` where:
+/// - `deployment_frequency` is the number of deployments per day.
+/// - `lead_time_hours` is the median time from merge to deployment, also given as `lead_time`.
+/// - `change_failure_rate` is the percentage of deployments linked to a failure.
+/// - `mttr_hours` is the median time from failure to fix, over the failures that have been fixed,
+///   also given as `mttr`.
+/// - `hotfix_rate` is the percentage of deployments of a hotfix.
+/// - `deploy_duration_p50` and `deploy_duration_p95` are percentiles of how long the
+///   deployments' workflow runs took, over the deployments that report it.
+///
+/// Metrics without any underlying data are `None`.
+///
+/// # Example
+///
+/// ```rust
+/// let records = link_data(gathered_data);
+///
+/// let summary = summarize(&records, 30.0);
+///
+/// println!("Deployments per day: {}", summary.deployment_frequency);
+/// ```
+pub fn summarize(records: &[&ResponseRecord], days: f64) -> MetricsSummary {
+    let deployments = records.len();
+
+    let lead_times: Vec<f64> = records
+        .iter()
+        .filter_map(|record| {
+            record
+                .merged_at
+                .map(|merged_at| hours_between(merged_at, record.created_at))
+        })
+        .collect();
+
+    let failures: Vec<&&ResponseRecord> = records
+        .iter()
+        .filter(|record| record.failed_at.is_some())
+        .collect();
+
+    let restore_times: Vec<f64> = failures
+        .iter()
+        .filter_map(|record| match (record.failed_at, record.fixed_at) {
+            (Some(failed_at), Some(fixed_at)) => Some(hours_between(failed_at, fixed_at)),
+            _ => None,
+        })
+        .collect();
+
+    let hotfixes = records.iter().filter(|record| record.hotfix).count();
+    let deploy_durations: Vec<f64> = records
+        .iter()
+        .filter_map(|record| record.deploy_duration_seconds)
+        .map(|seconds| seconds as f64)
+        .collect();
+    let lead_time_count = lead_times.len();
+    let lead_time_hours = median(lead_times);
+    let restored = restore_times.len();
+    let mttr_hours = median(restore_times);
+
+    MetricsSummary {
+        deployments,
+        deployment_frequency: if days > 0.0 {
+            deployments as f64 / days
+        } else {
+            0.0
+        },
+        lead_time_count,
+        lead_time_hours,
+        lead_time: lead_time_hours.map(DurationValue::from_hours),
+        failures: failures.len(),
+        change_failure_rate: if deployments > 0 {
+            Some(failures.len() as f64 / deployments as f64 * 100.0)
+        } else {
+            None
+        },
+        restored,
+        mttr_hours,
+        mttr: mttr_hours.map(DurationValue::from_hours),
+        hotfixes,
+        hotfix_rate: if deployments > 0 {
+            Some(hotfixes as f64 / deployments as f64 * 100.0)
+        } else {
+            None
+        },
+        deploy_duration_p50: percentile(deploy_durations.clone(), 50.0)
+            .map(|seconds| DurationValue::from_seconds(seconds.round() as i64)),
+        deploy_duration_p95: percentile(deploy_durations, 95.0)
+            .map(|seconds| DurationValue::from_seconds(seconds.round() as i64)),
+    }
+}
+
+/// How a metric changed from the previous window to the current one.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricChange {
+    pub current: Option<f64>,
+    pub previous: Option<f64>,
+    pub delta: Option<f64>,
+    /// The delta as a percentage of the previous value, `None` when the previous value is zero.
+    pub percent_change: Option<f64>,
+}
+
+impl MetricChange {
+    fn between(current: Option<f64>, previous: Option<f64>) -> Self {
+        let delta = match (current, previous) {
+            (Some(current), Some(previous)) => Some(current - previous),
+            _ => None,
+        };
+
+        MetricChange {
+            current,
+            previous,
+            delta,
+            percent_change: match (delta, previous) {
+                (Some(delta), Some(previous)) if previous != 0.0 => Some(delta / previous * 100.0),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The change in each DORA metric between two windows.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricsTrend {
+    pub deployment_frequency: MetricChange,
+    pub lead_time_hours: MetricChange,
+    pub change_failure_rate: MetricChange,
+    pub mttr_hours: MetricChange,
+}
+
+/// Compares the DORA metrics of a window to those of a previous window.
+pub fn trend(current: &MetricsSummary, previous: &MetricsSummary) -> MetricsTrend {
+    MetricsTrend {
+        deployment_frequency: MetricChange::between(
+            Some(current.deployment_frequency),
+            Some(previous.deployment_frequency),
+        ),
+        lead_time_hours: MetricChange::between(current.lead_time_hours, previous.lead_time_hours),
+        change_failure_rate: MetricChange::between(
+            current.change_failure_rate,
+            previous.change_failure_rate,
+        ),
+        mttr_hours: MetricChange::between(current.mttr_hours, previous.mttr_hours),
+    }
+}
+
+/// The time to restore over a set of failed deployments.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RestoreSummary {
+    pub failures: usize,
+    pub restored: usize,
+    /// Failures without a fix yet, which are left out of the mean and median.
+    pub open: usize,
+    pub mean: Option<DurationValue>,
+    pub median: Option<DurationValue>,
+}
+
+/// Summarizes the time from failure to fix over the failed deployments in a set of records.
+pub fn summarize_restores(records: &[&ResponseRecord]) -> RestoreSummary {
+    let failures: Vec<&&ResponseRecord> = records
+        .iter()
+        .filter(|record| record.failed_at.is_some())
+        .collect();
+
+    let restore_seconds: Vec<f64> = failures
+        .iter()
+        .filter_map(|record| match (record.failed_at, record.fixed_at) {
+            (Some(failed_at), Some(fixed_at)) => Some((fixed_at - failed_at).num_seconds() as f64),
+            _ => None,
+        })
+        .collect();
+
+    RestoreSummary {
+        failures: failures.len(),
+        restored: restore_seconds.len(),
+        open: failures.len() - restore_seconds.len(),
+        mean: mean(&restore_seconds).map(|value| DurationValue::from_seconds(value.round() as i64)),
+        median: median(restore_seconds)
+            .map(|value| DurationValue::from_seconds(value.round() as i64)),
+    }
+}
+
+/// Groups deployment records by a key derived from each record.
+pub fn group_by<K, F>(records: &[ResponseRecord], key: F) -> BTreeMap<K, Vec<&ResponseRecord>>
+where
+    K: Ord,
+    F: Fn(&ResponseRecord) -> K,
+{
+    let mut grouped: BTreeMap<K, Vec<&ResponseRecord>> = BTreeMap::new();
+
+    for record in records {
+        grouped.entry(key(record)).or_default().push(record);
+    }
+
+    grouped
+}
+
+/// Groups deployment records by their team.
+pub fn group_by_team(records: &[ResponseRecord]) -> BTreeMap<String, Vec<&ResponseRecord>> {
+    group_by(records, |record| record.team.clone())
+}
+
+/// How aggregated metrics are broken down, by team or by each team's repositories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grouping {
+    Team,
+    #[default]
+    Repository,
+}
+
+/// Groups deployment records by their team, and by repository unless grouping by team alone.
+pub fn group_by_grouping(
+    records: &[ResponseRecord],
+    grouping: Grouping,
+) -> BTreeMap<(String, Option<String>), Vec<&ResponseRecord>> {
+    group_by(records, |record| match grouping {
+        Grouping::Team => (record.team.clone(), None),
+        Grouping::Repository => (record.team.clone(), Some(record.repository.clone())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0, 2.0, 3.0]), Some(2.5));
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(vec![], 50.0), None);
+        assert_eq!(percentile(vec![7.0], 95.0), Some(7.0));
+        assert_eq!(percentile(vec![4.0, 1.0, 3.0, 2.0], 50.0), Some(2.5));
+        assert_eq!(
+            percentile((1..=21).map(f64::from).collect(), 95.0),
+            Some(20.0)
+        );
+        assert_eq!(percentile(vec![1.0, 2.0], 100.0), Some(2.0));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let now = Utc::now();
+
+        let records = [
+            ResponseRecord {
+                created_at: now,
+                merged_at: Some(now - Duration::hours(4)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                created_at: now,
+                failed_at: Some(now),
+                fixed_at: Some(now + Duration::hours(2)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                created_at: now,
+                failed_at: Some(now),
+                ..Default::default()
+            },
+            ResponseRecord {
+                created_at: now,
+                hotfix: true,
+                deploy_duration_seconds: Some(300),
+                ..Default::default()
+            },
+        ];
+
+        let summary = summarize(&records.iter().collect::<Vec<_>>(), 2.0);
+
+        assert_eq!(summary.deployments, 4);
+        assert_eq!(summary.deployment_frequency, 2.0);
+        assert_eq!(summary.lead_time_hours, Some(4.0));
+        assert_eq!(summary.lead_time_count, 1);
+        assert_eq!(summary.failures, 2);
+        assert_eq!(summary.change_failure_rate, Some(50.0));
+        assert_eq!(summary.mttr_hours, Some(2.0));
+        assert_eq!(summary.mttr, Some(DurationValue::from_seconds(7200)));
+        assert_eq!(summary.restored, 1);
+        assert_eq!(summary.hotfixes, 1);
+        assert_eq!(summary.hotfix_rate, Some(25.0));
+        assert_eq!(
+            summary.deploy_duration_p50,
+            Some(DurationValue::from_seconds(300))
+        );
+    }
+
+    #[test]
+    fn test_summarize_restores() {
+        let now = Utc::now();
+
+        let records = [
+            ResponseRecord {
+                failed_at: Some(now),
+                fixed_at: Some(now + Duration::hours(1)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                failed_at: Some(now),
+                fixed_at: Some(now + Duration::hours(5)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                failed_at: Some(now),
+                fixed_at: Some(now + Duration::hours(6)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                failed_at: Some(now),
+                ..Default::default()
+            },
+            ResponseRecord {
+                created_at: now,
+                ..Default::default()
+            },
+        ];
+
+        let summary = summarize_restores(&records.iter().collect::<Vec<_>>());
+
+        assert_eq!(summary.failures, 4);
+        assert_eq!(summary.restored, 3);
+        assert_eq!(summary.open, 1);
+        assert_eq!(summary.mean, Some(DurationValue::from_seconds(4 * 3600)));
+        assert_eq!(summary.median, Some(DurationValue::from_seconds(5 * 3600)));
+        assert_eq!(summarize_restores(&[]).mean, None);
+    }
+
+    #[test]
+    fn test_trend() {
+        let current = MetricsSummary {
+            deployment_frequency: 3.0,
+            lead_time_hours: Some(6.0),
+            change_failure_rate: Some(10.0),
+            ..Default::default()
+        };
+
+        let previous = MetricsSummary {
+            deployment_frequency: 2.0,
+            lead_time_hours: Some(8.0),
+            change_failure_rate: Some(0.0),
+            mttr_hours: Some(1.0),
+            ..Default::default()
+        };
+
+        let trend = trend(&current, &previous);
+
+        assert_eq!(trend.deployment_frequency.delta, Some(1.0));
+        assert_eq!(trend.deployment_frequency.percent_change, Some(50.0));
+        assert_eq!(trend.lead_time_hours.percent_change, Some(-25.0));
+        assert_eq!(trend.change_failure_rate.delta, Some(10.0));
+        assert_eq!(trend.change_failure_rate.percent_change, None);
+        assert_eq!(trend.mttr_hours.previous, Some(1.0));
+        assert_eq!(trend.mttr_hours.delta, None);
+    }
+
+    #[test]
+    fn test_summarize_empty() {
+        let summary = summarize(&[], 7.0);
+
+        assert_eq!(summary.deployments, 0);
+        assert_eq!(summary.change_failure_rate, None);
+        assert_eq!(summary.lead_time_hours, None);
+        assert_eq!(summary.mttr_hours, None);
+    }
+}
@@ -1,6 +1,58 @@
-pub mod event_vendor;
+#[cfg(feature = "server")]
+pub mod alerts;
+pub mod anomalies;
+pub mod auth;
+pub mod batching;
+pub mod branches;
+pub mod breaker;
+pub mod buckets;
+pub mod cache;
+pub mod cohorts;
+pub mod context;
+#[cfg(feature = "server")]
+pub mod cors;
+pub mod csv;
+pub mod deduplication;
+pub mod demo;
+#[cfg(feature = "server")]
+pub mod digest;
+pub mod domain;
+pub mod duration;
+pub mod environments;
+pub mod fixtures;
+pub mod forecast;
 pub mod gatherer;
-pub mod github;
+pub mod github_api;
+pub mod hotfixes;
+pub mod http;
+pub mod inflight;
+pub mod instrumentation;
+#[cfg(feature = "server")]
+pub mod limits;
+pub mod logql;
 pub mod loki;
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod notifiers;
+pub mod oidc;
+pub mod pagination;
+pub mod patterns;
+#[cfg(feature = "server")]
+pub mod prewarm;
+pub mod prometheus;
+pub mod readiness;
+pub mod recordings;
 pub mod request;
 pub mod response;
+pub mod reviews;
+pub mod scoring;
+pub mod secrets;
+#[cfg(feature = "server")]
+pub mod settings;
+pub mod targets;
+pub mod throughput;
+#[cfg(feature = "server")]
+pub mod trace;
+pub mod traceability;
+pub mod upstreams;
+pub mod usage;
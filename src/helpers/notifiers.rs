@@ -0,0 +1,429 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde_json::{json, Value};
+use std::{fmt, str::FromStr};
+
+use crate::config::DigestSettings;
+
+/// The kinds of destination a notification can be sent to, which alert rules select by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Slack,
+    Teams,
+    Webhook,
+    Email,
+}
+
+impl FromStr for Channel {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "slack" => Ok(Channel::Slack),
+            "teams" => Ok(Channel::Teams),
+            "webhook" => Ok(Channel::Webhook),
+            "email" => Ok(Channel::Email),
+            _ => Err(anyhow!("Unknown channel {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Channel::Slack => "slack",
+            Channel::Teams => "teams",
+            Channel::Webhook => "webhook",
+            Channel::Email => "email",
+        })
+    }
+}
+
+/// A message to send, which each notifier renders in its destination's format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// A one line summary, used as the email subject and the Teams card title.
+    pub subject: String,
+    pub text: String,
+    /// The JSON generic webhooks are sent, `{"text": ...}` unless the sender adds details for receivers that
+    /// process notifications themselves.
+    pub payload: Value,
+}
+
+impl Notification {
+    pub fn new(subject: String, text: String) -> Self {
+        Notification {
+            payload: json!({ "text": text }),
+            subject,
+            text,
+        }
+    }
+}
+
+/// Sends notifications to one destination.
+pub trait Notifier: Send + Sync {
+    fn channel(&self) -> Channel;
+
+    /// Sends a notification, returning an error when the destination couldn't be reached or rejected it.
+    fn notify<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Sends a notification to every notifier, logging the ones that fail under `source`, e.g. `Alert`.
+pub async fn notify_all(
+    client: &reqwest::Client,
+    notifiers: &[Box<dyn Notifier>],
+    notification: &Notification,
+    source: &str,
+) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.notify(client, notification).await {
+            tracing::error!(
+                "{} Notification Failed: {}: {:?}",
+                source,
+                notifier.channel(),
+                e
+            );
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: Value) -> Result<()> {
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Posts the text of notifications to a Slack incoming webhook.
+#[derive(Debug, Clone)]
+pub struct SlackNotifier {
+    pub url: String,
+}
+
+impl SlackNotifier {
+    fn body(notification: &Notification) -> Value {
+        json!({ "text": notification.text })
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Slack
+    }
+
+    fn notify<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(post(client, &self.url, Self::body(notification)))
+    }
+}
+
+/// Posts notifications to a Microsoft Teams incoming webhook as a message card.
+#[derive(Debug, Clone)]
+pub struct TeamsNotifier {
+    pub url: String,
+}
+
+impl TeamsNotifier {
+    /// Teams renders the card's text as Markdown, where single line breaks are dropped, so each line is made a
+    /// paragraph of its own.
+    fn body(notification: &Notification) -> Value {
+        json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": notification.subject,
+            "title": notification.subject,
+            "text": notification.text.replace('\n', "\n\n"),
+        })
+    }
+}
+
+impl Notifier for TeamsNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Teams
+    }
+
+    fn notify<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(post(client, &self.url, Self::body(notification)))
+    }
+}
+
+/// Posts the payload of notifications to any webhook.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    pub url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Webhook
+    }
+
+    fn notify<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(post(client, &self.url, notification.payload.clone()))
+    }
+}
+
+/// The SMTP relay notifications are emailed through, and who they're emailed to.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub credentials: Option<Credentials>,
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+}
+
+impl SmtpConfig {
+    /// Builds the relay from the `DIGEST_SMTP_*` and `DIGEST_EMAIL_FROM` settings, emailing the addresses in `to`,
+    /// which are read from the `setting` named. Returns `None` when `smtp_host` isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `DIGEST_EMAIL_FROM` is missing or an email address is invalid.
+    pub fn from_config(
+        config: &DigestSettings,
+        to: &[String],
+        setting: &str,
+    ) -> Result<Option<Self>> {
+        let host = match config.smtp_host.as_deref().map(str::trim) {
+            Some(host) if !host.is_empty() => host,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(SmtpConfig {
+            host: host.to_string(),
+            port: config.smtp_port,
+            credentials: match (&config.smtp_username, &config.smtp_password) {
+                (Some(username), Some(password)) => {
+                    Some(Credentials::new(username.clone(), password.clone()))
+                }
+                _ => None,
+            },
+            from: config
+                .email_from
+                .as_deref()
+                .ok_or_else(|| anyhow!("DIGEST_EMAIL_FROM is required with DIGEST_SMTP_HOST"))?
+                .parse()
+                .map_err(|e| anyhow!(format!("{}: DIGEST_EMAIL_FROM", e)))?,
+            to: to
+                .iter()
+                .map(|address| address.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| anyhow!(format!("{}: {}", e, setting)))?,
+        }))
+    }
+}
+
+/// Emails the text of notifications through an SMTP relay.
+#[derive(Debug, Clone)]
+pub struct EmailNotifier {
+    pub smtp: SmtpConfig,
+}
+
+impl EmailNotifier {
+    fn message(&self, notification: &Notification) -> Result<Message> {
+        let mut message = Message::builder()
+            .from(self.smtp.from.clone())
+            .subject(notification.subject.clone());
+
+        for to in &self.smtp.to {
+            message = message.to(to.clone());
+        }
+
+        Ok(message.body(notification.text.clone())?)
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let message = self.message(notification)?;
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp.host)?;
+
+        if let Some(port) = self.smtp.port {
+            transport = transport.port(port);
+        }
+
+        if let Some(credentials) = &self.smtp.credentials {
+            transport = transport.credentials(credentials.clone());
+        }
+
+        transport.build().send(message).await?;
+
+        Ok(())
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> Channel {
+        Channel::Email
+    }
+
+    fn notify<'a>(
+        &'a self,
+        _client: &'a reqwest::Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.send(notification))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    fn notification() -> Notification {
+        Notification::new(
+            "DORA alert for team-a".to_string(),
+            "first line\nsecond line".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_channels() {
+        assert_eq!(" Teams".parse::<Channel>().unwrap(), Channel::Teams);
+        assert_eq!(Channel::Email.to_string(), "email");
+        assert!("pager".parse::<Channel>().is_err());
+    }
+
+    #[test]
+    fn test_bodies() {
+        assert_eq!(
+            SlackNotifier::body(&notification()),
+            json!({ "text": "first line\nsecond line" })
+        );
+        assert_eq!(
+            TeamsNotifier::body(&notification()),
+            json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": "DORA alert for team-a",
+                "title": "DORA alert for team-a",
+                "text": "first line\n\nsecond line",
+            })
+        );
+    }
+
+    #[test]
+    fn test_email_message() {
+        let config = DigestSettings {
+            smtp_host: Some("smtp.example.com".to_string()),
+            email_from: Some("dora@example.com".to_string()),
+            ..Default::default()
+        };
+        let smtp = SmtpConfig::from_config(
+            &config,
+            &["leads@example.com".to_string()],
+            "ALERT_EMAIL_TO",
+        )
+        .unwrap()
+        .unwrap();
+
+        let message = EmailNotifier { smtp }.message(&notification()).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+
+        assert!(formatted.contains("Subject: DORA alert for team-a"));
+        assert!(formatted.contains("To: leads@example.com"));
+        assert!(
+            SmtpConfig::from_config(&DigestSettings::default(), &[], "ALERT_EMAIL_TO")
+                .unwrap()
+                .is_none()
+        );
+        assert!(SmtpConfig::from_config(
+            &config,
+            &["not an address".to_string()],
+            "ALERT_EMAIL_TO"
+        )
+        .unwrap_err()
+        .to_string()
+        .ends_with("ALERT_EMAIL_TO"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifiers_post() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let hooks = Router::new().route(
+            "/:hook",
+            post({
+                let received = received.clone();
+
+                move |axum::extract::Path(hook): axum::extract::Path<String>,
+                      Json(body): Json<Value>| async move {
+                    received.lock().unwrap().push((hook, body));
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, hooks).await.unwrap() });
+
+        let notification = Notification {
+            payload: json!({ "text": "first line\nsecond line", "team": "team-a" }),
+            ..notification()
+        };
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(SlackNotifier {
+                url: format!("{}/slack", url),
+            }),
+            Box::new(TeamsNotifier {
+                url: format!("{}/teams", url),
+            }),
+            Box::new(WebhookNotifier {
+                url: format!("{}/webhook", url),
+            }),
+        ];
+
+        for notifier in &notifiers {
+            notifier
+                .notify(&reqwest::Client::new(), &notification)
+                .await
+                .unwrap();
+        }
+
+        assert!(WebhookNotifier {
+            url: format!("{}/missing/hook", url),
+        }
+        .notify(&reqwest::Client::new(), &notification)
+        .await
+        .is_err());
+
+        let received = received.lock().unwrap();
+
+        assert_eq!(received.len(), 3);
+        assert_eq!(
+            received[0],
+            ("slack".to_string(), SlackNotifier::body(&notification))
+        );
+        assert_eq!(
+            received[1],
+            ("teams".to_string(), TeamsNotifier::body(&notification))
+        );
+        assert_eq!(
+            received[2],
+            ("webhook".to_string(), notification.payload.clone())
+        );
+    }
+}
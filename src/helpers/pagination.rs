@@ -0,0 +1,135 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+
+use super::response::ResponseRecord;
+
+/// The position of the last record on a page, records are ordered by `created_at` then `sha`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub sha: String,
+}
+
+impl Cursor {
+    fn from_record(record: &ResponseRecord) -> Self {
+        Cursor {
+            created_at: record.created_at,
+            sha: record.sha.clone(),
+        }
+    }
+
+    /// Encodes the cursor as an opaque, URL safe token.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}|{}",
+            self.created_at.timestamp_millis(),
+            self.sha
+        ))
+    }
+
+    /// Decodes a token created with `encode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token wasn't created by `encode`.
+    pub fn decode(token: &str) -> Result<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(token)?)?;
+
+        let (millis, sha) = decoded.split_once('|').ok_or(anyhow!("Malformed Cursor"))?;
+
+        let created_at = DateTime::from_timestamp_millis(millis.parse()?)
+            .ok_or(anyhow!("Cursor Timestamp Out Of Range"))?;
+
+        Ok(Cursor {
+            created_at,
+            sha: sha.to_string(),
+        })
+    }
+
+    fn precedes(&self, record: &ResponseRecord) -> bool {
+        (self.created_at, self.sha.as_str()) < (record.created_at, record.sha.as_str())
+    }
+}
+
+/// Orders records by `created_at` then `sha`, and returns up to `limit` records after the cursor.
+///
+/// # Arguments
+///
+/// * `records` - The linked records, in any order.
+/// * `limit` - The most records to return.
+/// * `cursor` - The position of the last record on the previous page, if any.
+///
+/// # Returns
+///
+/// * The page of records, and the cursor for the next page when there are more records.
+pub fn paginate(
+    mut records: Vec<ResponseRecord>,
+    limit: usize,
+    cursor: Option<&Cursor>,
+) -> (Vec<ResponseRecord>, Option<Cursor>) {
+    records.sort_by(|a, b| (a.created_at, &a.sha).cmp(&(b.created_at, &b.sha)));
+
+    let mut page: Vec<ResponseRecord> = records
+        .into_iter()
+        .filter(|record| cursor.is_none_or(|cursor| cursor.precedes(record)))
+        .take(limit + 1)
+        .collect();
+
+    let next = match page.len() > limit {
+        true => {
+            page.truncate(limit);
+            page.last().map(Cursor::from_record)
+        }
+        false => None,
+    };
+
+    (page, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn record(created_at: DateTime<Utc>, sha: &str) -> ResponseRecord {
+        ResponseRecord {
+            created_at,
+            sha: sha.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: DateTime::from_timestamp_millis(1725903252123).unwrap(),
+            sha: "abc123".to_string(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor").is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let now = Utc::now();
+        let records = vec![
+            record(now, "c"),
+            record(now - Duration::hours(1), "b"),
+            record(now, "a"),
+            record(now + Duration::hours(1), "d"),
+        ];
+
+        let (first, cursor) = paginate(records.clone(), 2, None);
+        let shas: Vec<&str> = first.iter().map(|r| r.sha.as_str()).collect();
+
+        assert_eq!(shas, vec!["b", "a"]);
+
+        let (second, cursor) = paginate(records, 2, cursor.as_ref());
+        let shas: Vec<&str> = second.iter().map(|r| r.sha.as_str()).collect();
+
+        assert_eq!(shas, vec!["c", "d"]);
+        assert_eq!(cursor, None);
+    }
+}
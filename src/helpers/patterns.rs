@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// A name to match, e.g. an environment or repository, compared case-insensitively against the whole name.
+///
+/// * `re:<regex>` - Matches names the regex matches in full, e.g. `re:prod-\d+`.
+/// * A name containing `*` or `?` - A glob, e.g. `platform-*`.
+/// * Anything else - An exact name, e.g. `production`.
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    Exact(String),
+    Glob(Regex),
+    Regex(Regex),
+}
+
+impl FromStr for NamePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+
+        if let Some(pattern) = value.strip_prefix("re:") {
+            return Regex::new(&format!("(?i)^(?:{})$", pattern))
+                .map(NamePattern::Regex)
+                .map_err(|e| anyhow!(format!("{}: {}", e, value)));
+        }
+
+        if value.contains(['*', '?']) {
+            return Regex::new(&format!("^{}$", glob_to_regex(&value.to_lowercase())))
+                .map(NamePattern::Glob)
+                .map_err(|e| anyhow!(format!("{}: {}", e, value)));
+        }
+
+        Ok(NamePattern::Exact(value.to_lowercase()))
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    glob.chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            other => regex::escape(&other.to_string()),
+        })
+        .collect()
+}
+
+impl NamePattern {
+    /// Matches a lowercased name.
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Exact(exact) => exact == name,
+            NamePattern::Glob(regex) | NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    /// The pattern as a regex for a LogQL `=~` matcher, which Loki anchors to the whole value.
+    fn logql(&self) -> String {
+        match self {
+            NamePattern::Exact(exact) => regex::escape(exact),
+            NamePattern::Glob(regex) => regex
+                .as_str()
+                .trim_start_matches('^')
+                .trim_end_matches('$')
+                .to_string(),
+            NamePattern::Regex(regex) => regex
+                .as_str()
+                .trim_start_matches("(?i)^")
+                .trim_end_matches('$')
+                .to_string(),
+        }
+    }
+}
+
+/// Writes the pattern back in the form it was parsed from, lowercased, e.g. `platform-*` or `re:prod-\d+`.
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamePattern::Exact(exact) => write!(f, "{}", exact),
+            NamePattern::Glob(_) => write!(f, "{}", regex_to_glob(&self.logql())),
+            NamePattern::Regex(regex) => {
+                let pattern = regex.as_str();
+                let pattern = pattern.strip_prefix("(?i)^(?:").unwrap_or(pattern);
+
+                write!(f, "re:{}", pattern.strip_suffix(")$").unwrap_or(pattern))
+            }
+        }
+    }
+}
+
+impl Serialize for NamePattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn regex_to_glob(regex: &str) -> String {
+    let mut glob = String::new();
+    let mut chars = regex.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => glob.extend(chars.next()),
+            '.' if chars.next_if_eq(&'*').is_some() => glob.push('*'),
+            '.' => glob.push('?'),
+            other => glob.push(other),
+        }
+    }
+
+    glob
+}
+
+/// Parses a list of name patterns, skipping empty entries.
+pub fn parse_patterns<T: AsRef<str>>(values: &[T]) -> Result<Vec<NamePattern>> {
+    values
+        .iter()
+        .map(|value| value.as_ref())
+        .filter(|value| !value.trim().is_empty())
+        .map(NamePattern::from_str)
+        .collect()
+}
+
+/// Returns whether any of the patterns matches a name, in any case.
+pub fn matches_any(patterns: &[NamePattern], name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    patterns.iter().any(|pattern| pattern.is_match(&name))
+}
+
+/// Builds a case-insensitive LogQL regex matching any of the patterns.
+pub fn to_logql(patterns: &[NamePattern]) -> String {
+    let alternatives: Vec<String> = patterns.iter().map(NamePattern::logql).collect();
+
+    format!("(?i){}", alternatives.join("|"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_patterns() {
+        let patterns = parse_patterns(&["api", "platform-*", "re:svc-\\d+", ""]).unwrap();
+
+        assert_eq!(patterns.len(), 3);
+        assert!(patterns[0].is_match("api"));
+        assert!(!patterns[0].is_match("api-gateway"));
+        assert!(patterns[1].is_match("platform-tools"));
+        assert!(patterns[2].is_match("svc-12"));
+        assert!(!patterns[2].is_match("svc-12-legacy"));
+        assert_eq!(to_logql(&patterns), r"(?i)api|platform\-.*|(?:svc-\d+)");
+        assert!(parse_patterns(&["repo`} | drop"]).unwrap()[0].is_match("repo`} | drop"));
+    }
+
+    #[test]
+    fn test_name_pattern_display() {
+        let values = ["api", "platform-*", "hotfix/v?", "re:(prod|stage)-\\d+"];
+        let patterns = parse_patterns(&values).unwrap();
+
+        for (pattern, value) in patterns.iter().zip(values) {
+            assert_eq!(pattern.to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_invalid_name_patterns() {
+        assert!(parse_patterns(&["re:(svc"]).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use super::context::Context;
+use super::request::DataRequest;
+use crate::{
+    config::PrewarmSettings,
+    routes::data::{refresh_cache, DataCache},
+};
+
+/// Tracks whether the instance has finished its cold-start prewarm and may receive traffic.
+#[derive(Clone, Debug, Default)]
+pub struct WarmupStatus {
+    ready: Arc<AtomicBool>,
+}
+
+impl WarmupStatus {
+    pub fn new(ready: bool) -> Self {
+        WarmupStatus {
+            ready: Arc::new(AtomicBool::new(ready)),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrewarmConfig {
+    pub teams: Vec<Option<String>>,
+    pub days: Vec<i64>,
+    pub readiness_gate: bool,
+    pub readiness_timeout: std::time::Duration,
+    pub schedule: Option<Schedule>,
+}
+
+impl PrewarmConfig {
+    /// Builds the prewarm configuration from the `prewarm` settings, see `PREWARM_TEAMS`, `PREWARM_DAYS`,
+    /// `PREWARM_READINESS_GATE`, `PREWARM_READINESS_TIMEOUT_SECONDS` and `PREWARM_SCHEDULE`. Day ranges that
+    /// aren't positive are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `PREWARM_SCHEDULE` isn't a valid cron expression.
+    pub fn from_config(config: &PrewarmSettings) -> Result<Self> {
+        let teams = config
+            .teams
+            .iter()
+            .map(|team| team.trim())
+            .filter(|team| !team.is_empty())
+            .map(|team| match team {
+                "*" => None,
+                _ => Some(team.to_string()),
+            })
+            .collect();
+
+        let schedule = match config.schedule.as_deref().map(str::trim) {
+            Some(value) if !value.is_empty() => Some(
+                Schedule::from_str(value)
+                    .map_err(|e| anyhow!(format!("{}: PREWARM_SCHEDULE", e)))?,
+            ),
+            _ => None,
+        };
+
+        Ok(PrewarmConfig {
+            teams,
+            days: config
+                .days
+                .iter()
+                .copied()
+                .filter(|days| *days > 0)
+                .collect(),
+            readiness_gate: config.readiness_gate,
+            readiness_timeout: std::time::Duration::from_secs(config.readiness_timeout_seconds),
+            schedule,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.teams.is_empty() && !self.days.is_empty()
+    }
+
+    /// Builds the `DataRequest`s to prewarm, aligned to whole UTC days ending at the next midnight.
+    pub fn requests(&self, now: DateTime<Utc>) -> Vec<DataRequest> {
+        self.teams
+            .iter()
+            .flat_map(|team| {
+                self.days
+                    .iter()
+                    .map(move |days| DataRequest::trailing_days(team.clone(), *days, now))
+            })
+            .collect()
+    }
+}
+
+/// Warms the data cache for the configured priority queries, then marks the instance as ready.
+///
+/// The queries are run one at a time so a cold start doesn't flood Loki. When the readiness gate is
+/// enabled and the prewarm takes longer than the configured timeout, the instance is marked as ready
+/// anyway and the prewarm continues in the background.
+///
+/// # Arguments
+///
+/// * `config` - The prewarm configuration, see `PrewarmConfig::from_env`.
+/// * `ctx` - The configuration and HTTP client the data is gathered with.
+/// * `cache` - The data cache to fill.
+/// * `status` - The readiness status reported by `/ready`.
+pub async fn prewarm(config: PrewarmConfig, ctx: Context, cache: DataCache, status: WarmupStatus) {
+    let warm = warm(&config, &ctx, &cache);
+
+    tokio::pin!(warm);
+
+    if tokio::time::timeout(config.readiness_timeout, &mut warm)
+        .await
+        .is_err()
+    {
+        tracing::warn!("Prewarm exceeded the readiness timeout, continuing in the background");
+        status.mark_ready();
+        warm.await;
+    }
+
+    status.mark_ready();
+    tracing::info!("Prewarm completed");
+
+    if let Some(schedule) = &config.schedule {
+        prewarm_on_schedule(&config, schedule, &ctx, &cache).await;
+    }
+}
+
+/// Runs the prewarm queries one at a time, so warming doesn't flood Loki.
+async fn warm(config: &PrewarmConfig, ctx: &Context, cache: &DataCache) {
+    for request in config.requests(Utc::now()) {
+        if let Err(e) = refresh_cache(ctx, cache, request, false).await {
+            tracing::error!("Prewarm Query Failed: {:?}", e);
+        }
+    }
+}
+
+/// Re-runs the prewarm queries each time the schedule fires, so the cached ranges move forward with the
+/// day and the first dashboard load after the data cache TTL is never slow.
+async fn prewarm_on_schedule(
+    config: &PrewarmConfig,
+    schedule: &Schedule,
+    ctx: &Context,
+    cache: &DataCache,
+) {
+    while let Some(next) = schedule.upcoming(Utc).next() {
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::time::sleep(wait).await;
+
+        tracing::info!("Running scheduled prewarm");
+        warm(config, ctx, cache).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prewarm_requests_are_day_aligned() {
+        let config = PrewarmConfig {
+            teams: vec![Some("team-a".to_string()), None],
+            days: vec![7, 30],
+            ..Default::default()
+        };
+
+        let now = DateTime::parse_from_rfc3339("2024-09-09T17:34:12Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let requests = config.requests(now);
+
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].team, Some("team-a".to_string()));
+        assert_eq!(requests[0].end.to_rfc3339(), "2024-09-10T00:00:00+00:00");
+        assert_eq!(requests[0].start.to_rfc3339(), "2024-09-03T00:00:00+00:00");
+        assert_eq!(requests[3].team, None);
+        assert_eq!(requests[3].start.to_rfc3339(), "2024-08-11T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_prewarm_disabled_without_teams() {
+        let config = PrewarmConfig {
+            days: vec![30],
+            ..Default::default()
+        };
+
+        assert!(!config.is_enabled());
+    }
+
+    #[test]
+    fn test_prewarm_schedule() {
+        let schedule = Schedule::from_str("0 0 * * * *").unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-09-09T17:34:12Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let next = schedule.after(&now).next().unwrap();
+
+        assert_eq!(next.to_rfc3339(), "2024-09-09T18:00:00+00:00");
+    }
+}
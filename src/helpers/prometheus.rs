@@ -0,0 +1,282 @@
+use std::{
+    env,
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Configures the DORA gauges exported on `/metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportConfig {
+    pub window_days: i64,
+    /// How long the records the DORA gauges are calculated from are reused across scrapes.
+    pub refresh: Duration,
+}
+
+impl ExportConfig {
+    /// Reads the export configuration from the environment.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `METRICS_WINDOW_DAYS` - The trailing number of days the DORA gauges are calculated over. Defaults to `30`.
+    /// * `METRICS_REFRESH_SECONDS` - How long the DORA gauges are reused before they are calculated again.
+    ///   Defaults to `300`.
+    pub fn from_env() -> Self {
+        let window_days = env::var("METRICS_WINDOW_DAYS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(30);
+
+        let refresh = env::var("METRICS_REFRESH_SECONDS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(300));
+
+        ExportConfig {
+            window_days,
+            refresh,
+        }
+    }
+}
+
+/// Builds a response in the Prometheus text exposition format.
+#[derive(Debug, Default)]
+pub struct Exposition {
+    body: String,
+}
+
+impl Exposition {
+    /// Writes a gauge family, skipping samples without a value.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The metric name, e.g. `dora_lead_time_seconds`.
+    /// * `help` - A description of the metric.
+    /// * `samples` - The label pairs and value of each sample.
+    pub fn gauge<'a, I>(&mut self, name: &str, help: &str, samples: I)
+    where
+        I: IntoIterator<Item = (Vec<(&'a str, &'a str)>, Option<f64>)>,
+    {
+        self.family(name, help, "gauge", samples);
+    }
+
+    /// Writes a counter family. Counter names should end in `_total`.
+    pub fn counter<'a, I>(&mut self, name: &str, help: &str, samples: I)
+    where
+        I: IntoIterator<Item = (Vec<(&'a str, &'a str)>, Option<f64>)>,
+    {
+        self.family(name, help, "counter", samples);
+    }
+
+    /// Writes a histogram family, as cumulative `_bucket` samples followed by `_sum` and `_count`.
+    pub fn histogram<'a, I>(&mut self, name: &str, help: &str, samples: I)
+    where
+        I: IntoIterator<Item = (Vec<(&'a str, &'a str)>, HistogramSnapshot)>,
+    {
+        let _ = writeln!(self.body, "# HELP {} {}", name, help);
+        let _ = writeln!(self.body, "# TYPE {} histogram", name);
+
+        for (labels, snapshot) in samples {
+            let mut cumulative = 0;
+
+            for (bound, count) in snapshot.buckets.iter().zip(&snapshot.counts) {
+                cumulative += count;
+
+                let le = bound.to_string();
+                let mut bucket_labels = labels.clone();
+
+                bucket_labels.push(("le", &le));
+
+                let _ = writeln!(
+                    self.body,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(&bucket_labels),
+                    cumulative
+                );
+            }
+
+            let mut bucket_labels = labels.clone();
+
+            bucket_labels.push(("le", "+Inf"));
+
+            let _ = writeln!(
+                self.body,
+                "{}_bucket{} {}",
+                name,
+                format_labels(&bucket_labels),
+                snapshot.count
+            );
+            let _ = writeln!(
+                self.body,
+                "{}_sum{} {}",
+                name,
+                format_labels(&labels),
+                snapshot.sum
+            );
+            let _ = writeln!(
+                self.body,
+                "{}_count{} {}",
+                name,
+                format_labels(&labels),
+                snapshot.count
+            );
+        }
+    }
+
+    fn family<'a, I>(&mut self, name: &str, help: &str, kind: &str, samples: I)
+    where
+        I: IntoIterator<Item = (Vec<(&'a str, &'a str)>, Option<f64>)>,
+    {
+        let _ = writeln!(self.body, "# HELP {} {}", name, help);
+        let _ = writeln!(self.body, "# TYPE {} {}", name, kind);
+
+        for (labels, value) in samples {
+            if let Some(value) = value {
+                let _ = writeln!(self.body, "{}{} {}", name, format_labels(&labels), value);
+            }
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        self.body
+    }
+}
+
+/// The default histogram buckets, in seconds, from 5ms to 60s.
+pub const DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 60.0,
+];
+
+/// A histogram of durations, safe to observe from many requests at once.
+#[derive(Debug)]
+pub struct Histogram {
+    counts: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: DURATION_BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.counts[index].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: &DURATION_BUCKETS,
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            sum: self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The observations of a `Histogram` at a point in time, with non-cumulative bucket counts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: &'static [f64],
+    pub counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gauge_exposition() {
+        let mut exposition = Exposition::default();
+
+        exposition.gauge(
+            "dora_mttr_seconds",
+            "Median time to restore.",
+            vec![
+                (
+                    vec![("team", "team-a"), ("repository", "say \"hi\"")],
+                    Some(7200.0),
+                ),
+                (vec![("team", "team-b"), ("repository", "repo")], None),
+            ],
+        );
+
+        assert_eq!(
+            exposition.into_string(),
+            "# HELP dora_mttr_seconds Median time to restore.\n\
+             # TYPE dora_mttr_seconds gauge\n\
+             dora_mttr_seconds{team=\"team-a\",repository=\"say \\\"hi\\\"\"} 7200\n"
+        );
+    }
+
+    #[test]
+    fn test_histogram_exposition() {
+        let histogram = Histogram::default();
+
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_millis(300));
+        histogram.observe(Duration::from_secs(120));
+
+        let mut exposition = Exposition::default();
+
+        exposition.histogram(
+            "loki_query_duration_seconds",
+            "Loki query duration.",
+            vec![(vec![("outcome", "ok")], histogram.snapshot())],
+        );
+
+        let body = exposition.into_string();
+
+        assert!(body.contains("loki_query_duration_seconds_bucket{outcome=\"ok\",le=\"0.01\"} 0\n"));
+        assert!(
+            body.contains("loki_query_duration_seconds_bucket{outcome=\"ok\",le=\"0.025\"} 1\n")
+        );
+        assert!(body.contains("loki_query_duration_seconds_bucket{outcome=\"ok\",le=\"60\"} 2\n"));
+        assert!(body.contains("loki_query_duration_seconds_bucket{outcome=\"ok\",le=\"+Inf\"} 3\n"));
+        assert!(body.contains("loki_query_duration_seconds_sum{outcome=\"ok\"} 120.32\n"));
+        assert!(body.contains("loki_query_duration_seconds_count{outcome=\"ok\"} 3\n"));
+    }
+}
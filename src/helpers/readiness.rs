@@ -0,0 +1,259 @@
+//! Probes of the upstreams the API can't serve data without, so an instance that can't reach them is taken out
+//! of rotation instead of failing every request.
+
+use anyhow::{anyhow, Result};
+use futures::join;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{
+    context::Context, loki::query_tenant, recordings::RecordingMode, request::DataRequest,
+    upstreams::Upstream,
+};
+use crate::config::ReadinessSettings;
+
+/// Configures the upstream probes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadinessConfig {
+    pub timeout: Duration,
+    /// How long the result of a probe is reused, so frequent probes don't each call the upstreams.
+    pub cache_ttl: Duration,
+    /// Whether an unreachable GitHub makes the instance un-ready. Only `/teams` and `/repositories` need
+    /// GitHub, so by default it is reported without failing readiness.
+    pub require_github: bool,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        ReadinessConfig {
+            timeout: Duration::from_secs(2),
+            cache_ttl: Duration::from_secs(10),
+            require_github: false,
+        }
+    }
+}
+
+impl ReadinessConfig {
+    /// Builds the readiness probe configuration from the `readiness` settings, see `READY_TIMEOUT_SECONDS`,
+    /// `READY_CACHE_SECONDS` and `READY_REQUIRE_GITHUB`. A timeout of `0` keeps the default.
+    pub fn from_config(config: &ReadinessSettings) -> Self {
+        ReadinessConfig {
+            timeout: Some(config.timeout_seconds)
+                .filter(|value| *value > 0)
+                .map_or(ReadinessConfig::default().timeout, Duration::from_secs),
+            cache_ttl: Duration::from_secs(config.cache_seconds),
+            require_github: config.require_github,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyState {
+    Ok,
+    Failed,
+    /// Not probed, since fixtures, demo data or recordings are served in its place.
+    Skipped,
+}
+
+/// The result of probing one upstream.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DependencyStatus {
+    pub upstream: &'static str,
+    pub state: DependencyState,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+    /// Whether a failed probe makes the instance un-ready.
+    pub required: bool,
+}
+
+impl DependencyStatus {
+    fn skipped(upstream: Upstream) -> Self {
+        DependencyStatus {
+            upstream: upstream.name(),
+            state: DependencyState::Skipped,
+            latency_ms: None,
+            error: None,
+            required: true,
+        }
+    }
+
+    fn from_result(upstream: Upstream, started: Instant, result: Result<()>) -> Self {
+        DependencyStatus {
+            upstream: upstream.name(),
+            state: match result {
+                Ok(_) => DependencyState::Ok,
+                Err(_) => DependencyState::Failed,
+            },
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: result.err().map(|e| e.to_string()),
+            required: true,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.required || self.state != DependencyState::Failed
+    }
+}
+
+/// The last probe results and when they were taken, see `ReadinessConfig::cache_ttl`.
+static LAST_CHECK: Mutex<Option<(Instant, Vec<DependencyStatus>)>> = Mutex::new(None);
+
+/// Probes Loki and GitHub concurrently, or returns the last results while they are younger than
+/// `cache_ttl`.
+pub async fn check(ctx: &Context, config: &ReadinessConfig) -> Vec<DependencyStatus> {
+    if let Some((checked_at, statuses)) = LAST_CHECK.lock().unwrap().as_ref() {
+        if checked_at.elapsed() < config.cache_ttl {
+            return statuses.clone();
+        }
+    }
+
+    let (loki, mut github) = join!(
+        check_loki(ctx, config.timeout),
+        check_github(ctx, config.timeout)
+    );
+    github.required = config.require_github;
+
+    let statuses = vec![loki, github];
+
+    *LAST_CHECK.lock().unwrap() = Some((Instant::now(), statuses.clone()));
+
+    statuses
+}
+
+/// Returns the URL of Loki's labels API beside the `query_range` API `LOKI_URL` points to, or below `LOKI_URL`
+/// when it is Loki's base URL.
+fn labels_url(url: &str) -> Result<Url> {
+    let url = Url::parse(url)?;
+
+    match url.path().trim_end_matches('/').ends_with("query_range") {
+        true => Ok(url.join("labels")?),
+        false => Ok(Url::parse(&format!(
+            "{}/loki/api/v1/labels",
+            url.as_str().trim_end_matches('/')
+        ))?),
+    }
+}
+
+/// Lists Loki's labels over the last few minutes, which answers quickly without reading any log lines.
+async fn check_loki(ctx: &Context, timeout: Duration) -> DependencyStatus {
+    let loki = &ctx.config.loki;
+
+    if ctx.fixtures.is_some() || loki.recordings == Some(RecordingMode::Replay) {
+        return DependencyStatus::skipped(Upstream::Loki);
+    }
+
+    let started = Instant::now();
+
+    let result = async {
+        let url = labels_url(loki.url.as_deref().unwrap_or_default())?;
+        let mut request = ctx
+            .client
+            .get(url)
+            .query(&[("since", "5m")])
+            .timeout(timeout);
+
+        if let Some(tenant) = query_tenant(loki, &DataRequest::default()) {
+            request = request.header("X-Scope-OrgID", tenant);
+        }
+
+        if let Some(user) = loki.user.as_deref().filter(|user| !user.is_empty()) {
+            request = request.basic_auth(user, loki.token.as_deref());
+        }
+
+        let status = request.send().await?.status();
+
+        match status.is_success() {
+            true => Ok(()),
+            false => Err(anyhow!(format!("Loki Responded with status: {:?}", status))),
+        }
+    }
+    .await;
+
+    DependencyStatus::from_result(Upstream::Loki, started, result)
+}
+
+/// Reads GitHub's rate limit, which doesn't count against it, and fails once the token has no requests left.
+async fn check_github(ctx: &Context, timeout: Duration) -> DependencyStatus {
+    if ctx.fixtures.is_some() {
+        return DependencyStatus::skipped(Upstream::GitHub);
+    }
+
+    let started = Instant::now();
+
+    let result = async {
+        let response = ctx
+            .client
+            .get("https://api.github.com/rate_limit")
+            .header("User-Agent", "request")
+            .header(
+                "Authorization",
+                format!(
+                    "token {}",
+                    ctx.config.github.token.as_deref().unwrap_or_default()
+                ),
+            )
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .timeout(timeout)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(format!(
+                "GitHub Responded with status: {:?}",
+                response.status()
+            )));
+        }
+
+        let body: Value = response.json().await?;
+
+        match body["resources"]["core"]["remaining"].as_u64() {
+            Some(0) => Err(anyhow!("GitHub rate limit exhausted")),
+            _ => Ok(()),
+        }
+    }
+    .await;
+
+    DependencyStatus::from_result(Upstream::GitHub, started, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optional_dependency_is_ready() {
+        let mut github = DependencyStatus::from_result(
+            Upstream::GitHub,
+            Instant::now(),
+            Err(anyhow!("unreachable")),
+        );
+
+        assert!(!github.is_ready());
+
+        github.required = false;
+
+        assert!(github.is_ready());
+    }
+
+    #[test]
+    fn test_labels_url() {
+        assert_eq!(
+            labels_url("https://loki.example.com/loki/api/v1/query_range")
+                .unwrap()
+                .as_str(),
+            "https://loki.example.com/loki/api/v1/labels"
+        );
+        assert_eq!(
+            labels_url("https://loki.example.com/").unwrap().as_str(),
+            "https://loki.example.com/loki/api/v1/labels"
+        );
+        assert!(labels_url("").is_err());
+    }
+}
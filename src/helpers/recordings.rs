@@ -0,0 +1,171 @@
+//! Loki query responses recorded to and replayed from local JSON files, so the whole `/data` pipeline, from
+//! batching and paging to linking, can run offline and be tested deterministically against real responses.
+//!
+//! Each query is recorded to its own file, named after a hash of the query, its window, limit and tenant, e.g.
+//! `recordings/5f2a0c9e1b7d4a36.json`. The file holds the query beside Loki's response, so recordings can be
+//! reviewed and edited by hand.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::loki::QueryParams;
+
+/// Whether Loki query responses are replayed from or recorded to the recordings directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingMode {
+    /// Responses are read from the recordings instead of Loki, and a query that wasn't recorded fails.
+    Replay,
+    /// Queries are sent to Loki and every successful response is written to the recordings.
+    Record,
+}
+
+impl FromStr for RecordingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "replay" => Ok(RecordingMode::Replay),
+            "record" => Ok(RecordingMode::Record),
+            _ => Err(anyhow!(format!("Unknown recording mode: {}", value))),
+        }
+    }
+}
+
+impl fmt::Display for RecordingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingMode::Replay => write!(f, "replay"),
+            RecordingMode::Record => write!(f, "record"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Recording {
+    query: String,
+    start: String,
+    end: String,
+    limit: u16,
+    tenant: Option<String>,
+    response: Value,
+}
+
+/// Returns the file a query is recorded to. The principal isn't part of the name, since it doesn't change
+/// what Loki returns.
+pub fn path(dir: &Path, data: &QueryParams) -> PathBuf {
+    // FNV-1a, which unlike the standard library's hasher is stable across Rust versions.
+    let key = [
+        data.query.as_str(),
+        data.start.as_str(),
+        data.end.as_str(),
+        &data.limit.to_string(),
+        data.tenant.as_deref().unwrap_or_default(),
+    ]
+    .join("\n")
+    .bytes()
+    .fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+
+    dir.join(format!("{:016x}.json", key))
+}
+
+/// Reads the recorded Loki response to a query.
+///
+/// # Errors
+///
+/// Returns an error naming the query when it wasn't recorded, so a test can't silently pass on missing data.
+pub fn replay(dir: &Path, data: &QueryParams) -> Result<Value> {
+    let path = path(dir, data);
+
+    let contents = fs::read_to_string(&path).map_err(|e| {
+        anyhow!(format!(
+            "No recording of Loki query {} from {} to {}: {}: {}",
+            data.query,
+            data.start,
+            data.end,
+            e,
+            path.display()
+        ))
+    })?;
+
+    let recording: Recording = serde_json::from_str(&contents)
+        .map_err(|e| anyhow!(format!("{}: {}", e, path.display())))?;
+
+    Ok(recording.response)
+}
+
+/// Writes the Loki response to a query, replacing any earlier recording of it.
+///
+/// # Errors
+///
+/// Returns an error if the response isn't JSON or the recording can't be written.
+pub fn record(dir: &Path, data: &QueryParams, body: &str) -> Result<()> {
+    let recording = Recording {
+        query: data.query.clone(),
+        start: data.start.clone(),
+        end: data.end.clone(),
+        limit: data.limit,
+        tenant: data.tenant.clone(),
+        response: serde_json::from_str(body)?,
+    };
+
+    let path = path(dir, data);
+
+    fs::create_dir_all(dir)?;
+    fs::write(&path, serde_json::to_string_pretty(&recording)?)
+        .map_err(|e| anyhow!(format!("{}: {}", e, path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::env;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = env::temp_dir().join(format!("dora-recordings-{}", std::process::id()));
+        let data = QueryParams {
+            query: "{source=\"github\"}".to_string(),
+            start: "1625097600000000000".to_string(),
+            end: "1625101200000000000".to_string(),
+            limit: 5000,
+            principal: "alice".to_string(),
+            ..Default::default()
+        };
+        let body = r#"{"data":{"result":[]}}"#;
+
+        assert!(replay(&dir, &data).is_err());
+
+        record(&dir, &data, body).unwrap();
+
+        let other_principal = QueryParams {
+            principal: "bob".to_string(),
+            ..data.clone()
+        };
+        let other_tenant = QueryParams {
+            tenant: Some("team-a".to_string()),
+            ..data.clone()
+        };
+
+        assert_eq!(
+            replay(&dir, &other_principal).unwrap(),
+            json!({"data": {"result": []}})
+        );
+        assert!(replay(&dir, &other_tenant).is_err());
+        assert_eq!(
+            path(&dir, &data).file_name().unwrap().len(),
+            "0123456789abcdef.json".len()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
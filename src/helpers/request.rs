@@ -1,10 +1,492 @@
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
 use serde::Deserialize;
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug, Clone)]
+use super::{
+    deduplication::Deduplication,
+    logql::{Matcher, Stage},
+    patterns::{parse_patterns, to_logql, NamePattern},
+    response::ResponseRecord,
+};
+
+/// The authors of automated pull requests, left out of lead time by `exclude_bots`.
+const BOT_AUTHORS: [&str; 3] = ["*[bot]", "dependabot*", "renovate*"];
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct DataRequest {
+    /// The repositories to include, as names, globs or regexes, see `NamePattern`.
     pub repositories: Option<Vec<String>>,
+    /// The repositories to leave out, in the same format as `repositories`.
+    pub exclude_repositories: Option<Vec<String>>,
+    /// The authors whose merges are left out of lead time, in the same format as `repositories`.
+    pub exclude_authors: Option<Vec<String>>,
+    /// When `true`, merges by common bots like Dependabot and Renovate are left out of lead time.
+    pub exclude_bots: Option<bool>,
     pub team: Option<String>,
+    /// Several teams to request at once, in addition to `team`.
+    pub teams: Option<Vec<String>>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub include_child_teams: Option<bool>,
+    /// When `true`, the first commit of each merged pull request is looked up in GitHub.
+    pub include_first_commit: Option<bool>,
+    /// The deployment environments to include in place of the configured production environments.
+    pub environments: Option<Vec<String>>,
+    /// How repeated deployments of a commit are counted in place of `DEPLOYMENT_DEDUPLICATION`, see
+    /// `Deduplication`.
+    pub deduplication: Option<String>,
+    /// The days after the window that deployments and issues are also queried for, only to resolve the
+    /// failures within it. Limited by `FAILURE_LOOKAHEAD_MAX_DAYS`.
+    pub failure_lookahead_days: Option<i64>,
+    /// The services of a monorepo to include, in the same format as `repositories`, see `Deployment::service`.
+    pub services: Option<Vec<String>>,
+    /// The service namespaces the events are queried from in place of `SERVICE_NAME`.
+    pub namespaces: Option<Vec<String>>,
+    /// The Loki tenant the events are queried from in place of `LOKI_TENANT_ID`, limited by
+    /// `LOKI_ALLOWED_TENANTS`.
+    pub tenant: Option<String>,
+    /// Who the auth middleware authenticated the request as, see `Authenticated::principal`.
+    #[serde(skip)]
+    pub authenticated_as: Option<String>,
+    #[serde(skip)]
+    pub child_teams: Vec<String>,
+    #[serde(skip)]
+    pub partial: bool,
+}
+
+impl DataRequest {
+    /// The principal Loki usage for this request is attributed to, who it was authenticated as, or else the
+    /// requested teams.
+    pub fn principal(&self) -> String {
+        self.authenticated_as
+            .clone()
+            .unwrap_or_else(|| self.team_label())
+    }
+
+    /// The requested teams joined by commas, or `org` for organization wide requests.
+    pub fn team_label(&self) -> String {
+        match self.requested_teams().as_slice() {
+            [] => "org".to_string(),
+            teams => teams.join(","),
+        }
+    }
+
+    /// The teams named by `team` and `teams`, without duplicates.
+    pub fn requested_teams(&self) -> Vec<&str> {
+        let mut teams: Vec<&str> = vec![];
+
+        for team in self.team.iter().chain(self.teams.iter().flatten()) {
+            if !teams.contains(&team.as_str()) {
+                teams.push(team);
+            }
+        }
+
+        teams
+    }
+
+    /// The requested teams followed by their child teams, or an empty list for organization wide requests.
+    pub fn team_names(&self) -> Vec<&str> {
+        let mut teams = self.requested_teams();
+
+        for team in &self.child_teams {
+            if !teams.contains(&team.as_str()) {
+                teams.push(team);
+            }
+        }
+
+        teams
+    }
+
+    /// Parses the repository patterns of the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob or regex is invalid.
+    pub fn repository_filter(&self) -> Result<RepositoryFilter> {
+        Ok(RepositoryFilter {
+            include: match &self.repositories {
+                Some(repositories) => Some(parse_patterns(repositories)?),
+                None => None,
+            },
+            exclude: parse_patterns(self.exclude_repositories.as_deref().unwrap_or_default())?,
+        })
+    }
+
+    /// Parses the authors whose merges are left out of lead time, including the bots when `exclude_bots` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob or regex is invalid.
+    pub fn excluded_authors(&self) -> Result<Vec<NamePattern>> {
+        let mut authors = parse_patterns(self.exclude_authors.as_deref().unwrap_or_default())?;
+
+        if self.exclude_bots.unwrap_or_default() {
+            authors.extend(parse_patterns(&BOT_AUTHORS)?);
+        }
+
+        Ok(authors)
+    }
+
+    /// Parses the requested deduplication strategy, or `None` when the configured one applies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the strategy is unknown.
+    pub fn deduplication(&self) -> Result<Option<Deduplication>> {
+        self.deduplication.as_deref().map(str::parse).transpose()
+    }
+
+    /// Parses the requested services, or `None` when deployments of every service, and without one, are included.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob or regex is invalid.
+    pub fn service_filter(&self) -> Result<Option<Vec<NamePattern>>> {
+        match self
+            .services
+            .as_deref()
+            .filter(|services| !services.is_empty())
+        {
+            Some(services) => Ok(Some(parse_patterns(services)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The service namespaces the caller asked for, or `None` when the configured `SERVICE_NAME` applies.
+    pub fn requested_namespaces(&self) -> Option<&[String]> {
+        self.namespaces
+            .as_deref()
+            .filter(|namespaces| !namespaces.is_empty())
+    }
+
+    /// The Loki tenant the caller asked for, or `None` when `LOKI_TENANT_ID` applies.
+    pub fn requested_tenant(&self) -> Option<&str> {
+        self.tenant
+            .as_deref()
+            .map(str::trim)
+            .filter(|tenant| !tenant.is_empty())
+    }
+
+    /// The environments the caller asked for, or `None` when the configured production environments apply.
+    pub fn requested_environments(&self) -> Option<&[String]> {
+        self.environments
+            .as_deref()
+            .filter(|environments| !environments.is_empty())
+    }
+
+    /// Builds a request for the trailing number of whole UTC days, ending at the next midnight, so repeated
+    /// requests throughout a day share a cache entry.
+    pub fn trailing_days(team: Option<String>, days: i64, now: DateTime<Utc>) -> Self {
+        let end = (now.date_naive() + Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+
+        DataRequest {
+            team,
+            start: end - Duration::days(days),
+            end,
+            ..Default::default()
+        }
+    }
+}
+
+/// The repositories a request includes and excludes.
+#[derive(Debug, Clone, Default)]
+pub struct RepositoryFilter {
+    include: Option<Vec<NamePattern>>,
+    exclude: Vec<NamePattern>,
+}
+
+impl RepositoryFilter {
+    pub fn is_match(&self, repository: &str) -> bool {
+        let repository = repository.to_lowercase();
+
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.iter().any(|pattern| pattern.is_match(&repository)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.is_match(&repository))
+    }
+
+    /// Builds the LogQL stages filtering the repositories under any of the labels a repository may be named
+    /// by, e.g. `| vcs_repository_name=~"(?i)platform\\-.*" or repository_name=~"(?i)platform\\-.*"`. Excluded
+    /// repositories are filtered out under every label.
+    pub fn stages(&self, labels: &[&'static str]) -> Vec<Stage> {
+        let mut stages = vec![];
+
+        if let Some(include) = self.include.as_ref().filter(|include| !include.is_empty()) {
+            let pattern = to_logql(include);
+
+            stages.push(Stage::Any(
+                labels
+                    .iter()
+                    .map(|label| Matcher::re(label, pattern.clone()))
+                    .collect(),
+            ));
+        }
+
+        if !self.exclude.is_empty() {
+            let pattern = to_logql(&self.exclude);
+
+            stages.extend(
+                labels
+                    .iter()
+                    .map(|label| Stage::Any(vec![Matcher::not_re(label, pattern.clone())])),
+            );
+        }
+
+        stages
+    }
+}
+
+/// Two selections of deployments to compare, e.g. two teams or two quarters.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompareRequest {
+    pub baseline: DataRequest,
+    pub comparison: DataRequest,
+}
+
+/// A time window across the whole organization, without team or repository filters.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct WindowRequest {
     pub start: DateTime<Utc>,
     pub end: DateTime<Utc>,
 }
+
+impl From<WindowRequest> for DataRequest {
+    fn from(window: WindowRequest) -> Self {
+        DataRequest {
+            start: window.start,
+            end: window.end,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Deployments,
+    Failures,
+    LeadTimes,
+}
+
+impl FromStr for Section {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "deployments" => Ok(Section::Deployments),
+            "failures" => Ok(Section::Failures),
+            "lead_times" => Ok(Section::LeadTimes),
+            other => Err(anyhow!(format!("Unknown section: {}", other))),
+        }
+    }
+}
+
+/// The order records are returned in, see `sort_by` on `/data`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    CreatedAt,
+    Repository,
+    LeadTime,
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "created_at" => Ok(SortKey::CreatedAt),
+            "repository" => Ok(SortKey::Repository),
+            "lead_time" => Ok(SortKey::LeadTime),
+            other => Err(anyhow!(format!("Unknown sort key: {}", other))),
+        }
+    }
+}
+
+impl SortKey {
+    /// Sorts records by this key, then by `created_at` and `sha`, so records with the same key keep the order
+    /// pages are returned in. Records without a lead time are sorted after the ones with one.
+    pub fn sort(self, records: &mut [ResponseRecord]) {
+        match self {
+            SortKey::CreatedAt => {
+                records.sort_by(|a, b| (a.created_at, &a.sha).cmp(&(b.created_at, &b.sha)))
+            }
+            SortKey::Repository => records.sort_by(|a, b| {
+                (&a.repository, a.created_at, &a.sha).cmp(&(&b.repository, b.created_at, &b.sha))
+            }),
+            SortKey::LeadTime => records.sort_by(|a, b| {
+                let lead_time = |record: &ResponseRecord| {
+                    record
+                        .lead_time
+                        .as_ref()
+                        .map_or((true, 0), |lead_time| (false, lead_time.seconds))
+                };
+
+                (lead_time(a), a.created_at, &a.sha).cmp(&(lead_time(b), b.created_at, &b.sha))
+            }),
+        }
+    }
+}
+
+/// Parses a comma-separated list of record fields, e.g. `repository,created_at,lead_time`.
+///
+/// # Errors
+///
+/// Returns an error if a field isn't a key of `ResponseRecord`, or no field is given.
+pub fn parse_fields(value: &str) -> Result<Vec<String>> {
+    let known = ResponseRecord::field_names();
+
+    let fields = value
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| match known.iter().any(|name| name == field) {
+            true => Ok(field.to_string()),
+            false => Err(anyhow!(format!("Unknown field: {}", field))),
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    match fields.is_empty() {
+        true => Err(anyhow!("No fields selected")),
+        false => Ok(fields),
+    }
+}
+
+/// Parses a comma-separated list of response sections, e.g. `deployments,failures`.
+pub fn parse_sections(value: &str) -> Result<Vec<Section>> {
+    value
+        .split(',')
+        .filter(|section| !section.trim().is_empty())
+        .map(Section::from_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{duration::DurationValue, patterns::matches_any};
+
+    #[test]
+    fn test_parse_sections() {
+        let sections = parse_sections("deployments, lead_times").unwrap();
+
+        assert_eq!(sections, vec![Section::Deployments, Section::LeadTimes]);
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(
+            parse_fields("repository, lead_time").unwrap(),
+            vec!["repository".to_string(), "lead_time".to_string()]
+        );
+        assert!(parse_fields("repository,secret").is_err());
+        assert!(parse_fields(",").is_err());
+    }
+
+    #[test]
+    fn test_sort_by_lead_time() {
+        let now = Utc::now();
+        let record = |sha: &str, hours: Option<i64>| ResponseRecord {
+            sha: sha.to_string(),
+            created_at: now,
+            lead_time: hours.map(|hours| DurationValue::from_hours(hours as f64)),
+            ..Default::default()
+        };
+        let mut records = vec![
+            record("a", None),
+            record("b", Some(5)),
+            record("c", Some(1)),
+        ];
+
+        "lead_time".parse::<SortKey>().unwrap().sort(&mut records);
+
+        let shas: Vec<&str> = records.iter().map(|r| r.sha.as_str()).collect();
+
+        assert_eq!(shas, vec!["c", "b", "a"]);
+        assert!("lead".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_team_names() {
+        let request = DataRequest {
+            team: Some("platform".to_string()),
+            teams: Some(vec!["delivery".to_string(), "platform".to_string()]),
+            child_teams: vec!["delivery".to_string(), "o11y".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(request.team_names(), vec!["platform", "delivery", "o11y"]);
+        assert_eq!(request.principal(), "platform,delivery");
+        assert_eq!(DataRequest::default().principal(), "org");
+
+        let authenticated = DataRequest {
+            authenticated_as: Some("dashboard".to_string()),
+            ..request
+        };
+
+        assert_eq!(authenticated.principal(), "dashboard");
+        assert_eq!(authenticated.team_label(), "platform,delivery");
+    }
+
+    #[test]
+    fn test_repository_filter() {
+        let request = DataRequest {
+            repositories: Some(vec!["platform-*".to_string(), "api".to_string()]),
+            exclude_repositories: Some(vec!["platform-legacy".to_string()]),
+            ..Default::default()
+        };
+
+        let filter = request.repository_filter().unwrap();
+
+        assert!(filter.is_match("Platform-Tools"));
+        assert!(filter.is_match("api"));
+        assert!(!filter.is_match("platform-legacy"));
+        assert!(!filter.is_match("web"));
+        assert!(DataRequest::default()
+            .repository_filter()
+            .unwrap()
+            .is_match("web"));
+        assert_eq!(
+            filter
+                .stages(&["repo", "repo_v1"])
+                .iter()
+                .map(Stage::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                r#"| repo=~"(?i)platform\\-.*|api" or repo_v1=~"(?i)platform\\-.*|api""#,
+                r#"| repo!~"(?i)platform\\-legacy""#,
+                r#"| repo_v1!~"(?i)platform\\-legacy""#
+            ]
+        );
+    }
+
+    #[test]
+    fn test_excluded_authors() {
+        let request = DataRequest {
+            exclude_authors: Some(vec!["release-robot".to_string()]),
+            exclude_bots: Some(true),
+            ..Default::default()
+        };
+
+        let authors = request.excluded_authors().unwrap();
+
+        assert!(matches_any(&authors, "Release-Robot"));
+        assert!(matches_any(&authors, "dependabot[bot]"));
+        assert!(matches_any(&authors, "renovate-bot"));
+        assert!(!matches_any(&authors, "octocat"));
+        assert!(DataRequest::default()
+            .excluded_authors()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_sections_unknown() {
+        assert!(parse_sections("deployments,incidents").is_err());
+    }
+}
@@ -1,26 +1,510 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use super::{
+    anomalies::Annotation,
+    buckets::BucketSize,
+    domain::{DeploymentState, RepoName, Sha, TeamName},
+    duration::DurationValue,
+    forecast::MetricForecast,
+    metrics::{MetricsSummary, MetricsTrend, RestoreSummary},
+    reviews::{ReviewRecord, ReviewSummary},
+    targets::TargetReport,
+};
+
 #[derive(Serialize, Debug, Clone, Default)]
 pub struct ResponseRecord {
     pub repository: String,
     pub team: String,
+    pub environment: String,
+    /// The service of a monorepo the deployment is for, when the deployment names one.
+    pub service: Option<String>,
     pub title: Option<String>,
     pub user: Option<String>,
     pub sha: String,
     pub status: bool,
     pub failed_at: Option<DateTime<Utc>>,
     pub merged_at: Option<DateTime<Utc>>,
+    /// When the first commit of the deployed pull request was authored, only set when the request includes
+    /// first commits.
+    pub first_commit_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub fixed_at: Option<DateTime<Utc>>,
     pub fixed_url: Option<String>,
+    /// Whether the deployment is linked to a failure that no later deployment or closed issue has fixed yet.
+    pub open_failure: bool,
     pub deploy_url: String,
     pub issue_url: Option<String>,
     pub change_url: String,
+    /// Whether the deployed change was a hotfix, see `HotfixMatcher`.
+    pub hotfix: bool,
+    /// How long the deployment's workflow run took.
+    pub deploy_duration_seconds: Option<i64>,
+    /// Deprecated in favor of `cycle_time`, the same value in hours, kept until existing clients have
+    /// migrated.
+    pub total_cycle_time: Option<f32>,
+    /// The time from the first commit, or the merge when the first commit isn't known, to a successful
+    /// deployment.
+    pub cycle_time: Option<DurationValue>,
+    /// The time from merge to deployment.
+    pub lead_time: Option<DurationValue>,
+    /// The time from failure to fix.
+    pub time_to_restore: Option<DurationValue>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct DeploymentRecord {
+    pub repository: String,
+    pub team: String,
+    pub environment: String,
+    pub sha: String,
+    pub status: bool,
+    pub created_at: DateTime<Utc>,
+    pub deploy_url: String,
+    pub change_url: String,
+}
+
+/// Everything linked to one commit, from its merge to each deployment and the failures they caused.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ChangeResponse {
+    pub sha: String,
+    pub repository: String,
+    pub team: String,
+    pub title: Option<String>,
+    pub user: Option<String>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub first_commit_at: Option<DateTime<Utc>>,
+    pub change_url: String,
+    pub hotfix: bool,
+    /// The time from merge to the first successful deployment.
+    pub lead_time: Option<DurationValue>,
+    /// Deprecated in favor of `cycle_time`, the same value in hours.
     pub total_cycle_time: Option<f32>,
+    /// The time from the first commit, or the merge, to the first successful deployment.
+    pub cycle_time: Option<DurationValue>,
+    /// Every deployment of the commit, to every environment, oldest first.
+    pub deployments: Vec<ResponseRecord>,
+    pub incidents: Vec<FailureRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// A deployment that has started but not concluded yet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ActiveDeployment {
+    pub repository: RepoName,
+    pub team: TeamName,
+    pub environment: String,
+    pub sha: Sha,
+    /// The latest status, `queued`, `pending` or `in_progress`.
+    pub state: DeploymentState,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deploy_url: String,
+    pub change_url: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ActiveDeploymentsResponse {
+    pub deployments: Vec<ActiveDeployment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FailureRecord {
+    pub repository: String,
+    pub team: String,
+    pub environment: String,
+    pub sha: String,
+    pub failed_at: DateTime<Utc>,
+    pub fixed_at: Option<DateTime<Utc>>,
+    /// Whether the failure is still unresolved, with no later deployment or closed issue fixing it.
+    pub open: bool,
+    pub fixed_url: Option<String>,
+    pub deploy_url: String,
+    pub issue_url: Option<String>,
+    pub time_to_restore: Option<DurationValue>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct IncidentsResponse {
+    /// The number of incidents still open.
+    pub open: usize,
+    pub incidents: Vec<FailureRecord>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LeadTimeRecord {
+    pub repository: String,
+    pub team: String,
+    pub sha: String,
+    pub title: Option<String>,
+    pub user: Option<String>,
+    pub merged_at: DateTime<Utc>,
+    pub deployed_at: DateTime<Utc>,
+    pub lead_time: DurationValue,
+    pub change_url: String,
+}
+
+impl From<&ResponseRecord> for DeploymentRecord {
+    fn from(record: &ResponseRecord) -> Self {
+        DeploymentRecord {
+            repository: record.repository.clone(),
+            team: record.team.clone(),
+            environment: record.environment.clone(),
+            sha: record.sha.clone(),
+            status: record.status,
+            created_at: record.created_at,
+            deploy_url: record.deploy_url.clone(),
+            change_url: record.change_url.clone(),
+        }
+    }
+}
+
+impl ResponseRecord {
+    /// Returns the keys a record is serialized with, which the `fields` of a data request select from.
+    pub fn field_names() -> Vec<String> {
+        match serde_json::to_value(ResponseRecord::default()) {
+            Ok(serde_json::Value::Object(record)) => record.keys().cloned().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns the failure portion of this record, if the deployment is linked to a failure.
+    pub fn failure(&self) -> Option<FailureRecord> {
+        self.failed_at.map(|failed_at| FailureRecord {
+            repository: self.repository.clone(),
+            team: self.team.clone(),
+            environment: self.environment.clone(),
+            sha: self.sha.clone(),
+            failed_at,
+            fixed_at: self.fixed_at,
+            open: self.fixed_at.is_none(),
+            fixed_url: self.fixed_url.clone(),
+            deploy_url: self.deploy_url.clone(),
+            issue_url: self.issue_url.clone(),
+            time_to_restore: self.time_to_restore.clone(),
+        })
+    }
+
+    /// Returns the merge/deploy pair used for lead time, if the deployment is linked to a merge.
+    pub fn lead_time(&self) -> Option<LeadTimeRecord> {
+        self.merged_at.map(|merged_at| LeadTimeRecord {
+            repository: self.repository.clone(),
+            team: self.team.clone(),
+            sha: self.sha.clone(),
+            title: self.title.clone(),
+            user: self.user.clone(),
+            merged_at,
+            deployed_at: self.created_at,
+            lead_time: DurationValue::from(self.created_at - merged_at),
+            change_url: self.change_url.clone(),
+        })
+    }
 }
 
 #[derive(Serialize, Debug, Default, Clone)]
 pub struct TeamsResponse {
     pub teams: Vec<String>,
 }
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct TeamParent {
+    pub id: u64,
+    pub name: String,
+    pub slug: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct TeamRecord {
+    pub id: u64,
+    pub name: String,
+    pub slug: String,
+    pub parent: Option<TeamParent>,
+    pub children: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct TeamsResponseV2 {
+    pub teams: Vec<TeamRecord>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct RepositoryRecord {
+    pub name: String,
+    pub archived: bool,
+    pub language: Option<String>,
+    pub topics: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct RepositoriesResponse {
+    pub repositories: Vec<RepositoryRecord>,
+}
+
+/// A window's metrics alongside their health index, see `ScoringModel::score`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScoredSummary {
+    #[serde(flatten)]
+    pub metrics: MetricsSummary,
+    pub score: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TeamScore {
+    pub team: String,
+    pub score: Option<f64>,
+    pub metrics: MetricsSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<TargetReport>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScorecardResponse {
+    pub teams: Vec<TeamScore>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SummaryResponse {
+    pub metrics: MetricsSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<TargetReport>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ThroughputBucket {
+    pub start: DateTime<Utc>,
+    pub opened: usize,
+    pub merged: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PrThroughputSeries {
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub opened: usize,
+    pub merged: usize,
+    pub buckets: Vec<ThroughputBucket>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PrThroughputResponse {
+    pub bucket: BucketSize,
+    pub series: Vec<PrThroughputSeries>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ReviewsResponse {
+    pub summary: ReviewSummary,
+    pub records: Vec<ReviewRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct OrgRollupResponse {
+    pub teams: usize,
+    pub repositories: usize,
+    pub metrics: MetricsSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<TargetReport>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SelectionSummary {
+    pub team: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub metrics: MetricsSummary,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CompareResponse {
+    pub baseline: SelectionSummary,
+    pub comparison: SelectionSummary,
+    pub changes: MetricsTrend,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ForecastResponse {
+    pub weeks: usize,
+    pub deployment_frequency: MetricForecast,
+    pub lead_time_hours: MetricForecast,
+    pub change_failure_rate: MetricForecast,
+    pub mttr_hours: MetricForecast,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TrendsResponse {
+    pub current: ScoredSummary,
+    pub previous: ScoredSummary,
+    pub previous_start: DateTime<Utc>,
+    pub previous_end: DateTime<Utc>,
+    pub changes: MetricsTrend,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CohortSummary {
+    pub cohort: String,
+    pub repositories: usize,
+    pub metrics: MetricsSummary,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CohortsResponse {
+    pub cohorts: Vec<CohortSummary>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FrequencyBucket {
+    pub start: DateTime<Utc>,
+    pub deployments: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct DeploymentFrequencySeries {
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub buckets: Vec<FrequencyBucket>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct DeploymentFrequencyResponse {
+    pub bucket: BucketSize,
+    pub series: Vec<DeploymentFrequencySeries>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ChangeFailureBucket {
+    pub start: DateTime<Utc>,
+    pub deployments: usize,
+    pub failed_deployments: usize,
+    pub change_failure_rate: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ChangeFailureRateSeries {
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub deployments: usize,
+    pub failed_deployments: usize,
+    pub change_failure_rate: Option<f64>,
+    pub buckets: Vec<ChangeFailureBucket>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ChangeFailureRateResponse {
+    pub bucket: BucketSize,
+    pub series: Vec<ChangeFailureRateSeries>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LeadTimeBucket {
+    pub start: DateTime<Utc>,
+    pub changes: usize,
+    pub lead_time_hours: Option<f64>,
+    pub lead_time: Option<DurationValue>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LeadTimeSeries {
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub changes: usize,
+    pub lead_time_hours: Option<f64>,
+    pub lead_time: Option<DurationValue>,
+    pub buckets: Vec<LeadTimeBucket>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LeadTimeResponse {
+    pub bucket: BucketSize,
+    pub series: Vec<LeadTimeSeries>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MttrSeries {
+    pub team: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(flatten)]
+    pub summary: RestoreSummary,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MttrResponse {
+    pub series: Vec<MttrSeries>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_sections_from_linked_record() {
+        let created_at = Utc::now();
+
+        let record = ResponseRecord {
+            repository: "repo-a".to_string(),
+            sha: "abcdef".to_string(),
+            created_at,
+            failed_at: Some(created_at),
+            merged_at: Some(created_at - Duration::hours(2)),
+            ..Default::default()
+        };
+
+        let failure = record.failure().unwrap();
+        let lead_time = record.lead_time().unwrap();
+
+        assert_eq!(failure.failed_at, created_at);
+        assert_eq!(failure.fixed_at, None);
+        assert!(failure.open);
+        assert_eq!(lead_time.deployed_at, created_at);
+        assert_eq!(lead_time.merged_at, created_at - Duration::hours(2));
+        assert_eq!(lead_time.lead_time.iso8601, "PT2H");
+        assert_eq!(DeploymentRecord::from(&record).sha, "abcdef");
+    }
+
+    #[test]
+    fn test_sections_from_unlinked_record() {
+        let record = ResponseRecord {
+            repository: "repo-a".to_string(),
+            sha: "abcdef".to_string(),
+            status: true,
+            ..Default::default()
+        };
+
+        assert_eq!(record.failure(), None);
+        assert_eq!(record.lead_time(), None);
+    }
+
+    #[test]
+    fn test_scored_summary_is_flat() {
+        let summary = ScoredSummary {
+            metrics: MetricsSummary {
+                deployments: 3,
+                ..Default::default()
+            },
+            score: Some(75.0),
+        };
+
+        let value = serde_json::to_value(summary).unwrap();
+
+        assert_eq!(value["deployments"], 3);
+        assert_eq!(value["score"], 75.0);
+    }
+}
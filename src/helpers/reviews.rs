@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::{
+    duration::DurationValue,
+    loki::QueryResponse,
+    metrics::{hours_between, median},
+};
+
+/// How long a pull request waited for its first review and its approval.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ReviewRecord {
+    pub repository: String,
+    pub team: String,
+    pub number: u32,
+    pub title: String,
+    pub user: String,
+    pub url: Option<String>,
+    pub opened_at: DateTime<Utc>,
+    pub first_review_at: Option<DateTime<Utc>>,
+    pub approved_at: Option<DateTime<Utc>>,
+    /// The time from opening the pull request to its first review.
+    pub time_to_first_review: Option<DurationValue>,
+    /// The time from opening the pull request to its first approval.
+    pub time_to_approval: Option<DurationValue>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ReviewSummary {
+    pub pull_requests: usize,
+    pub reviewed: usize,
+    pub approved: usize,
+    /// The median time from opening a pull request to its first review, over the reviewed pull requests.
+    pub time_to_first_review: Option<DurationValue>,
+    /// The median time from opening a pull request to its approval, over the approved pull requests.
+    pub time_to_approval: Option<DurationValue>,
+}
+
+/// Groups review events by pull request, keeping the earliest review and approval of each.
+///
+/// Reviews by the pull request's own author, e.g. replies to review comments, don't count as reviews.
+/// Events without the pull request's number, creation time or review submission time are skipped.
+pub fn link_reviews(data: QueryResponse) -> Vec<ReviewRecord> {
+    let mut records: HashMap<(String, u32), ReviewRecord> = HashMap::new();
+
+    for result in data.data.result {
+        for value in result.values {
+            let (Some(pr), Some(review)) = (value.json_data.pull_request, value.json_data.review)
+            else {
+                continue;
+            };
+
+            let (Some(number), Some(opened_at), Some(submitted_at)) =
+                (pr.number, pr.created_at, review.submitted_at)
+            else {
+                continue;
+            };
+
+            if review
+                .user
+                .is_some_and(|reviewer| reviewer.login == pr.user.login)
+            {
+                continue;
+            }
+
+            let record = records
+                .entry((result.stream.vcs_repository_name.clone(), number))
+                .or_insert_with(|| ReviewRecord {
+                    repository: result.stream.vcs_repository_name.clone(),
+                    team: result.stream.team_name.clone(),
+                    number,
+                    title: pr.title.clone(),
+                    user: pr.user.login.clone(),
+                    url: pr.html_url.clone(),
+                    opened_at,
+                    ..Default::default()
+                });
+
+            record.first_review_at = Some(
+                record
+                    .first_review_at
+                    .map_or(submitted_at, |at| at.min(submitted_at)),
+            );
+
+            if review.state.eq_ignore_ascii_case("approved") {
+                record.approved_at = Some(
+                    record
+                        .approved_at
+                        .map_or(submitted_at, |at| at.min(submitted_at)),
+                );
+            }
+        }
+    }
+
+    let mut records: Vec<ReviewRecord> = records
+        .into_values()
+        .map(|mut record| {
+            record.time_to_first_review = record
+                .first_review_at
+                .map(|at| DurationValue::from(at - record.opened_at));
+            record.time_to_approval = record
+                .approved_at
+                .map(|at| DurationValue::from(at - record.opened_at));
+            record
+        })
+        .collect();
+
+    records.sort_by(|l, r| {
+        (l.opened_at, &l.repository, l.number).cmp(&(r.opened_at, &r.repository, r.number))
+    });
+
+    records
+}
+
+pub fn summarize_reviews(records: &[ReviewRecord]) -> ReviewSummary {
+    let first_reviews: Vec<f64> = records
+        .iter()
+        .filter_map(|record| {
+            record
+                .first_review_at
+                .map(|at| hours_between(record.opened_at, at))
+        })
+        .collect();
+
+    let approvals: Vec<f64> = records
+        .iter()
+        .filter_map(|record| {
+            record
+                .approved_at
+                .map(|at| hours_between(record.opened_at, at))
+        })
+        .collect();
+
+    ReviewSummary {
+        pull_requests: records.len(),
+        reviewed: first_reviews.len(),
+        approved: approvals.len(),
+        time_to_first_review: median(first_reviews).map(DurationValue::from_hours),
+        time_to_approval: median(approvals).map(DurationValue::from_hours),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(number: u32, reviewer: &str, state: &str, submitted_at: &str) -> serde_json::Value {
+        let payload = serde_json::json!({
+            "action": "submitted",
+            "review": {"state": state, "submitted_at": submitted_at, "user": {"login": reviewer}},
+            "pull_request": {
+                "title": "Change",
+                "number": number,
+                "created_at": "2024-09-09T08:00:00Z",
+                "user": {"login": "author"},
+                "merge_commit_sha": null,
+            },
+        });
+
+        serde_json::json!(["1725900000000000000", payload.to_string()])
+    }
+
+    fn review_data() -> QueryResponse {
+        serde_json::from_value(serde_json::json!({"data": {"result": [{
+            "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a"},
+            "values": [
+                review(1, "author", "commented", "2024-09-09T08:30:00Z"),
+                review(1, "reviewer", "approved", "2024-09-09T14:00:00Z"),
+                review(1, "reviewer", "changes_requested", "2024-09-09T10:00:00Z"),
+                review(2, "reviewer", "commented", "2024-09-09T12:00:00Z"),
+            ],
+        }]}}))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_link_reviews() {
+        let records = link_reviews(review_data());
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].number, 1);
+        assert_eq!(
+            records[0].time_to_first_review,
+            Some(DurationValue::from_seconds(2 * 3600))
+        );
+        assert_eq!(
+            records[0].time_to_approval,
+            Some(DurationValue::from_seconds(6 * 3600))
+        );
+        assert_eq!(records[1].approved_at, None);
+        assert_eq!(records[1].time_to_approval, None);
+    }
+
+    #[test]
+    fn test_summarize_reviews() {
+        let summary = summarize_reviews(&link_reviews(review_data()));
+
+        assert_eq!(summary.pull_requests, 2);
+        assert_eq!(summary.reviewed, 2);
+        assert_eq!(summary.approved, 1);
+        assert_eq!(
+            summary.time_to_first_review,
+            Some(DurationValue::from_seconds(3 * 3600))
+        );
+        assert_eq!(
+            summary.time_to_approval,
+            Some(DurationValue::from_seconds(6 * 3600))
+        );
+        assert_eq!(summarize_reviews(&[]), ReviewSummary::default());
+    }
+}
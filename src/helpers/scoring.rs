@@ -0,0 +1,243 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::{env, fs};
+
+use super::metrics::MetricsSummary;
+
+/// A piecewise-linear curve mapping a raw metric value onto a 0–100 score.
+///
+/// Each point is a `(value, score)` pair. Values between two points are interpolated linearly and values
+/// outside the curve take the score of the nearest end point.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Curve(pub Vec<(f64, f64)>);
+
+impl Curve {
+    pub fn score(&self, value: f64) -> f64 {
+        let mut points = self.0.clone();
+        points.sort_by(|l, r| l.0.total_cmp(&r.0));
+
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 0.0,
+        };
+
+        if value <= first.0 {
+            return first.1;
+        }
+
+        if value >= last.0 {
+            return last.1;
+        }
+
+        points
+            .windows(2)
+            .find(|pair| value >= pair[0].0 && value <= pair[1].0)
+            .map(|pair| {
+                let (x0, y0) = pair[0];
+                let (x1, y1) = pair[1];
+
+                if x1 == x0 {
+                    y1
+                } else {
+                    y0 + (value - x0) / (x1 - x0) * (y1 - y0)
+                }
+            })
+            .unwrap_or(last.1)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Weights {
+    pub deployment_frequency: f64,
+    pub lead_time: f64,
+    pub change_failure_rate: f64,
+    pub mttr: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            deployment_frequency: 1.0,
+            lead_time: 1.0,
+            change_failure_rate: 1.0,
+            mttr: 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Curves {
+    pub deployment_frequency: Curve,
+    pub lead_time: Curve,
+    pub change_failure_rate: Curve,
+    pub mttr: Curve,
+}
+
+impl Default for Curves {
+    fn default() -> Self {
+        Curves {
+            deployment_frequency: Curve(vec![
+                (0.0, 0.0),
+                (1.0 / 30.0, 25.0),
+                (1.0 / 7.0, 50.0),
+                (0.5, 75.0),
+                (1.0, 100.0),
+            ]),
+            lead_time: Curve(vec![
+                (1.0, 100.0),
+                (24.0, 75.0),
+                (168.0, 50.0),
+                (720.0, 25.0),
+                (4320.0, 0.0),
+            ]),
+            change_failure_rate: Curve(vec![
+                (0.0, 100.0),
+                (5.0, 90.0),
+                (15.0, 75.0),
+                (30.0, 40.0),
+                (60.0, 0.0),
+            ]),
+            mttr: Curve(vec![
+                (1.0, 100.0),
+                (24.0, 75.0),
+                (168.0, 50.0),
+                (720.0, 0.0),
+            ]),
+        }
+    }
+}
+
+/// A weighted model combining the four DORA metrics into a single 0–100 health index.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ScoringModel {
+    pub weights: Weights,
+    pub curves: Curves,
+}
+
+impl ScoringModel {
+    /// Loads the scoring model from the JSON file named by `SCORING_CONFIG_FILE`.
+    ///
+    /// Any weight or curve missing from the file falls back to its default. If the variable is not set, the
+    /// default model is returned, which weighs all four metrics equally against the DORA performance bands.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ScoringModel`, or an error if the file cannot be read or parsed.
+    ///
+    /// # Example
+    ///
+    /// ```json
+    /// {
+    ///   "weights": { "deployment_frequency": 2, "lead_time": 1, "change_failure_rate": 1, "mttr": 1 },
+    ///   "curves": { "lead_time": [[1, 100], [24, 50], [168, 0]] }
+    /// }
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        match env::var("SCORING_CONFIG_FILE") {
+            Ok(path) => {
+                let contents = fs::read_to_string(path)?;
+                Ok(serde_json::from_str(&contents)?)
+            }
+            Err(_) => Ok(ScoringModel::default()),
+        }
+    }
+
+    /// Scores a metrics summary from 0 to 100.
+    ///
+    /// Metrics without data are left out and the remaining weights are renormalized, so a team without any
+    /// failures is not penalized for having no MTTR. Returns `None` if no metric could be scored.
+    pub fn score(&self, summary: &MetricsSummary) -> Option<f64> {
+        let scored = [
+            (
+                self.weights.deployment_frequency,
+                (summary.deployments > 0).then_some(summary.deployment_frequency),
+                &self.curves.deployment_frequency,
+            ),
+            (
+                self.weights.lead_time,
+                summary.lead_time_hours,
+                &self.curves.lead_time,
+            ),
+            (
+                self.weights.change_failure_rate,
+                summary.change_failure_rate,
+                &self.curves.change_failure_rate,
+            ),
+            (self.weights.mttr, summary.mttr_hours, &self.curves.mttr),
+        ];
+
+        let (total, weights) = scored
+            .iter()
+            .filter_map(|(weight, value, curve)| value.map(|v| (*weight, curve.score(v))))
+            .fold((0.0, 0.0), |(total, weights), (weight, score)| {
+                (total + weight * score, weights + weight)
+            });
+
+        if weights <= 0.0 {
+            return None;
+        }
+
+        Some((total / weights).clamp(0.0, 100.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_interpolates_between_points() {
+        let curve = Curve(vec![(0.0, 100.0), (10.0, 0.0)]);
+
+        assert_eq!(curve.score(5.0), 50.0);
+        assert_eq!(curve.score(-1.0), 100.0);
+        assert_eq!(curve.score(20.0), 0.0);
+    }
+
+    #[test]
+    fn test_curve_empty() {
+        assert_eq!(Curve(vec![]).score(5.0), 0.0);
+    }
+
+    #[test]
+    fn test_score_renormalizes_missing_metrics() {
+        let model = ScoringModel::default();
+
+        let summary = MetricsSummary {
+            deployments: 30,
+            deployment_frequency: 1.0,
+            lead_time_hours: Some(1.0),
+            change_failure_rate: Some(0.0),
+            mttr_hours: None,
+            ..Default::default()
+        };
+
+        assert_eq!(model.score(&summary), Some(100.0));
+    }
+
+    #[test]
+    fn test_score_without_data() {
+        let model = ScoringModel::default();
+
+        assert_eq!(model.score(&MetricsSummary::default()), None);
+    }
+
+    #[test]
+    fn test_score_from_partial_config() {
+        let model: ScoringModel =
+            serde_json::from_str(r#"{ "weights": { "deployment_frequency": 0 } }"#).unwrap();
+
+        let summary = MetricsSummary {
+            deployments: 1,
+            deployment_frequency: 0.0,
+            change_failure_rate: Some(15.0),
+            ..Default::default()
+        };
+
+        assert_eq!(model.curves, Curves::default());
+        assert_eq!(model.score(&summary), Some(75.0));
+    }
+}
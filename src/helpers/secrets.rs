@@ -0,0 +1,241 @@
+//! Secrets kept out of the environment, read from files mounted by Docker or Kubernetes, or fetched from an
+//! external secret manager at startup.
+//!
+//! A secret setting holds either the secret itself or a reference to it:
+//!
+//! * `vault:<path>#<key>` - The key of a HashiCorp Vault KV secret, e.g. `vault:secret/data/dora#github_token`.
+//! * `aws-sm:<secret-id>` - An AWS Secrets Manager secret, or with `#<key>` a key of a JSON secret. Requires
+//!   the `aws-secrets-manager` feature.
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use reqwest::Url;
+use serde_json::Value;
+use std::{env, fs, path::Path};
+
+use super::http;
+
+/// Fetches secrets from an external secret manager.
+pub trait SecretProvider: Send + Sync {
+    /// Fetches the secret a reference names, without the reference's scheme, e.g. `secret/data/dora#token`.
+    fn fetch<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+const VAULT: &str = "vault:";
+const AWS_SECRETS_MANAGER: &str = "aws-sm:";
+
+/// Resolves a secret setting, fetching it from its secret manager when it is a reference.
+///
+/// # Errors
+///
+/// Returns an error naming the setting when the secret can't be fetched.
+pub async fn resolve(name: &str, value: &str) -> Result<String> {
+    fetch(value)
+        .await
+        .map_err(|e| anyhow!(format!("{}: {}", e, name)))
+}
+
+async fn fetch(value: &str) -> Result<String> {
+    let (provider, reference): (Box<dyn SecretProvider>, &str) =
+        if let Some(reference) = value.strip_prefix(VAULT) {
+            (Box::new(VaultProvider::from_env()?), reference)
+        } else if let Some(reference) = value.strip_prefix(AWS_SECRETS_MANAGER) {
+            (aws_secrets_manager().await?, reference)
+        } else {
+            return Ok(value.to_string());
+        };
+
+    provider.fetch(reference).await
+}
+
+/// Reads a secret from a file, without the trailing newline most files end with.
+///
+/// # Errors
+///
+/// Returns an error naming the file when it can't be read.
+pub fn read_file(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow!(format!("Reading {} Failed: {}", path.display(), e)))?;
+
+    Ok(contents.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Splits a reference into the secret and the key within it, e.g. `secret/data/dora` and `github_token`.
+fn split_key(reference: &str) -> (&str, Option<&str>) {
+    match reference.rsplit_once('#') {
+        Some((secret, key)) if !key.is_empty() => (secret, Some(key)),
+        _ => (reference, None),
+    }
+}
+
+/// Fetches secrets from the KV secrets engine of a HashiCorp Vault, version 1 or 2.
+#[derive(Debug, Clone)]
+pub struct VaultProvider {
+    address: Url,
+    token: String,
+    namespace: Option<String>,
+}
+
+impl VaultProvider {
+    /// Reads the Vault connection from the environment.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `VAULT_ADDR` - The address of the Vault, e.g. `https://vault.example.com:8200`.
+    /// * `VAULT_TOKEN` or `VAULT_TOKEN_FILE` - The token, or a file holding the token, secrets are read with.
+    /// * `VAULT_NAMESPACE` - The Vault Enterprise namespace of the secrets, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the address or token isn't set or is invalid.
+    pub fn from_env() -> Result<Self> {
+        let address = env::var("VAULT_ADDR")
+            .map_err(|_| anyhow!("VAULT_ADDR is required for vault: secrets"))?;
+
+        let token = match (env::var("VAULT_TOKEN"), env::var("VAULT_TOKEN_FILE")) {
+            (Ok(token), _) => token,
+            (Err(_), Ok(path)) => read_file(Path::new(&path))?,
+            _ => return Err(anyhow!("VAULT_TOKEN is required for vault: secrets")),
+        };
+
+        Ok(VaultProvider {
+            address: Url::parse(&address).map_err(|e| anyhow!(format!("{}: VAULT_ADDR", e)))?,
+            token,
+            namespace: env::var("VAULT_NAMESPACE").ok(),
+        })
+    }
+
+    async fn read(&self, reference: &str) -> Result<String> {
+        let (path, Some(key)) = split_key(reference) else {
+            return Err(anyhow!(
+                "Vault references have to name a key, e.g. path#key"
+            ));
+        };
+
+        let url = self
+            .address
+            .join(&format!("v1/{}", path.trim_start_matches('/')))?;
+        let mut request = http::client().get(url).header("X-Vault-Token", &self.token);
+
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(format!(
+                "Vault Responded with status: {:?}",
+                response.status()
+            )));
+        }
+
+        kv_value(&response.json().await?, key)
+            .ok_or_else(|| anyhow!(format!("Vault secret {} has no key {}", path, key)))
+    }
+}
+
+impl SecretProvider for VaultProvider {
+    fn fetch<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.read(reference))
+    }
+}
+
+/// Returns a key of a KV secret. Version 2 nests the secret under `data.data`, beside its `metadata`.
+fn kv_value(body: &Value, key: &str) -> Option<String> {
+    let data = &body["data"];
+
+    let data = match data.get("metadata") {
+        Some(_) => &data["data"],
+        None => data,
+    };
+
+    data.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+async fn aws_secrets_manager() -> Result<Box<dyn SecretProvider>> {
+    Ok(Box::new(AwsSecretsManager::from_env().await))
+}
+
+#[cfg(not(feature = "aws-secrets-manager"))]
+async fn aws_secrets_manager() -> Result<Box<dyn SecretProvider>> {
+    Err(anyhow!(
+        "aws-sm: secrets require the aws-secrets-manager feature"
+    ))
+}
+
+/// Fetches secrets from AWS Secrets Manager, with the credentials and region of the standard AWS environment
+/// variables, profile or instance role.
+#[cfg(feature = "aws-secrets-manager")]
+#[derive(Debug, Clone)]
+pub struct AwsSecretsManager {
+    client: aws_sdk_secretsmanager::Client,
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+impl AwsSecretsManager {
+    pub async fn from_env() -> Self {
+        let config = aws_config::load_from_env().await;
+
+        AwsSecretsManager {
+            client: aws_sdk_secretsmanager::Client::new(&config),
+        }
+    }
+
+    async fn read(&self, reference: &str) -> Result<String> {
+        let (secret_id, key) = split_key(reference);
+
+        let secret = self
+            .client
+            .get_secret_value()
+            .secret_id(secret_id)
+            .send()
+            .await
+            .map_err(|e| anyhow!(format!("AWS Secrets Manager Failed: {}", e)))?
+            .secret_string
+            .ok_or_else(|| anyhow!(format!("AWS secret {} is not a string", secret_id)))?;
+
+        match key {
+            None => Ok(secret),
+            Some(key) => serde_json::from_str::<Value>(&secret)?
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!(format!("AWS secret {} has no key {}", secret_id, key))),
+        }
+    }
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+impl SecretProvider for AwsSecretsManager {
+    fn fetch<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.read(reference))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_split_key() {
+        assert_eq!(
+            split_key("secret/data/dora#github_token"),
+            ("secret/data/dora", Some("github_token"))
+        );
+        assert_eq!(split_key("dora/loki"), ("dora/loki", None));
+        assert_eq!(split_key("dora/loki#"), ("dora/loki#", None));
+    }
+
+    #[test]
+    fn test_kv_value() {
+        let v1 = json!({"data": {"token": "s3cr3t"}});
+        let v2 = json!({"data": {"data": {"token": "s3cr3t"}, "metadata": {"version": 1}}});
+
+        assert_eq!(kv_value(&v1, "token"), Some("s3cr3t".to_string()));
+        assert_eq!(kv_value(&v2, "token"), Some("s3cr3t".to_string()));
+        assert_eq!(kv_value(&v2, "other"), None);
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fs};
+
+use super::metrics::MetricsSummary;
+
+/// The target for each DORA metric. Deployment frequency is a minimum, the other metrics are maximums.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub struct Targets {
+    pub deployment_frequency: Option<f64>,
+    pub lead_time_hours: Option<f64>,
+    pub change_failure_rate: Option<f64>,
+    pub mttr_hours: Option<f64>,
+}
+
+impl Targets {
+    /// Fills the targets missing here from another set of targets.
+    fn or(self, fallback: Targets) -> Targets {
+        Targets {
+            deployment_frequency: self.deployment_frequency.or(fallback.deployment_frequency),
+            lead_time_hours: self.lead_time_hours.or(fallback.lead_time_hours),
+            change_failure_rate: self.change_failure_rate.or(fallback.change_failure_rate),
+            mttr_hours: self.mttr_hours.or(fallback.mttr_hours),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Targets::default()
+    }
+}
+
+/// Service level targets for the organization, with overrides per team.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TargetsConfig {
+    pub default: Targets,
+    pub teams: HashMap<String, Targets>,
+}
+
+impl TargetsConfig {
+    /// Loads the targets from the JSON file named by `TARGETS_CONFIG_FILE`.
+    ///
+    /// A team's targets fall back to the `default` targets for any metric they don't set. If the variable
+    /// is not set, no targets are configured.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TargetsConfig`, or an error if the file cannot be read or parsed.
+    ///
+    /// # Example
+    ///
+    /// ```json
+    /// {
+    ///   "default": { "lead_time_hours": 24, "change_failure_rate": 15 },
+    ///   "teams": { "team-a": { "deployment_frequency": 1, "mttr_hours": 4 } }
+    /// }
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        match env::var("TARGETS_CONFIG_FILE") {
+            Ok(path) => {
+                let contents = fs::read_to_string(path)?;
+                Ok(serde_json::from_str(&contents)?)
+            }
+            Err(_) => Ok(TargetsConfig::default()),
+        }
+    }
+
+    /// Returns the targets for a team, or the default targets for organization wide metrics.
+    pub fn for_team(&self, team: Option<&str>) -> Targets {
+        team.and_then(|team| self.teams.get(team))
+            .map(|targets| targets.or(self.default))
+            .unwrap_or(self.default)
+    }
+
+    /// Compares metrics to a team's targets, returning `None` when the team has no targets.
+    pub fn evaluate(&self, team: Option<&str>, summary: &MetricsSummary) -> Option<TargetReport> {
+        let targets = self.for_team(team);
+
+        if targets.is_empty() {
+            return None;
+        }
+
+        Some(TargetReport {
+            deployment_frequency: targets.deployment_frequency.map(|target| {
+                TargetStatus::minimum(
+                    target,
+                    (summary.deployments > 0).then_some(summary.deployment_frequency),
+                )
+            }),
+            lead_time_hours: targets
+                .lead_time_hours
+                .map(|target| TargetStatus::maximum(target, summary.lead_time_hours)),
+            change_failure_rate: targets
+                .change_failure_rate
+                .map(|target| TargetStatus::maximum(target, summary.change_failure_rate)),
+            mttr_hours: targets
+                .mttr_hours
+                .map(|target| TargetStatus::maximum(target, summary.mttr_hours)),
+        })
+    }
+}
+
+/// A metric compared to its target. Metrics without data are not considered breached.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct TargetStatus {
+    pub target: f64,
+    pub actual: Option<f64>,
+    pub breached: bool,
+}
+
+impl TargetStatus {
+    fn minimum(target: f64, actual: Option<f64>) -> Self {
+        TargetStatus {
+            target,
+            actual,
+            breached: actual.is_some_and(|actual| actual < target),
+        }
+    }
+
+    fn maximum(target: f64, actual: Option<f64>) -> Self {
+        TargetStatus {
+            target,
+            actual,
+            breached: actual.is_some_and(|actual| actual > target),
+        }
+    }
+}
+
+/// The status of each metric that has a target.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct TargetReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployment_frequency: Option<TargetStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lead_time_hours: Option<TargetStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_failure_rate: Option<TargetStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mttr_hours: Option<TargetStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> TargetsConfig {
+        serde_json::from_str(
+            r#"{
+                "default": { "lead_time_hours": 24, "change_failure_rate": 15 },
+                "teams": { "team-a": { "deployment_frequency": 1, "change_failure_rate": 5 } }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_team_targets_fall_back_to_default() {
+        let targets = config().for_team(Some("team-a"));
+
+        assert_eq!(targets.deployment_frequency, Some(1.0));
+        assert_eq!(targets.lead_time_hours, Some(24.0));
+        assert_eq!(targets.change_failure_rate, Some(5.0));
+        assert_eq!(config().for_team(Some("team-b")), config().default);
+    }
+
+    #[test]
+    fn test_evaluate_targets() {
+        let summary = MetricsSummary {
+            deployments: 10,
+            deployment_frequency: 0.5,
+            lead_time_hours: Some(12.0),
+            change_failure_rate: Some(10.0),
+            ..Default::default()
+        };
+
+        let report = config().evaluate(Some("team-a"), &summary).unwrap();
+
+        assert!(report.deployment_frequency.unwrap().breached);
+        assert!(!report.lead_time_hours.unwrap().breached);
+        assert!(report.change_failure_rate.unwrap().breached);
+        assert_eq!(report.mttr_hours, None);
+        assert_eq!(TargetsConfig::default().evaluate(None, &summary), None);
+    }
+}
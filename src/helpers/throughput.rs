@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use dora_event_vendor::ValueItem;
+use std::collections::{BTreeMap, HashSet};
+
+use super::{
+    buckets::count_times,
+    loki::{QueryResponse, Stream},
+    metrics::Grouping,
+    response::{PrThroughputSeries, ThroughputBucket},
+};
+
+/// A pull request being opened or merged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEntry {
+    pub repository: String,
+    pub team: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Collects pull request events once per repository and pull request number, at the time `time` reads from
+/// each event. Events without a number are all kept, and events without a time are skipped.
+fn collect_changes<F>(data: QueryResponse, time: F) -> Vec<ChangeEntry>
+where
+    F: Fn(&Stream, &ValueItem) -> Option<DateTime<Utc>>,
+{
+    let mut seen = HashSet::new();
+    let mut changes = vec![];
+
+    for result in data.data.result {
+        for value in result.values {
+            let Some(at) = time(&result.stream, &value) else {
+                continue;
+            };
+
+            let number = value
+                .json_data
+                .pull_request
+                .as_ref()
+                .and_then(|pr| pr.number);
+
+            if let Some(number) = number {
+                if !seen.insert((result.stream.vcs_repository_name.clone(), number)) {
+                    continue;
+                }
+            }
+
+            changes.push(ChangeEntry {
+                repository: result.stream.vcs_repository_name.clone(),
+                team: result.stream.team_name.clone(),
+                at,
+            });
+        }
+    }
+
+    changes
+}
+
+/// Collects the pull requests opened, at the time they were created.
+pub fn opened_changes(data: QueryResponse) -> Vec<ChangeEntry> {
+    collect_changes(data, |_, value| {
+        value
+            .json_data
+            .pull_request
+            .as_ref()
+            .and_then(|pr| pr.created_at)
+            .or(Some(value.timestamp))
+    })
+}
+
+/// Collects the pull requests merged, at the time they were merged.
+pub fn merged_changes(data: QueryResponse) -> Vec<ChangeEntry> {
+    collect_changes(data, |stream, _| stream.merged_at)
+}
+
+/// The times pull requests were opened and merged in one team or repository.
+type ChangeTimes = (Vec<DateTime<Utc>>, Vec<DateTime<Utc>>);
+
+/// Counts the pull requests opened and merged per team or repository in each bucket.
+pub fn throughput(
+    starts: &[DateTime<Utc>],
+    opened: &[ChangeEntry],
+    merged: &[ChangeEntry],
+    grouping: Grouping,
+) -> Vec<PrThroughputSeries> {
+    let key = |change: &ChangeEntry| match grouping {
+        Grouping::Team => (change.team.clone(), None),
+        Grouping::Repository => (change.team.clone(), Some(change.repository.clone())),
+    };
+
+    let mut groups: BTreeMap<(String, Option<String>), ChangeTimes> = BTreeMap::new();
+
+    for change in opened {
+        groups.entry(key(change)).or_default().0.push(change.at);
+    }
+
+    for change in merged {
+        groups.entry(key(change)).or_default().1.push(change.at);
+    }
+
+    groups
+        .into_iter()
+        .map(|((team, repository), (opened, merged))| {
+            let buckets: Vec<ThroughputBucket> = starts
+                .iter()
+                .zip(count_times(starts, &opened))
+                .zip(count_times(starts, &merged))
+                .map(|((start, opened), merged)| ThroughputBucket {
+                    start: *start,
+                    opened,
+                    merged,
+                })
+                .collect();
+
+            PrThroughputSeries {
+                team,
+                repository,
+                opened: buckets.iter().map(|bucket| bucket.opened).sum(),
+                merged: buckets.iter().map(|bucket| bucket.merged).sum(),
+                buckets,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::buckets::BucketSize;
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn change(repository: &str, at: &str) -> ChangeEntry {
+        ChangeEntry {
+            repository: repository.to_string(),
+            team: "team-a".to_string(),
+            at: time(at),
+        }
+    }
+
+    #[test]
+    fn test_opened_changes_are_counted_once() {
+        let opened = |number: u32, created_at: &str| {
+            let payload = serde_json::json!({"pull_request": {
+                "title": "Change",
+                "user": {"login": "octocat"},
+                "number": number,
+                "created_at": created_at,
+            }});
+
+            serde_json::json!(["1725900000000000000", payload.to_string()])
+        };
+        let data: QueryResponse = serde_json::from_value(serde_json::json!({"data": {"result": [{
+            "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a"},
+            "values": [
+                opened(1, "2024-09-02T10:00:00Z"),
+                opened(1, "2024-09-02T10:00:00Z"),
+                opened(2, "2024-09-03T10:00:00Z"),
+            ],
+        }]}}))
+        .unwrap();
+
+        let changes = opened_changes(data);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0], change("repo-a", "2024-09-02T10:00:00Z"));
+    }
+
+    #[test]
+    fn test_throughput() {
+        let starts =
+            BucketSize::Week.starts(time("2024-09-02T00:00:00Z"), time("2024-09-16T00:00:00Z"));
+        let opened = vec![
+            change("repo-a", "2024-09-02T10:00:00Z"),
+            change("repo-a", "2024-09-10T10:00:00Z"),
+            change("repo-b", "2024-09-03T10:00:00Z"),
+        ];
+        let merged = vec![change("repo-a", "2024-09-11T10:00:00Z")];
+
+        let by_repository = throughput(&starts, &opened, &merged, Grouping::Repository);
+
+        assert_eq!(by_repository.len(), 2);
+        assert_eq!(by_repository[0].repository, Some("repo-a".to_string()));
+        assert_eq!(by_repository[0].opened, 2);
+        assert_eq!(by_repository[0].merged, 1);
+        assert_eq!(
+            by_repository[0].buckets[1],
+            ThroughputBucket {
+                start: time("2024-09-09T00:00:00Z"),
+                opened: 1,
+                merged: 1,
+            }
+        );
+
+        let by_team = throughput(&starts, &opened, &merged, Grouping::Team);
+
+        assert_eq!(by_team.len(), 1);
+        assert_eq!(by_team[0].opened, 3);
+    }
+}
@@ -0,0 +1,88 @@
+//! The OpenTelemetry server span and access log line of every request, so operators get a trace and a log
+//! line per request beside the request metrics, see `instrumentation::record_request`.
+
+use axum::{
+    extract::{MatchedPath, Request},
+    response::Response,
+};
+use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+use std::time::Duration;
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    trace::TraceLayer,
+};
+use tracing::Span;
+
+use crate::telemetry;
+
+/// The operational routes, polled by probes and scrapers, which would otherwise flood the traces and logs.
+const UNTRACED_ROUTES: [&str; 3] = ["/health", "/ready", "/metrics"];
+
+pub type AccessLogLayer = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    fn(&Request) -> Span,
+    (),
+    fn(&Response, Duration, &Span),
+>;
+
+fn is_traced(path: &str) -> bool {
+    !UNTRACED_ROUTES.contains(&path)
+}
+
+/// Returns the layer creating one OpenTelemetry server span per request, continuing the caller's trace from
+/// its `traceparent` header.
+pub fn otel_layer() -> OtelAxumLayer {
+    OtelAxumLayer::default().filter(is_traced)
+}
+
+/// Returns the layer logging each request once it is answered, with its method, matched route, request ID,
+/// status and latency. Server errors are also logged as failures.
+pub fn access_log_layer() -> AccessLogLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(request_span as fn(&Request) -> Span)
+        .on_request(())
+        .on_response(log_response as fn(&Response, Duration, &Span))
+}
+
+fn request_span(request: &Request) -> Span {
+    if !is_traced(request.uri().path()) {
+        return Span::none();
+    }
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str());
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id = %telemetry::request_id(),
+    )
+}
+
+fn log_response(response: &Response, latency: Duration, span: &Span) {
+    if span.is_none() {
+        return;
+    }
+
+    tracing::info!(
+        status = response.status().as_u16(),
+        latency_ms = latency.as_millis() as u64,
+        "Responded"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_traced() {
+        assert!(is_traced("/data"));
+        assert!(is_traced("/v1/teams"));
+        assert!(!is_traced("/health"));
+        assert!(!is_traced("/metrics"));
+    }
+}
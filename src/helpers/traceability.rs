@@ -0,0 +1,165 @@
+use super::{
+    loki::QueryResponse,
+    response::{ChangeResponse, ResponseRecord},
+};
+
+/// The repositories and environments a commit was deployed to, lowercased and without duplicates.
+pub struct DeployedTo {
+    pub repositories: Vec<String>,
+    pub environments: Vec<String>,
+}
+
+/// Finds where a commit was deployed from its deployment events, so only those repositories and
+/// environments are linked.
+pub fn deployed_to(data: &QueryResponse, sha: &str) -> DeployedTo {
+    let mut deployed = DeployedTo {
+        repositories: Vec::new(),
+        environments: Vec::new(),
+    };
+
+    for result in &data.data.result {
+        let deployed_sha = result.values.iter().any(|value| {
+            value
+                .json_data
+                .deployment
+                .as_ref()
+                .is_some_and(|deployment| deployment.sha.eq_ignore_ascii_case(sha))
+        });
+
+        if !deployed_sha {
+            continue;
+        }
+
+        if !deployed
+            .repositories
+            .contains(&result.stream.vcs_repository_name)
+        {
+            deployed
+                .repositories
+                .push(result.stream.vcs_repository_name.clone());
+        }
+
+        if let Some(environment) = &result.stream.deployment_environment_name {
+            let environment = environment.trim().to_lowercase();
+
+            if !deployed.environments.contains(&environment) {
+                deployed.environments.push(environment);
+            }
+        }
+    }
+
+    deployed
+}
+
+/// Collects the linked records of a commit into its story, from merge to every deployment and the failures
+/// they caused, or `None` when the commit wasn't deployed.
+///
+/// The lead time and total cycle time are those of the first successful deployment.
+pub fn trace_change(sha: &str, records: Vec<ResponseRecord>) -> Option<ChangeResponse> {
+    let mut deployments: Vec<ResponseRecord> = records
+        .into_iter()
+        .filter(|record| record.sha.eq_ignore_ascii_case(sha))
+        .collect();
+
+    deployments.sort_by(|l, r| (l.created_at, &l.environment).cmp(&(r.created_at, &r.environment)));
+
+    let first = deployments.first()?;
+    let first_success = deployments.iter().find(|record| record.status);
+
+    Some(ChangeResponse {
+        sha: first.sha.clone(),
+        repository: first.repository.clone(),
+        team: first.team.clone(),
+        title: first.title.clone(),
+        user: first.user.clone(),
+        merged_at: first.merged_at,
+        first_commit_at: first.first_commit_at,
+        change_url: first.change_url.clone(),
+        hotfix: first.hotfix,
+        lead_time: first_success.and_then(|record| record.lead_time.clone()),
+        total_cycle_time: first_success.and_then(|record| record.total_cycle_time),
+        cycle_time: first_success.and_then(|record| record.cycle_time.clone()),
+        incidents: deployments
+            .iter()
+            .filter_map(|record| record.failure())
+            .collect(),
+        deployments,
+        warnings: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::duration::DurationValue;
+    use chrono::{DateTime, Utc};
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn record(sha: &str, environment: &str, status: bool, created_at: &str) -> ResponseRecord {
+        ResponseRecord {
+            repository: "repo-a".to_string(),
+            team: "team-a".to_string(),
+            environment: environment.to_string(),
+            sha: sha.to_string(),
+            status,
+            merged_at: Some(time("2024-09-10T09:00:00Z")),
+            created_at: time(created_at),
+            lead_time: Some(DurationValue::from(
+                time(created_at) - time("2024-09-10T09:00:00Z"),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_trace_change() {
+        let mut failed = record("abc", "production", false, "2024-09-10T11:00:00Z");
+        failed.failed_at = Some(time("2024-09-10T11:30:00Z"));
+        failed.fixed_at = Some(time("2024-09-10T13:00:00Z"));
+
+        let records = vec![
+            failed,
+            record("def", "production", true, "2024-09-10T10:00:00Z"),
+            record("abc", "staging", true, "2024-09-10T10:00:00Z"),
+        ];
+
+        let change = trace_change("ABC", records).unwrap();
+
+        assert_eq!(change.sha, "abc");
+        assert_eq!(change.deployments.len(), 2);
+        assert_eq!(change.deployments[0].environment, "staging");
+        assert_eq!(change.lead_time, Some(DurationValue::from_seconds(3600)));
+        assert_eq!(change.incidents.len(), 1);
+        assert_eq!(
+            change.incidents[0].fixed_at,
+            Some(time("2024-09-10T13:00:00Z"))
+        );
+
+        assert!(trace_change("xyz", vec![]).is_none());
+    }
+
+    #[test]
+    fn test_deployed_to() {
+        let data: QueryResponse = serde_json::from_value(serde_json::json!({"data": {"result": [
+            {
+                "stream": {"vcs_repository_name": "repo-a", "team_name": "team-a", "deployment_environment_name": "Production"},
+                "values": [["1725962400000000000", "{\"deployment\": {\"id\": 1, \"sha\": \"abc\", \"url\": \"\", \"created_at\": \"2024-09-10T10:00:00Z\"}}"]],
+            },
+            {
+                "stream": {"vcs_repository_name": "repo-b", "team_name": "team-a", "deployment_environment_name": "staging"},
+                "values": [["1725962400000000000", "{\"deployment\": {\"id\": 2, \"sha\": \"def\", \"url\": \"\", \"created_at\": \"2024-09-10T10:00:00Z\"}}"]],
+            },
+        ]}}))
+        .unwrap();
+
+        let deployed = deployed_to(&data, "abc");
+
+        assert_eq!(deployed.repositories, vec!["repo-a"]);
+        assert_eq!(deployed.environments, vec!["production"]);
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The window the upstream statistics are reported over.
+pub const WINDOW: Duration = Duration::from_secs(15 * 60);
+
+const CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upstream {
+    Loki,
+    GitHub,
+}
+
+impl Upstream {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Upstream::Loki => "Loki",
+            Upstream::GitHub => "GitHub",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    latency: Duration,
+    ok: bool,
+}
+
+/// A fixed-size buffer of the most recent call samples to an upstream.
+#[derive(Debug)]
+struct RingBuffer {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        RingBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, sample: Sample) {
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    fn stats(&self, now: Instant, window: Duration) -> UpstreamStats {
+        let recent: Vec<&Sample> = self
+            .samples
+            .iter()
+            .filter(|sample| now.duration_since(sample.at) <= window)
+            .collect();
+
+        let mut latencies: Vec<f64> = recent
+            .iter()
+            .map(|sample| sample.latency.as_secs_f64() * 1000.0)
+            .collect();
+
+        latencies.sort_by(|l, r| l.total_cmp(r));
+
+        let errors = recent.iter().filter(|sample| !sample.ok).count();
+
+        UpstreamStats {
+            requests: recent.len(),
+            errors,
+            error_rate: if recent.is_empty() {
+                None
+            } else {
+                Some(errors as f64 / recent.len() as f64)
+            },
+            p50_ms: percentile(&latencies, 50.0),
+            p95_ms: percentile(&latencies, 95.0),
+        }
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted values, or `None` if there are no values.
+fn percentile(sorted: &[f64], percent: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UpstreamStats {
+    pub requests: usize,
+    pub errors: usize,
+    pub error_rate: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+}
+
+static LOKI: LazyLock<Mutex<RingBuffer>> = LazyLock::new(|| Mutex::new(RingBuffer::new(CAPACITY)));
+static GITHUB: LazyLock<Mutex<RingBuffer>> =
+    LazyLock::new(|| Mutex::new(RingBuffer::new(CAPACITY)));
+
+fn buffer(upstream: Upstream) -> &'static Mutex<RingBuffer> {
+    match upstream {
+        Upstream::Loki => &LOKI,
+        Upstream::GitHub => &GITHUB,
+    }
+}
+
+/// Records the latency and outcome of a call to an upstream.
+///
+/// # Arguments
+///
+/// * `upstream` - The upstream that was called.
+/// * `started` - When the call was started.
+/// * `ok` - Whether the call succeeded with a successful status.
+pub fn record(upstream: Upstream, started: Instant, ok: bool) {
+    let sample = Sample {
+        at: Instant::now(),
+        latency: started.elapsed(),
+        ok,
+    };
+
+    if let Ok(mut buffer) = buffer(upstream).lock() {
+        buffer.push(sample);
+    }
+}
+
+/// Returns the rolling statistics of an upstream over the last `WINDOW`.
+pub fn stats(upstream: Upstream) -> UpstreamStats {
+    match buffer(upstream).lock() {
+        Ok(buffer) => buffer.stats(Instant::now(), WINDOW),
+        Err(_) => UpstreamStats::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: Instant, millis: u64, ok: bool) -> Sample {
+        Sample {
+            at,
+            latency: Duration::from_millis(millis),
+            ok,
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<f64> = (1..=100).map(|value| value as f64).collect();
+
+        assert_eq!(percentile(&values, 50.0), Some(50.0));
+        assert_eq!(percentile(&values, 95.0), Some(95.0));
+        assert_eq!(percentile(&[7.0], 95.0), Some(7.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest_samples() {
+        let now = Instant::now();
+        let mut buffer = RingBuffer::new(2);
+
+        buffer.push(sample(now, 10, false));
+        buffer.push(sample(now, 20, true));
+        buffer.push(sample(now, 30, true));
+
+        let stats = buffer.stats(now, WINDOW);
+
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.errors, 0);
+        assert_eq!(stats.p50_ms, Some(20.0));
+    }
+
+    #[test]
+    fn test_ring_buffer_stats_within_window() {
+        let start = Instant::now();
+        let now = start + Duration::from_secs(20 * 60);
+        let mut buffer = RingBuffer::new(10);
+
+        buffer.push(sample(start, 1000, false));
+        buffer.push(sample(now, 10, true));
+        buffer.push(sample(now, 30, false));
+
+        let stats = buffer.stats(now, WINDOW);
+
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.error_rate, Some(0.5));
+        assert_eq!(stats.p95_ms, Some(30.0));
+    }
+
+    #[test]
+    fn test_ring_buffer_empty() {
+        let buffer = RingBuffer::new(10);
+
+        assert_eq!(
+            buffer.stats(Instant::now(), WINDOW),
+            UpstreamStats::default()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+/// The Loki usage attributed to a single principal since the service started.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrincipalUsage {
+    pub principal: String,
+    pub queries: u64,
+    pub bytes_processed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    queries: u64,
+    bytes_processed: u64,
+}
+
+/// The most principals the ledger tracks. Unauthenticated requests are attributed to the teams they request, so
+/// principals are capped to keep arbitrary team combinations from growing the ledger without bound.
+const MAX_PRINCIPALS: usize = 1000;
+
+/// The principal the usage of any further principals is attributed to once the ledger is full, in parentheses so
+/// it can't be mistaken for a team or API key of the same name.
+const OTHER_PRINCIPAL: &str = "(other)";
+
+#[derive(Debug)]
+struct UsageLedger {
+    usage: DashMap<String, Usage>,
+    max_principals: usize,
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        UsageLedger {
+            usage: DashMap::new(),
+            max_principals: MAX_PRINCIPALS,
+        }
+    }
+}
+
+impl UsageLedger {
+    fn record(&self, principal: &str, bytes_processed: u64) {
+        let principal =
+            match self.usage.contains_key(principal) || self.usage.len() < self.max_principals {
+                true => principal,
+                false => OTHER_PRINCIPAL,
+            };
+
+        let mut usage = self.usage.entry(principal.to_string()).or_default();
+
+        usage.queries += 1;
+        usage.bytes_processed += bytes_processed;
+    }
+
+    fn report(&self) -> Vec<PrincipalUsage> {
+        let mut report: Vec<PrincipalUsage> = self
+            .usage
+            .iter()
+            .map(|entry| PrincipalUsage {
+                principal: entry.key().clone(),
+                queries: entry.queries,
+                bytes_processed: entry.bytes_processed,
+            })
+            .collect();
+
+        report.sort_by(|l, r| {
+            r.bytes_processed
+                .cmp(&l.bytes_processed)
+                .then_with(|| l.principal.cmp(&r.principal))
+        });
+
+        report
+    }
+}
+
+static LEDGER: LazyLock<UsageLedger> = LazyLock::new(UsageLedger::default);
+
+/// Records a Loki query and the bytes it processed against the principal that requested it.
+pub fn record(principal: &str, bytes_processed: u64) {
+    LEDGER.record(principal, bytes_processed);
+}
+
+/// Returns the usage of every principal, the heaviest first.
+pub fn report() -> Vec<PrincipalUsage> {
+    LEDGER.report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_ledger_report() {
+        let ledger = UsageLedger::default();
+
+        ledger.record("team-a", 100);
+        ledger.record("team-b", 500);
+        ledger.record("team-a", 50);
+
+        assert_eq!(
+            ledger.report(),
+            vec![
+                PrincipalUsage {
+                    principal: "team-b".to_string(),
+                    queries: 1,
+                    bytes_processed: 500,
+                },
+                PrincipalUsage {
+                    principal: "team-a".to_string(),
+                    queries: 2,
+                    bytes_processed: 150,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_usage_ledger_is_capped() {
+        let ledger = UsageLedger {
+            max_principals: 2,
+            ..Default::default()
+        };
+
+        ledger.record("team-a", 100);
+        ledger.record("team-b", 200);
+        ledger.record("team-c", 300);
+        ledger.record("team-d", 400);
+        ledger.record("team-a", 100);
+
+        let report = ledger.report();
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].principal, "(other)");
+        assert_eq!(report[0].queries, 2);
+        assert_eq!(report[0].bytes_processed, 700);
+        assert_eq!(report[1].principal, "team-a");
+        assert_eq!(report[1].queries, 2);
+    }
+}
@@ -0,0 +1,71 @@
+//! The gathering and linking of DORA events from Loki and GitHub, and the metrics computed from them, so other
+//! tools can embed the computation without running the HTTP server.
+//!
+//! The routes, gRPC service and background jobs that make up the server are behind the default `server`
+//! feature.
+
+use anyhow::{anyhow, Result};
+
+pub mod cli;
+pub mod config;
+#[cfg(feature = "server")]
+pub mod grpc;
+pub mod helpers;
+#[cfg(feature = "server")]
+pub mod routes;
+#[cfg(feature = "server")]
+pub mod state;
+pub mod telemetry;
+
+use cli::Cli;
+use config::AppConfig;
+
+/// Loads the configuration and sets up the helpers from it, reporting every problem with it at once instead of
+/// only the first, so a misconfigured deployment fails before serving any request. Tools embedding the library
+/// call this before gathering any data, with `Cli::default()` to read only the environment, and pass the
+/// returned configuration to the helpers, e.g. through `helpers::context::Context::loaded`.
+///
+/// # Errors
+///
+/// Returns an error listing every problem found with the configuration.
+pub async fn init(cli: &Cli) -> Result<AppConfig> {
+    let fixtures = helpers::fixtures::init_from_env();
+
+    let (config, mut problems) =
+        match AppConfig::load(cli, helpers::fixtures::get().is_some()).await {
+            Ok(config) => (Some(config), vec![]),
+            Err(problems) => (None, problems),
+        };
+
+    problems.extend(fixtures.err().map(|e| e.to_string()));
+
+    if let Some(config) = &config {
+        problems.extend(
+            [
+                helpers::batching::init(&config.batching),
+                helpers::breaker::init(&config.breaker),
+                helpers::environments::init(&config.environments),
+                helpers::branches::init(&config.branches),
+                helpers::hotfixes::init(&config.hotfixes),
+                helpers::deduplication::init(&config.dedup),
+                helpers::auth::init(&config.auth),
+                helpers::oidc::init(&config.oidc),
+            ]
+            .into_iter()
+            .filter_map(Result::err)
+            .map(|e| e.to_string()),
+        );
+    }
+
+    match (config, problems.is_empty()) {
+        (Some(config), true) => Ok(config),
+        _ => Err(anyhow!(format!(
+            "Invalid configuration:\n{}",
+            problems
+                .iter()
+                .map(|problem| format!("  - {}", problem))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))),
+    }
+}
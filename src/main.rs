@@ -1,38 +1,260 @@
 use anyhow::Result;
 use axum::{
-    extract::Extension,
+    middleware,
     routing::{get, post},
     Router,
 };
-use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
+use axum_tracing_opentelemetry::middleware::OtelInResponseLayer;
+use clap::Parser;
 use dashmap::DashMap;
 use dotenv::dotenv;
 use std::{env, sync::Arc};
 
-mod helpers;
-mod routes;
+use liatrio_dora_api::{cli, grpc, helpers, routes, state::AppState, telemetry};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+    let cli = cli::Cli::parse();
+
+    // Both loggers read their filter from `RUST_LOG`.
+    if let Some(level) = &cli.log_level {
+        env::set_var("RUST_LOG", level);
+    }
+
+    let telemetry = telemetry::init()?;
     env_logger::init();
 
-    let data_cache: routes::data::DataCache = Arc::new(DashMap::new());
+    let config = liatrio_dora_api::init(&cli).await?;
+
+    if cli.check_config {
+        println!("Configuration is valid");
+        return Ok(());
+    }
+
+    let ctx = helpers::context::Context::loaded(config);
+    let data_cache_config = helpers::cache::CacheConfig::from_config(&ctx.config.cache);
+    let data_cache: routes::data::DataCache =
+        Arc::new(routes::data::DataCaches::new(data_cache_config));
     let teams_cache: routes::teams::TeamsCache = Arc::new(DashMap::new());
+    let repositories_cache: routes::repositories::RepositoriesCache = Arc::new(DashMap::new());
 
-    let app = Router::new()
+    tokio::spawn(helpers::cache::sweep_periodically(
+        data_cache.clone(),
+        data_cache_config
+            .ttl
+            .min(std::time::Duration::from_secs(60)),
+    ));
+
+    tokio::spawn(routes::teams::refresh_periodically(
+        ctx.clone(),
+        teams_cache.clone(),
+        routes::teams::get_cache_ttl(&ctx.config.github),
+    ));
+
+    let scoring_model = helpers::scoring::ScoringModel::from_env()?;
+    let cohort_stacks = helpers::cohorts::from_config(&ctx.config.cohorts);
+    let targets_config = helpers::targets::TargetsConfig::from_env()?;
+    let export_config = helpers::prometheus::ExportConfig::from_env();
+    let anomaly_config = helpers::anomalies::AnomalyConfig::from_env();
+
+    let prewarm_config = helpers::prewarm::PrewarmConfig::from_config(&ctx.config.prewarm)?;
+    let warmup_status = helpers::prewarm::WarmupStatus::new(
+        !(prewarm_config.is_enabled() && prewarm_config.readiness_gate),
+    );
+
+    if prewarm_config.is_enabled() {
+        tokio::spawn(helpers::prewarm::prewarm(
+            prewarm_config,
+            ctx.clone(),
+            data_cache.clone(),
+            warmup_status.clone(),
+        ));
+    }
+
+    let alert_config =
+        helpers::alerts::AlertConfig::from_config(&ctx.config.alerts, &ctx.config.digest)?;
+
+    if alert_config.is_enabled() {
+        tokio::spawn(helpers::alerts::evaluate_periodically(
+            alert_config,
+            ctx.clone(),
+            data_cache.clone(),
+        ));
+    }
+
+    let digest_config = helpers::digest::DigestConfig::from_config(&ctx.config.digest)?;
+
+    if digest_config.is_enabled() {
+        tokio::spawn(helpers::digest::send_on_schedule(
+            digest_config,
+            ctx.clone(),
+            data_cache.clone(),
+        ));
+    }
+
+    if let Some(grpc_port) = ctx.config.server.grpc_port {
+        let grpc_addr = format!("[::]:{grpc_port}").parse::<std::net::SocketAddr>()?;
+        let grpc_server =
+            grpc::DoraMetricsServer::new(ctx.clone(), data_cache.clone(), teams_cache.clone());
+
+        tracing::warn!("gRPC listening on {:?}", grpc_addr);
+
+        tokio::spawn(async move {
+            if let Err(e) = tonic::transport::Server::builder()
+                .add_service(grpc_server)
+                .serve(grpc_addr)
+                .await
+            {
+                tracing::error!("gRPC Server Failed: {:?}", e);
+            }
+        });
+    }
+
+    let port = ctx.config.server.port.unwrap_or_default();
+    let settings = Arc::new(helpers::settings::resolved(&ctx)?);
+    let readiness = helpers::readiness::ReadinessConfig::from_config(&ctx.config.readiness);
+    let limits = helpers::limits::LimitsConfig::from_config(&ctx.config.limits);
+    let cors = helpers::cors::CorsConfig::from_config(&ctx.config.cors)?;
+
+    let state = AppState {
+        ctx,
+        data_cache,
+        teams_cache,
+        repositories_cache,
+        scoring_model,
+        cohort_stacks,
+        targets: targets_config,
+        anomalies: anomaly_config,
+        export: export_config,
+        readiness,
+        warmup_status,
+        settings,
+    };
+
+    let admin = Router::new()
+        .route("/admin/usage", get(routes::admin::handle_usage_request))
+        .route("/admin/config", get(routes::admin::handle_config_request))
+        .route(
+            "/admin/cache/stats",
+            get(routes::admin::handle_cache_stats_request),
+        )
+        .route(
+            "/admin/cache/purge",
+            post(routes::admin::handle_cache_purge_request),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            routes::admin::require_admin_token,
+        ));
+
+    let v1 = Router::new()
         .route("/data", post(routes::data::handle_request))
-        .layer(OtelInResponseLayer)
-        .layer(OtelAxumLayer::default())
-        .layer(Extension(data_cache))
+        .route(
+            "/metrics/summary",
+            post(routes::metrics::handle_summary_request),
+        )
+        .route(
+            "/metrics/trends",
+            post(routes::metrics::handle_trends_request),
+        )
+        .route(
+            "/metrics/org",
+            post(routes::metrics::handle_org_rollup_request),
+        )
+        .route(
+            "/metrics/compare",
+            post(routes::metrics::handle_compare_request),
+        )
+        .route(
+            "/metrics/forecast",
+            post(routes::metrics::handle_forecast_request),
+        )
+        .route(
+            "/metrics/scorecard",
+            post(routes::metrics::handle_scorecard_request),
+        )
+        .route(
+            "/metrics/cohorts",
+            post(routes::metrics::handle_cohorts_request),
+        )
+        .route(
+            "/metrics/deployment-frequency",
+            post(routes::metrics::handle_deployment_frequency_request),
+        )
+        .route(
+            "/metrics/change-failure-rate",
+            post(routes::metrics::handle_change_failure_rate_request),
+        )
+        .route(
+            "/metrics/lead-time",
+            post(routes::metrics::handle_lead_time_request),
+        )
+        .route("/metrics/mttr", post(routes::metrics::handle_mttr_request))
+        .route(
+            "/metrics/reviews",
+            post(routes::changes::handle_reviews_request),
+        )
+        .route(
+            "/metrics/pr-throughput",
+            post(routes::changes::handle_pr_throughput_request),
+        )
+        .route("/changes/:sha", get(routes::changes::handle_change_request))
+        .route("/incidents", post(routes::incidents::handle_request))
+        .route(
+            "/deployments/active",
+            get(routes::deployments::handle_active_request),
+        )
         .route("/teams", get(routes::teams::handle_request))
+        .route("/repositories", get(routes::repositories::handle_request))
+        .route(
+            "/teams/:team/repositories",
+            get(routes::repositories::handle_team_request),
+        )
+        .route(
+            "/diagnostics/upstreams",
+            get(routes::diagnostics::handle_upstreams_request),
+        );
+
+    // Breaking response changes ship as a new router nested under its own version, e.g. `/v2`, while the
+    // unversioned aliases keep serving `/v1` for existing clients. Operational routes aren't versioned.
+    let v2 = Router::new().route("/teams", get(routes::teams::handle_v2_request));
+
+    let v1 = limits.apply(v1);
+    let v2 = limits.apply(v2);
+    let admin = limits.apply(admin);
+
+    let prometheus = Router::new().route("/metrics", get(routes::prometheus::handle_request));
+
+    let app = Router::new()
+        .nest("/v1", v1.clone())
+        .nest("/v2", v2)
+        .merge(v1)
+        .route_layer(middleware::from_fn(routes::auth::authenticate))
+        // The admin routes check their own token, which the API key and OIDC authentication would reject.
+        .nest("/v1", admin.clone())
+        .merge(admin)
+        // Scraped by Prometheus, which authenticates with neither an API key nor an OIDC token.
+        .merge(prometheus)
+        .route("/health", get(routes::health::handle_request))
+        .route("/ready", get(routes::health::handle_ready_request))
+        .with_state(state);
+
+    let app = app
+        .layer(middleware::from_fn(routes::health::add_retry_after))
+        .layer(middleware::from_fn(
+            routes::prometheus::record_request_metrics,
+        ))
+        .layer(helpers::trace::access_log_layer())
+        .layer(helpers::trace::otel_layer())
         .layer(OtelInResponseLayer)
-        .layer(OtelAxumLayer::default())
-        .layer(Extension(teams_cache))
-        .route("/health", get(routes::health::handle_request));
+        .layer(middleware::from_fn(routes::request_id::propagate));
+
+    let app = match cors {
+        Some(cors) => app.layer(cors.layer()),
+        None => app,
+    };
 
-    let port = env::var("PORT")?;
     let addr = format!("[::]:{port}")
         .parse::<std::net::SocketAddr>()
         .unwrap();
@@ -46,13 +268,12 @@ async fn main() -> Result<()> {
         .await
         .unwrap();
 
+    telemetry.shutdown().await;
+
     Ok(())
 }
 
 async fn shutdown_signal() {
-    use std::sync::mpsc;
-    use std::{thread, time::Duration};
-
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -76,13 +297,4 @@ async fn shutdown_signal() {
     }
 
     tracing::warn!("signal received, starting graceful shutdown");
-    let (sender, receiver) = mpsc::channel();
-    let _ = thread::spawn(move || {
-        opentelemetry::global::shutdown_tracer_provider();
-        sender.send(()).ok()
-    });
-    let shutdown_res = receiver.recv_timeout(Duration::from_millis(2_000));
-    if shutdown_res.is_err() {
-        tracing::error!("failed to shutdown OpenTelemetry");
-    }
 }
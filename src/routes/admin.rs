@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::{Json, Response},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    helpers::{
+        auth::tokens_match,
+        cache::CacheStats,
+        context::Context,
+        oidc::{self, Verifier},
+        settings::Settings,
+        usage::{report, PrincipalUsage},
+    },
+    routes::data::DataCache,
+};
+
+#[derive(Serialize, Debug)]
+pub struct UsageResponse {
+    pub principals: Vec<PrincipalUsage>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheStatsResponse {
+    pub responses: CacheStats,
+    pub gathered: CacheStats,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PurgeRequest {
+    pub pattern: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PurgeResponse {
+    pub purged: usize,
+}
+
+/// Rejects requests that don't carry the `ADMIN_TOKEN`, or an OIDC token carrying `OIDC_ADMIN_CLAIM`, as a
+/// bearer token. The admin routes aren't behind `routes::auth::authenticate`, which would reject the admin token
+/// as an invalid OIDC token.
+///
+/// When neither `ADMIN_TOKEN` nor OIDC is configured, every admin request is rejected.
+pub async fn require_admin_token(
+    State(ctx): State<Context>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    authorize_admin(
+        ctx.config.server.admin_token.as_deref(),
+        provided.as_deref(),
+        oidc::verifier(),
+    )
+    .await?;
+
+    Ok(next.run(request).await)
+}
+
+/// Checks a bearer token against the admin token before validating it as an OIDC token, since the admin token
+/// isn't one. Tokens that are valid but lack the admin claim are forbidden rather than unauthorized.
+async fn authorize_admin(
+    admin_token: Option<&str>,
+    provided: Option<&str>,
+    verifier: Option<&Verifier>,
+) -> Result<(), StatusCode> {
+    let Some(provided) = provided else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let expected = admin_token.unwrap_or_default();
+
+    if !expected.is_empty() && tokens_match(provided, expected) {
+        return Ok(());
+    }
+
+    let Some(verifier) = verifier else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    match verifier.verify(provided).await {
+        Ok(claims) if verifier.is_admin(&claims) => {
+            tracing::info!("Admin Authorized With Token For: {}", claims.sub);
+            Ok(())
+        }
+        Ok(claims) => {
+            tracing::warn!("Admin Forbidden For: {}", claims.sub);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Err(e) => {
+            tracing::warn!("Admin Rejected: {}", e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+pub async fn handle_usage_request() -> Result<Json<UsageResponse>, StatusCode> {
+    Ok(Json(UsageResponse {
+        principals: report(),
+    }))
+}
+
+pub async fn handle_config_request(State(settings): State<Settings>) -> Json<Value> {
+    Json(settings.as_ref().clone())
+}
+
+pub async fn handle_cache_stats_request(
+    State(cache): State<DataCache>,
+) -> Result<Json<CacheStatsResponse>, StatusCode> {
+    Ok(Json(CacheStatsResponse {
+        responses: cache.responses.stats(),
+        gathered: cache.gathered.stats(),
+    }))
+}
+
+pub async fn handle_cache_purge_request(
+    State(cache): State<DataCache>,
+    request: Option<Json<PurgeRequest>>,
+) -> Result<Json<PurgeResponse>, StatusCode> {
+    let Json(request) = request.unwrap_or_default();
+
+    let pattern = match request.pattern.as_deref().map(Regex::new) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            tracing::error!("Invalid Purge Pattern: {:?}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let purged = cache.responses.purge(pattern.as_ref()) + cache.gathered.purge(pattern.as_ref());
+
+    tracing::info!("Purged {} cache entries", purged);
+
+    Ok(Json(PurgeResponse { purged }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::oidc::OidcConfig;
+    use std::time::Duration;
+
+    fn verifier() -> Verifier {
+        Verifier::new(OidcConfig {
+            issuer: "https://sso.example.com".to_string(),
+            audience: "dora-api".to_string(),
+            jwks_url: Some("http://127.0.0.1:9/jwks".to_string()),
+            jwks_ttl: Duration::from_secs(3600),
+            admin_claim: Some(("groups".to_string(), "dora-admins".to_string())),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_admin_token_is_accepted_with_oidc() {
+        let verifier = verifier();
+
+        assert_eq!(
+            authorize_admin(Some("s3cr3t"), Some("s3cr3t"), Some(&verifier)).await,
+            Ok(())
+        );
+        assert_eq!(
+            authorize_admin(Some("s3cr3t"), Some("wrong"), Some(&verifier)).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            authorize_admin(None, Some("s3cr3t"), Some(&verifier)).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            authorize_admin(Some("s3cr3t"), None, Some(&verifier)).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            authorize_admin(Some("s3cr3t"), Some("s3cr3t"), None).await,
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::Request,
+    http::{header::AUTHORIZATION, HeaderName, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+
+use crate::helpers::auth::{self, Authenticated};
+
+static API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Rejects requests that carry neither one of the `API_KEYS` in the `X-Api-Key` header nor a valid OIDC token
+/// as a bearer token, and logs who each accepted request was made by. Who the request was made by, and the claims
+/// of a token, are added to the request for the handlers to attribute and authorize it with.
+///
+/// When neither `API_KEYS` nor `OIDC_ISSUER_URL` is set, every request is let through.
+pub async fn authenticate(mut request: Request, next: Next) -> Result<Response, StatusCode> {
+    let api_key = header(&request, &API_KEY);
+    let bearer = header(&request, &AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer ").map(str::to_string));
+    let target = format!("{} {}", request.method(), request.uri().path());
+
+    match auth::authenticate(api_key.as_deref(), bearer.as_deref()).await {
+        Ok(authenticated) => {
+            log_authenticated(&target, &authenticated);
+
+            if let Authenticated::Token(claims) = &authenticated {
+                request.extensions_mut().insert(claims.clone());
+            }
+
+            request.extensions_mut().insert(authenticated);
+
+            Ok(next.run(request).await)
+        }
+        Err(e) => {
+            tracing::warn!("{} Rejected: {}", target, e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+/// Returns the principal a request added to by `authenticate` is attributed to, `None` when it was made
+/// anonymously.
+pub fn principal(authenticated: Option<Extension<Authenticated>>) -> Option<String> {
+    authenticated.and_then(|Extension(authenticated)| authenticated.principal())
+}
+
+/// Copies a header out of the request, since the request can't be held across the token validation.
+fn header(request: &Request, name: &HeaderName) -> Option<String> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+pub fn log_authenticated(target: &str, authenticated: &Authenticated) {
+    match authenticated {
+        Authenticated::Anonymous => {}
+        Authenticated::ApiKey(name) => {
+            tracing::info!("{} Authorized With API Key: {}", target, name)
+        }
+        Authenticated::Token(claims) => {
+            tracing::info!("{} Authorized With Token For: {}", target, claims.sub)
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::{
+    helpers::{
+        auth::Authenticated,
+        breaker::error_status,
+        context::Context,
+        loki::{gather_events, EventKind},
+        request::DataRequest,
+        response::{ChangeResponse, PrThroughputResponse, ReviewsResponse},
+        reviews::{link_reviews, summarize_reviews},
+        throughput::{merged_changes, opened_changes, throughput},
+        traceability::{deployed_to, trace_change},
+    },
+    routes::{
+        auth,
+        data::{get_records, validate_patterns, DataCache},
+        metrics::SeriesParams,
+        teams::{expand_child_teams, TeamsCache},
+    },
+};
+
+/// Returns how long each pull request opened in the window waited for its first review and approval, so
+/// the time before merge that lead time doesn't show can be broken down.
+pub async fn handle_reviews_request(
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<ReviewsResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    validate_patterns(&ctx, &request)?;
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let (start, end) = (request.start, request.end);
+
+    let (events, warnings) = match gather_events(&ctx, request, EventKind::Reviewed).await {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("Gathering Reviews Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    let records: Vec<_> = link_reviews(events)
+        .into_iter()
+        .filter(|record| record.opened_at >= start && record.opened_at <= end)
+        .collect();
+
+    Ok(Json(ReviewsResponse {
+        summary: summarize_reviews(&records),
+        records,
+        warnings,
+    }))
+}
+
+/// Returns the pull requests opened and merged per team or repository for each time bucket, to show how
+/// much change flows through teams alongside the deployment metrics.
+pub async fn handle_pr_throughput_request(
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    Query(params): Query<SeriesParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<PrThroughputResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let grouping = params.grouping()?;
+    let bucket = params.bucket()?;
+
+    validate_patterns(&ctx, &request)?;
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let starts = bucket.starts(request.start, request.end);
+
+    let (opened, merged) = tokio::join!(
+        gather_events(&ctx, request.clone(), EventKind::Opened),
+        gather_events(&ctx, request, EventKind::Merged)
+    );
+
+    let ((opened, mut warnings), (merged, merged_warnings)) = match (opened, merged) {
+        (Ok(opened), Ok(merged)) => (opened, merged),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!("Gathering Changes Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    // Both queries cover the same window, so they are clamped with the same warning.
+    if warnings.is_empty() {
+        warnings = merged_warnings;
+    }
+
+    Ok(Json(PrThroughputResponse {
+        bucket,
+        series: throughput(
+            &starts,
+            &opened_changes(opened),
+            &merged_changes(merged),
+            grouping,
+        ),
+        warnings,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangeParams {
+    pub team: Option<String>,
+    pub repository: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// How far back a commit's deployments are looked for when the request doesn't give a `start`.
+const DEFAULT_CHANGE_WINDOW_DAYS: i64 = 30;
+
+/// Returns the full story of one commit, its merge, every deployment to every environment, the failures
+/// they caused and the resulting durations, for audits and postmortems.
+pub async fn handle_change_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    Path(sha): Path<String>,
+    authenticated: Option<Extension<Authenticated>>,
+    Query(params): Query<ChangeParams>,
+) -> Result<Json<ChangeResponse>, StatusCode> {
+    let end = params.end.unwrap_or_else(Utc::now);
+    let start = params
+        .start
+        .unwrap_or(end - Duration::days(DEFAULT_CHANGE_WINDOW_DAYS));
+
+    if start >= end || sha.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut request = DataRequest {
+        team: params.team,
+        repositories: params.repository.map(|repository| vec![repository]),
+        start,
+        end,
+        authenticated_as: auth::principal(authenticated),
+        ..Default::default()
+    };
+
+    validate_patterns(&ctx, &request)?;
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let (events, warnings) =
+        match gather_events(&ctx, request.clone(), EventKind::Deployed(sha.clone())).await {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::error!("Gathering Deployments Failed: {:?}", e);
+                return Err(error_status(&e));
+            }
+        };
+
+    let deployed = deployed_to(&events, &sha);
+
+    if deployed.repositories.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Link every environment the commit reached, not only the production ones.
+    request.repositories = Some(deployed.repositories);
+    request.environments = Some(deployed.environments);
+
+    let records = get_records(&ctx, &cache, request).await?;
+
+    match trace_change(&sha, records) {
+        Some(change) => Ok(Json(ChangeResponse { warnings, ..change })),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
@@ -1,61 +1,895 @@
 use anyhow::Result;
 use axum::{
-    extract::{Extension, Query},
-    http::StatusCode,
-    response::Json,
+    body::Body,
+    extract::{Query, State},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Json, Response},
+    Extension,
 };
-use dashmap::DashMap;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::HashSet, convert::Infallible, mem::size_of, str::FromStr, sync::Arc};
+use tracing::{field, Instrument, Span};
 
-use crate::helpers::{
-    gatherer::link_data, loki::gather_data, request::DataRequest, response::ResponseRecord,
+use crate::{
+    helpers::{
+        auth::Authenticated,
+        breaker::error_status,
+        cache::{Cache, CacheConfig, EstimateSize, Sweep},
+        context::Context,
+        csv::to_csv,
+        gatherer::{
+            link_data, link_records, missing_windows, CoveredData, DeployEntry, GatheredData,
+            IssueEntry, MergeEntry, SkippedEvent,
+        },
+        loki::{self, gather_data},
+        pagination::{paginate, Cursor},
+        request::{parse_fields, parse_sections, DataRequest, Section, SortKey},
+        response::{DeploymentRecord, FailureRecord, LeadTimeRecord, ResponseRecord},
+    },
+    routes::{
+        auth,
+        teams::{expand_child_teams, TeamsCache},
+    },
+    telemetry,
 };
 
-pub type DataCache = Arc<DashMap<String, DataResponse>>;
+pub type DataCache = Arc<DataCaches>;
+
+/// The linked `/data` responses, and the gathered events they were linked from so overlapping
+/// requests only have to query Loki for what is missing.
+#[derive(Debug)]
+pub struct DataCaches {
+    pub responses: Cache<DataResponse>,
+    pub gathered: Cache<CoveredData>,
+    /// How far back from the end of the gathered events they are queried again, see `CacheConfig::requery`.
+    pub requery: Duration,
+}
+
+impl DataCaches {
+    pub fn new(config: CacheConfig) -> Self {
+        DataCaches {
+            responses: Cache::new(config),
+            gathered: Cache::new(config),
+            requery: Duration::from_std(config.requery).unwrap_or_default(),
+        }
+    }
+
+    /// Returns how far gathered events ending at `end` are covered, leaving the recent tail to be queried again.
+    fn covered_until(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> DateTime<Utc> {
+        start.max(end - self.requery)
+    }
+}
+
+impl Sweep for DataCaches {
+    fn sweep(&self) -> usize {
+        self.responses.sweep() + self.gathered.sweep()
+    }
+}
+
+impl EstimateSize for DataResponse {
+    fn estimated_bytes(&self) -> usize {
+        serde_json::to_vec(self).map(|json| json.len()).unwrap_or(0)
+    }
+}
+
+impl EstimateSize for CoveredData {
+    fn estimated_bytes(&self) -> usize {
+        let deployments: usize = self
+            .data
+            .deployments_by_repo
+            .values()
+            .flatten()
+            .map(|d| {
+                size_of::<DeployEntry>()
+                    + d.repository.len()
+                    + d.team.len()
+                    + d.sha.len()
+                    + d.deploy_url.len()
+                    + d.change_url.len()
+            })
+            .sum();
+
+        let issues: usize = self.data.issues_by_repo.values().map(Vec::len).sum();
+
+        let merges: usize = self
+            .data
+            .merges_by_sha
+            .iter()
+            .map(|(sha, m)| size_of::<MergeEntry>() + sha.len() + m.user.len() + m.title.len())
+            .sum();
+
+        deployments + issues * size_of::<IssueEntry>() + merges
+    }
+}
 
 #[derive(Serialize, Debug, Default, Clone)]
 pub struct DataResponse {
-    records: Vec<ResponseRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    records: Option<Vec<ResponseRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployments: Option<Vec<DeploymentRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failures: Option<Vec<FailureRecord>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lead_times: Option<Vec<LeadTimeRecord>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped_events: Vec<SkippedEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+impl DataResponse {
+    pub fn into_records(self) -> Vec<ResponseRecord> {
+        self.records.unwrap_or_default()
+    }
+
+    /// The number of distinct repositories the records were deployed from.
+    fn repository_count(&self) -> usize {
+        self.records
+            .iter()
+            .flatten()
+            .map(|record| record.repository.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Orders the records by `key`, see `SortKey::sort`.
+    fn sorted(mut self, key: SortKey) -> DataResponse {
+        if let Some(records) = self.records.as_mut() {
+            key.sort(records);
+        }
+
+        self
+    }
+
+    /// Serializes the response with each record limited to `fields`, see `parse_fields`.
+    fn select_fields(&self, fields: &[String]) -> serde_json::Value {
+        let mut response = serde_json::to_value(self).unwrap_or_default();
+
+        if let Some(records) = response
+            .get_mut("records")
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            for record in records
+                .iter_mut()
+                .filter_map(serde_json::Value::as_object_mut)
+            {
+                record.retain(|key, _| fields.contains(key));
+            }
+        }
+
+        response
+    }
+
+    /// Limits the records to one page, setting `next_cursor` when there are more records.
+    fn into_page(self, limit: usize, cursor: Option<&Cursor>) -> DataResponse {
+        let (records, next) = paginate(self.records.unwrap_or_default(), limit, cursor);
+
+        DataResponse {
+            records: Some(records),
+            warnings: self.warnings,
+            skipped_events: self.skipped_events,
+            next_cursor: next.map(|cursor| cursor.encode()),
+            ..Default::default()
+        }
+    }
+
+    /// Splits the linked records into the requested sections instead of the combined record list.
+    fn into_sections(self, sections: &[Section]) -> DataResponse {
+        let records = self.records.unwrap_or_default();
+        let mut response = DataResponse {
+            warnings: self.warnings,
+            skipped_events: self.skipped_events,
+            next_cursor: self.next_cursor,
+            ..Default::default()
+        };
+
+        for section in sections {
+            match section {
+                Section::Deployments => {
+                    response.deployments =
+                        Some(records.iter().map(DeploymentRecord::from).collect())
+                }
+                Section::Failures => {
+                    response.failures = Some(records.iter().filter_map(|r| r.failure()).collect())
+                }
+                Section::LeadTimes => {
+                    response.lead_times =
+                        Some(records.iter().filter_map(|r| r.lead_time()).collect())
+                }
+            }
+        }
+
+        response
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct RequestParams {
     pub no_cache: Option<bool>,
+    pub sections: Option<String>,
+    pub partial: Option<bool>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub format: Option<String>,
+    pub sort_by: Option<String>,
+    pub fields: Option<String>,
 }
 
+const NDJSON: &str = "application/x-ndjson";
+const CSV: &str = "text/csv";
+
 pub async fn handle_request(
-    Extension(cache): Extension<DataCache>,
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
     Query(params): Query<RequestParams>,
-    Json(request): Json<DataRequest>,
-) -> Result<Json<DataResponse>, StatusCode> {
-    let request_key = format!("{:?}", request);
+    headers: HeaderMap,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Response, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let span = data_span(&request);
+
+    respond(&ctx, &cache, params, headers, request)
+        .instrument(span)
+        .await
+}
+
+/// Returns the span a data request is processed in, whose fields are included in every log line written while
+/// processing it. `repositories` is recorded once the records are linked.
+fn data_span(request: &DataRequest) -> Span {
+    tracing::info_span!(
+        "data_request",
+        request_id = %telemetry::request_id(),
+        team = %request.team_label(),
+        start = %request.start.to_rfc3339(),
+        end = %request.end.to_rfc3339(),
+        repositories = field::Empty,
+    )
+}
+
+async fn respond(
+    ctx: &Context,
+    cache: &DataCache,
+    params: RequestParams,
+    headers: HeaderMap,
+    mut request: DataRequest,
+) -> Result<Response, StatusCode> {
+    request.partial = params.partial.unwrap_or_default();
+
+    validate_patterns(ctx, &request)?;
+
+    let sections = match params.sections.as_deref().map(parse_sections) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            tracing::error!("Invalid Sections: {:?}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let sort_by = match params.sort_by.as_deref().map(SortKey::from_str) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            tracing::error!("Invalid Sort Key: {:?}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let fields = match params.fields.as_deref().map(parse_fields) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            tracing::error!("Invalid Fields: {:?}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    if fields.is_some() && sections.is_some() {
+        tracing::error!("Fields can't be selected with sections");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let no_cache = params.no_cache.unwrap_or_default();
+
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let accepts_ndjson = accept.contains(NDJSON);
+
+    let csv = match params.format.as_deref() {
+        Some("csv") => true,
+        Some("json") => false,
+        Some(value) => {
+            tracing::error!("Invalid Format: {}", value);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => accept.contains(CSV),
+    };
+
+    if csv && sections.is_some() {
+        tracing::error!("Sections can't be exported as CSV");
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    if !params.no_cache.unwrap_or_default() {
-        if let Some(cached_response) = cache.get(&request_key) {
-            return Ok(Json(cached_response.clone()));
+    if csv && fields.is_some() {
+        tracing::error!("Fields can't be selected for CSV");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let cursor = match params.cursor.as_deref().map(Cursor::decode) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            tracing::error!("Invalid Cursor: {:?}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let limit = match (params.limit, &cursor) {
+        (Some(0), _) => {
+            tracing::error!("Invalid Limit: 0");
+            return Err(StatusCode::BAD_REQUEST);
         }
+        (Some(value), _) => Some(value),
+        (None, Some(_)) => {
+            tracing::error!("A cursor requires a limit");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        (None, None) => None,
+    };
+
+    if limit.is_some() && sort_by.is_some_and(|key| key != SortKey::CreatedAt) {
+        tracing::error!("Pages can only be sorted by created_at");
+        return Err(StatusCode::BAD_REQUEST);
     }
 
-    let data_set = gather_data(request).await;
+    if accepts_ndjson && !csv {
+        if limit.is_some() {
+            tracing::error!("Pages can't be streamed as NDJSON");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        if sections.is_some() {
+            tracing::error!("Sections can't be streamed as NDJSON");
+            return Err(StatusCode::BAD_REQUEST);
+        }
 
-    match data_set {
-        Ok(data) => {
-            let records = link_data(data);
+        if sort_by.is_some() || fields.is_some() {
+            tracing::error!("Sorted or selected records can't be streamed as NDJSON");
+            return Err(StatusCode::BAD_REQUEST);
+        }
 
-            let response = DataResponse { records };
+        return stream_response(ctx, cache, no_cache, request).await;
+    }
+
+    let mut response = get_response(ctx, cache, no_cache, request).await?;
+
+    if let Some(key) = sort_by {
+        response = response.sorted(key);
+    }
 
-            if cache.contains_key(&request_key) {
-                cache.alter(&request_key, |_, _| response.clone());
-            } else {
-                cache.insert(request_key, response.clone());
+    if let Some(value) = limit {
+        response = response.into_page(value, cursor.as_ref());
+    }
+
+    if csv {
+        return Ok(csv_response(response));
+    }
+
+    match (sections, fields) {
+        (Some(value), _) => Ok(Json(response.into_sections(&value)).into_response()),
+        (None, Some(value)) => Ok(Json(response.select_fields(&value)).into_response()),
+        (None, None) => Ok(Json(response).into_response()),
+    }
+}
+
+/// Returns the linked records for a request, from the cache when available.
+pub async fn get_records(
+    ctx: &Context,
+    cache: &DataCache,
+    request: DataRequest,
+) -> Result<Vec<ResponseRecord>, StatusCode> {
+    let span = data_span(&request);
+    let response = get_response(ctx, cache, false, request)
+        .instrument(span)
+        .await?;
+
+    Ok(response.into_records())
+}
+
+/// Returns the cached response for a request, refreshing it in the background when it is stale.
+fn get_cached_response(
+    ctx: &Context,
+    cache: &DataCache,
+    request: &DataRequest,
+) -> Option<DataResponse> {
+    let request_key = request_key(request);
+    let cached = cache.responses.get(&request_key)?;
+
+    if cached.stale && cache.responses.begin_refresh(&request_key) {
+        let ctx = ctx.clone();
+        let cache = cache.clone();
+        let request = request.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = refresh_cache(&ctx, &cache, request, false).await {
+                tracing::error!("Background Refresh Failed: {:?}", e);
             }
 
-            Ok(Json(response))
+            cache.responses.end_refresh(&request_key);
+        });
+    }
+
+    Some(cached.value)
+}
+
+async fn get_response(
+    ctx: &Context,
+    cache: &DataCache,
+    no_cache: bool,
+    request: DataRequest,
+) -> Result<DataResponse, StatusCode> {
+    validate_patterns(ctx, &request)?;
+
+    if !no_cache {
+        if let Some(cached_response) = get_cached_response(ctx, cache, &request) {
+            Span::current().record("repositories", cached_response.repository_count());
+            tracing::info!("Served Data from the Cache");
+            return Ok(cached_response);
+        }
+    }
+
+    match refresh_cache(ctx, cache, request, no_cache).await {
+        Ok(response) => {
+            Span::current().record("repositories", response.repository_count());
+            tracing::info!("Served Data");
+            Ok(response)
         }
         Err(e) => {
             tracing::error!("Processing Data Failed: {:?}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(error_status(&e))
         }
     }
 }
+
+/// Rejects requests whose window is inverted or out of range, or whose repository or author patterns, or
+/// deduplication strategy, are invalid, before they reach the cache or Loki.
+pub fn validate_patterns(ctx: &Context, request: &DataRequest) -> Result<(), StatusCode> {
+    if request.start > request.end
+        || request.start.timestamp_nanos_opt().is_none()
+        || request.end.timestamp_nanos_opt().is_none()
+    {
+        tracing::error!(
+            "Invalid Window: {} to {}",
+            request.start.to_rfc3339(),
+            request.end.to_rfc3339()
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = request.repository_filter() {
+        tracing::error!("Invalid Repositories: {:?}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = request.excluded_authors() {
+        tracing::error!("Invalid Authors: {:?}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = request.service_filter() {
+        tracing::error!("Invalid Services: {:?}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = request.deduplication() {
+        tracing::error!("Invalid Deduplication: {:?}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = loki::validate_tenant(&ctx.config.loki, request) {
+        tracing::error!("Invalid Tenant: {:?}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(())
+}
+
+/// Queries and links the data for a request, storing the result in the cache unless it is incomplete.
+///
+/// Unless `no_cache` is set, previously gathered events overlapping the request are reused and only the
+/// missing windows are queried.
+pub async fn refresh_cache(
+    ctx: &Context,
+    cache: &DataCache,
+    request: DataRequest,
+    no_cache: bool,
+) -> Result<DataResponse> {
+    let request_key = request_key(&request);
+
+    let data = gather(ctx, cache, request, no_cache).await?;
+    let warnings = data.warnings.clone();
+    let skipped_events = data.skipped_events.clone();
+    let incomplete = data.incomplete;
+    let records = link_data(data);
+
+    let response = DataResponse {
+        records: Some(records),
+        warnings,
+        skipped_events,
+        ..Default::default()
+    };
+
+    if !incomplete {
+        cache.responses.insert(request_key, response.clone());
+    }
+
+    Ok(response)
+}
+
+/// Serializes a record as an NDJSON line, or returns `None` after logging a record that can't be serialized,
+/// so it is left out instead of written as an empty line.
+fn ndjson_line(record: &ResponseRecord) -> Option<Result<Vec<u8>, Infallible>> {
+    match serde_json::to_vec(record) {
+        Ok(mut line) => {
+            line.push(b'\n');
+            Some(Ok(line))
+        }
+        Err(e) => {
+            tracing::error!("Record Serialization Failed: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Builds an NDJSON response, one record per line, with each warning as an `X-Data-Warning` header.
+fn ndjson_response<I>(warnings: &[String], lines: I) -> Response
+where
+    I: Iterator<Item = Result<Vec<u8>, Infallible>> + Send + 'static,
+{
+    let mut response = Body::from_stream(futures::stream::iter(lines)).into_response();
+
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(NDJSON));
+
+    with_warnings(response, warnings)
+}
+
+/// Builds a CSV response, with each warning as an `X-Data-Warning` header and the next page's cursor as
+/// `X-Next-Cursor`.
+fn csv_response(response: DataResponse) -> Response {
+    let records = response.records.unwrap_or_default();
+    let mut csv = to_csv(&records).into_response();
+    let headers = csv.headers_mut();
+
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/csv; charset=utf-8"),
+    );
+
+    if let Some(value) = response
+        .next_cursor
+        .and_then(|cursor| HeaderValue::from_str(&cursor).ok())
+    {
+        headers.insert("X-Next-Cursor", value);
+    }
+
+    with_warnings(csv, &response.warnings)
+}
+
+fn with_warnings(mut response: Response, warnings: &[String]) -> Response {
+    let headers = response.headers_mut();
+
+    for warning in warnings {
+        if let Ok(value) = HeaderValue::from_str(warning) {
+            headers.append("X-Data-Warning", value);
+        }
+    }
+
+    response
+}
+
+/// Streams the records for a request as NDJSON.
+///
+/// The events are gathered in full before the response starts, since failures are linked across the whole
+/// window and the warnings are sent as headers. The records are then linked and serialized one line at a
+/// time, so the response body is never held in memory, and the linked records are kept and cached once the
+/// last one has been sent.
+async fn stream_response(
+    ctx: &Context,
+    cache: &DataCache,
+    no_cache: bool,
+    request: DataRequest,
+) -> Result<Response, StatusCode> {
+    if !no_cache {
+        if let Some(cached_response) = get_cached_response(ctx, cache, &request) {
+            let records = cached_response.records.unwrap_or_default();
+
+            return Ok(ndjson_response(
+                &cached_response.warnings,
+                records
+                    .into_iter()
+                    .filter_map(|record| ndjson_line(&record)),
+            ));
+        }
+    }
+
+    let request_key = request_key(&request);
+
+    let data = match gather(ctx, cache, request, no_cache).await {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("Processing Data Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    let warnings = data.warnings.clone();
+    let mut skipped_events = data.skipped_events.clone();
+    let incomplete = data.incomplete;
+    let cache = cache.clone();
+    let mut records = link_records(data);
+    let mut linked = vec![];
+    let mut done = false;
+
+    let lines = std::iter::from_fn({
+        let warnings = warnings.clone();
+
+        move || {
+            if done {
+                return None;
+            }
+
+            for record in records.by_ref() {
+                let line = ndjson_line(&record);
+
+                if !incomplete {
+                    linked.push(record);
+                }
+
+                if line.is_some() {
+                    return line;
+                }
+            }
+
+            done = true;
+
+            if !incomplete {
+                let response = DataResponse {
+                    records: Some(std::mem::take(&mut linked)),
+                    warnings: warnings.clone(),
+                    skipped_events: std::mem::take(&mut skipped_events),
+                    ..Default::default()
+                };
+
+                cache.responses.insert(request_key.clone(), response);
+            }
+
+            None
+        }
+    });
+
+    Ok(ndjson_response(&warnings, lines))
+}
+
+/// Identifies the response to a request, independent of who made it.
+fn request_key(request: &DataRequest) -> String {
+    let key = DataRequest {
+        authenticated_as: None,
+        ..request.clone()
+    };
+
+    format!("{:?}", key)
+}
+
+/// Identifies the events a request gathers, independent of its window, whether it accepts partial results and
+/// who made it.
+fn scope_key(request: &DataRequest) -> String {
+    let scope = DataRequest {
+        start: DateTime::<Utc>::default(),
+        end: DateTime::<Utc>::default(),
+        partial: false,
+        authenticated_as: None,
+        ..request.clone()
+    };
+
+    format!("{:?}", scope)
+}
+
+async fn gather(
+    ctx: &Context,
+    cache: &DataCache,
+    request: DataRequest,
+    no_cache: bool,
+) -> Result<GatheredData> {
+    let scope_key = scope_key(&request);
+    let (start, end) = (request.start, request.end);
+    // Events that haven't happened yet can't be covered, so a window ending in the future is only
+    // covered up to now, and the most recent events are queried again by the next request reaching them.
+    let covered_end = cache.covered_until(start, end.min(Utc::now()));
+
+    let cached = match no_cache {
+        true => None,
+        false => cache.gathered.get(&scope_key),
+    };
+
+    if let Some(covered) = cached.map(|cached| cached.value) {
+        if let Some(windows) = missing_windows(&covered, start, end) {
+            let mut warnings = vec![];
+            let mut incomplete = false;
+            let mut covered = covered;
+
+            for (window_start, window_end) in windows {
+                let window = DataRequest {
+                    start: window_start,
+                    end: window_end,
+                    ..request.clone()
+                };
+
+                let mut data = gather_data(ctx, window).await?;
+
+                warnings.append(&mut data.warnings);
+                incomplete |= data.incomplete;
+                covered.start = covered.start.min(window_start);
+                covered.data.merge(data);
+            }
+
+            covered.end = covered.end.max(covered_end);
+
+            for warning in warnings {
+                if !covered.data.warnings.contains(&warning) {
+                    covered.data.warnings.push(warning);
+                }
+            }
+
+            let mut data = covered.data.within(start, end);
+            data.incomplete = incomplete;
+
+            if !incomplete {
+                cache.gathered.insert(scope_key, covered);
+            }
+
+            return Ok(data);
+        }
+    }
+
+    let data = gather_data(ctx, request).await?;
+
+    if !data.incomplete {
+        cache.gathered.insert(
+            scope_key,
+            CoveredData {
+                start,
+                end: covered_end,
+                data: data.clone(),
+            },
+        );
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[test]
+    fn test_select_fields() {
+        let response = DataResponse {
+            records: Some(vec![ResponseRecord {
+                repository: "repo-a".to_string(),
+                sha: "abcdef".to_string(),
+                ..Default::default()
+            }]),
+            warnings: vec!["warning".to_string()],
+            ..Default::default()
+        };
+
+        let selected = response.select_fields(&["repository".to_string()]);
+
+        assert_eq!(
+            selected,
+            serde_json::json!({"records": [{"repository": "repo-a"}], "warnings": ["warning"]})
+        );
+    }
+
+    #[test]
+    fn test_scope_key_ignores_window_and_partial() {
+        let request = DataRequest {
+            team: Some("team-a".to_string()),
+            start: Utc::now() - Duration::days(30),
+            end: Utc::now(),
+            ..Default::default()
+        };
+
+        let other = DataRequest {
+            start: Utc::now() - Duration::days(7),
+            partial: true,
+            ..request.clone()
+        };
+
+        let other_team = DataRequest {
+            team: Some("team-b".to_string()),
+            ..request.clone()
+        };
+
+        assert_eq!(scope_key(&request), scope_key(&other));
+        assert_ne!(scope_key(&request), scope_key(&other_team));
+    }
+
+    #[test]
+    fn test_validate_patterns_rejects_invalid_windows() {
+        let ctx = Context::new(AppConfig::default(), reqwest::Client::new());
+        let epoch = DateTime::<Utc>::from_timestamp(0, 0).unwrap();
+
+        let out_of_range = DataRequest {
+            start: "1500-01-01T00:00:00Z".parse().unwrap(),
+            end: epoch,
+            ..Default::default()
+        };
+        let inverted = DataRequest {
+            start: epoch + Duration::days(1),
+            end: epoch,
+            ..Default::default()
+        };
+        let valid = DataRequest {
+            start: epoch,
+            end: epoch + Duration::days(1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate_patterns(&ctx, &out_of_range),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            validate_patterns(&ctx, &inverted),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(validate_patterns(&ctx, &valid), Ok(()));
+    }
+
+    #[test]
+    fn test_ndjson_response() {
+        let records = [
+            ResponseRecord {
+                sha: "a".to_string(),
+                ..Default::default()
+            },
+            ResponseRecord {
+                sha: "b".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let lines: Vec<Vec<u8>> = records
+            .iter()
+            .map(|record| ndjson_line(record).unwrap().unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.ends_with(b"}\n")));
+
+        let response =
+            ndjson_response(&["Missing failures".to_string()], lines.into_iter().map(Ok));
+
+        assert_eq!(response.headers()[CONTENT_TYPE], NDJSON);
+        assert_eq!(response.headers()["X-Data-Warning"], "Missing failures");
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::{
+    helpers::{
+        auth::Authenticated,
+        breaker::error_status,
+        context::Context,
+        inflight::find_active_deployments,
+        loki::{gather_events, EventKind},
+        request::DataRequest,
+        response::ActiveDeploymentsResponse,
+    },
+    routes::{
+        auth,
+        data::validate_patterns,
+        teams::{expand_child_teams, TeamsCache},
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct ActiveParams {
+    pub team: Option<String>,
+    pub repository: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// How far back deployments are looked for when the request doesn't give a `start`.
+const DEFAULT_WINDOW_HOURS: i64 = 24;
+
+/// Returns the deployments started in the window that are still queued or in progress, for release
+/// dashboards to show what is rolling out right now.
+pub async fn handle_active_request(
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Query(params): Query<ActiveParams>,
+) -> Result<Json<ActiveDeploymentsResponse>, StatusCode> {
+    let end = params.end.unwrap_or_else(Utc::now);
+    let start = params
+        .start
+        .unwrap_or(end - Duration::hours(DEFAULT_WINDOW_HOURS));
+
+    if start >= end {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut request = DataRequest {
+        team: params.team,
+        repositories: params.repository.map(|repository| vec![repository]),
+        start,
+        end,
+        authenticated_as: auth::principal(authenticated),
+        ..Default::default()
+    };
+
+    validate_patterns(&ctx, &request)?;
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let (events, warnings) = match gather_events(&ctx, request, EventKind::DeploymentStatuses).await
+    {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("Gathering Deployment Statuses Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    Ok(Json(ActiveDeploymentsResponse {
+        deployments: find_active_deployments(events, start, end),
+        warnings,
+    }))
+}
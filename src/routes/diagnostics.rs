@@ -0,0 +1,21 @@
+use axum::{http::StatusCode, response::Json};
+use serde::Serialize;
+
+use crate::helpers::upstreams::{stats, Upstream, UpstreamStats, WINDOW};
+
+#[derive(Serialize, Debug)]
+pub struct UpstreamsResponse {
+    pub window_seconds: u64,
+    pub loki: UpstreamStats,
+    pub github: UpstreamStats,
+}
+
+pub async fn handle_upstreams_request() -> Result<Json<UpstreamsResponse>, StatusCode> {
+    let response = UpstreamsResponse {
+        window_seconds: WINDOW.as_secs(),
+        loki: stats(Upstream::Loki),
+        github: stats(Upstream::GitHub),
+    };
+
+    Ok(Json(response))
+}
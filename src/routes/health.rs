@@ -1,11 +1,79 @@
-use axum::{http::StatusCode, response::Json};
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Json, Response},
+};
 use serde::Serialize;
 
+use crate::helpers::{
+    breaker::{self, BreakerStatus},
+    context::Context,
+    prewarm::WarmupStatus,
+    readiness::{self, DependencyStatus, ReadinessConfig},
+};
+
+#[derive(Serialize, Debug)]
+pub struct HealthResponse {
+    /// The state of the circuit breaker around each upstream.
+    pub breakers: Vec<BreakerStatus>,
+}
+
+/// Reports the instance as alive. This is the liveness check, so it never waits on the cache prewarm or the
+/// upstreams, see `handle_ready_request`.
+pub async fn handle_request() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        breakers: breaker::statuses(),
+    })
+}
+
 #[derive(Serialize, Debug)]
-pub struct HealthResponse {}
+pub struct ReadyResponse {
+    pub ready: bool,
+    /// Whether the cache prewarm has completed, or `true` when `PREWARM_READINESS_GATE` is disabled.
+    pub warmed_up: bool,
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+/// Probes Loki and GitHub, and responds with `503` when Loki, or GitHub when `READY_REQUIRE_GITHUB` is set,
+/// can't be reached or the cache prewarm hasn't completed, so traffic is only routed to instances that can
+/// serve it.
+pub async fn handle_ready_request(
+    State(ctx): State<Context>,
+    State(status): State<WarmupStatus>,
+    State(config): State<ReadinessConfig>,
+) -> (StatusCode, Json<ReadyResponse>) {
+    let dependencies = readiness::check(&ctx, &config).await;
+    let warmed_up = status.is_ready();
+    let ready = warmed_up && dependencies.iter().all(DependencyStatus::is_ready);
+
+    let code = match ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        code,
+        Json(ReadyResponse {
+            ready,
+            warmed_up,
+            dependencies,
+        }),
+    )
+}
+
+/// Adds a `Retry-After` header to `503` responses while an upstream's circuit breaker is open, with the time
+/// until it lets calls through again.
+pub async fn add_retry_after(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
 
-pub async fn handle_request() -> Result<Json<HealthResponse>, StatusCode> {
-    let response: HealthResponse = HealthResponse {};
+    if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+        if let Some(seconds) = breaker::retry_after() {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+    }
 
-    Ok(Json(response))
+    response
 }
@@ -0,0 +1,88 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    helpers::{
+        auth::Authenticated,
+        context::Context,
+        request::DataRequest,
+        response::{FailureRecord, IncidentsResponse},
+    },
+    routes::{
+        auth,
+        data::{get_records, validate_patterns, DataCache},
+        teams::{expand_child_teams, TeamsCache},
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct IncidentParams {
+    /// When `true`, only the incidents that are still open are returned.
+    pub open: Option<bool>,
+}
+
+/// Returns the failures linked to the deployments of a request as incidents, oldest first, so unresolved
+/// failures can be reviewed without reading through every deployment.
+pub async fn handle_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    Query(params): Query<IncidentParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<IncidentsResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    validate_patterns(&ctx, &request)?;
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let mut incidents: Vec<FailureRecord> = records
+        .iter()
+        .filter_map(|record| record.failure())
+        .filter(|incident| !params.open.unwrap_or_default() || incident.open)
+        .collect();
+
+    incidents.sort_by(|l, r| {
+        (l.failed_at, &l.repository, &l.sha).cmp(&(r.failed_at, &r.repository, &r.sha))
+    });
+
+    Ok(Json(IncidentsResponse {
+        open: incidents.iter().filter(|incident| incident.open).count(),
+        incidents,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::AppConfig, helpers::cache::CacheConfig, routes::data::DataCaches};
+    use dashmap::DashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_request_rejects_invalid_patterns() {
+        let request = DataRequest {
+            repositories: Some(vec!["re:(svc".to_string()]),
+            ..Default::default()
+        };
+
+        let result = handle_request(
+            State(Arc::new(DataCaches::new(CacheConfig::default()))),
+            State(Arc::new(DashMap::new())),
+            State(Context::new(AppConfig::default(), reqwest::Client::new())),
+            Query(IncidentParams { open: None }),
+            None,
+            Json(request),
+        )
+        .await;
+
+        assert_eq!(result.err(), Some(StatusCode::BAD_REQUEST));
+    }
+}
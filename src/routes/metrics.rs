@@ -0,0 +1,626 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{collections::HashSet, str::FromStr};
+
+use crate::{
+    helpers::{
+        anomalies::{detect, AnomalyConfig},
+        auth::Authenticated,
+        buckets::{bucket_records, BucketSize},
+        cohorts::{assign_cohorts, CohortKind, CohortStacks, UNKNOWN_COHORT},
+        context::Context,
+        forecast::{forecast, ForecastPoint},
+        metrics::{
+            group_by, group_by_grouping, group_by_team, summarize, summarize_restores, trend,
+            Grouping, MetricsSummary,
+        },
+        request::{CompareRequest, DataRequest, WindowRequest},
+        response::{
+            ChangeFailureBucket, ChangeFailureRateResponse, ChangeFailureRateSeries, CohortSummary,
+            CohortsResponse, CompareResponse, DeploymentFrequencyResponse,
+            DeploymentFrequencySeries, ForecastResponse, FrequencyBucket, LeadTimeBucket,
+            LeadTimeResponse, LeadTimeSeries, MttrResponse, MttrSeries, OrgRollupResponse,
+            ScorecardResponse, ScoredSummary, SelectionSummary, SummaryResponse, TeamScore,
+            TrendsResponse,
+        },
+        scoring::ScoringModel,
+        targets::TargetsConfig,
+    },
+    routes::{
+        auth,
+        data::{get_records, DataCache},
+        repositories::{get_org_repository_records, RepositoriesCache},
+        teams::{expand_child_teams, TeamsCache},
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct CohortParams {
+    pub group_by: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GroupParams {
+    pub group_by: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeriesParams {
+    pub group_by: Option<String>,
+    pub bucket: Option<String>,
+}
+
+fn parse_grouping(value: Option<&str>) -> Result<Grouping, StatusCode> {
+    match value {
+        None | Some("repository") => Ok(Grouping::Repository),
+        Some("team") => Ok(Grouping::Team),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+impl SeriesParams {
+    pub fn grouping(&self) -> Result<Grouping, StatusCode> {
+        parse_grouping(self.group_by.as_deref())
+    }
+
+    pub fn bucket(&self) -> Result<BucketSize, StatusCode> {
+        match self.bucket.as_deref().map(BucketSize::from_str) {
+            None => Ok(BucketSize::default()),
+            Some(Ok(value)) => Ok(value),
+            Some(Err(e)) => {
+                tracing::error!("Invalid Bucket: {:?}", e);
+                Err(StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ForecastParams {
+    pub weeks: Option<usize>,
+}
+
+const MAX_FORECAST_WEEKS: usize = 52;
+
+fn window_days(request: &DataRequest) -> f64 {
+    (request.end - request.start).num_seconds() as f64 / 86_400.0
+}
+
+/// Returns the four DORA metrics over every record in the request, so dashboards don't have to aggregate
+/// `/data` themselves.
+pub async fn handle_summary_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(targets): State<TargetsConfig>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<SummaryResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let team = request.team.clone();
+    let days = window_days(&request);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let metrics = summarize(&records.iter().collect::<Vec<_>>(), days);
+
+    Ok(Json(SummaryResponse {
+        targets: targets.evaluate(team.as_deref(), &metrics),
+        metrics,
+    }))
+}
+
+async fn summarize_selection(
+    ctx: &Context,
+    cache: &DataCache,
+    teams_cache: &TeamsCache,
+    mut request: DataRequest,
+) -> Result<SelectionSummary, StatusCode> {
+    expand_child_teams(ctx, teams_cache, &mut request).await?;
+
+    let team = request.team.clone();
+    let (start, end) = (request.start, request.end);
+    let days = window_days(&request);
+    let records = get_records(ctx, cache, request).await?;
+
+    Ok(SelectionSummary {
+        team,
+        start,
+        end,
+        metrics: summarize(&records.iter().collect::<Vec<_>>(), days),
+    })
+}
+
+/// Returns the four DORA metrics for two selections side by side, with the change from the baseline to
+/// the comparison, for comparing teams or time ranges.
+pub async fn handle_compare_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(request): Json<CompareRequest>,
+) -> Result<Json<CompareResponse>, StatusCode> {
+    let authenticated_as = auth::principal(authenticated);
+    let baseline = DataRequest {
+        authenticated_as: authenticated_as.clone(),
+        ..request.baseline
+    };
+    let comparison = DataRequest {
+        authenticated_as,
+        ..request.comparison
+    };
+
+    let (baseline, comparison) = tokio::join!(
+        summarize_selection(&ctx, &cache, &teams_cache, baseline),
+        summarize_selection(&ctx, &cache, &teams_cache, comparison)
+    );
+
+    let (baseline, comparison) = (baseline?, comparison?);
+
+    Ok(Json(CompareResponse {
+        changes: trend(&comparison.metrics, &baseline.metrics),
+        baseline,
+        comparison,
+    }))
+}
+
+/// Returns the four DORA metrics across every team and repository in the organization.
+///
+/// Every deployment is counted once in a single pool, so teams and repositories are weighted by how often
+/// they deploy rather than averaged equally.
+pub async fn handle_org_rollup_request(
+    State(cache): State<DataCache>,
+    State(targets): State<TargetsConfig>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(window): Json<WindowRequest>,
+) -> Result<Json<OrgRollupResponse>, StatusCode> {
+    let request = DataRequest {
+        authenticated_as: auth::principal(authenticated),
+        ..DataRequest::from(window)
+    };
+    let days = window_days(&request);
+    let records = get_records(&ctx, &cache, request).await?;
+    let metrics = summarize(&records.iter().collect::<Vec<_>>(), days);
+
+    Ok(Json(OrgRollupResponse {
+        teams: group_by_team(&records).len(),
+        repositories: records
+            .iter()
+            .map(|record| record.repository.as_str())
+            .collect::<HashSet<&str>>()
+            .len(),
+        targets: targets.evaluate(None, &metrics),
+        metrics,
+    }))
+}
+
+/// Returns the four DORA metrics and the health index for the requested window and the window of equal
+/// length before it, with the change in each metric.
+pub async fn handle_trends_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(model): State<ScoringModel>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<TrendsResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let days = window_days(&request);
+    let previous_request = DataRequest {
+        start: request.start - (request.end - request.start),
+        end: request.start,
+        ..request.clone()
+    };
+
+    let previous_start = previous_request.start;
+    let previous_end = previous_request.end;
+
+    let (records, previous_records) = tokio::join!(
+        get_records(&ctx, &cache, request),
+        get_records(&ctx, &cache, previous_request)
+    );
+
+    let current = summarize(&records?.iter().collect::<Vec<_>>(), days);
+    let previous = summarize(&previous_records?.iter().collect::<Vec<_>>(), days);
+
+    Ok(Json(TrendsResponse {
+        changes: trend(&current, &previous),
+        current: ScoredSummary {
+            score: model.score(&current),
+            metrics: current,
+        },
+        previous: ScoredSummary {
+            score: model.score(&previous),
+            metrics: previous,
+        },
+        previous_start,
+        previous_end,
+    }))
+}
+
+pub async fn handle_scorecard_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(model): State<ScoringModel>,
+    State(targets): State<TargetsConfig>,
+    State(ctx): State<Context>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<ScorecardResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let days = window_days(&request);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let teams = group_by_team(&records)
+        .into_iter()
+        .map(|(team, team_records)| {
+            let metrics = summarize(&team_records, days);
+
+            TeamScore {
+                score: model.score(&metrics),
+                targets: targets.evaluate(Some(&team), &metrics),
+                team,
+                metrics,
+            }
+        })
+        .collect();
+
+    Ok(Json(ScorecardResponse { teams }))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_cohorts_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(repositories_cache): State<RepositoriesCache>,
+    State(stacks): State<CohortStacks>,
+    State(ctx): State<Context>,
+    Query(params): Query<CohortParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<CohortsResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let kind = match params.group_by.as_deref() {
+        None | Some("language") => CohortKind::Language,
+        Some("stack") => CohortKind::Stack,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let days = window_days(&request);
+    let repositories = get_org_repository_records(&ctx, &repositories_cache).await?;
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let cohorts = assign_cohorts(&repositories, kind, &stacks);
+
+    let grouped = group_by(&records, |record| {
+        cohorts
+            .get(&record.repository)
+            .cloned()
+            .unwrap_or(UNKNOWN_COHORT.to_string())
+    });
+
+    let cohorts = grouped
+        .into_iter()
+        .map(|(cohort, cohort_records)| CohortSummary {
+            cohort,
+            repositories: cohort_records
+                .iter()
+                .map(|record| record.repository.as_str())
+                .collect::<HashSet<&str>>()
+                .len(),
+            metrics: summarize(&cohort_records, days),
+        })
+        .collect();
+
+    Ok(Json(CohortsResponse { cohorts }))
+}
+
+/// Returns whether the request's window covers each bucket in full. The partial first and last buckets are
+/// left out of anomaly detection, since their fewer deployments would be flagged as a drop.
+fn full_buckets(bucket: BucketSize, starts: &[DateTime<Utc>], request: &DataRequest) -> Vec<bool> {
+    starts
+        .iter()
+        .map(|start| bucket.is_full(*start, request.start, request.end))
+        .collect()
+}
+
+/// Returns deployment counts per team or repository for each time bucket, annotated with the buckets
+/// that deviate significantly from the ones before them.
+pub async fn handle_deployment_frequency_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(anomaly_config): State<AnomalyConfig>,
+    State(ctx): State<Context>,
+    Query(params): Query<SeriesParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<DeploymentFrequencyResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let grouping = params.grouping()?;
+    let bucket = params.bucket()?;
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let starts = bucket.starts(request.start, request.end);
+    let full = full_buckets(bucket, &starts, &request);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let series = group_by_grouping(&records, grouping)
+        .into_iter()
+        .map(|((team, repository), group)| {
+            let buckets: Vec<FrequencyBucket> = starts
+                .iter()
+                .zip(bucket_records(&starts, &group))
+                .map(|(start, bucket)| FrequencyBucket {
+                    start: *start,
+                    deployments: bucket.len(),
+                })
+                .collect();
+
+            let values: Vec<(DateTime<Utc>, Option<f64>)> = buckets
+                .iter()
+                .zip(&full)
+                .map(|(bucket, full)| (bucket.start, full.then_some(bucket.deployments as f64)))
+                .collect();
+
+            DeploymentFrequencySeries {
+                team,
+                repository,
+                annotations: detect("deployments", &values, &anomaly_config),
+                buckets,
+            }
+        })
+        .collect();
+
+    Ok(Json(DeploymentFrequencyResponse { bucket, series }))
+}
+
+/// Returns the change failure rate per team or repository, in total and for each time bucket. A deployment
+/// counts as failed when it is linked to a failure, see `find_failures_per_deployment`. Buckets whose rate
+/// deviates significantly from the ones before them are annotated.
+pub async fn handle_change_failure_rate_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(anomaly_config): State<AnomalyConfig>,
+    State(ctx): State<Context>,
+    Query(params): Query<SeriesParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<ChangeFailureRateResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let grouping = params.grouping()?;
+    let bucket = params.bucket()?;
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let days = window_days(&request);
+    let starts = bucket.starts(request.start, request.end);
+    let full = full_buckets(bucket, &starts, &request);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let series = group_by_grouping(&records, grouping)
+        .into_iter()
+        .map(|((team, repository), group)| {
+            let total = summarize(&group, days);
+
+            let buckets: Vec<ChangeFailureBucket> = starts
+                .iter()
+                .zip(bucket_records(&starts, &group))
+                .map(|(start, bucket)| {
+                    let summary = summarize(&bucket, days);
+
+                    ChangeFailureBucket {
+                        start: *start,
+                        deployments: summary.deployments,
+                        failed_deployments: summary.failures,
+                        change_failure_rate: summary.change_failure_rate,
+                    }
+                })
+                .collect();
+
+            let values: Vec<(DateTime<Utc>, Option<f64>)> = buckets
+                .iter()
+                .zip(&full)
+                .map(|(bucket, full)| (bucket.start, bucket.change_failure_rate.filter(|_| *full)))
+                .collect();
+
+            ChangeFailureRateSeries {
+                team,
+                repository,
+                deployments: total.deployments,
+                failed_deployments: total.failures,
+                change_failure_rate: total.change_failure_rate,
+                annotations: detect("change_failure_rate", &values, &anomaly_config),
+                buckets,
+            }
+        })
+        .collect();
+
+    Ok(Json(ChangeFailureRateResponse { bucket, series }))
+}
+
+/// Returns the median lead time for changes per team or repository, in total and for each time bucket,
+/// from the merges linked to the deployments in each bucket. Buckets whose lead time deviates significantly
+/// from the ones before them are annotated.
+pub async fn handle_lead_time_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(anomaly_config): State<AnomalyConfig>,
+    State(ctx): State<Context>,
+    Query(params): Query<SeriesParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<LeadTimeResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let grouping = params.grouping()?;
+    let bucket = params.bucket()?;
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let days = window_days(&request);
+    let starts = bucket.starts(request.start, request.end);
+    let full = full_buckets(bucket, &starts, &request);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let series = group_by_grouping(&records, grouping)
+        .into_iter()
+        .map(|((team, repository), group)| {
+            let total = summarize(&group, days);
+
+            let buckets: Vec<LeadTimeBucket> = starts
+                .iter()
+                .zip(bucket_records(&starts, &group))
+                .map(|(start, bucket)| {
+                    let summary = summarize(&bucket, days);
+
+                    LeadTimeBucket {
+                        start: *start,
+                        changes: summary.lead_time_count,
+                        lead_time_hours: summary.lead_time_hours,
+                        lead_time: summary.lead_time,
+                    }
+                })
+                .collect();
+
+            let values: Vec<(DateTime<Utc>, Option<f64>)> = buckets
+                .iter()
+                .zip(&full)
+                .map(|(bucket, full)| (bucket.start, bucket.lead_time_hours.filter(|_| *full)))
+                .collect();
+
+            LeadTimeSeries {
+                team,
+                repository,
+                changes: total.lead_time_count,
+                lead_time_hours: total.lead_time_hours,
+                lead_time: total.lead_time,
+                annotations: detect("lead_time_hours", &values, &anomaly_config),
+                buckets,
+            }
+        })
+        .collect();
+
+    Ok(Json(LeadTimeResponse { bucket, series }))
+}
+
+pub async fn handle_mttr_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    Query(params): Query<GroupParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<MttrResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let grouping = parse_grouping(params.group_by.as_deref())?;
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let series = group_by_grouping(&records, grouping)
+        .into_iter()
+        .map(|((team, repository), group)| MttrSeries {
+            team,
+            repository,
+            summary: summarize_restores(&group),
+        })
+        .collect();
+
+    Ok(Json(MttrResponse { series }))
+}
+
+/// Fits a trend line over the weekly values of each DORA metric in the requested window, and projects
+/// it over the following `weeks`.
+pub async fn handle_forecast_request(
+    State(cache): State<DataCache>,
+    State(teams_cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+    Query(params): Query<ForecastParams>,
+    authenticated: Option<Extension<Authenticated>>,
+    Json(mut request): Json<DataRequest>,
+) -> Result<Json<ForecastResponse>, StatusCode> {
+    request.authenticated_as = auth::principal(authenticated);
+
+    let weeks = params.weeks.unwrap_or(4);
+
+    if weeks == 0 || weeks > MAX_FORECAST_WEEKS {
+        tracing::error!("Invalid Forecast Weeks: {}", weeks);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    expand_child_teams(&ctx, &teams_cache, &mut request).await?;
+
+    let (start, end) = (request.start, request.end);
+    let starts = BucketSize::Week.starts(start, end);
+    let records = get_records(&ctx, &cache, request).await?;
+
+    let weekly: Vec<(DateTime<Utc>, MetricsSummary)> = starts
+        .iter()
+        .enumerate()
+        .zip(bucket_records(&starts, &records.iter().collect::<Vec<_>>()))
+        .map(|((index, week), bucket)| {
+            let covered_from = (*week).max(start);
+            let covered_to = starts.get(index + 1).copied().unwrap_or(end).min(end);
+            let days = (covered_to - covered_from).num_seconds() as f64 / 86_400.0;
+
+            (*week, summarize(&bucket, days))
+        })
+        .collect();
+
+    let history = |value: fn(&MetricsSummary) -> Option<f64>| -> Vec<ForecastPoint> {
+        weekly
+            .iter()
+            .map(|(start, summary)| ForecastPoint {
+                start: *start,
+                value: value(summary),
+            })
+            .collect()
+    };
+
+    Ok(Json(ForecastResponse {
+        weeks,
+        deployment_frequency: forecast(
+            history(|summary| Some(summary.deployment_frequency)),
+            weeks,
+            (0.0, f64::MAX),
+        ),
+        lead_time_hours: forecast(
+            history(|summary| summary.lead_time_hours),
+            weeks,
+            (0.0, f64::MAX),
+        ),
+        change_failure_rate: forecast(
+            history(|summary| summary.change_failure_rate),
+            weeks,
+            (0.0, 100.0),
+        ),
+        mttr_hours: forecast(
+            history(|summary| summary.mttr_hours),
+            weeks,
+            (0.0, f64::MAX),
+        ),
+    }))
+}
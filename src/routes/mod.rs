@@ -1,3 +1,13 @@
+pub mod admin;
+pub mod auth;
+pub mod changes;
 pub mod data;
+pub mod deployments;
+pub mod diagnostics;
 pub mod health;
+pub mod incidents;
+pub mod metrics;
+pub mod prometheus;
+pub mod repositories;
+pub mod request_id;
 pub mod teams;
@@ -0,0 +1,232 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    helpers::{
+        cache::CacheStats,
+        context::Context,
+        instrumentation,
+        metrics::{group_by_grouping, summarize, Grouping, MetricsSummary},
+        prometheus::{ExportConfig, Exposition},
+        request::DataRequest,
+        response::ResponseRecord,
+    },
+    routes::data::{get_records, DataCache},
+};
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The records the DORA gauges were last calculated from, and when they were gathered.
+static LAST_RECORDS: Mutex<Option<(Instant, Arc<Vec<ResponseRecord>>)>> = Mutex::new(None);
+
+/// Returns the records the DORA gauges are calculated from, gathering them again once they are older than
+/// `METRICS_REFRESH_SECONDS`. When gathering fails the last records are reused, or `None` is returned when
+/// there are none yet.
+async fn dora_records(
+    ctx: &Context,
+    cache: &DataCache,
+    config: ExportConfig,
+) -> Option<Arc<Vec<ResponseRecord>>> {
+    let last = LAST_RECORDS.lock().unwrap().clone();
+
+    if let Some((gathered_at, records)) = &last {
+        if gathered_at.elapsed() < config.refresh {
+            return Some(records.clone());
+        }
+    }
+
+    let request = DataRequest::trailing_days(None, config.window_days, Utc::now());
+
+    match get_records(ctx, cache, request).await {
+        Ok(records) => {
+            let records = Arc::new(records);
+            *LAST_RECORDS.lock().unwrap() = Some((Instant::now(), records.clone()));
+
+            Some(records)
+        }
+        Err(status) => {
+            tracing::error!("DORA Gauges Not Refreshed: {}", status);
+            last.map(|(_, records)| records)
+        }
+    }
+}
+
+/// Exports the API's own request, cache and Loki metrics, followed by the DORA metrics for each team and
+/// repository as Prometheus gauges.
+///
+/// The DORA metrics are calculated over the trailing `METRICS_WINDOW_DAYS` and reused for
+/// `METRICS_REFRESH_SECONDS`, so scrapes don't query Loki each time. When they can't be gathered the last
+/// gauges are exported again, or left out until they first succeed, without failing the scrape.
+pub async fn handle_request(
+    State(cache): State<DataCache>,
+    State(ctx): State<Context>,
+    State(config): State<ExportConfig>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut exposition = Exposition::default();
+
+    write_cache_counters(
+        &mut exposition,
+        &[
+            ("responses", cache.responses.stats()),
+            ("gathered", cache.gathered.stats()),
+        ],
+    );
+    instrumentation::write(&mut exposition);
+
+    if let Some(records) = dora_records(&ctx, &cache, config).await {
+        write_dora_gauges(&mut exposition, &records, config.window_days as f64);
+    }
+
+    Ok((
+        [(CONTENT_TYPE, CONTENT_TYPE_TEXT)],
+        exposition.into_string(),
+    ))
+}
+
+/// Records the duration of every request, labelled with its matched route so unmatched paths can't
+/// create unbounded series.
+pub async fn record_request_metrics(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or("unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    instrumentation::record_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+
+    response
+}
+
+fn write_cache_counters(exposition: &mut Exposition, caches: &[(&str, CacheStats)]) {
+    exposition.counter(
+        "dora_api_cache_hits_total",
+        "Lookups served from the data cache.",
+        caches
+            .iter()
+            .map(|(cache, stats)| (vec![("cache", *cache)], Some(stats.hits as f64))),
+    );
+    exposition.counter(
+        "dora_api_cache_misses_total",
+        "Lookups not found in the data cache.",
+        caches
+            .iter()
+            .map(|(cache, stats)| (vec![("cache", *cache)], Some(stats.misses as f64))),
+    );
+}
+
+fn write_dora_gauges(exposition: &mut Exposition, records: &[ResponseRecord], days: f64) {
+    let grouped = group_by_grouping(records, Grouping::Repository);
+
+    let summaries: Vec<(Vec<(&str, &str)>, MetricsSummary)> = grouped
+        .iter()
+        .map(|((team, repository), records)| {
+            (
+                vec![
+                    ("team", team.as_str()),
+                    ("repository", repository.as_deref().unwrap_or_default()),
+                ],
+                summarize(records, days),
+            )
+        })
+        .collect();
+
+    let gauge = |exposition: &mut Exposition,
+                 name: &str,
+                 help: &str,
+                 value: fn(&MetricsSummary) -> Option<f64>| {
+        exposition.gauge(
+            name,
+            help,
+            summaries
+                .iter()
+                .map(|(labels, summary)| (labels.clone(), value(summary))),
+        );
+    };
+
+    gauge(
+        exposition,
+        "dora_deployment_frequency",
+        "Deployments per day over the metrics window.",
+        |summary| Some(summary.deployment_frequency),
+    );
+    gauge(
+        exposition,
+        "dora_lead_time_seconds",
+        "Median time from merge to deployment over the metrics window.",
+        |summary| summary.lead_time.as_ref().map(|d| d.seconds as f64),
+    );
+    gauge(
+        exposition,
+        "dora_change_failure_ratio",
+        "Ratio of deployments linked to a failure over the metrics window.",
+        |summary| summary.change_failure_rate.map(|rate| rate / 100.0),
+    );
+    gauge(
+        exposition,
+        "dora_mttr_seconds",
+        "Median time from failure to fix over the metrics window.",
+        |summary| summary.mttr.as_ref().map(|d| d.seconds as f64),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_dora_gauges() {
+        let now = Utc::now();
+        let records = [
+            ResponseRecord {
+                team: "team-a".to_string(),
+                repository: "repo-a".to_string(),
+                created_at: now,
+                merged_at: Some(now - Duration::hours(1)),
+                ..Default::default()
+            },
+            ResponseRecord {
+                team: "team-a".to_string(),
+                repository: "repo-b".to_string(),
+                created_at: now,
+                failed_at: Some(now),
+                ..Default::default()
+            },
+        ];
+
+        let mut exposition = Exposition::default();
+
+        write_dora_gauges(&mut exposition, &records, 2.0);
+
+        let body = exposition.into_string();
+
+        assert!(
+            body.contains("dora_deployment_frequency{team=\"team-a\",repository=\"repo-a\"} 0.5\n")
+        );
+        assert!(
+            body.contains("dora_lead_time_seconds{team=\"team-a\",repository=\"repo-a\"} 3600\n")
+        );
+        assert!(
+            body.contains("dora_change_failure_ratio{team=\"team-a\",repository=\"repo-b\"} 1\n")
+        );
+        assert!(!body.contains("dora_mttr_seconds{"));
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::helpers::{
+    breaker::error_status,
+    context::Context,
+    github_api::{get_org_and_token, get_paginated},
+    response::{RepositoriesResponse, RepositoryRecord},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitHubRepository {
+    name: String,
+    archived: bool,
+    language: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+impl From<GitHubRepository> for RepositoryRecord {
+    fn from(repository: GitHubRepository) -> Self {
+        RepositoryRecord {
+            name: repository.name,
+            archived: repository.archived,
+            language: repository.language,
+            topics: repository.topics,
+        }
+    }
+}
+
+pub type RepositoriesCache = Arc<DashMap<String, Vec<RepositoryRecord>>>;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RequestParams {
+    pub topic: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Filters repositories by topic and archived status.
+///
+/// # Arguments
+///
+/// * `repositories` - The repositories to filter.
+/// * `params` - The optional `topic` the repository must have and the `archived` status it must match.
+///
+/// # Returns
+///
+/// A `Vec<RepositoryRecord>` containing only the repositories matching every supplied filter.
+fn filter_repositories(
+    repositories: Vec<RepositoryRecord>,
+    params: &RequestParams,
+) -> Vec<RepositoryRecord> {
+    repositories
+        .into_iter()
+        .filter(|repository| match &params.topic {
+            Some(topic) => repository.topics.contains(topic),
+            None => true,
+        })
+        .filter(|repository| match params.archived {
+            Some(archived) => repository.archived == archived,
+            None => true,
+        })
+        .collect()
+}
+
+/// Accepts team slugs as GitHub makes them, letters, digits, `-` and `_`, so a slug can't change the path of the
+/// GitHub request it is sent in.
+fn is_valid_slug(team: &str) -> bool {
+    !team.is_empty()
+        && team
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+async fn get_org_repositories(
+    ctx: &Context,
+    gh_org: &str,
+    gh_token: &str,
+) -> Result<Vec<GitHubRepository>> {
+    let url = format!("https://api.github.com/orgs/{}/repos", gh_org);
+
+    get_paginated(&ctx.client, url, gh_token).await
+}
+
+async fn get_team_repositories(
+    ctx: &Context,
+    gh_org: &str,
+    gh_token: &str,
+    team: &str,
+) -> Result<Vec<GitHubRepository>> {
+    let url = format!(
+        "https://api.github.com/orgs/{}/teams/{}/repos",
+        gh_org, team
+    );
+
+    get_paginated(&ctx.client, url, gh_token).await
+}
+
+pub async fn handle_request(
+    State(cache): State<RepositoriesCache>,
+    State(ctx): State<Context>,
+    Query(params): Query<RequestParams>,
+) -> Result<Json<RepositoriesResponse>, StatusCode> {
+    let records = get_org_repository_records(&ctx, &cache).await?;
+
+    Ok(Json(RepositoriesResponse {
+        repositories: filter_repositories(records, &params),
+    }))
+}
+
+/// Returns the repositories of the organization, from the cache when available.
+pub async fn get_org_repository_records(
+    ctx: &Context,
+    cache: &RepositoriesCache,
+) -> Result<Vec<RepositoryRecord>, StatusCode> {
+    let request_key = "org".to_string();
+
+    if let Some(cached_response) = cache.get(&request_key) {
+        return Ok(cached_response.clone());
+    }
+
+    let (gh_org, gh_token) = match get_org_and_token(&ctx.config.github) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let repositories = match get_org_repositories(ctx, &gh_org, &gh_token).await {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("GitHub Request Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    let records: Vec<RepositoryRecord> = repositories
+        .into_iter()
+        .map(RepositoryRecord::from)
+        .collect();
+
+    cache.insert(request_key, records.clone());
+
+    Ok(records)
+}
+
+pub async fn handle_team_request(
+    State(cache): State<RepositoriesCache>,
+    State(ctx): State<Context>,
+    Path(team): Path<String>,
+) -> Result<Json<RepositoriesResponse>, StatusCode> {
+    if !is_valid_slug(&team) {
+        tracing::error!("Invalid team slug: {}", team);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let request_key = format!("team:{}", team);
+
+    if let Some(cached_response) = cache.get(&request_key) {
+        return Ok(Json(RepositoriesResponse {
+            repositories: cached_response.clone(),
+        }));
+    }
+
+    let (gh_org, gh_token) = match get_org_and_token(&ctx.config.github) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let repositories = match get_team_repositories(&ctx, &gh_org, &gh_token, &team).await {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!("GitHub Request Failed: {:?}", e);
+            return Err(error_status(&e));
+        }
+    };
+
+    let records: Vec<RepositoryRecord> = repositories
+        .into_iter()
+        .map(RepositoryRecord::from)
+        .collect();
+
+    cache.insert(request_key, records.clone());
+
+    Ok(Json(RepositoriesResponse {
+        repositories: records,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    fn repositories() -> Vec<RepositoryRecord> {
+        vec![
+            RepositoryRecord {
+                name: "repo-a".to_string(),
+                topics: vec!["platform".to_string()],
+                ..Default::default()
+            },
+            RepositoryRecord {
+                name: "repo-b".to_string(),
+                archived: true,
+                topics: vec!["platform".to_string()],
+                ..Default::default()
+            },
+            RepositoryRecord {
+                name: "repo-c".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_filter_repositories_without_filters() {
+        let result = filter_repositories(repositories(), &RequestParams::default());
+
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn test_filter_repositories_by_topic_and_archived() {
+        let params = RequestParams {
+            topic: Some("platform".to_string()),
+            archived: Some(false),
+        };
+
+        let result = filter_repositories(repositories(), &params);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].name, "repo-a");
+    }
+
+    #[tokio::test]
+    async fn test_team_request_rejects_invalid_slug() {
+        let cache: RepositoriesCache = Arc::new(DashMap::new());
+
+        for team in ["../../user", "team?per_page=1", "team%2Fother", ""] {
+            let result = handle_team_request(
+                State(cache.clone()),
+                State(Context::new(AppConfig::default(), reqwest::Client::new())),
+                Path(team.to_string()),
+            )
+            .await;
+
+            assert_eq!(result.err(), Some(StatusCode::BAD_REQUEST), "{}", team);
+        }
+
+        assert!(is_valid_slug("platform-team_2"));
+    }
+}
@@ -0,0 +1,87 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderName, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::telemetry;
+
+pub static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The body given to error responses that have none.
+#[derive(Serialize, Debug)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+    request_id: &'a str,
+}
+
+/// Handles each request with the ID from its `X-Request-Id` header, e.g. set by the dashboard or a gateway, or a
+/// generated one when it has none, and returns the ID in the response's `X-Request-Id` header. The ID is
+/// logged with the request and sent on to Loki and GitHub, see `http::trace_headers`.
+///
+/// Error responses without a body are given a JSON body naming the error and the request ID, so a failure a
+/// user reports can be found in the logs.
+pub async fn propagate(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut response = telemetry::with_request_id(request_id.clone(), next.run(request)).await;
+    let status = response.status();
+
+    if (status.is_client_error() || status.is_server_error())
+        && response.body().size_hint().exact() == Some(0)
+    {
+        let body = serde_json::to_vec(&ErrorResponse {
+            error: status.canonical_reason().unwrap_or_default(),
+            request_id: &request_id,
+        })
+        .unwrap_or_default();
+
+        let (mut parts, _) = response.into_parts();
+        parts.headers.remove(CONTENT_LENGTH);
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        response = Response::from_parts(parts, Body::from(body));
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID.clone(), value);
+    }
+
+    response
+}
+
+/// Accepts IDs of up to 128 visible ASCII characters, so an ID can't break a log line or a header.
+fn is_valid(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= 128
+        && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("4bf92f35-77b3-4da6-a3ce-929d0e0e4736"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("two words"));
+        assert!(!is_valid("line\nbreak"));
+        assert!(!is_valid(&"a".repeat(129)));
+    }
+}
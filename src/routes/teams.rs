@@ -1,116 +1,278 @@
-use anyhow::{anyhow, Result};
-use axum::{extract::Extension, http::StatusCode, response::Json};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, response::Json};
 use dashmap::DashMap;
-use reqwest::Error;
 use serde::Deserialize;
-use std::{env, sync::Arc};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::helpers::response::TeamsResponse;
+use crate::{
+    config::GithubConfig,
+    helpers::{
+        breaker::error_status,
+        context::Context,
+        github_api::{get_org_and_token, get_paginated},
+        request::DataRequest,
+        response::{TeamParent, TeamRecord, TeamsResponse, TeamsResponseV2},
+    },
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitHubTeamParent {
+    id: u64,
+    name: String,
+    slug: String,
+}
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct GitHubTeam {
+    id: u64,
     name: String,
+    slug: String,
+    parent: Option<GitHubTeamParent>,
 }
 
-pub type TeamsCache = Arc<DashMap<String, TeamsResponse>>;
+impl From<GitHubTeam> for TeamRecord {
+    fn from(team: GitHubTeam) -> Self {
+        TeamRecord {
+            id: team.id,
+            name: team.name,
+            slug: team.slug,
+            parent: team.parent.map(|parent| TeamParent {
+                id: parent.id,
+                name: parent.name,
+                slug: parent.slug,
+            }),
+            children: vec![],
+        }
+    }
+}
 
-async fn get_teams(gh_org: &String, gh_token: &String, page: usize) -> Result<Vec<GitHubTeam>> {
-    let client = reqwest::Client::new();
+#[derive(Debug, Clone)]
+pub struct CachedTeams {
+    pub teams: Vec<TeamRecord>,
+    pub fetched_at: Instant,
+}
+
+pub type TeamsCache = Arc<DashMap<String, CachedTeams>>;
+
+const TEAMS_KEY: &str = "teams";
+
+/// Retrieves how long the teams cache is considered fresh.
+///
+/// This function reads the configured `TEAMS_CACHE_TTL_SECONDS`, defaulting to one hour if it is not set or
+/// is zero.
+pub fn get_cache_ttl(github: &GithubConfig) -> Duration {
+    Some(github.teams_cache_ttl())
+        .filter(|ttl| !ttl.is_zero())
+        .unwrap_or(Duration::from_secs(3600))
+}
+
+async fn get_teams(ctx: &Context, gh_org: &str, gh_token: &str) -> Result<Vec<GitHubTeam>> {
     let url = format!("https://api.github.com/orgs/{}/teams", gh_org);
 
-    let response_result = client
-        .get(url)
-        .query(&[("page", page), ("per_page", 100)])
-        .header("User-Agent", "request")
-        .header("Authorization", format!("token {}", gh_token))
-        .header("Accept", "application/vnd.github+json")
-        .header("X-GitHub-Api-Version", "2022-11-28")
-        .send()
-        .await;
-
-    match response_result {
-        Ok(response) => {
-            let status = response.status();
-
-            if !status.is_success() {
-                tracing::error!("GitHub Teams Request Responded with status: {:?}", status);
-                return Err(anyhow!(format!(
-                    "GitHub responded with status: {:?}",
-                    status
-                )));
-            }
-
-            let parse_result: Result<Vec<GitHubTeam>, Error> = response.json().await;
-
-            match parse_result {
-                Ok(value) => Ok(value),
-                Err(e) => {
-                    tracing::error!("GitHub Teams Response Parsing Failed: {:?}", e);
-                    Err(e.into())
-                }
-            }
+    get_paginated(&ctx.client, url, gh_token).await
+}
+
+/// Returns the names of the organization's teams.
+pub async fn handle_request(
+    State(cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+) -> Result<Json<TeamsResponse>, StatusCode> {
+    let teams = get_team_records(&ctx, &cache).await?;
+
+    Ok(Json(TeamsResponse {
+        teams: teams.into_iter().map(|team| team.name).collect(),
+    }))
+}
+
+/// Returns the organization's teams as records with their ids, slugs and hierarchy, served under `/v2`.
+pub async fn handle_v2_request(
+    State(cache): State<TeamsCache>,
+    State(ctx): State<Context>,
+) -> Result<Json<TeamsResponseV2>, StatusCode> {
+    let teams = get_team_records(&ctx, &cache).await?;
+
+    Ok(Json(TeamsResponseV2 { teams }))
+}
+
+/// Fills in the direct `children` of every team from the teams' `parent` links.
+fn link_children(teams: &mut [TeamRecord]) {
+    let parents: Vec<Option<u64>> = teams
+        .iter()
+        .map(|team| team.parent.as_ref().map(|parent| parent.id))
+        .collect();
+
+    for index in 0..teams.len() {
+        let id = teams[index].id;
+
+        teams[index].children = teams
+            .iter()
+            .zip(parents.iter())
+            .filter(|(_, parent)| **parent == Some(id))
+            .map(|(team, _)| team.name.clone())
+            .collect();
+    }
+}
+
+/// Finds the names of every team nested below a team, at any depth.
+///
+/// # Arguments
+///
+/// * `teams` - The teams of the organization with their `children` linked.
+/// * `team` - The name or slug of the parent team.
+///
+/// # Returns
+///
+/// A `Vec<String>` of descendant team names, in breadth-first order. Empty if the team has no children or
+/// does not exist.
+fn find_descendants(teams: &[TeamRecord], team: &str) -> Vec<String> {
+    let mut descendants: Vec<String> = vec![];
+    let mut pending: Vec<String> = teams
+        .iter()
+        .filter(|record| record.name == team || record.slug == team)
+        .flat_map(|record| record.children.clone())
+        .collect();
+
+    while !pending.is_empty() {
+        let name = pending.remove(0);
+
+        if descendants.contains(&name) {
+            continue;
         }
-        Err(e) => {
-            tracing::error!("GitHub Teams Request Failed: {:?}", e);
-            Err(e.into())
+
+        if let Some(record) = teams.iter().find(|record| record.name == name) {
+            pending.extend(record.children.clone());
         }
+
+        descendants.push(name);
     }
+
+    descendants
 }
 
-pub async fn handle_request(
-    Extension(cache): Extension<TeamsCache>,
-) -> Result<Json<TeamsResponse>, StatusCode> {
-    let request_key = "teams".to_string();
+/// Adds the child teams of the requested teams to a `DataRequest` when `include_child_teams` is set.
+pub async fn expand_child_teams(
+    ctx: &Context,
+    cache: &TeamsCache,
+    request: &mut DataRequest,
+) -> Result<(), StatusCode> {
+    if !request.include_child_teams.unwrap_or_default() {
+        return Ok(());
+    }
 
-    if let Some(cached_response) = cache.get(&request_key) {
-        return Ok(Json(cached_response.clone()));
+    if request.requested_teams().is_empty() {
+        return Ok(());
     }
 
-    let mut response: TeamsResponse = Default::default();
+    let teams = get_team_records(ctx, cache).await?;
 
-    let gh_org_var = env::var("GITHUB_ORG");
-    let gh_token_var = env::var("GITHUB_TOKEN");
+    request.child_teams = request
+        .requested_teams()
+        .into_iter()
+        .flat_map(|team| find_descendants(&teams, team))
+        .collect();
 
-    let gh_org = match gh_org_var {
-        Ok(value) => value,
-        Err(e) => {
-            tracing::error!("{}: GITHUB_ORG", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(())
+}
+
+async fn get_team_records(
+    ctx: &Context,
+    cache: &TeamsCache,
+) -> Result<Vec<TeamRecord>, StatusCode> {
+    if let Some(cached_response) = cache.get(TEAMS_KEY) {
+        if cached_response.fetched_at.elapsed() < get_cache_ttl(&ctx.config.github) {
+            return Ok(cached_response.teams.clone());
         }
-    };
+    }
 
-    let gh_token = match gh_token_var {
-        Ok(value) => value,
+    match fetch_team_records(ctx, cache).await {
+        Ok(records) => Ok(records),
         Err(e) => {
-            tracing::error!("{}: GITHUB_TOKEN", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            tracing::error!("{}", e);
+            Err(error_status(&e))
         }
-    };
+    }
+}
+
+async fn fetch_team_records(ctx: &Context, cache: &TeamsCache) -> Result<Vec<TeamRecord>> {
+    let (gh_org, gh_token) = get_org_and_token(&ctx.config.github)?;
+
+    let all_teams = get_teams(ctx, &gh_org, &gh_token).await?;
 
-    let mut page = 1;
-    let mut all_teams: Vec<GitHubTeam> = [].to_vec();
+    let mut records: Vec<TeamRecord> = all_teams.into_iter().map(TeamRecord::from).collect();
+
+    link_children(&mut records);
+
+    cache.insert(
+        TEAMS_KEY.to_string(),
+        CachedTeams {
+            teams: records.clone(),
+            fetched_at: Instant::now(),
+        },
+    );
+
+    Ok(records)
+}
+
+/// Refreshes the teams cache in the background every TTL, so new teams appear without a restart and
+/// requests don't pay for the GitHub round trip when an entry expires.
+///
+/// The cache is only refreshed once it has been filled by a request, so instances that never serve
+/// `/teams` don't call GitHub.
+pub async fn refresh_periodically(ctx: Context, cache: TeamsCache, ttl: Duration) {
+    let mut interval = tokio::time::interval(ttl);
+    interval.tick().await;
 
     loop {
-        let team_result = get_teams(&gh_org, &gh_token, page).await;
-
-        match team_result {
-            Ok(mut teams) => {
-                if !teams.is_empty() {
-                    all_teams.append(&mut teams);
-                    page += 1;
-                } else {
-                    break;
-                }
-            }
-            Err(_) => {
-                tracing::error!("GitHub Request Failed");
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
+        interval.tick().await;
+
+        if !cache.contains_key(TEAMS_KEY) {
+            continue;
+        }
+
+        if let Err(e) = fetch_team_records(&ctx, &cache).await {
+            tracing::error!("Teams Cache Refresh Failed: {:?}", e);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    response.teams = all_teams.iter().map(|team| team.name.clone()).collect();
+    fn team(id: u64, name: &str, parent: Option<u64>) -> TeamRecord {
+        TeamRecord {
+            id,
+            name: name.to_string(),
+            slug: name.to_lowercase(),
+            parent: parent.map(|id| TeamParent {
+                id,
+                ..Default::default()
+            }),
+            children: vec![],
+        }
+    }
 
-    cache.insert(request_key, response.clone());
-    Ok(Json(response))
+    #[test]
+    fn test_find_descendants() {
+        let mut teams = vec![
+            team(1, "Platform", None),
+            team(2, "Delivery", Some(1)),
+            team(3, "Observability", Some(1)),
+            team(4, "Pipelines", Some(2)),
+            team(5, "Product", None),
+        ];
+
+        link_children(&mut teams);
+
+        assert_eq!(teams[0].children, vec!["Delivery", "Observability"]);
+        assert_eq!(
+            find_descendants(&teams, "platform"),
+            vec!["Delivery", "Observability", "Pipelines"]
+        );
+        assert_eq!(find_descendants(&teams, "Product"), Vec::<String>::new());
+        assert_eq!(find_descendants(&teams, "Unknown"), Vec::<String>::new());
+    }
 }
@@ -0,0 +1,60 @@
+//! The state shared by the handlers, built once at startup from the configuration, so handlers don't read the
+//! environment while serving and can be called with a state of their own in tests.
+
+use axum::extract::FromRef;
+
+use crate::{
+    helpers::{
+        anomalies::AnomalyConfig, cohorts::CohortStacks, context::Context, prewarm::WarmupStatus,
+        prometheus::ExportConfig, readiness::ReadinessConfig, scoring::ScoringModel,
+        settings::Settings, targets::TargetsConfig,
+    },
+    routes::{data::DataCache, repositories::RepositoriesCache, teams::TeamsCache},
+};
+
+/// The caches and configuration the handlers extract with `State`, each part on its own, e.g.
+/// `State(cache): State<DataCache>`.
+#[derive(Clone)]
+pub struct AppState {
+    /// The configuration and HTTP client the Loki and GitHub helpers are called with.
+    pub ctx: Context,
+    pub data_cache: DataCache,
+    pub teams_cache: TeamsCache,
+    pub repositories_cache: RepositoriesCache,
+    pub scoring_model: ScoringModel,
+    pub cohort_stacks: CohortStacks,
+    pub targets: TargetsConfig,
+    pub anomalies: AnomalyConfig,
+    pub export: ExportConfig,
+    pub readiness: ReadinessConfig,
+    pub warmup_status: WarmupStatus,
+    /// The resolved configuration `/admin/config` serves.
+    pub settings: Settings,
+}
+
+macro_rules! from_ref {
+    ($($field:ident: $type:ty),* $(,)?) => {
+        $(
+            impl FromRef<AppState> for $type {
+                fn from_ref(state: &AppState) -> Self {
+                    state.$field.clone()
+                }
+            }
+        )*
+    };
+}
+
+from_ref!(
+    ctx: Context,
+    data_cache: DataCache,
+    teams_cache: TeamsCache,
+    repositories_cache: RepositoriesCache,
+    scoring_model: ScoringModel,
+    cohort_stacks: CohortStacks,
+    targets: TargetsConfig,
+    anomalies: AnomalyConfig,
+    export: ExportConfig,
+    readiness: ReadinessConfig,
+    warmup_status: WarmupStatus,
+    settings: Settings,
+);
@@ -0,0 +1,385 @@
+//! Logging and OpenTelemetry tracing, set up once at startup. The tracer provider is kept so the spans still
+//! batched in memory are exported during graceful shutdown instead of being lost.
+
+use anyhow::{anyhow, Result};
+use init_tracing_opentelemetry::{
+    init_propagator,
+    resource::DetectResource,
+    tracing_subscriber_ext::{build_logger_text, build_loglevel_filter_layer},
+};
+use opentelemetry_otlp::{SpanExporterBuilder, WithExportConfig};
+use opentelemetry_sdk::trace::{Sampler, Tracer, TracerProvider};
+use std::{env, fmt, future::Future, str::FromStr, time::Duration};
+use tracing::Subscriber;
+use tracing_subscriber::{layer::SubscriberExt, registry::LookupSpan, Layer};
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines, spread over several lines with source locations in debug builds.
+    Text,
+    /// One JSON object per line, with the event's fields at the top level beside the fields of the span it
+    /// was logged in, e.g. the `request_id`, `team` and window of a data request.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow!(format!("Unknown log format: {}", value))),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// The protocol spans are exported to the collector with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OtlpProtocol {
+    Grpc,
+    /// Protobuf over HTTP.
+    Http,
+}
+
+impl FromStr for OtlpProtocol {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "grpc" => Ok(OtlpProtocol::Grpc),
+            "http" | "http/protobuf" => Ok(OtlpProtocol::Http),
+            _ => Err(anyhow!(format!("Unknown OTLP protocol: {}", value))),
+        }
+    }
+}
+
+impl fmt::Display for OtlpProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtlpProtocol::Grpc => write!(f, "grpc"),
+            OtlpProtocol::Http => write!(f, "http/protobuf"),
+        }
+    }
+}
+
+/// Configures the log format, the OTLP exporter, and how long shutdown waits for the remaining spans to be
+/// exported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    pub log_format: LogFormat,
+    pub enabled: bool,
+    pub protocol: OtlpProtocol,
+    /// The URL spans are sent to, including the `/v1/traces` path for HTTP.
+    pub endpoint: String,
+    pub sample_ratio: f64,
+    pub flush_timeout: Duration,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            log_format: match cfg!(debug_assertions) {
+                true => LogFormat::Text,
+                false => LogFormat::Json,
+            },
+            enabled: true,
+            protocol: OtlpProtocol::Http,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            sample_ratio: 1.0,
+            flush_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// Reads the telemetry configuration from the environment. Headers, e.g. to authenticate with the collector,
+    /// are read by the exporter itself from `OTEL_EXPORTER_OTLP_HEADERS`.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `LOG_FORMAT` - `text` or `json`. Defaults to `text` in debug builds and `json` in release builds.
+    /// * `TRACING_ENABLED` - `false` to only log, without exporting spans. `OTEL_SDK_DISABLED=true` and
+    ///   `OTEL_TRACES_EXPORTER=none` turn the exporter off too. Defaults to `true`.
+    /// * `OTEL_EXPORTER_OTLP_PROTOCOL` - `grpc` or `http/protobuf`. Defaults to `grpc` when the endpoint's port
+    ///   is `4317`, `http/protobuf` otherwise.
+    /// * `OTEL_EXPORTER_OTLP_ENDPOINT` - The collector's base URL. Defaults to `http://localhost:4317` for gRPC
+    ///   and `http://localhost:4318` for HTTP.
+    /// * `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` - The full URL spans are sent to, in place of the base URL.
+    /// * `TRACING_SAMPLE_RATIO` - The share of traces sampled, from `0.0` to `1.0`. Traces started by a
+    ///   sampled caller are always sampled. Defaults to `1.0`.
+    /// * `TRACING_FLUSH_TIMEOUT_SECONDS` - How long shutdown waits for the remaining spans to be exported
+    ///   before giving up on them. Defaults to `5`.
+    pub fn from_env() -> Self {
+        let defaults = TelemetryConfig::default();
+
+        let log_format = env::var("LOG_FORMAT")
+            .ok()
+            .and_then(|value| value.parse::<LogFormat>().ok())
+            .unwrap_or(defaults.log_format);
+
+        let enabled = env::var("TRACING_ENABLED")
+            .ok()
+            .and_then(|value| value.parse::<bool>().ok())
+            .unwrap_or(defaults.enabled)
+            && env::var("OTEL_SDK_DISABLED").ok().as_deref() != Some("true")
+            && env::var("OTEL_TRACES_EXPORTER").ok().as_deref() != Some("none");
+
+        let traces_endpoint = non_empty_var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT");
+        let endpoint = non_empty_var("OTEL_EXPORTER_OTLP_ENDPOINT");
+
+        let protocol = non_empty_var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .or_else(|| non_empty_var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+            .and_then(|value| value.parse::<OtlpProtocol>().ok())
+            .unwrap_or_else(|| match traces_endpoint.as_ref().or(endpoint.as_ref()) {
+                Some(url) if url.contains(":4317") => OtlpProtocol::Grpc,
+                _ => defaults.protocol,
+            });
+
+        let sample_ratio = env::var("TRACING_SAMPLE_RATIO")
+            .ok()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| (0.0..=1.0).contains(value))
+            .unwrap_or(defaults.sample_ratio);
+
+        let flush_timeout = env::var("TRACING_FLUSH_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .map_or(defaults.flush_timeout, Duration::from_secs);
+
+        TelemetryConfig {
+            log_format,
+            enabled,
+            protocol,
+            endpoint: traces_endpoint
+                .unwrap_or_else(|| signal_endpoint(protocol, endpoint.as_deref())),
+            sample_ratio,
+            flush_timeout,
+        }
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Returns the URL spans are sent to below the collector's base URL. gRPC sends them to the base URL itself.
+fn signal_endpoint(protocol: OtlpProtocol, endpoint: Option<&str>) -> String {
+    match protocol {
+        OtlpProtocol::Grpc => endpoint.unwrap_or("http://localhost:4317").to_string(),
+        OtlpProtocol::Http => format!(
+            "{}/v1/traces",
+            endpoint
+                .unwrap_or("http://localhost:4318")
+                .trim_end_matches('/')
+        ),
+    }
+}
+
+/// Builds the layer writing log lines to stdout in the configured format.
+fn build_logger<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync + 'static>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    match format {
+        LogFormat::Text if cfg!(debug_assertions) => build_logger_text(),
+        LogFormat::Text => Box::new(tracing_subscriber::fmt::layer()),
+        LogFormat::Json => Box::new(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false),
+        ),
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Handles a request with its ID, so `request_id` returns it wherever the request is handled.
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// Returns the ID of the HTTP request being handled, see `routes::request_id::propagate`, or the current trace
+/// ID outside of one, e.g. in a gRPC call. Empty when there is neither.
+pub fn request_id() -> String {
+    REQUEST_ID
+        .try_with(Clone::clone)
+        .ok()
+        .or_else(tracing_opentelemetry_instrumentation_sdk::find_current_trace_id)
+        .unwrap_or_default()
+}
+
+/// Builds the OTLP exporter and installs its tracer provider globally. The exporter connects lazily, so an
+/// unreachable collector only fails the exports, which the batch processor logs and drops.
+fn init_tracer(config: &TelemetryConfig) -> Result<Tracer> {
+    let exporter: SpanExporterBuilder = match config.protocol {
+        OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(&config.endpoint)
+            .into(),
+        OtlpProtocol::Http => opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(&config.endpoint)
+            .into(),
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(
+            opentelemetry_sdk::trace::config()
+                .with_resource(DetectResource::default().build())
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    config.sample_ratio,
+                )))),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    Ok(tracer)
+}
+
+/// The installed tracer provider, shut down with `Telemetry::shutdown`.
+pub struct Telemetry {
+    provider: Option<TracerProvider>,
+    config: TelemetryConfig,
+}
+
+/// Installs the global subscriber, which logs to stdout and exports spans through OTLP as configured by
+/// `TelemetryConfig::from_env`. When the exporter can't be built, e.g. from an invalid endpoint, the error is
+/// logged and the API runs without exporting spans.
+///
+/// # Errors
+///
+/// Returns an error if a global subscriber is already installed.
+pub fn init() -> Result<Telemetry> {
+    let config = TelemetryConfig::from_env();
+
+    // Logs written while setting up the exporter go to a temporary subscriber.
+    let setup = tracing_subscriber::registry()
+        .with(build_loglevel_filter_layer())
+        .with(build_logger(config.log_format));
+    let guard = tracing::subscriber::set_default(setup);
+    tracing::info!("init logging & tracing");
+
+    let tracer = match config.enabled {
+        true => match init_tracer(&config) {
+            Ok(tracer) => {
+                tracing::info!(
+                    "Exporting Spans with OTLP over {} to {}",
+                    config.protocol,
+                    config.endpoint
+                );
+                Some(tracer)
+            }
+            Err(e) => {
+                tracing::error!("Building the OTLP Exporter Failed: {:?}", e);
+                None
+            }
+        },
+        false => {
+            tracing::info!("Exporting Spans is disabled");
+            None
+        }
+    };
+    let provider = tracer.as_ref().and_then(|tracer| tracer.provider());
+
+    init_propagator()?;
+    drop(guard);
+
+    let subscriber = tracing_subscriber::registry()
+        .with(tracer.map(|tracer| {
+            tracing_opentelemetry::layer()
+                .with_error_records_to_exceptions(true)
+                .with_tracer(tracer)
+        }))
+        .with(build_loglevel_filter_layer())
+        .with(build_logger(config.log_format));
+
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    Ok(Telemetry { provider, config })
+}
+
+impl Telemetry {
+    /// Exports the spans still batched in memory and shuts the tracer provider down, waiting at most
+    /// `TRACING_FLUSH_TIMEOUT_SECONDS`, so an unreachable collector can't hold up the exit.
+    pub async fn shutdown(self) {
+        let Some(provider) = self.provider else {
+            return;
+        };
+
+        let flushed = tokio::task::spawn_blocking(move || {
+            let failures = provider
+                .force_flush()
+                .into_iter()
+                .filter_map(Result::err)
+                .count();
+
+            // The provider shuts its span processors down once the last handle to it, the global one, is
+            // dropped.
+            drop(provider);
+            opentelemetry::global::shutdown_tracer_provider();
+
+            failures
+        });
+
+        match tokio::time::timeout(self.config.flush_timeout, flushed).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(failures)) => {
+                tracing::error!("Exporting Spans Failed for {} span processors", failures)
+            }
+            Ok(Err(e)) => tracing::error!("Shutting Down OpenTelemetry Failed: {:?}", e),
+            Err(_) => tracing::error!(
+                "Shutting Down OpenTelemetry Timed Out after {:?}",
+                self.config.flush_timeout
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_endpoint() {
+        assert_eq!(
+            signal_endpoint(OtlpProtocol::Http, Some("https://otel.example.com:4318/")),
+            "https://otel.example.com:4318/v1/traces"
+        );
+        assert_eq!(
+            signal_endpoint(OtlpProtocol::Http, None),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            signal_endpoint(OtlpProtocol::Grpc, Some("http://collector:4317")),
+            "http://collector:4317"
+        );
+        assert_eq!(
+            "http/protobuf".parse::<OtlpProtocol>().unwrap(),
+            OtlpProtocol::Http
+        );
+        assert!("thrift".parse::<OtlpProtocol>().is_err());
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+        assert!("logfmt".parse::<LogFormat>().is_err());
+    }
+}